@@ -0,0 +1,22 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0) var<uniform> color: vec4<f32>;
+// x: the half of the pheromone texture our colony is in, y: its channel,
+// z: 1 to shade hexes out of sight, w: pheromone shown at full strength
+@group(2) @binding(1) var<uniform> params: vec4<f32>;
+@group(2) @binding(2) var pheromone: texture_2d<f32>;
+@group(2) @binding(3) var visible: texture_2d<f32>;
+
+// Every vertex of a hex carries its texel in the UV, so a whole hex reads
+// the same values
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = vec2<i32>(in.uv);
+    let side = i32(textureDimensions(visible).x);
+    let trails = textureLoad(pheromone, texel + vec2(i32(params.x) * side, 0), 0);
+    let heat = 0.6 * clamp(trails[u32(params.y)] / params.w, 0.0, 1.0);
+    if params.z > 0.5 && textureLoad(visible, texel, 0).r < 0.5 {
+        return vec4(color.rgb * heat, max(heat, 0.45));
+    }
+    return vec4(color.rgb, heat);
+}
@@ -0,0 +1,79 @@
+// Pheromone and sight fields, one texel per hex: texel (q, r) offset so the
+// map's square starts at zero. See `gpu_fields`; the constants come from
+// `territory` and `vision`, whose CPU path this matches step for step.
+
+// Non-queen ants and queens on each hex, per colony. The pheromone textures
+// hold four colonies per half, the second half to the right of the first.
+@group(0) @binding(0) var walkers: texture_2d<f32>;
+@group(0) @binding(1) var nests: texture_2d<f32>;
+@group(0) @binding(2) var previous: texture_2d<f32>;
+@group(0) @binding(3) var pheromone_out: texture_storage_2d<rgba32float, write>;
+// Hexes with an ant that sees for our team, and the hexes in sight
+@group(0) @binding(4) var sight: texture_2d<f32>;
+@group(0) @binding(5) var visible_out: texture_storage_2d<r32float, write>;
+// x: hexes along a side of the map's square, y: nest radius, z: sight radius
+@group(0) @binding(6) var<uniform> sizes: vec4<u32>;
+// x: decay, y: traces forgotten below, z: deposit, w: deposit on neighbors
+@group(0) @binding(7) var<uniform> rates: vec4<f32>;
+
+var<private> NEIGHBORS: array<vec2<i32>, 6> = array<vec2<i32>, 6>(
+    vec2(1, 0), vec2(1, -1), vec2(0, -1), vec2(-1, 0), vec2(-1, 1), vec2(0, 1),
+);
+
+fn inside(texel: vec2<i32>) -> bool {
+    let side = i32(sizes.x);
+    return all(texel >= vec2(0)) && all(texel < vec2(side));
+}
+
+// Fades the previous field, forgets faint traces, then adds what each ant
+// leaves: on its own hex and its neighbors, or over the whole nest for queens
+@compute @workgroup_size(8, 8, 1)
+fn pheromone(@builtin(global_invocation_id) id: vec3<u32>) {
+    let side = i32(sizes.x);
+    let global = vec2<i32>(id.xy);
+    if global.x >= side * 2 || global.y >= side {
+        return;
+    }
+    let offset = vec2(global.x / side * side, 0);
+    let texel = global - offset;
+
+    var trail = textureLoad(previous, global, 0) * rates.x;
+    trail = select(trail, vec4(0.0), trail < vec4(rates.y));
+    trail += textureLoad(walkers, global, 0) * rates.z;
+    for (var i = 0; i < 6; i++) {
+        let neighbor = texel + NEIGHBORS[i];
+        if inside(neighbor) {
+            trail += textureLoad(walkers, neighbor + offset, 0) * rates.w;
+        }
+    }
+    let n = i32(sizes.y);
+    for (var dq = -n; dq <= n; dq++) {
+        for (var dr = max(-n, -dq - n); dr <= min(n, n - dq); dr++) {
+            let near = texel + vec2(dq, dr);
+            if inside(near) {
+                trail += textureLoad(nests, near + offset, 0) * rates.z;
+            }
+        }
+    }
+    textureStore(pheromone_out, global, trail);
+}
+
+// A hex is in sight when an ant of our team stands within the sight radius
+@compute @workgroup_size(8, 8, 1)
+fn reveal(@builtin(global_invocation_id) id: vec3<u32>) {
+    let texel = vec2<i32>(id.xy);
+    if !inside(texel) {
+        return;
+    }
+    let n = i32(sizes.z);
+    var seen = 0.0;
+    for (var dq = -n; dq <= n; dq++) {
+        for (var dr = max(-n, -dq - n); dr <= min(n, n - dq); dr++) {
+            let near = texel + vec2(dq, dr);
+            if inside(near) && textureLoad(sight, near, 0).r > 0.0 {
+                seen = 1.0;
+            }
+        }
+    }
+    textureStore(visible_out, texel, vec4(seen, 0.0, 0.0, 0.0));
+}
@@ -0,0 +1,40 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0) var<uniform> color: vec4<f32>;
+// The sprite's atlas frame in texture UV: xy the corner, zw the size
+@group(2) @binding(1) var<uniform> frame: vec4<f32>;
+// xy: padding around the sprite as a fraction of the quad,
+// zw: outline width in texture UV
+@group(2) @binding(2) var<uniform> params: vec4<f32>;
+@group(2) @binding(3) var sprite_texture: texture_2d<f32>;
+@group(2) @binding(4) var sprite_sampler: sampler;
+
+// Sprite coverage at a point of the frame (0..1 across it); nothing outside it
+fn coverage(local: vec2<f32>) -> f32 {
+    if any(local < vec2(0.0)) || any(local > vec2(1.0)) {
+        return 0.0;
+    }
+    let uv = frame.xy + local * frame.zw;
+    return textureSampleLevel(sprite_texture, sprite_sampler, uv, 0.0).a;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Where this fragment falls on the sprite, which sits inside the padding
+    let local = (in.uv - params.xy) / (vec2(1.0) - 2.0 * params.xy);
+    // The sprite itself is drawn on top; only its surroundings are ours
+    if coverage(local) > 0.5 {
+        discard;
+    }
+    // Outline where the sprite is within the outline width in any direction
+    let step = params.zw / frame.zw;
+    var near = 0.0;
+    for (var i = 0; i < 8; i++) {
+        let angle = f32(i) * 0.7853982;
+        near = max(near, coverage(local + vec2(cos(angle), sin(angle)) * step));
+    }
+    if near < 0.5 {
+        discard;
+    }
+    return color;
+}
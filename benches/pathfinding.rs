@@ -0,0 +1,45 @@
+// Route search on open ground and around walls, at increasing distances.
+// Run with `cargo bench --bench pathfinding`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use hexx::Hex;
+use std::collections::HashSet;
+
+#[path = "../src/pathfinding/route.rs"]
+mod route;
+
+use route::hex_path;
+
+const MAP_RADIUS: u32 = 60;
+
+// Every other ring is a wall with a single gap, alternating sides, so routes
+// have to zigzag outwards
+fn rings_with_gaps() -> HashSet<Hex> {
+    let mut walls = HashSet::new();
+    for radius in (2..MAP_RADIUS).step_by(2) {
+        let ring: Vec<Hex> = Hex::ZERO.ring(radius).collect();
+        let gap = if radius % 4 == 0 { 0 } else { ring.len() / 2 };
+        walls.extend(ring.into_iter().enumerate().filter(|&(i, _)| i != gap).map(|(_, hex)| hex));
+    }
+    walls
+}
+
+fn bench_routes(c: &mut Criterion) {
+    let walls = rings_with_gaps();
+    let on_map = |hex: Hex| hex.unsigned_distance_to(Hex::ZERO) < MAP_RADIUS;
+
+    let mut group = c.benchmark_group("hex_path");
+    for distance in [5, 20, 50] {
+        let to = Hex::new(distance, 0);
+        group.bench_with_input(BenchmarkId::new("open", distance), &to, |b, &to| {
+            b.iter(|| hex_path(black_box(Hex::ZERO), black_box(to), on_map))
+        });
+        group.bench_with_input(BenchmarkId::new("walls", distance), &to, |b, &to| {
+            b.iter(|| hex_path(black_box(Hex::ZERO), black_box(to), |hex| on_map(hex) && !walls.contains(&hex)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_routes);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+// Keeping the unit index up to date as units move, and proximity queries.
+// Run with `cargo bench --bench spatial_index`.
+
+#![allow(dead_code)]
+
+use bevy::prelude::Entity;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use hexx::Hex;
+
+#[path = "../src/spatial/index.rs"]
+mod index;
+
+use index::SpatialIndex;
+
+const MAP_RADIUS: u32 = 60;
+
+fn populated(units: u32) -> (SpatialIndex, Vec<(Entity, Hex)>) {
+    let mut index = SpatialIndex::default();
+    let hexes: Vec<Hex> = Hex::ZERO.spiral_range(0..MAP_RADIUS).collect();
+    let placed: Vec<(Entity, Hex)> = (0..units)
+        .map(|i| (Entity::from_raw(i), hexes[(i as usize * 7919) % hexes.len()]))
+        .collect();
+    for &(entity, hex) in &placed {
+        index.insert(entity, hex);
+    }
+    (index, placed)
+}
+
+fn bench_spatial_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spatial_index");
+    for units in [1_000, 10_000] {
+        // Every unit steps to a neighbouring hex, as in one busy frame
+        group.bench_with_input(BenchmarkId::new("move_all", units), &units, |b, &units| {
+            let (mut index, mut placed) = populated(units);
+            b.iter(|| {
+                for (entity, hex) in placed.iter_mut() {
+                    *hex += Hex::new(1, 0);
+                    index.insert(*entity, *hex);
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("within_3", units), &units, |b, &units| {
+            let (index, _) = populated(units);
+            b.iter(|| index.within(black_box(Hex::new(5, -3)), 3).count())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_spatial_index);
+criterion_main!(benches);
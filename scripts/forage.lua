@@ -0,0 +1,31 @@
+-- Example behaviour script, loaded with `cargo run --features scripting`.
+-- Idle workers fetch from the nearest food pile and bring it home.
+
+local FORAGE_RADIUS = 12
+
+function on_idle(unit)
+  if unit.caste ~= "Worker" then
+    return
+  end
+  if unit.carrying > 0 then
+    local queen = game.queen()
+    if queen then
+      game.move(unit.id, queen.q, queen.r)
+    end
+    return
+  end
+
+  local nearest = nil
+  for _, pile in ipairs(game.food_near(unit.q, unit.r, FORAGE_RADIUS)) do
+    if nearest == nil or pile.distance < nearest.distance then
+      nearest = pile
+    end
+  end
+  if nearest then
+    game.move(unit.id, nearest.q, nearest.r)
+  end
+end
+
+function on_event(name, value)
+  game.log(name .. (value and (": " .. value) or ""))
+end
@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::bots::Bots;
+use crate::coords::HexLayoutExt;
+use crate::event_log::EventLog;
+use crate::metrics::Metrics;
+use crate::net::{KickPlayer, LocalPlayer, NetConnection, PlayerId};
+use crate::save::WorldSave;
+use crate::session_replay::{self, SessionRecorder};
+use crate::units::UnitSpawner;
+use crate::{chunk_of, Ant, Caste, Faction, MapLayout, MapSize};
+
+// Live inspection and control over HTTP (built with `--features admin`).
+// A listener thread parses each request into an `AdminCommand` and sends it
+// down a channel; `serve_admin_requests` runs it against the world on the
+// main thread and hands the plain-text answer back, so the HTTP side never
+// touches the ECS itself. Only reachable from this machine.
+//   GET  /players                            connected players, bots included
+//   GET  /chunks                             units per chunk, as "q r count"
+//   GET  /metrics                            `Metrics` for Prometheus
+//   GET  /events                             the event log, as "seconds x y event"
+//   POST /kick/<player>                      disconnect a player
+//   POST /spawn/<queen|worker|soldier>/<player>/<q>/<r>
+//   POST /save                               write changed chunks to disk now
+//   POST /flag/<player>                      record the match as suspicious (see `session_replay`)
+//   GET  /sessions                           recorded sessions and replay reports
+
+const ADMIN_ADDR: &str = "127.0.0.1:7878";
+// How long a request waits for the game to answer, e.g. while still loading
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+// Requests are served one at a time, so a client that stalls mid-request
+// is dropped after this long instead of holding up everyone after it
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+
+enum AdminCommand {
+    ListPlayers,
+    ChunkCounts,
+    Metrics,
+    Events,
+    Kick(PlayerId),
+    Spawn { caste: Caste, player: PlayerId, hex: Hex },
+    Save,
+    Flag(PlayerId),
+    Sessions,
+}
+
+struct AdminRequest {
+    command: AdminCommand,
+    reply: Sender<Result<String, String>>,
+}
+
+#[derive(Resource)]
+struct AdminInbox(Mutex<Receiver<AdminRequest>>);
+
+fn parse_command(method: &str, path: &str) -> Option<AdminCommand> {
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let command = match (method, parts.as_slice()) {
+        ("GET", ["players"]) => AdminCommand::ListPlayers,
+        ("GET", ["chunks"]) => AdminCommand::ChunkCounts,
+        ("GET", ["metrics"]) => AdminCommand::Metrics,
+        ("GET", ["events"]) => AdminCommand::Events,
+        ("POST", ["kick", player]) => AdminCommand::Kick(PlayerId(player.parse().ok()?)),
+        ("POST", ["spawn", caste, player, q, r]) => AdminCommand::Spawn {
+            caste: Caste::from_name(caste)?,
+            player: PlayerId(player.parse().ok()?),
+            hex: Hex::new(q.parse().ok()?, r.parse().ok()?),
+        },
+        ("POST", ["save"]) => AdminCommand::Save,
+        ("POST", ["flag", player]) => AdminCommand::Flag(PlayerId(player.parse().ok()?)),
+        ("GET", ["sessions"]) => AdminCommand::Sessions,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    // The client hanging up early is its own problem
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn handle_connection(mut stream: TcpStream, requests: &Sender<AdminRequest>) {
+    if stream.set_read_timeout(Some(IO_TIMEOUT)).and_then(|()| stream.set_write_timeout(Some(IO_TIMEOUT))).is_err() {
+        return;
+    }
+    let mut request_line = String::new();
+    if BufReader::new(&stream).read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut words = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (words.next(), words.next()) else {
+        respond(&mut stream, "400 Bad Request", "malformed request\n");
+        return;
+    };
+    let Some(command) = parse_command(method, path) else {
+        respond(&mut stream, "404 Not Found", "unknown command\n");
+        return;
+    };
+
+    let (reply, answer) = mpsc::channel();
+    if requests.send(AdminRequest { command, reply }).is_err() {
+        respond(&mut stream, "503 Service Unavailable", "game is shutting down\n");
+        return;
+    }
+    match answer.recv_timeout(REPLY_TIMEOUT) {
+        Ok(Ok(body)) => respond(&mut stream, "200 OK", &body),
+        Ok(Err(body)) => respond(&mut stream, "400 Bad Request", &body),
+        Err(_) => respond(&mut stream, "503 Service Unavailable", "game did not answer\n"),
+    }
+}
+
+fn start_admin_server(mut commands: Commands) {
+    let listener = match TcpListener::bind(ADMIN_ADDR) {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("Admin interface disabled, could not bind {ADMIN_ADDR}: {err}");
+            return;
+        }
+    };
+    let (requests, inbox) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            handle_connection(stream, &requests);
+        }
+    });
+    commands.insert_resource(AdminInbox(Mutex::new(inbox)));
+    info!("Admin interface listening on http://{ADMIN_ADDR}");
+}
+
+fn serve_admin_requests(
+    mut commands: Commands,
+    inbox: Option<Res<AdminInbox>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    local_player: Res<LocalPlayer>,
+    connection: Res<NetConnection>,
+    spawner: UnitSpawner,
+    metrics: Res<Metrics>,
+    event_log: Res<EventLog>,
+    mut bots: ResMut<Bots>,
+    mut world_save: WorldSave,
+    mut recorder: ResMut<SessionRecorder>,
+    unit_q: Query<&Transform, With<Ant>>,
+    mut kicks: EventWriter<KickPlayer>,
+) {
+    let Some(inbox) = inbox else {
+        return;
+    };
+    let Ok(inbox) = inbox.0.lock() else {
+        return;
+    };
+    for request in inbox.try_iter() {
+        let result = match request.command {
+            AdminCommand::ListPlayers => {
+                let mut body = format!("{} (local)\n", local_player.0 .0);
+                for player in &connection.peers {
+                    body.push_str(&format!("{} (remote)\n", player.0));
+                }
+                for player in bots.players() {
+                    body.push_str(&format!("{} (bot)\n", player.0));
+                }
+                Ok(body)
+            }
+            AdminCommand::ChunkCounts => {
+                let mut counts: BTreeMap<(i32, i32), usize> = BTreeMap::new();
+                for transform in unit_q.iter() {
+                    let chunk = chunk_of(layout.0.world_to_hex(transform.translation.truncate()));
+                    *counts.entry((chunk.x, chunk.y)).or_default() += 1;
+                }
+                Ok(counts.iter().map(|((q, r), count)| format!("{q} {r} {count}\n")).collect())
+            }
+            AdminCommand::Metrics => Ok(metrics.prometheus()),
+            AdminCommand::Events => Ok(event_log
+                .entries()
+                .map(|entry| format!("{:.1} {} {} {:?}\n", entry.time_secs, entry.pos.x, entry.pos.y, entry.event))
+                .collect()),
+            AdminCommand::Kick(player) if player == local_player.0 => Err("can't kick the host\n".to_string()),
+            AdminCommand::Kick(player) => {
+                if bots.kick(player) {
+                    Ok(format!("kicked {}\n", player.0))
+                } else if connection.peers.contains(&player) {
+                    kicks.send(KickPlayer(player));
+                    Ok(format!("kicked {}\n", player.0))
+                } else {
+                    Err(format!("player {} is not connected\n", player.0))
+                }
+            }
+            AdminCommand::Spawn { hex, .. } if !map_size.contains(hex) => {
+                Err(format!("({}, {}) is off the map\n", hex.x, hex.y))
+            }
+            AdminCommand::Spawn { caste, player, hex } => {
+                let entity = spawner.spawn(&mut commands, caste, Faction(player), layout.0.hex_to_world(hex));
+                Ok(format!("{}\n", entity.to_bits()))
+            }
+            AdminCommand::Save => Ok(format!("saved {} chunks\n", world_save.save())),
+            AdminCommand::Flag(player) => recorder.flag(player),
+            AdminCommand::Sessions => Ok(session_replay::list_sessions()),
+        };
+        // The HTTP side may have timed out already
+        let _ = request.reply.send(result);
+    }
+}
+
+pub struct AdminPlugin;
+
+impl Plugin for AdminPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, start_admin_server)
+            .add_systems(Update, serve_admin_requests.run_if(in_state(crate::loading::AppState::InGame)));
+    }
+}
@@ -0,0 +1,318 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::collections::VecDeque;
+
+use crate::burrow::BurrowNetwork;
+use crate::coords::HexLayoutExt;
+use crate::interactions::{Engaged, FoodStores};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::loading::AppState;
+use crate::locale::Locale;
+use crate::net::{IncomingMessage, LocalPlayer, NetMessage, OutgoingMessage, PlayerId};
+use crate::predators::Predator;
+use crate::spatial::SpatialIndex;
+use crate::{Ant, Faction, MainCamera, MapLayout, Queen, Relation, Teams};
+
+// Alerts: the colony is under attack (an enemy ant or a predator is biting one
+// of ours), a hex was dug, or the food store ran low. Each one pulses a ring
+// on the map where it happened, points an arrow from the screen edge while
+// it's out of view, and shows a line of text at the top of the screen; X
+// jumps the camera to the latest. V pings the hex under the cursor for our
+// allies, who get it as an alert of their own. Attacks near one already
+// reported aren't reported again for a while, so a long fight is one alert.
+// Alerts age on real time, so they fade out while paused like anywhere else.
+
+// Alerts kept for jumping back to
+const MAX_ALERTS: usize = 8;
+// How long the ring pulses, the edge arrow shows and the text stays up
+const PULSE_SECS: f32 = 3.0;
+const ARROW_SECS: f32 = 8.0;
+const TEXT_SECS: f32 = 5.0;
+// Hexes between a predator and one of our ants that count as an attack
+const PREDATOR_RANGE: u32 = 2;
+// A new attack within this many hexes of an alerted one is the same fight...
+const ATTACK_RADIUS: u32 = 6;
+// ...as long as that alert is younger than this
+const ATTACK_REPEAT_SECS: f32 = 20.0;
+// Food below which the store counts as running low
+const LOW_FOOD: u32 = 10;
+// Ring and arrow sizes, in screen pixels
+const PULSE_RADIUS: f32 = 40.0;
+const ARROW_SIZE: f32 = 12.0;
+const ARROW_INSET: f32 = 24.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum AlertKind {
+    Attacked,
+    Dug,
+    LowFood,
+    Ping(PlayerId),
+}
+
+impl AlertKind {
+    fn color(self) -> Color {
+        let hex = match self {
+            AlertKind::Attacked => "F44336",
+            AlertKind::Dug => "A1887F",
+            AlertKind::LowFood => "FFC107",
+            AlertKind::Ping(_) => "00BFFF",
+        };
+        Color::from(Srgba::hex(hex).unwrap())
+    }
+
+    fn text(self, locale: &Locale, local_player: PlayerId) -> String {
+        match self {
+            AlertKind::Attacked => locale.get("alerts.attacked").to_string(),
+            AlertKind::Dug => locale.get("alerts.dug").to_string(),
+            AlertKind::LowFood => locale.get("alerts.low-food").to_string(),
+            AlertKind::Ping(player) if player == local_player => locale.get("alerts.pinged").to_string(),
+            AlertKind::Ping(player) => locale.format("alerts.ping", &[("player", &player.0)]),
+        }
+    }
+}
+
+struct Alert {
+    kind: AlertKind,
+    pos: Vec2,
+    age_secs: f32,
+}
+
+// Newest last
+#[derive(Resource, Default)]
+struct Alerts(VecDeque<Alert>);
+
+impl Alerts {
+    fn raise(&mut self, kind: AlertKind, pos: Vec2) {
+        if self.0.len() == MAX_ALERTS {
+            self.0.pop_front();
+        }
+        self.0.push_back(Alert { kind, pos, age_secs: 0.0 });
+    }
+}
+
+fn age_alerts(time: Res<Time<Real>>, mut alerts: ResMut<Alerts>) {
+    if alerts.0.is_empty() {
+        return;
+    }
+    for alert in alerts.0.iter_mut() {
+        alert.age_secs += time.delta_seconds();
+    }
+}
+
+// Our ants bitten by enemy ants or with a predator close by
+fn detect_attacks(
+    layout: Res<MapLayout>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+    spatial_index: Res<SpatialIndex>,
+    attacker_q: Query<(&Engaged, &Faction)>,
+    ant_q: Query<(&Faction, &Transform), With<Ant>>,
+    predator_q: Query<&Transform, With<Predator>>,
+    mut alerts: ResMut<Alerts>,
+) {
+    let ours = |entity: Entity| ant_q.get(entity).ok().filter(|(faction, _)| faction.0 == local_player.0);
+    let bitten = attacker_q
+        .iter()
+        .filter(|(_, faction)| teams.relation(local_player.0, faction.0) == Relation::Enemy)
+        .filter_map(|(engaged, _)| ours(engaged.0).map(|(_, transform)| transform.translation.truncate()));
+    let hunted = predator_q.iter().filter_map(|transform| {
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        spatial_index.within(hex, PREDATOR_RANGE).find_map(ours).map(|(_, transform)| transform.translation.truncate())
+    });
+    for pos in bitten.chain(hunted).collect::<Vec<_>>() {
+        let hex = layout.0.world_to_hex(pos);
+        let known = alerts.0.iter().any(|alert| {
+            alert.kind == AlertKind::Attacked
+                && alert.age_secs < ATTACK_REPEAT_SECS
+                && layout.0.world_to_hex(alert.pos).unsigned_distance_to(hex) <= ATTACK_RADIUS
+        });
+        if !known {
+            alerts.raise(AlertKind::Attacked, pos);
+        }
+    }
+}
+
+fn detect_digs(
+    state: Res<State<AppState>>,
+    layout: Res<MapLayout>,
+    mut network: ResMut<BurrowNetwork>,
+    mut alerts: ResMut<Alerts>,
+) {
+    // The starter tunnel and loaded saves are dug before the game starts
+    let dug = network.take_newly_dug();
+    if *state.get() != AppState::InGame {
+        return;
+    }
+    // One alert for a whole batch, at the last hex dug
+    if let Some(&hex) = dug.last() {
+        alerts.raise(AlertKind::Dug, layout.0.hex_to_world(hex));
+    }
+}
+
+fn detect_low_food(
+    stores: Res<FoodStores>,
+    local_player: Res<LocalPlayer>,
+    queen_q: Query<(&Faction, &Transform), With<Queen>>,
+    mut was_low: Local<Option<bool>>,
+    mut alerts: ResMut<Alerts>,
+) {
+    if !stores.is_changed() {
+        return;
+    }
+    let low = stores.0.get(&local_player.0).copied().unwrap_or(0) < LOW_FOOD;
+    // Only running low counts, not starting low
+    if low && *was_low == Some(false) {
+        let queen = queen_q.iter().find(|(faction, _)| faction.0 == local_player.0);
+        let pos = queen.map_or(Vec2::ZERO, |(_, transform)| transform.translation.truncate());
+        alerts.raise(AlertKind::LowFood, pos);
+    }
+    *was_low = Some(low);
+}
+
+fn cursor_world_pos(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera_q: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera_q.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
+
+fn alert_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    layout: Res<MapLayout>,
+    local_player: Res<LocalPlayer>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut camera_transform_q: Query<&mut Transform, With<MainCamera>>,
+    mut alerts: ResMut<Alerts>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::JumpToAlert) {
+        if let (Some(alert), Ok(mut transform)) = (alerts.0.back(), camera_transform_q.get_single_mut()) {
+            transform.translation.x = alert.pos.x;
+            transform.translation.y = alert.pos.y;
+        }
+    }
+    if bindings.just_pressed(&keyboard_input, Action::Ping) {
+        let Some(pos) = cursor_world_pos(&windows, &camera_q) else {
+            return;
+        };
+        let hex = layout.0.world_to_hex(pos);
+        alerts.raise(AlertKind::Ping(local_player.0), layout.0.hex_to_world(hex));
+        // Everyone gets it; only allies show it
+        outgoing.send(OutgoingMessage::broadcast(NetMessage::Ping { hex }));
+    }
+}
+
+fn receive_pings(
+    mut incoming: EventReader<IncomingMessage>,
+    layout: Res<MapLayout>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+    mut alerts: ResMut<Alerts>,
+) {
+    for message in incoming.read() {
+        if let NetMessage::Ping { hex } = message.message {
+            if teams.relation(local_player.0, message.from) == Relation::Ally {
+                alerts.raise(AlertKind::Ping(message.from), layout.0.hex_to_world(hex));
+            }
+        }
+    }
+}
+
+// Rings growing out of each fresh alert, and arrows on the screen edge
+// pointing at the ones out of view
+fn draw_alerts(
+    mut gizmos: Gizmos,
+    alerts: Res<Alerts>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let (Ok(window), Ok((camera, camera_transform, projection))) = (windows.get_single(), camera_q.get_single())
+    else {
+        return;
+    };
+    let corner = |pos| camera.viewport_to_world_2d(camera_transform, pos);
+    let (Some(min), Some(max)) = (corner(Vec2::new(0.0, window.height())), corner(Vec2::new(window.width(), 0.0)))
+    else {
+        return;
+    };
+    let scale = projection.scale;
+    let view = Rect::from_corners(min, max);
+    let edge = view.inflate(-ARROW_INSET * scale);
+
+    for alert in alerts.0.iter() {
+        let color = alert.kind.color();
+        if alert.age_secs < PULSE_SECS {
+            let t = alert.age_secs / PULSE_SECS;
+            // Two rings, the second half a pulse behind the first
+            for phase in [0.0, 0.5] {
+                let ring = (t * 2.0 + phase).fract();
+                gizmos.circle_2d(alert.pos, ring * PULSE_RADIUS * scale, color.with_alpha(1.0 - ring));
+            }
+        }
+        if alert.age_secs < ARROW_SECS && !view.contains(alert.pos) && !edge.is_empty() {
+            let tip = alert.pos.clamp(edge.min, edge.max);
+            let back = (tip - alert.pos).normalize_or_zero() * ARROW_SIZE * scale;
+            let side = back.perp() * 0.5;
+            gizmos.linestrip_2d([tip, tip + back + side, tip + back - side, tip], color);
+        }
+    }
+}
+
+#[derive(Component)]
+struct AlertText;
+
+fn setup_alert_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 16.0, color: Color::WHITE, ..default() }).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            left: Val::Percent(40.0),
+            ..default()
+        }),
+        AlertText,
+    ));
+}
+
+fn update_alert_text(
+    alerts: Res<Alerts>,
+    locale: Res<Locale>,
+    local_player: Res<LocalPlayer>,
+    mut text_q: Query<&mut Text, With<AlertText>>,
+) {
+    let latest = alerts.0.back().filter(|alert| alert.age_secs < TEXT_SECS);
+    for mut text in text_q.iter_mut() {
+        let value = latest.map(|alert| alert.kind.text(&locale, local_player.0)).unwrap_or_default();
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+            text.sections[0].style.color = latest.map_or(Color::WHITE, |alert| alert.kind.color());
+        }
+    }
+}
+
+pub struct AlertsPlugin;
+
+impl Plugin for AlertsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Alerts>()
+            .register_action(Action::JumpToAlert, &[KeyCode::KeyX])
+            .register_action(Action::Ping, &[KeyCode::KeyV])
+            .add_systems(Startup, setup_alert_text)
+            .add_systems(
+                Update,
+                (
+                    age_alerts,
+                    detect_digs,
+                    (detect_attacks, detect_low_food).run_if(in_state(AppState::InGame)),
+                    alert_keys,
+                    receive_pings,
+                    draw_alerts,
+                    update_alert_text,
+                )
+                    .chain(),
+            );
+    }
+}
@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use bevy::sprite::Anchor;
+use bevy_rapier2d::prelude::Velocity;
+use std::f32::consts::FRAC_PI_2;
+
+use crate::Caste;
+
+// Ant sprites come from one texture atlas: a row per caste, column 0 is the
+// idle pose and the rest are a walk cycle. Frames are drawn white so the
+// sprite color still tints them. The atlas is painted at startup; swapping in
+// an artist's sheet only needs the same grid layout.
+//
+// Idle ants aren't frozen: they look around, shuffle a little inside their
+// hex and stop to groom, driven by a cheap per-entity noise on real time (so
+// it keeps going while the game is paused). Only the sprite's heading and
+// draw anchor change; the transform's position, and so occupancy, collisions
+// and every gameplay system, never see it.
+
+const FRAME_SIZE: u32 = 16;
+const WALK_FRAMES: u32 = 3;
+const COLUMNS: u32 = 1 + WALK_FRAMES;
+const CASTES: [Caste; 3] = [Caste::Queen, Caste::Worker, Caste::Soldier];
+// Below this speed (px/s) an ant shows its idle frame
+const IDLE_SPEED: f32 = 5.0;
+// Walk cycle frames per second at 100 px/s, scaled by actual speed
+const FRAMES_PER_SEC: f32 = 12.0;
+// Idle shuffle range, as a fraction of the sprite size
+const IDLE_STEP: f32 = 0.2;
+// How far (radians) an idle ant turns to look around, and its antenna twitch
+const IDLE_LOOK: f32 = 0.5;
+const IDLE_TWITCH: f32 = 0.08;
+// Noise level above which an idle ant is grooming
+const GROOM_THRESHOLD: f32 = 0.6;
+
+#[derive(Resource)]
+pub struct AntSprites {
+    texture: Handle<Image>,
+    layout: Handle<TextureAtlasLayout>,
+}
+
+impl AntSprites {
+    fn row(caste: Caste) -> usize {
+        CASTES.iter().position(|&c| c == caste).unwrap_or(0)
+    }
+
+    // Texture and atlas (idle frame) for a new unit of this caste
+    pub fn for_caste(&self, caste: Caste) -> (Handle<Image>, TextureAtlas) {
+        let atlas = TextureAtlas {
+            layout: self.layout.clone(),
+            index: Self::row(caste) * COLUMNS as usize,
+        };
+        (self.texture.clone(), atlas)
+    }
+}
+
+#[derive(Component, Default)]
+pub struct AntAnimation {
+    // Fractional walk cycle position, in frames
+    phase: f32,
+    // Heading when the ant stopped; idle looking around swings about it
+    idle_heading: Option<f32>,
+}
+
+// Hash of a seed and lattice point, in -1..1
+fn hash(seed: u32, i: i32) -> f32 {
+    let mut x = seed.wrapping_mul(0x9E37_79B1) ^ (i as u32).wrapping_mul(0x85EB_CA6B);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x2C1B_3C6D);
+    x ^= x >> 12;
+    x as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+// Smooth 1D value noise in -1..1; one octave is plenty for fidgeting
+fn noise(seed: u32, t: f32) -> f32 {
+    let i = t.floor();
+    let f = t - i;
+    let smooth = f * f * (3.0 - 2.0 * f);
+    let (a, b) = (hash(seed, i as i32), hash(seed, i as i32 + 1));
+    a + (b - a) * smooth
+}
+
+fn paint_disc(data: &mut [u8], width: u32, center: Vec2, radius: f32) {
+    let min = (center - radius).floor().max(Vec2::ZERO).as_uvec2();
+    let max = (center + radius).ceil().as_uvec2();
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            if Vec2::new(x as f32 + 0.5, y as f32 + 0.5).distance(center) <= radius {
+                let i = ((y * width + x) * 4) as usize;
+                data[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+            }
+        }
+    }
+}
+
+fn paint_line(data: &mut [u8], width: u32, from: Vec2, to: Vec2) {
+    let steps = (from.distance(to) * 2.0).ceil().max(1.0) as u32;
+    for step in 0..=steps {
+        paint_disc(data, width, from.lerp(to, step as f32 / steps as f32), 0.5);
+    }
+}
+
+// One frame with its top-left at `origin`. The ant faces up the texture (-y).
+fn paint_ant(data: &mut [u8], width: u32, origin: Vec2, caste: Caste, column: u32) {
+    let center = origin + Vec2::splat(FRAME_SIZE as f32 / 2.0);
+    let abdomen = if caste == Caste::Queen { 3.5 } else { 2.5 };
+    // Soldiers have the big head and mandibles
+    let head = if caste == Caste::Soldier { 2.6 } else { 1.8 };
+
+    paint_disc(data, width, center + Vec2::new(0.0, -4.5), head); // head
+    paint_disc(data, width, center, 1.6); // thorax
+    paint_disc(data, width, center + Vec2::new(0.0, 4.0), abdomen);
+    if caste == Caste::Soldier {
+        for side in [-1.0_f32, 1.0] {
+            paint_line(data, width, center + Vec2::new(side * 1.5, -6.5), center + Vec2::new(side * 0.6, -7.8));
+        }
+    }
+
+    // Legs alternate between two tripods: each walk frame swings them a bit
+    let swing = match column {
+        0 => 0.0,
+        c => [-1.5, 0.0, 1.5][(c - 1) as usize % 3],
+    };
+    for (i, leg_y) in [-2.0_f32, 0.0, 2.0].into_iter().enumerate() {
+        let tripod = if i % 2 == 0 { swing } else { -swing };
+        for side in [-1.0_f32, 1.0] {
+            let offset = if side < 0.0 { tripod } else { -tripod };
+            let hip = center + Vec2::new(side * 1.0, leg_y * 0.5);
+            let foot = center + Vec2::new(side * 5.5, leg_y * 1.6 + offset);
+            paint_line(data, width, hip, foot);
+        }
+    }
+}
+
+fn setup_ant_sprites(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let width = FRAME_SIZE * COLUMNS;
+    let height = FRAME_SIZE * CASTES.len() as u32;
+    let mut data = vec![0; (width * height * 4) as usize];
+    for (row, &caste) in CASTES.iter().enumerate() {
+        for column in 0..COLUMNS {
+            let origin = Vec2::new((column * FRAME_SIZE) as f32, (row as u32 * FRAME_SIZE) as f32);
+            paint_ant(&mut data, width, origin, caste, column);
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d { width, height, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    // Crisp pixels when zoomed in
+    image.sampler = ImageSampler::nearest();
+
+    let layout = TextureAtlasLayout::from_grid(UVec2::splat(FRAME_SIZE), COLUMNS, CASTES.len() as u32, None, None);
+    commands.insert_resource(AntSprites {
+        texture: images.add(image),
+        layout: layouts.add(layout),
+    });
+}
+
+// Walk cycle driven by velocity, facing the direction of travel; idle ants
+// fidget instead (see the top of this file)
+fn animate_ants(
+    time: Res<Time>,
+    real_time: Res<Time<Real>>,
+    mut q: Query<(Entity, &Caste, Option<&Velocity>, &mut AntAnimation, &mut TextureAtlas, &mut Transform, &mut Sprite)>,
+) {
+    let t = real_time.elapsed_seconds();
+    for (entity, &caste, velocity, mut animation, mut atlas, mut transform, mut sprite) in q.iter_mut() {
+        let first = AntSprites::row(caste) * COLUMNS as usize;
+        let linvel = velocity.map_or(Vec2::ZERO, |velocity| velocity.linvel);
+        let speed = linvel.length();
+        if speed < IDLE_SPEED {
+            animation.phase = 0.0;
+            let heading = *animation
+                .idle_heading
+                .get_or_insert_with(|| transform.rotation.to_euler(EulerRot::XYZ).2);
+
+            // Independent noise channels per ant, so neighbours don't move in step
+            let seed = entity.index().wrapping_mul(8);
+            let look = noise(seed, t * 0.4) * IDLE_LOOK + noise(seed + 1, t * 7.0) * IDLE_TWITCH;
+            transform.rotation = Quat::from_rotation_z(heading + look);
+            sprite.anchor = Anchor::Custom(Vec2::new(noise(seed + 2, t * 0.3), noise(seed + 3, t * 0.3)) * IDLE_STEP);
+            // Grooming: legs flick between two walk frames while standing still
+            atlas.index = if noise(seed + 4, t * 0.25) > GROOM_THRESHOLD {
+                first + 1 + 2 * ((t * 6.0) as usize % 2)
+            } else {
+                first
+            };
+            continue;
+        }
+
+        animation.idle_heading = None;
+        if sprite.anchor != Anchor::Center {
+            sprite.anchor = Anchor::Center;
+        }
+        animation.phase = (animation.phase + FRAMES_PER_SEC * speed / 100.0 * time.delta_seconds()) % WALK_FRAMES as f32;
+        atlas.index = first + 1 + animation.phase as usize;
+        // Frames face up the screen
+        transform.rotation = Quat::from_rotation_z(linvel.y.atan2(linvel.x) - FRAC_PI_2);
+    }
+}
+
+pub struct AntSpritesPlugin;
+
+impl Plugin for AntSpritesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_ant_sprites)
+            .add_systems(Update, animate_ants.run_if(crate::lod::full_detail));
+    }
+}
@@ -0,0 +1,238 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::BTreeMap;
+
+use crate::coords::HexLayoutExt;
+use crate::health::Health;
+use crate::game_commands::{GameCommand, SelectMode};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::locale::{Locale, Localized};
+use crate::net::LocalPlayer;
+use crate::{chunk_of, Ant, Caste, Faction, MainCamera, MapLayout};
+
+// Order of battle: a collapsible list of our soldiers grouped by map chunk,
+// with each group's total health. Clicking a row selects that group, its "Go"
+// button jumps the camera to it, so big armies don't have to be found sprite
+// by sprite.
+
+const REFRESH_SECS: f32 = 0.5;
+
+#[derive(Resource)]
+struct OrderOfBattle {
+    open: bool,
+    refresh: Timer,
+}
+
+impl Default for OrderOfBattle {
+    fn default() -> Self {
+        Self {
+            open: false,
+            refresh: Timer::from_seconds(REFRESH_SECS, TimerMode::Repeating),
+        }
+    }
+}
+
+#[derive(Component)]
+struct BattlePanel;
+
+// Container the group rows are rebuilt into
+#[derive(Component)]
+struct BattlePanelRows;
+
+// Soldiers in one chunk
+#[derive(Default)]
+struct Group {
+    chunk: Hex,
+    members: Vec<Entity>,
+    pos_sum: Vec2,
+    health: f32,
+    max_health: f32,
+}
+
+#[derive(Component)]
+struct GroupSelectButton(Vec<Entity>);
+
+#[derive(Component)]
+struct GroupJumpButton(Vec2);
+
+fn setup_battle_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(8.0),
+                    right: Val::Px(8.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.85).into(),
+                ..default()
+            },
+            // Lets the pointer input skip clicks that land on the panel
+            Interaction::default(),
+            BattlePanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 18.0, color: Color::WHITE, ..default() }),
+                Localized("battle.title"),
+            ));
+            panel.spawn((
+                NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(2.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                BattlePanelRows,
+            ));
+        });
+}
+
+fn toggle_battle_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut panel: ResMut<OrderOfBattle>,
+    mut style_q: Query<&mut Style, With<BattlePanel>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleOrderOfBattle) {
+        return;
+    }
+    panel.open = !panel.open;
+    // Rebuild right away instead of showing stale rows
+    let duration = panel.refresh.duration();
+    panel.refresh.set_elapsed(duration);
+    for mut style in style_q.iter_mut() {
+        style.display = if panel.open { Display::Flex } else { Display::None };
+    }
+}
+
+fn refresh_battle_panel(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut panel: ResMut<OrderOfBattle>,
+    local_player: Res<LocalPlayer>,
+    layout: Res<MapLayout>,
+    locale: Res<Locale>,
+    unit_q: Query<(Entity, &Caste, &Faction, &Transform, &Health), With<Ant>>,
+    rows_q: Query<Entity, With<BattlePanelRows>>,
+) {
+    if !panel.open || !panel.refresh.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // chunk -> soldiers there; BTreeMap keeps the rows in a stable order
+    let mut groups: BTreeMap<(i32, i32), Group> = BTreeMap::new();
+    for (entity, &caste, faction, transform, health) in unit_q.iter() {
+        if faction.0 != local_player.0 || caste != Caste::Soldier {
+            continue;
+        }
+        let pos = transform.translation.truncate();
+        let chunk = chunk_of(layout.0.world_to_hex(pos));
+        let group = groups.entry((chunk.x, chunk.y)).or_insert_with(|| Group { chunk, ..default() });
+        group.members.push(entity);
+        group.pos_sum += pos;
+        group.health += health.current;
+        group.max_health += health.max;
+    }
+
+    let Ok(rows) = rows_q.get_single() else {
+        return;
+    };
+    commands.entity(rows).despawn_descendants();
+    commands.entity(rows).with_children(|rows| {
+        if groups.is_empty() {
+            rows.spawn(TextBundle::from_section(
+                locale.get("battle.no-units"),
+                TextStyle { font_size: 14.0, color: Color::srgb(0.6, 0.6, 0.6), ..default() },
+            ));
+        }
+        for (_, Group { chunk, members, pos_sum, health, max_health }) in groups {
+            let center = pos_sum / members.len() as f32;
+            let label = locale.format(
+                "battle.group",
+                &[
+                    ("caste", &locale.get(Caste::Soldier.name_key())),
+                    ("count", &members.len()),
+                    ("q", &chunk.x),
+                    ("r", &chunk.y),
+                    ("health", &health.ceil()),
+                    ("max", &max_health.ceil()),
+                ],
+            );
+            rows.spawn(NodeBundle {
+                style: Style { column_gap: Val::Px(6.0), ..default() },
+                ..default()
+            })
+            .with_children(|row| {
+                spawn_row_button(row, &label, GroupSelectButton(members));
+                spawn_row_button(row, locale.get("battle.go"), GroupJumpButton(center));
+            });
+        }
+    });
+}
+
+fn spawn_row_button(row: &mut ChildBuilder, label: &str, marker: impl Component) {
+    row.spawn((
+        ButtonBundle {
+            style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+            background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+            ..default()
+        },
+        marker,
+    ))
+    .with_children(|button| {
+        button.spawn(TextBundle::from_section(
+            label,
+            TextStyle { font_size: 14.0, color: Color::WHITE, ..default() },
+        ));
+    });
+}
+
+fn handle_battle_panel_clicks(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    select_q: Query<(&Interaction, &GroupSelectButton), Changed<Interaction>>,
+    jump_q: Query<(&Interaction, &GroupJumpButton), Changed<Interaction>>,
+    mut game_commands: EventWriter<GameCommand>,
+    mut camera_q: Query<&mut Transform, With<MainCamera>>,
+) {
+    for (interaction, group) in select_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        // Same rule as clicking units: Shift adds to the selection
+        let additive = bindings.pressed(&keyboard_input, Action::AddToSelection);
+        game_commands.send(GameCommand::Select {
+            units: group.0.clone(),
+            mode: if additive { SelectMode::Add } else { SelectMode::Replace },
+        });
+    }
+
+    for (interaction, jump) in jump_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        for mut transform in camera_q.iter_mut() {
+            transform.translation.x = jump.0.x;
+            transform.translation.y = jump.0.y;
+        }
+    }
+}
+
+pub struct BattlePanelPlugin;
+
+impl Plugin for BattlePanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OrderOfBattle>()
+            .register_action(Action::ToggleOrderOfBattle, &[KeyCode::KeyO])
+            .add_systems(Startup, setup_battle_panel)
+            .add_systems(Update, (toggle_battle_panel, refresh_battle_panel, handle_battle_panel_clicks).chain());
+    }
+}
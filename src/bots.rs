@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+
+use crate::diplomacy::Offer;
+use crate::game_commands::{GameCommand, MoveMode};
+use crate::net::{tick_is_newer, IncomingMessage, NetConnection, NetMessage, OutgoingMessage, PlayerId, UnitState};
+use crate::order_ack::SQUAD_SIZE;
+use crate::placement::SpawnPlacer;
+use crate::units::UnitSpawner;
+use crate::{Ant, Caste, Faction, MapSize, Path};
+
+// Headless players. A bot is a client with no window, camera or input: it
+// only receives the `NetMessage`s the server sends its player and answers
+// with messages of its own, so anything that can drive a `BotBrain` (an AI
+// opponent, a load test) plays by exactly the same protocol as a person.
+// Until there is a socket transport bots are attached in-process: what we
+// send reaches them as if over the wire, and what they send comes back as
+// `IncomingMessage`s from their player. `--bots N` starts N wandering bots,
+// each with a colony of its own.
+
+const SNAPSHOT_SECS: f32 = 1.0;
+// Bot players get ids from here up, well clear of real players
+const FIRST_BOT_PLAYER: u32 = 1000;
+const BOT_WORKERS: usize = 3;
+// How far from its queen a `WanderBot` sends workers, in world units
+const WANDER_RANGE: f32 = 80.0;
+
+// Decision making for one bot player
+pub trait BotBrain: Send + Sync + 'static {
+    // Called for every message the server sends `me`; whatever is pushed to
+    // `replies` is sent back as `me`
+    fn on_message(&mut self, me: PlayerId, message: &NetMessage, replies: &mut Vec<NetMessage>);
+}
+
+struct Bot {
+    player: PlayerId,
+    brain: Box<dyn BotBrain>,
+}
+
+#[derive(Resource)]
+pub struct Bots {
+    bots: Vec<Bot>,
+    next_player: u32,
+}
+
+impl Default for Bots {
+    fn default() -> Self {
+        Self { bots: Vec::new(), next_player: FIRST_BOT_PLAYER }
+    }
+}
+
+impl Bots {
+    // Connects a new bot and returns the player it plays as
+    pub fn join(&mut self, brain: impl BotBrain) -> PlayerId {
+        let player = PlayerId(self.next_player);
+        self.next_player += 1;
+        self.bots.push(Bot { player, brain: Box::new(brain) });
+        player
+    }
+
+    pub fn players(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        self.bots.iter().map(|bot| bot.player)
+    }
+
+    // Keeps the bots connected but stops them deciding anything, for when
+    // their orders come from elsewhere (see `session_replay`)
+    #[cfg(feature = "admin")]
+    pub fn silence(&mut self) {
+        for bot in self.bots.iter_mut() {
+            bot.brain = Box::new(IdleBot);
+        }
+    }
+
+    // Disconnects a bot; its colony stays in the world. False if no bot plays as `player`.
+    pub fn kick(&mut self, player: PlayerId) -> bool {
+        let before = self.bots.len();
+        self.bots.retain(|bot| bot.player != player);
+        self.bots.len() != before
+    }
+}
+
+#[derive(Resource)]
+struct SnapshotTimer(Timer);
+
+// Never sends anything
+#[cfg(feature = "admin")]
+struct IdleBot;
+
+#[cfg(feature = "admin")]
+impl BotBrain for IdleBot {
+    fn on_message(&mut self, _me: PlayerId, _message: &NetMessage, _replies: &mut Vec<NetMessage>) {}
+}
+
+// Sends its idle workers to random spots around its queen. Takes any peace or
+// food offered, but never allies with anyone.
+#[derive(Default)]
+pub struct WanderBot {
+    next_order: u32,
+    last_snapshot: Option<u32>,
+    // Seeded with the bot's player id, so a deterministic run or a replayed
+    // session sees the same wandering every time
+    rng: Option<StdRng>,
+}
+
+impl BotBrain for WanderBot {
+    fn on_message(&mut self, me: PlayerId, message: &NetMessage, replies: &mut Vec<NetMessage>) {
+        if let NetMessage::Proposal { proposal, offer, .. } = message {
+            replies.push(NetMessage::Answer { proposal: *proposal, accept: *offer != Offer::Alliance });
+            return;
+        }
+        let NetMessage::UnitSnapshot { tick, units } = message else {
+            return;
+        };
+        // Snapshots travel unreliably; one older than what we acted on is stale
+        if !tick_is_newer(*tick, self.last_snapshot) {
+            return;
+        }
+        self.last_snapshot = Some(*tick);
+        let own: Vec<&UnitState> = units.iter().filter(|unit| unit.player == me).collect();
+        let Some(queen) = own.iter().find(|unit| unit.caste == Caste::Queen) else {
+            return;
+        };
+
+        let idle: Vec<u64> = own
+            .iter()
+            .filter(|unit| unit.caste == Caste::Worker && !unit.moving)
+            .map(|unit| unit.id)
+            .collect();
+        if idle.is_empty() {
+            return;
+        }
+        let rng = self.rng.get_or_insert_with(|| StdRng::seed_from_u64(me.0.into()));
+        let offset = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)) * rng.gen_range(0.0..WANDER_RANGE);
+        replies.push(NetMessage::GroupOrder { order: self.next_order, units: idle, destination: queen.position + offset });
+        self.next_order = self.next_order.wrapping_add(1);
+    }
+}
+
+fn bot_count_from_args() -> usize {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2)
+        .find(|pair| pair[0] == "--bots")
+        .and_then(|pair| pair[1].parse().ok())
+        .unwrap_or(0)
+}
+
+// Every bot starts with a queen and a few workers, spread around a ring
+// between the center colony and the edge of the map
+fn spawn_bot_colonies(
+    mut commands: Commands,
+    bots: Res<Bots>,
+    map_size: Res<MapSize>,
+    placer: SpawnPlacer,
+    spawner: UnitSpawner,
+) {
+    if bots.bots.is_empty() {
+        return;
+    }
+    let ring: Vec<Hex> = Hex::ZERO.ring(map_size.radius * 2 / 3).collect();
+    // Spawns from this frame aren't in the spatial index yet
+    let mut taken: HashSet<Hex> = HashSet::new();
+    for (i, bot) in bots.bots.iter().enumerate() {
+        let home = ring[i * ring.len() / bots.bots.len()];
+        let faction = Faction(bot.player);
+        let mut spots = placer.around(home);
+        for &hex in taken.iter() {
+            spots.reserve(hex);
+        }
+        let Some((queen_hex, queen_pos)) = spots.next() else {
+            warn!("No room for the colony of bot {}", bot.player.0);
+            continue;
+        };
+        spawner.spawn(&mut commands, Caste::Queen, faction, queen_pos);
+        taken.insert(queen_hex);
+        for (hex, pos) in spots.take(BOT_WORKERS) {
+            spawner.spawn(&mut commands, Caste::Worker, faction, pos);
+            taken.insert(hex);
+        }
+    }
+    info!("Started {} bot colonies", bots.bots.len());
+}
+
+// Stand-in for the server's state stream: while we are the authority, every
+// listener gets the position of every unit
+fn broadcast_unit_snapshots(
+    time: Res<Time>,
+    mut timer: ResMut<SnapshotTimer>,
+    connection: Res<NetConnection>,
+    bots: Res<Bots>,
+    unit_q: Query<(Entity, &Faction, &Caste, &Transform, &Path), With<Ant>>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+    mut tick: Local<u32>,
+) {
+    if connection.online || !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    // Nobody else is listening without bots or players who joined us
+    if bots.bots.is_empty() && connection.peers.is_empty() {
+        return;
+    }
+    let units = unit_q
+        .iter()
+        .map(|(entity, faction, caste, transform, path)| UnitState {
+            id: entity.to_bits(),
+            player: faction.0,
+            caste: *caste,
+            position: transform.translation.truncate(),
+            moving: !path.waypoints.is_empty(),
+        })
+        .collect();
+    *tick = tick.wrapping_add(1);
+    outgoing.send(OutgoingMessage::broadcast(NetMessage::UnitSnapshot { tick: *tick, units }));
+}
+
+// The in-process transport: hand each bot what was sent to its player and
+// feed its replies back in as messages from that player
+fn pump_bots(
+    mut bots: ResMut<Bots>,
+    mut outgoing: EventReader<OutgoingMessage>,
+    mut incoming: EventWriter<IncomingMessage>,
+) {
+    let mut replies = Vec::new();
+    for sent in outgoing.read() {
+        for bot in bots.bots.iter_mut() {
+            if sent.to.is_some_and(|to| to != bot.player) {
+                continue;
+            }
+            bot.brain.on_message(bot.player, &sent.message, &mut replies);
+            let player = bot.player;
+            incoming.send_batch(replies.drain(..).map(|message| IncomingMessage { from: player, message }));
+        }
+    }
+}
+
+// Authority side of `GroupOrder`: a squad is accepted only if the sender
+// owns every unit in it. Accepted units are ordered to the destination as a
+// `GameCommand`, the same way a local order moves the selection.
+fn apply_remote_group_orders(
+    connection: Res<NetConnection>,
+    mut incoming: EventReader<IncomingMessage>,
+    unit_q: Query<&Faction, With<Ant>>,
+    mut game_commands: EventWriter<GameCommand>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    for message in incoming.read() {
+        let NetMessage::GroupOrder { order, units, destination } = &message.message else {
+            continue;
+        };
+        if connection.online {
+            continue;
+        }
+
+        let owned = |bits: u64| {
+            Entity::try_from_bits(bits)
+                .ok()
+                .filter(|&entity| unit_q.get(entity).is_ok_and(|faction| faction.0 == message.from))
+        };
+        let squads: Vec<bool> = units.chunks(SQUAD_SIZE).map(|squad| squad.iter().all(|&bits| owned(bits).is_some())).collect();
+
+        let accepted: Vec<Entity> = units
+            .chunks(SQUAD_SIZE)
+            .zip(&squads)
+            .filter(|(_, accepted)| **accepted)
+            .flat_map(|(squad, _)| squad.iter().filter_map(|&bits| owned(bits)))
+            .collect();
+        if !accepted.is_empty() {
+            game_commands.send(GameCommand::Move {
+                player: message.from,
+                units: accepted,
+                destination: *destination,
+                mode: MoveMode::Direct,
+            });
+        }
+        outgoing.send(OutgoingMessage::to(message.from, NetMessage::OrderAck { order: *order, squads }));
+    }
+}
+
+pub struct BotsPlugin;
+
+impl Plugin for BotsPlugin {
+    fn build(&self, app: &mut App) {
+        let mut bots = Bots::default();
+        for _ in 0..bot_count_from_args() {
+            bots.join(WanderBot::default());
+        }
+        app.insert_resource(bots)
+            .insert_resource(SnapshotTimer(Timer::from_seconds(SNAPSHOT_SECS, TimerMode::Repeating)))
+            .add_systems(OnEnter(crate::loading::AppState::InGame), spawn_bot_colonies.after(crate::spawn_units))
+            .add_systems(
+                Update,
+                (broadcast_unit_snapshots, pump_bots, apply_remote_group_orders)
+                    .chain()
+                    .run_if(in_state(crate::loading::AppState::InGame)),
+            );
+    }
+}
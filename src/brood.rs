@@ -0,0 +1,233 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use hexx::Hex;
+use std::collections::BTreeSet;
+
+use crate::coords::HexLayoutExt;
+use crate::culling::CameraView;
+use crate::health::Health;
+use crate::interactions::FoodStores;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::net::LocalPlayer;
+use crate::placement::SpawnPlacer;
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::structures::Structures;
+use crate::succession::Princess;
+use crate::units::UnitSpawner;
+use crate::upgrades::ColonyUpgrades;
+use crate::{Caste, Faction, MainCamera, MapLayout, Queen};
+
+// New ants are raised, not spawned: each queen lays eggs on the hexes next to
+// her while the colony can pay for them and has a free brood slot. An egg
+// hatches into a larva after a while; a larva has to be fed from the food
+// store before it becomes a worker. Brood has little health and can be
+// killed. B over a larva moves it to the front of the feeding queue. A
+// colony with the princess research and no princess raises its next adult as
+// one (see `succession`).
+
+const LAY_SECS: f32 = 15.0;
+const EGG_COST: u32 = 2;
+const EGG_SECS: f32 = 20.0;
+const FEED_SECS: f32 = 5.0;
+// Meals a larva needs before it pupates into an adult
+const LARVA_MEALS: u32 = 3;
+const BROOD_HEALTH: f32 = 10.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BroodStage {
+    Egg,
+    Larva,
+}
+
+#[derive(Component)]
+pub struct Brood {
+    pub stage: BroodStage,
+    hex: Hex,
+    // Egg incubation; unused once hatched
+    timer: Timer,
+    meals: u32,
+    pub prioritized: bool,
+}
+
+#[derive(Component)]
+struct LayTimer(Timer);
+
+#[derive(Resource)]
+struct FeedingTimer(Timer);
+
+fn brood_sprite(stage: BroodStage) -> Sprite {
+    let (hex, size) = match stage {
+        BroodStage::Egg => ("F5F5F5", 5.0),
+        BroodStage::Larva => ("FFE0B2", 8.0),
+    };
+    Sprite {
+        color: Color::from(Srgba::hex(hex).unwrap()),
+        custom_size: Some(Vec2::splat(size)),
+        ..default()
+    }
+}
+
+fn add_lay_timers(mut commands: Commands, new_queens: Query<Entity, Added<Queen>>) {
+    for entity in new_queens.iter() {
+        commands.entity(entity).insert(LayTimer(Timer::from_seconds(LAY_SECS, TimerMode::Repeating)));
+    }
+}
+
+fn lay_eggs(
+    mut commands: Commands,
+    time: Res<Time>,
+    layout: Res<MapLayout>,
+    placer: SpawnPlacer,
+    upgrades: Res<ColonyUpgrades>,
+    structures: Res<Structures>,
+    mut stores: ResMut<FoodStores>,
+    mut queen_q: Query<(&Transform, &Faction, &mut LayTimer), With<Queen>>,
+    brood_q: Query<(&Brood, &Faction)>,
+) {
+    for (transform, faction, mut lay_timer) in queen_q.iter_mut() {
+        if !lay_timer.0.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let brood_count = brood_q.iter().filter(|(_, brood_faction)| *brood_faction == faction).count();
+        if brood_count >= upgrades.brood_slots(faction.0) + structures.brood_slot_bonus(faction.0) {
+            continue;
+        }
+        let Some(food) = stores.0.get_mut(&faction.0).filter(|food| **food >= EGG_COST) else {
+            continue;
+        };
+
+        // Only the ring right around the queen is nursery space
+        let queen_hex = layout.0.world_to_hex(transform.translation.truncate());
+        let mut spots = placer.around(queen_hex);
+        spots.reserve(queen_hex);
+        for (brood, _) in brood_q.iter() {
+            spots.reserve(brood.hex);
+        }
+        let Some((hex, pos)) = spots.next().filter(|(hex, _)| hex.unsigned_distance_to(queen_hex) <= 1) else {
+            continue;
+        };
+
+        *food -= EGG_COST;
+        commands.spawn((
+            SpriteBundle {
+                sprite: brood_sprite(BroodStage::Egg),
+                transform: Transform::from_translation(pos.extend(0.8)),
+                ..default()
+            },
+            Brood {
+                stage: BroodStage::Egg,
+                hex,
+                timer: Timer::from_seconds(EGG_SECS, TimerMode::Once),
+                meals: 0,
+                prioritized: false,
+            },
+            Health::new(BROOD_HEALTH),
+            *faction,
+        ));
+    }
+}
+
+fn hatch_eggs(time: Res<Time>, mut brood_q: Query<(&mut Brood, &mut Sprite)>) {
+    for (mut brood, mut sprite) in brood_q.iter_mut() {
+        if brood.stage == BroodStage::Egg && brood.timer.tick(time.delta()).just_finished() {
+            brood.stage = BroodStage::Larva;
+            *sprite = brood_sprite(BroodStage::Larva);
+        }
+    }
+}
+
+// Every feeding round each larva gets one meal, prioritized larvae first,
+// for as long as its colony's store lasts. Fully fed larvae become workers,
+// or a princess.
+fn feed_larvae(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut feeding: ResMut<FeedingTimer>,
+    mut stores: ResMut<FoodStores>,
+    upgrades: Res<ColonyUpgrades>,
+    spawner: UnitSpawner,
+    local_player: Res<LocalPlayer>,
+    mut brood_q: Query<(Entity, &mut Brood, &Faction, &Transform)>,
+    princess_q: Query<&Faction, With<Princess>>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    if !feeding.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let mut with_princess: BTreeSet<_> = princess_q.iter().map(|faction| faction.0).collect();
+    let mut larvae: Vec<_> = brood_q
+        .iter_mut()
+        .filter(|(_, brood, _, _)| brood.stage == BroodStage::Larva)
+        .collect();
+    larvae.sort_by_key(|(_, brood, _, _)| !brood.prioritized);
+
+    for (entity, mut brood, faction, transform) in larvae {
+        let Some(food) = stores.0.get_mut(&faction.0).filter(|food| **food > 0) else {
+            continue;
+        };
+        *food -= 1;
+        brood.meals += 1;
+        if brood.meals >= LARVA_MEALS {
+            commands.entity(entity).despawn_recursive();
+            let adult = spawner.spawn(&mut commands, Caste::Worker, *faction, transform.translation.truncate());
+            if upgrades.has_princess_research(faction.0) && with_princess.insert(faction.0) {
+                commands.entity(adult).insert(Princess);
+            }
+            if faction.0 == local_player.0 {
+                sounds.send(PlaySound::ui(SoundEffect::ProductionComplete));
+            }
+        }
+    }
+}
+
+fn prioritize_brood(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    layout: Res<MapLayout>,
+    mut brood_q: Query<&mut Brood>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::PrioritizeBrood) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_q.get_single()) else {
+        return;
+    };
+    let Some(world_pos) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let hex = layout.0.world_to_hex(world_pos);
+    for mut brood in brood_q.iter_mut() {
+        if brood.hex == hex {
+            brood.prioritized = !brood.prioritized;
+        }
+    }
+}
+
+fn draw_brood_priority(mut gizmos: Gizmos, view: Res<CameraView>, brood_q: Query<(&Brood, &Transform)>) {
+    let priority_color = Color::from(Srgba::hex("FFD54F").unwrap());
+    for (brood, transform) in brood_q.iter() {
+        let pos = transform.translation.truncate();
+        if brood.prioritized && view.overlaps_circle(pos, 7.0) {
+            gizmos.circle_2d(pos, 7.0, priority_color);
+        }
+    }
+}
+
+pub struct BroodPlugin;
+
+impl Plugin for BroodPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FeedingTimer(Timer::from_seconds(FEED_SECS, TimerMode::Repeating)))
+            .register_action(Action::PrioritizeBrood, &[KeyCode::KeyB])
+            .add_systems(
+                Update,
+                (add_lay_timers, lay_eggs, hatch_eggs, feed_larvae, prioritize_brood, draw_brood_priority)
+                    .chain()
+                    .run_if(in_state(crate::loading::AppState::InGame)),
+            );
+    }
+}
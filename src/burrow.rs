@@ -0,0 +1,390 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::ColliderDisabled;
+use hexx::{Hex, HexLayout};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::coords::HexLayoutExt;
+use crate::culling::CameraView;
+use crate::game_commands::AttackTarget;
+use crate::interactions::Engaged;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::pathfinding::{PathPriority, PathQueue};
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::{chunk_of, Faction, MapLayout, Path, TargetPosition};
+
+// Underground tunnels between nest entrances. Workers dig them: holding T and
+// tapping open ground sends them down the colony's nearest entrance to dig a
+// tunnel hex by hex towards the tapped spot, which opens as a new entrance.
+// Ants that enter one of their colony's entrances disappear for a transit time
+// proportional to the tunnel length and emerge at the other end, so digging
+// long tunnels pays off as fast travel. Underground ants have their colliders
+// off: nothing on the surface can pick them or bite them, and they bite
+// nothing.
+
+const TRANSIT_SECS_PER_HEX: f32 = 0.15;
+const DIG_SECS_PER_HEX: f32 = 1.5;
+// Farthest a new entrance can be from the entrance its tunnel starts at
+const MAX_TUNNEL_HEXES: u32 = 12;
+// Max ants in transit out of a single entrance; the rest queue at the entrance
+const ENTRANCE_CAPACITY: usize = 4;
+
+#[derive(Resource, Default)]
+pub struct BurrowNetwork {
+    dug: HashSet<Hex>,
+    // Each entrance and the colony that dug it
+    entrances: HashMap<Hex, PlayerId>,
+    // Chunks changed since the last save
+    dirty_chunks: HashSet<Hex>,
+    // Chunks changed since they were last sent to other players
+    unsynced_chunks: HashSet<Hex>,
+    // Hexes dug since the last alert check (see `alerts`)
+    newly_dug: Vec<Hex>,
+    // Hexes each colony's workers have dug, for its objectives
+    dug_by: HashMap<PlayerId, usize>,
+}
+
+impl BurrowNetwork {
+    pub fn dig(&mut self, hex: Hex) {
+        if self.dug.insert(hex) {
+            self.newly_dug.push(hex);
+        }
+        self.dirty_chunks.insert(chunk_of(hex));
+        self.unsynced_chunks.insert(chunk_of(hex));
+    }
+
+    // A hex dug by a colony's workers, rather than loaded or received
+    pub fn dig_for(&mut self, hex: Hex, player: PlayerId) {
+        if !self.is_dug(hex) {
+            *self.dug_by.entry(player).or_default() += 1;
+        }
+        self.dig(hex);
+    }
+
+    pub fn add_entrance(&mut self, hex: Hex, owner: PlayerId) {
+        self.dig(hex);
+        self.entrances.insert(hex, owner);
+    }
+
+    pub fn take_dirty_chunks(&mut self) -> HashSet<Hex> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
+
+    pub fn take_unsynced_chunks(&mut self) -> HashSet<Hex> {
+        std::mem::take(&mut self.unsynced_chunks)
+    }
+
+    pub fn take_newly_dug(&mut self) -> Vec<Hex> {
+        std::mem::take(&mut self.newly_dug)
+    }
+
+    pub fn is_dug(&self, hex: Hex) -> bool {
+        self.dug.contains(&hex)
+    }
+
+    // Dug hexes and entrances (with their owners) inside one chunk, sorted so
+    // saves are stable
+    pub fn chunk_contents(&self, chunk: Hex) -> (Vec<Hex>, Vec<(Hex, PlayerId)>) {
+        let mut dug: Vec<Hex> = self.dug.iter().copied().filter(|&h| chunk_of(h) == chunk).collect();
+        dug.sort_by_key(|h| (h.x, h.y));
+        let mut entrances: Vec<(Hex, PlayerId)> =
+            self.entrances.iter().map(|(&h, &owner)| (h, owner)).filter(|&(h, _)| chunk_of(h) == chunk).collect();
+        entrances.sort_by_key(|(h, _)| (h.x, h.y));
+        (dug, entrances)
+    }
+
+    // Overwrite one chunk with saved or received contents (doesn't mark it dirty)
+    pub fn replace_chunk(&mut self, chunk: Hex, dug: &[Hex], entrances: &[(Hex, PlayerId)]) {
+        self.dug.retain(|&h| chunk_of(h) != chunk);
+        self.entrances.retain(|&h, _| chunk_of(h) != chunk);
+        self.dug.extend(dug.iter().copied());
+        self.entrances.extend(entrances.iter().copied());
+    }
+
+    pub fn dug_by(&self, player: PlayerId) -> usize {
+        self.dug_by.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn entrance_count(&self) -> usize {
+        self.entrances.len()
+    }
+
+    pub fn is_entrance(&self, hex: Hex) -> bool {
+        self.entrances.contains_key(&hex)
+    }
+
+    pub fn entrance_owner(&self, hex: Hex) -> Option<PlayerId> {
+        self.entrances.get(&hex).copied()
+    }
+
+    fn owned_entrances(&self, owner: PlayerId) -> impl Iterator<Item = Hex> + '_ {
+        self.entrances.iter().filter(move |&(_, &o)| o == owner).map(|(&hex, _)| hex)
+    }
+
+    // Tunnel length in hexes, walking dug hexes only (BFS over the tunnel graph)
+    pub fn tunnel_length(&self, from: Hex, to: Hex) -> Option<u32> {
+        if !self.dug.contains(&from) || !self.dug.contains(&to) {
+            return None;
+        }
+
+        let mut visited = HashSet::from([from]);
+        let mut frontier = VecDeque::from([(from, 0)]);
+        while let Some((hex, dist)) = frontier.pop_front() {
+            if hex == to {
+                return Some(dist);
+            }
+            for neighbor in hex.all_neighbors() {
+                if self.dug.contains(&neighbor) && visited.insert(neighbor) {
+                    frontier.push_back((neighbor, dist + 1));
+                }
+            }
+        }
+        None
+    }
+
+    // The colony's closest entrance (on the surface) to `from` that has a
+    // tunnel to `exit`
+    pub fn nearest_connected_entrance(&self, from: Hex, exit: Hex, owner: PlayerId) -> Option<Hex> {
+        self.owned_entrances(owner)
+            .filter(|&entrance| entrance != exit && self.tunnel_length(entrance, exit).is_some())
+            .min_by_key(|entrance| (entrance.unsigned_distance_to(from), entrance.x, entrance.y))
+    }
+
+    // The colony's entrance a tunnel out to `target` would start from, if one
+    // is close enough
+    pub fn nearest_entrance_within_reach(&self, target: Hex, owner: PlayerId) -> Option<Hex> {
+        self.owned_entrances(owner)
+            .filter(|entrance| entrance.unsigned_distance_to(target) <= MAX_TUNNEL_HEXES)
+            .min_by_key(|entrance| (entrance.unsigned_distance_to(target), entrance.x, entrance.y))
+    }
+}
+
+// Walking to `entry` in order to take the tunnel to `exit`, or to dig one
+// out to it
+#[derive(Component)]
+pub struct FastTravel {
+    entry: Hex,
+    exit: Hex,
+    dig: bool,
+}
+
+#[derive(Component)]
+pub struct InTunnel {
+    entry: Hex,
+    exit: Hex,
+    timer: Timer,
+}
+
+// A worker underground digging its tunnel out to the `InTunnel` exit; the
+// transit only starts once the exit is dug
+#[derive(Component)]
+pub struct Digging {
+    timer: Timer,
+}
+
+// Every colony starts with one tunnel running from the nest out to a second
+// entrance
+pub fn seed_starter_tunnel(mut network: ResMut<BurrowNetwork>, local_player: Res<LocalPlayer>) {
+    let nest_entrance = Hex::new(-2, 0);
+    let far_entrance = Hex::new(6, -3);
+    for hex in nest_entrance.line_to(far_entrance) {
+        network.dig(hex);
+    }
+    network.add_entrance(nest_entrance, local_player.0);
+    network.add_entrance(far_entrance, local_player.0);
+    // The starter tunnel is part of the base map, not a change to save or sync
+    network.take_dirty_chunks();
+    network.take_unsynced_chunks();
+}
+
+// Tap one of the colony's entrances while holding T: the ordered units walk to
+// the nearest connected entrance and tunnel through to the tapped one. Tap
+// open ground (`open` says whether it is) and the ordered workers walk to the
+// colony's entrance nearest to it and dig a tunnel out there instead.
+pub fn order_tunnel(
+    world_pos: Vec2,
+    player: PlayerId,
+    ordered: &[(Entity, Hex)],
+    is_worker: impl Fn(Entity) -> bool,
+    open: impl Fn(Hex) -> bool,
+    commands: &mut Commands,
+    network: &BurrowNetwork,
+    path_queue: &mut PathQueue,
+    layout: &HexLayout,
+) {
+    let exit = layout.world_to_hex(world_pos);
+    match network.entrance_owner(exit) {
+        Some(owner) if owner == player => {
+            for &(entity, current_hex) in ordered {
+                let Some(entry) = network.nearest_connected_entrance(current_hex, exit, player) else {
+                    continue;
+                };
+                path_queue.request(entity, current_hex, entry, PathPriority::PlayerOrder);
+                commands.entity(entity).insert(FastTravel { entry, exit, dig: false });
+            }
+        }
+        Some(_) => {}
+        None => {
+            let Some(entry) = network.nearest_entrance_within_reach(exit, player).filter(|_| open(exit)) else {
+                return;
+            };
+            for &(entity, current_hex) in ordered.iter().filter(|&&(entity, _)| is_worker(entity)) {
+                path_queue.request(entity, current_hex, entry, PathPriority::PlayerOrder);
+                commands.entity(entity).insert(FastTravel { entry, exit, dig: true });
+            }
+        }
+    }
+}
+
+// Underground: hidden, and with the unit's colliders (its body and its
+// engagement sensor) off so the surface can't reach it nor it the surface
+fn go_underground(commands: &mut Commands, entity: Entity, children: Option<&Children>) {
+    commands.entity(entity).remove::<(FastTravel, Engaged, AttackTarget)>().insert((Visibility::Hidden, ColliderDisabled));
+    for &child in children.into_iter().flatten() {
+        commands.entity(child).insert(ColliderDisabled);
+    }
+}
+
+fn come_up(commands: &mut Commands, entity: Entity, children: Option<&Children>) {
+    commands.entity(entity).remove::<(InTunnel, ColliderDisabled)>();
+    for &child in children.into_iter().flatten() {
+        commands.entity(child).remove::<ColliderDisabled>();
+    }
+}
+
+fn enter_tunnels(
+    mut commands: Commands,
+    network: Res<BurrowNetwork>,
+    layout: Res<MapLayout>,
+    path_queue: Res<PathQueue>,
+    travellers: Query<(Entity, &Faction, &Transform, &TargetPosition, &Path, &FastTravel, Option<&Children>)>,
+    in_transit: Query<&InTunnel>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    let mut load: HashMap<Hex, usize> = HashMap::new();
+    for tunnel in in_transit.iter() {
+        *load.entry(tunnel.entry).or_default() += 1;
+    }
+
+    for (entity, faction, transform, target, path, travel, children) in travellers.iter() {
+        // Still walking to the entrance
+        let pos = transform.translation.truncate();
+        if !path.waypoints.is_empty() || path_queue.is_pending(entity) || pos.distance(target.0) > 2.0 {
+            continue;
+        }
+        let current_hex = layout.0.world_to_hex(pos);
+        if current_hex != travel.entry {
+            commands.entity(entity).remove::<FastTravel>();
+            continue;
+        }
+
+        // Tunnel was cut, or the entrance lost, while we were walking over
+        let length = network.tunnel_length(travel.entry, travel.exit);
+        if network.entrance_owner(travel.entry) != Some(faction.0) || (!travel.dig && length.is_none()) {
+            commands.entity(entity).remove::<FastTravel>();
+            continue;
+        }
+
+        let count = load.entry(travel.entry).or_default();
+        if *count >= ENTRANCE_CAPACITY {
+            continue;
+        }
+        *count += 1;
+
+        go_underground(&mut commands, entity, children);
+        let transit = length.unwrap_or(0) as f32 * TRANSIT_SECS_PER_HEX;
+        commands.entity(entity).insert(InTunnel {
+            entry: travel.entry,
+            exit: travel.exit,
+            timer: Timer::from_seconds(transit, TimerMode::Once),
+        });
+        if travel.dig {
+            commands.entity(entity).insert(Digging { timer: Timer::from_seconds(DIG_SECS_PER_HEX, TimerMode::Repeating) });
+        }
+        sounds.send(PlaySound::at(SoundEffect::Dig, pos));
+    }
+}
+
+// Diggers take the next undug hex on the line from their entrance to the exit
+// every few seconds, so several workers on one tunnel share the work. Once the
+// exit is dug it opens as the colony's entrance and they set off through the
+// finished tunnel to come up there.
+fn dig_tunnels(
+    mut commands: Commands,
+    time: Res<Time>,
+    layout: Res<MapLayout>,
+    mut network: ResMut<BurrowNetwork>,
+    mut digger_q: Query<(Entity, &Faction, &mut InTunnel, &mut Digging)>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for (entity, faction, mut tunnel, mut digging) in digger_q.iter_mut() {
+        if !digging.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        if let Some(hex) = tunnel.entry.line_to(tunnel.exit).find(|&hex| !network.is_dug(hex)) {
+            network.dig_for(hex, faction.0);
+            sounds.send(PlaySound::at(SoundEffect::Dig, layout.0.hex_to_world(hex)));
+        }
+        if !network.is_dug(tunnel.exit) {
+            continue;
+        }
+        if !network.is_entrance(tunnel.exit) {
+            network.add_entrance(tunnel.exit, faction.0);
+        }
+        let length = network.tunnel_length(tunnel.entry, tunnel.exit).unwrap_or(0);
+        tunnel.timer = Timer::from_seconds(length as f32 * TRANSIT_SECS_PER_HEX, TimerMode::Once);
+        commands.entity(entity).remove::<Digging>();
+    }
+}
+
+fn travel_tunnels(
+    mut commands: Commands,
+    time: Res<Time>,
+    layout: Res<MapLayout>,
+    mut q: Query<
+        (Entity, &mut InTunnel, &mut Transform, &mut TargetPosition, &mut Visibility, Option<&Children>),
+        Without<Digging>,
+    >,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for (entity, mut tunnel, mut transform, mut target, mut visibility, children) in q.iter_mut() {
+        if !tunnel.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        let exit_vec = layout.0.hex_to_world(tunnel.exit);
+        transform.translation.x = exit_vec.x;
+        transform.translation.y = exit_vec.y;
+        target.0 = exit_vec;
+        *visibility = Visibility::Inherited;
+        come_up(&mut commands, entity, children);
+        sounds.send(PlaySound::at(SoundEffect::Dig, exit_vec));
+    }
+}
+
+fn draw_burrows(mut gizmos: Gizmos, network: Res<BurrowNetwork>, layout: Res<MapLayout>, view: Res<CameraView>) {
+    let tunnel_color = Color::from(Srgba::hex("5C3A1E").unwrap());
+    let entrance_color = Color::from(Srgba::hex("D2A679").unwrap());
+
+    for &hex in network.dug.iter() {
+        let center = layout.0.hex_to_world(hex);
+        if view.overlaps_circle(center, 4.0) {
+            gizmos.circle_2d(center, 4.0, tunnel_color);
+        }
+    }
+    for &hex in network.entrances.keys() {
+        let center = layout.0.hex_to_world(hex);
+        if view.overlaps_circle(center, 12.0) {
+            gizmos.circle_2d(center, 12.0, entrance_color);
+        }
+    }
+}
+
+pub struct BurrowPlugin;
+
+impl Plugin for BurrowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BurrowNetwork>()
+            .add_systems(Startup, seed_starter_tunnel.before(crate::save::load_world))
+            .add_systems(Update, ((enter_tunnels, dig_tunnels, travel_tunnels).chain(), draw_burrows));
+    }
+}
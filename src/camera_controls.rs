@@ -0,0 +1,234 @@
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use bevy::transform::TransformSystem;
+
+use crate::input::pointer_over_ui;
+use crate::settings::Settings;
+use crate::{MainCamera, MapLayout, MapSize};
+
+// Mouse-driven camera control on top of the keyboard panning in `camera_movement`:
+// pushing the cursor against a window edge pans that way, and holding the middle
+// button drags the world. Both scale with zoom so they feel the same at any level.
+// The scroll wheel zooms toward the cursor, unless the cursor is over a
+// panel. Speeds and zoom range are in `Settings::camera`.
+
+// Exponential smoothing rate toward the target zoom
+const ZOOM_SMOOTHING: f32 = 12.0;
+// Pixel-precise touchpads report roughly this many pixels per notch
+const PIXELS_PER_NOTCH: f32 = 100.0;
+
+// Sent by every camera control that moved or zoomed the view from player input,
+// so anything steering the camera itself (following a shared camera) backs off
+#[derive(Event)]
+pub struct ManualCameraMove;
+
+// The systems that move the camera from player input
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CameraInput;
+
+// Allowed `OrthographicProjection::scale` range, derived from the map and window:
+// zoomed out the whole map just fits (times `max_zoom_out`), zoomed in a few
+// hexes still fill the view.
+// Every camera control clamps against this instead of its own constants.
+#[derive(Resource, Clone, Copy)]
+pub struct ZoomLimits {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for ZoomLimits {
+    fn default() -> Self {
+        Self { min: 0.1, max: 5.0 }
+    }
+}
+
+impl ZoomLimits {
+    pub fn clamp(&self, scale: f32) -> f32 {
+        scale.clamp(self.min, self.max)
+    }
+}
+
+// Recomputed every frame so growing maps and window resizes are picked up
+fn update_zoom_limits(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    settings: Res<Settings>,
+    mut limits: ResMut<ZoomLimits>,
+) {
+    let window = windows.single();
+    let screen = Vec2::new(window.width(), window.height());
+    if screen.min_element() <= 0.0 {
+        return; // Minimized
+    }
+
+    let map_rect = map_size.world_rect(&layout.0);
+    let max = (map_rect.size() / screen).max_element() * settings.camera.max_zoom_out;
+    // Hex height across flats/points is about twice the layout scale
+    let hex_size = layout.0.scale.max_element() * 2.0;
+    let min = (settings.camera.min_visible_hexes * hex_size / screen.min_element()).min(max);
+    if limits.min != min || limits.max != max {
+        *limits = ZoomLimits { min, max };
+    }
+}
+
+fn edge_pan(
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+    time: Res<Time<Real>>,
+    mut moved: EventWriter<ManualCameraMove>,
+) {
+    let settings = &settings.camera;
+    if !settings.edge_pan || settings.edge_pan_margin <= 0.0 {
+        return;
+    }
+    let window = windows.single();
+    // Cursor outside the window (or touch-only device): no edge panning
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+
+    // How far into the margin the cursor is, 0 at the inner border to 1 at the edge
+    let depth = |distance_to_edge: f32| (1.0 - distance_to_edge / settings.edge_pan_margin).clamp(0.0, 1.0);
+    let direction = Vec2::new(
+        depth(window.width() - cursor.x) - depth(cursor.x),
+        // Viewport y grows downward
+        depth(cursor.y) - depth(window.height() - cursor.y),
+    );
+    if direction == Vec2::ZERO {
+        return;
+    }
+
+    let (mut transform, projection) = camera_q.single_mut();
+    let step = direction * settings.edge_pan_speed * projection.scale * time.delta_seconds();
+    transform.translation += step.extend(0.0);
+    moved.send(ManualCameraMove);
+}
+
+fn middle_mouse_drag(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut camera_q: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+    mut moved: EventWriter<ManualCameraMove>,
+) {
+    let delta: Vec2 = motion.read().map(|event| event.delta).sum();
+    if !mouse_input.pressed(MouseButton::Middle) || delta == Vec2::ZERO {
+        return;
+    }
+
+    // Grab-and-drag: the world moves with the cursor
+    let (mut transform, projection) = camera_q.single_mut();
+    transform.translation.x -= delta.x * projection.scale;
+    transform.translation.y += delta.y * projection.scale;
+    moved.send(ManualCameraMove);
+}
+
+// Keep the view over the map: zoom stays within `ZoomLimits`, and panning stops
+// at the map's edge. Runs after every camera system has moved.
+fn clamp_camera_to_map(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    limits: Res<ZoomLimits>,
+    mut zoom: ResMut<ScrollZoom>,
+) {
+    let window = windows.single();
+    let screen = Vec2::new(window.width(), window.height());
+    if screen.min_element() <= 0.0 {
+        return; // Minimized
+    }
+
+    let map_rect = map_size.world_rect(&layout.0);
+    let (mut transform, mut projection) = camera_q.single_mut();
+
+    projection.scale = limits.clamp(projection.scale);
+    // Don't let a scroll zoom keep easing toward a level it can never reach
+    if let Some(target) = zoom.target_scale {
+        zoom.target_scale = Some(limits.clamp(target));
+    }
+
+    // Allowed range for the camera center; collapses to the map center on an
+    // axis where the view is already wider than the map
+    let half_view = screen * projection.scale / 2.0;
+    let center = map_rect.center();
+    let min = (map_rect.min + half_view).min(center);
+    let max = (map_rect.max - half_view).max(center);
+    transform.translation.x = transform.translation.x.clamp(min.x, max.x);
+    transform.translation.y = transform.translation.y.clamp(min.y, max.y);
+}
+
+// Zoom level the scroll wheel is easing toward, if a scroll zoom is in progress
+#[derive(Resource, Default)]
+struct ScrollZoom {
+    target_scale: Option<f32>,
+}
+
+fn scroll_zoom(
+    mut wheel: EventReader<MouseWheel>,
+    mut zoom: ResMut<ScrollZoom>,
+    limits: Res<ZoomLimits>,
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    ui_q: Query<&Interaction, With<Node>>,
+    time: Res<Time<Real>>,
+    mut moved: EventWriter<ManualCameraMove>,
+) {
+    let (mut transform, mut projection) = camera_q.single_mut();
+
+    let over_ui = pointer_over_ui(&ui_q);
+    let notches: f32 = wheel
+        .read()
+        .filter(|_| !over_ui)
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_NOTCH,
+        })
+        .sum();
+    if notches != 0.0 {
+        let current = zoom.target_scale.unwrap_or(projection.scale);
+        // Scrolling up zooms in
+        let target = current * (-notches * settings.camera.scroll_zoom_step).exp();
+        zoom.target_scale = Some(limits.clamp(target));
+        moved.send(ManualCameraMove);
+    }
+
+    let Some(target) = zoom.target_scale else {
+        return;
+    };
+    let t = 1.0 - (-ZOOM_SMOOTHING * time.delta_seconds()).exp();
+    let mut new_scale = projection.scale + (target - projection.scale) * t;
+    if (new_scale - target).abs() < 0.001 {
+        new_scale = target;
+        zoom.target_scale = None;
+    }
+
+    // Keep the world point under the cursor fixed (screen center without a cursor)
+    let window = windows.single();
+    if let Some(cursor) = window.cursor_position() {
+        let offset = Vec2::new(cursor.x - window.width() / 2.0, window.height() / 2.0 - cursor.y);
+        let shift = offset * (projection.scale - new_scale);
+        transform.translation.x += shift.x;
+        transform.translation.y += shift.y;
+    }
+    projection.scale = new_scale;
+}
+
+pub struct CameraControlsPlugin;
+
+impl Plugin for CameraControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScrollZoom>()
+            .init_resource::<ZoomLimits>()
+            .add_event::<ManualCameraMove>()
+            .add_systems(
+                Update,
+                (update_zoom_limits, (edge_pan, middle_mouse_drag, scroll_zoom).in_set(CameraInput)).chain(),
+            )
+            .add_systems(PostUpdate, clamp_camera_to_map.before(TransformSystem::TransformPropagate));
+    }
+}
@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::camera_controls::{CameraInput, ManualCameraMove};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::locale::Locale;
+use crate::net::{tick_is_newer, IncomingMessage, LocalPlayer, NetMessage, OutgoingMessage, PlayerId};
+use crate::{Faction, MainCamera, Queen, Relation, Teams};
+
+// "Look at my screen": while sharing is on, our camera is streamed to allies at a
+// low rate; an ally can opt in to follow it and drop out again instantly.
+// Snapshots only go to allies and are only followed while the sender is one.
+// A shared camera is forgotten when its owner turns sharing off, or when no
+// snapshot has arrived for a while because they left or the link dropped.
+
+const SHARE_INTERVAL_SECS: f32 = 0.1;
+// Snapshots are sent ten times a second, so this is many lost in a row
+const STALE_SECS: f64 = 2.0;
+// Exponential smoothing rate used to ease the follower camera between snapshots
+const FOLLOW_SMOOTHING: f32 = 10.0;
+
+#[derive(Resource)]
+struct CameraShare {
+    broadcasting: bool,
+    send_timer: Timer,
+    // Numbers our snapshots so followers can drop late ones
+    tick: u32,
+}
+
+impl Default for CameraShare {
+    fn default() -> Self {
+        Self {
+            broadcasting: false,
+            send_timer: Timer::from_seconds(SHARE_INTERVAL_SECS, TimerMode::Repeating),
+            tick: 0,
+        }
+    }
+}
+
+// Latest camera snapshot received from a player that is sharing
+struct SharedCamera {
+    tick: u32,
+    position: Vec2,
+    scale: f32,
+    // Real time it arrived, to notice when they stop sending
+    received: f64,
+}
+
+#[derive(Resource, Default)]
+struct SharedCameras(HashMap<PlayerId, SharedCamera>);
+
+// Players whose colonies are allied with ours
+fn allies(teams: &Teams, local_player: PlayerId, queen_q: &Query<&Faction, With<Queen>>) -> Vec<PlayerId> {
+    queen_q
+        .iter()
+        .map(|faction| faction.0)
+        .filter(|&player| teams.relation(local_player, player) == Relation::Ally)
+        .collect()
+}
+
+#[derive(Resource, Default)]
+struct CameraFollow {
+    target: Option<PlayerId>,
+}
+
+#[derive(Component)]
+struct CameraShareIndicator;
+
+fn setup_camera_share_indicator(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 18.0,
+                color: Color::from(Srgba::hex("00BFFF").unwrap()),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        }),
+        CameraShareIndicator,
+    ));
+}
+
+fn camera_share_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut share: ResMut<CameraShare>,
+    mut follow: ResMut<CameraFollow>,
+    shared: Res<SharedCameras>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+    queen_q: Query<&Faction, With<Queen>>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+    mut moved: EventReader<ManualCameraMove>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::ShareCamera) {
+        share.broadcasting = !share.broadcasting;
+        if !share.broadcasting {
+            for ally in allies(&teams, local_player.0, &queen_q) {
+                outgoing.send(OutgoingMessage::to(ally, NetMessage::CameraShareStopped));
+            }
+        }
+    }
+
+    if bindings.just_pressed(&keyboard_input, Action::FollowCamera) {
+        follow.target = match follow.target {
+            Some(_) => None,
+            // Follow an ally who is sharing (lowest id first, so the choice is stable)
+            None => shared
+                .0
+                .keys()
+                .filter(|&&player| teams.relation(local_player.0, player) == Relation::Ally)
+                .min_by_key(|id| id.0)
+                .copied(),
+        };
+    }
+
+    // Any manual camera input (keys, mouse, touch or gamepad) is an instant opt-out
+    let manual = moved.read().count() > 0;
+    if manual || bindings.pressed(&keyboard_input, Action::StopFollowing) {
+        follow.target = None;
+    }
+}
+
+fn broadcast_camera(
+    time: Res<Time>,
+    mut share: ResMut<CameraShare>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+    queen_q: Query<&Faction, With<Queen>>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    if !share.broadcasting {
+        return;
+    }
+    if !share.send_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let (transform, projection) = camera_q.single();
+    share.tick = share.tick.wrapping_add(1);
+    let snapshot = NetMessage::CameraState {
+        tick: share.tick,
+        position: transform.translation.truncate(),
+        scale: projection.scale,
+    };
+    for ally in allies(&teams, local_player.0, &queen_q) {
+        outgoing.send(OutgoingMessage::to(ally, snapshot.clone()));
+    }
+}
+
+fn receive_shared_cameras(
+    real_time: Res<Time<Real>>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut shared: ResMut<SharedCameras>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+) {
+    let now = real_time.elapsed_seconds_f64();
+    for message in incoming.read() {
+        match message.message {
+            NetMessage::CameraState { tick, position, scale } => {
+                if teams.relation(local_player.0, message.from) != Relation::Ally {
+                    continue;
+                }
+                // Sent unreliably, so an older snapshot may arrive after a newer one
+                let last = shared.0.get(&message.from).map(|camera| camera.tick);
+                if tick_is_newer(tick, last) {
+                    shared.0.insert(message.from, SharedCamera { tick, position, scale, received: now });
+                }
+            }
+            NetMessage::CameraShareStopped => {
+                shared.0.remove(&message.from);
+            }
+            _ => {}
+        }
+    }
+
+    // Also covers allies who left without saying so, or became enemies since
+    shared.0.retain(|&player, camera| {
+        now - camera.received < STALE_SECS && teams.relation(local_player.0, player) == Relation::Ally
+    });
+}
+
+fn follow_shared_camera(
+    time: Res<Time>,
+    mut follow: ResMut<CameraFollow>,
+    shared: Res<SharedCameras>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let Some(target) = follow.target else {
+        return;
+    };
+    let Some(&SharedCamera { position, scale, .. }) = shared.0.get(&target) else {
+        // They stopped sharing, left or are no longer allied
+        follow.target = None;
+        return;
+    };
+
+    let (mut transform, mut projection) = camera_q.single_mut();
+    let t = 1.0 - (-FOLLOW_SMOOTHING * time.delta_seconds()).exp();
+    let current = transform.translation.truncate();
+    let next = current.lerp(position, t);
+    transform.translation.x = next.x;
+    transform.translation.y = next.y;
+    projection.scale += (scale - projection.scale) * t;
+}
+
+fn update_camera_share_indicator(
+    share: Res<CameraShare>,
+    follow: Res<CameraFollow>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<CameraShareIndicator>>,
+) {
+    let mut lines = Vec::new();
+    if share.broadcasting {
+        lines.push(locale.get("camera-share.sharing").to_string());
+    }
+    if let Some(target) = follow.target {
+        lines.push(locale.format("camera-share.following", &[("player", &target.0)]));
+    }
+
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+pub struct CameraSharePlugin;
+
+impl Plugin for CameraSharePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShare>()
+            .register_action(Action::ShareCamera, &[KeyCode::F8])
+            .register_action(Action::FollowCamera, &[KeyCode::F9])
+            .register_action(Action::StopFollowing, &[KeyCode::Escape])
+            .init_resource::<SharedCameras>()
+            .init_resource::<CameraFollow>()
+            .add_systems(Startup, setup_camera_share_indicator)
+            .add_systems(
+                Update,
+                (
+                    camera_share_input.after(CameraInput),
+                    broadcast_camera,
+                    receive_shared_cameras,
+                    follow_shared_camera.after(receive_shared_cameras).after(camera_share_input),
+                    update_camera_share_indicator,
+                ),
+            );
+    }
+}
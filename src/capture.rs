@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use bevy::render::view::screenshot::ScreenshotManager;
+use bevy::window::PrimaryWindow;
+use std::fs;
+use std::path::Path;
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::locale::Localized;
+
+// Screenshots and timelapses. F12 saves the current frame as a PNG in
+// `screenshots/` (the browser build downloads it instead). F6 starts or stops
+// a timelapse: one frame every `TIMELAPSE_MINUTES` of simulated time, numbered
+// in a folder of its own under `screenshots/`, ready to stitch into a video of
+// the colony growing. Simulated time stops while paused and runs faster at
+// higher game speeds, so the frames stay evenly spaced in game. `timelapse
+// <minutes>` starts one at another interval, `timelapse stop` ends it.
+
+const SCREENSHOT_DIR: &str = "screenshots";
+const TIMELAPSE_MINUTES: f32 = 1.0;
+
+struct TimelapseRun {
+    folder: String,
+    interval_secs: f32,
+    since_last_secs: f32,
+    frames: u32,
+}
+
+#[derive(Resource, Default)]
+struct Timelapse(Option<TimelapseRun>);
+
+impl Timelapse {
+    fn start(&mut self, minutes: f32) -> String {
+        let folder = first_free(|n| format!("{SCREENSHOT_DIR}/timelapse-{n}"));
+        create_dir(&folder);
+        self.0 = Some(TimelapseRun {
+            folder: folder.clone(),
+            interval_secs: minutes * 60.0,
+            // The first frame is taken right away
+            since_last_secs: minutes * 60.0,
+            frames: 0,
+        });
+        folder
+    }
+
+    // Number of frames taken, if one was running
+    fn stop(&mut self) -> Option<u32> {
+        self.0.take().map(|run| run.frames)
+    }
+}
+
+// `name(n)` for the lowest `n` that doesn't exist yet
+fn first_free(name: impl Fn(u32) -> String) -> String {
+    (1..).map(name).find(|path| !Path::new(path).exists()).unwrap()
+}
+
+// Saving doesn't create the folder. The browser build downloads screenshots
+// instead and has no folders to make.
+fn create_dir(path: &str) {
+    if cfg!(feature = "native") {
+        if let Err(err) = fs::create_dir_all(path) {
+            warn!("Could not create {path}: {err}");
+        }
+    }
+}
+
+fn save_frame(manager: &mut ScreenshotManager, window: Entity, path: &str) {
+    if let Err(err) = manager.save_screenshot_to_disk(window, path) {
+        warn!("Could not save {path}: {err}");
+    }
+}
+
+fn capture_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    window_q: Query<Entity, With<PrimaryWindow>>,
+    mut manager: ResMut<ScreenshotManager>,
+    mut timelapse: ResMut<Timelapse>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::Screenshot) {
+        let Ok(window) = window_q.get_single() else {
+            return;
+        };
+        create_dir(SCREENSHOT_DIR);
+        let path = first_free(|n| format!("{SCREENSHOT_DIR}/screenshot-{n}.png"));
+        save_frame(&mut manager, window, &path);
+        info!("Saved {path}");
+    }
+    if bindings.just_pressed(&keyboard_input, Action::ToggleTimelapse) {
+        match timelapse.stop() {
+            Some(frames) => info!("Timelapse stopped after {frames} frames"),
+            None => info!("Timelapse started in {}", timelapse.start(TIMELAPSE_MINUTES)),
+        }
+    }
+}
+
+fn record_timelapse(
+    time: Res<Time>,
+    window_q: Query<Entity, With<PrimaryWindow>>,
+    mut manager: ResMut<ScreenshotManager>,
+    mut timelapse: ResMut<Timelapse>,
+) {
+    let Some(run) = timelapse.0.as_mut() else {
+        return;
+    };
+    run.since_last_secs += time.delta_seconds();
+    if run.since_last_secs < run.interval_secs {
+        return;
+    }
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    run.since_last_secs -= run.interval_secs;
+    run.frames += 1;
+    save_frame(&mut manager, window, &format!("{}/frame-{:05}.png", run.folder, run.frames));
+}
+
+#[derive(Component)]
+struct TimelapseIndicator;
+
+fn setup_timelapse_indicator(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle { font_size: 14.0, color: Color::from(Srgba::hex("F44336").unwrap()), ..default() },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.0),
+            right: Val::Percent(50.0),
+            display: Display::None,
+            ..default()
+        }),
+        Localized("capture.timelapse"),
+        TimelapseIndicator,
+    ));
+}
+
+fn show_timelapse_indicator(timelapse: Res<Timelapse>, mut style_q: Query<&mut Style, With<TimelapseIndicator>>) {
+    if !timelapse.is_changed() {
+        return;
+    }
+    for mut style in style_q.iter_mut() {
+        style.display = if timelapse.0.is_some() { Display::Flex } else { Display::None };
+    }
+}
+
+// `timelapse 5` takes a frame every five simulated minutes, `timelapse stop` ends it
+fn timelapse_command(In(args): In<Vec<String>>, mut timelapse: ResMut<Timelapse>) -> CommandResult {
+    match args.as_slice() {
+        [stop] if stop == "stop" => match timelapse.stop() {
+            Some(frames) => Ok(format!("timelapse stopped after {frames} frames")),
+            None => Err("no timelapse running".to_string()),
+        },
+        [minutes] => {
+            let minutes: f32 = minutes.parse().map_err(|_| format!("not a number of minutes: {minutes}"))?;
+            if minutes <= 0.0 {
+                return Err("the interval must be longer than zero".to_string());
+            }
+            timelapse.stop();
+            Ok(format!("timelapse started in {}", timelapse.start(minutes)))
+        }
+        _ => Err("usage: timelapse <minutes|stop>".to_string()),
+    }
+}
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timelapse>()
+            .register_action(Action::Screenshot, &[KeyCode::F12])
+            .add_systems(Startup, setup_timelapse_indicator)
+            .add_systems(Update, capture_keys);
+        // A timelapse writes a folder of files, which the browser can't
+        if cfg!(feature = "native") {
+            app.register_action(Action::ToggleTimelapse, &[KeyCode::F6])
+                .register_console_command(
+                    "timelapse",
+                    "timelapse <minutes|stop>: save a frame every few simulated minutes",
+                    timelapse_command,
+                )
+                .add_systems(Update, (record_timelapse, show_timelapse_indicator));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::coords::HexLayoutExt;
+use crate::{MapLayout, MapSize};
+
+// Collision layers. Units never push each other (they used to lock together
+// when crossing paths) but still collide with terrain: map boundaries,
+// structures and rock (see `rocks`). Sensors (food, engagement ranges) only
+// see units.
+pub const UNIT_GROUP: Group = Group::GROUP_1;
+pub const TERRAIN_GROUP: Group = Group::GROUP_2;
+pub const SENSOR_GROUP: Group = Group::GROUP_3;
+
+pub fn unit_collision_groups() -> CollisionGroups {
+    CollisionGroups::new(UNIT_GROUP, TERRAIN_GROUP | SENSOR_GROUP)
+}
+
+pub fn sensor_collision_groups() -> CollisionGroups {
+    CollisionGroups::new(SENSOR_GROUP, UNIT_GROUP)
+}
+
+pub fn terrain_collision_groups() -> CollisionGroups {
+    CollisionGroups::new(TERRAIN_GROUP, UNIT_GROUP)
+}
+
+#[derive(Component)]
+struct MapBoundary;
+
+// Every hex edge that faces outside the map, as one static polyline collider
+fn map_boundary_collider(layout: &MapLayout, map_size: &MapSize) -> Collider {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for hex in map_size.hexes() {
+        let center = layout.0.hex_to_world(hex);
+        let corners = layout.0.world_corners(hex);
+        for i in 0..6 {
+            let start = corners[i];
+            let end = corners[(i + 1) % 6];
+            // Step just past the edge midpoint to find the neighbor across it
+            let beyond = center + ((start + end) / 2.0 - center) * 1.5;
+            let neighbor = layout.0.world_to_hex(beyond);
+            if map_size.contains(neighbor) {
+                continue;
+            }
+            let first = vertices.len() as u32;
+            vertices.push(start);
+            vertices.push(end);
+            indices.push([first, first + 1]);
+        }
+    }
+    Collider::polyline(vertices, Some(indices))
+}
+
+// Rebuild the boundary only when the map itself changes
+fn sync_map_boundary(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    boundary_q: Query<Entity, With<MapBoundary>>,
+) {
+    if !layout.is_changed() && !map_size.is_changed() {
+        return;
+    }
+
+    for entity in boundary_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.spawn((
+        RigidBody::Fixed,
+        map_boundary_collider(&layout, &map_size),
+        terrain_collision_groups(),
+        TransformBundle::default(),
+        MapBoundary,
+    ));
+}
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, sync_map_boundary);
+    }
+}
@@ -0,0 +1,220 @@
+use bevy::ecs::system::SystemId;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::{ButtonState, InputSystem};
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+
+// Debug console: ` opens it, Enter runs the typed line. The first word picks
+// a command and the rest are its arguments. Commands are ordinary systems
+// taking `In<Vec<String>>` and returning a `CommandResult`; each plugin
+// registers its own with `register_console_command`. While the console is
+// open it swallows the keyboard, so typing never moves the camera.
+
+const HISTORY_LINES: usize = 100;
+const VISIBLE_LINES: usize = 12;
+
+pub type CommandResult = Result<String, String>;
+
+struct ConsoleCommand {
+    usage: &'static str,
+    system: SystemId<Vec<String>, CommandResult>,
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(BTreeMap<&'static str, ConsoleCommand>);
+
+#[derive(Resource, Default)]
+struct Console {
+    open: bool,
+    input: String,
+    history: Vec<String>,
+    // Lines entered this frame, run by `run_console_commands`
+    submitted: Vec<String>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.history.push(line.into());
+        let overflow = self.history.len().saturating_sub(HISTORY_LINES);
+        self.history.drain(..overflow);
+    }
+}
+
+// Lets each plugin contribute console commands; `usage` is what `help` shows
+pub trait ConsoleAppExt {
+    fn register_console_command<M>(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        system: impl IntoSystem<Vec<String>, CommandResult, M> + 'static,
+    ) -> &mut Self;
+}
+
+impl ConsoleAppExt for App {
+    fn register_console_command<M>(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        system: impl IntoSystem<Vec<String>, CommandResult, M> + 'static,
+    ) -> &mut Self {
+        let system = self.world_mut().register_system(system);
+        self.world_mut()
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .0
+            .insert(name, ConsoleCommand { usage, system });
+        self
+    }
+}
+
+#[derive(Component)]
+struct ConsolePanel;
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn setup_console(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.05, 0.05, 0.05, 0.9).into(),
+                ..default()
+            },
+            Interaction::default(),
+            ConsolePanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }),
+                ConsoleText,
+            ));
+        });
+}
+
+// Runs right after input is collected so that, while the console is open,
+// no other system sees a key press
+fn console_input(
+    mut console: ResMut<Console>,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut key_events: EventReader<KeyboardInput>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::ToggleConsole) {
+        console.open = !console.open;
+        keyboard_input.reset_all();
+        // The toggle key's own character mustn't end up in the input line
+        key_events.clear();
+        return;
+    }
+    if !console.open {
+        key_events.clear();
+        return;
+    }
+
+    for event in key_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                let line = std::mem::take(&mut console.input);
+                if !line.trim().is_empty() {
+                    console.submitted.push(line);
+                }
+            }
+            Key::Backspace => {
+                console.input.pop();
+            }
+            Key::Escape => console.open = false,
+            Key::Space => console.input.push(' '),
+            Key::Character(text) => console.input.push_str(text),
+            _ => {}
+        }
+    }
+    keyboard_input.reset_all();
+}
+
+fn run_console_commands(world: &mut World) {
+    let submitted = std::mem::take(&mut world.resource_mut::<Console>().submitted);
+    for line in submitted {
+        let mut words = line.split_whitespace().map(str::to_string);
+        let Some(name) = words.next() else {
+            continue;
+        };
+        let args: Vec<String> = words.collect();
+        let system = world.resource::<ConsoleCommands>().0.get(name.as_str()).map(|command| command.system);
+
+        let output = match system {
+            Some(system) => match world.run_system_with_input(system, args) {
+                Ok(result) => result,
+                Err(err) => Err(format!("{name} failed: {err:?}")),
+            },
+            None => Err(format!("unknown command `{name}`, try `help`")),
+        };
+        let mut console = world.resource_mut::<Console>();
+        console.print(format!("> {line}"));
+        match output {
+            Ok(message) if message.is_empty() => {}
+            Ok(message) => console.print(message),
+            Err(message) => console.print(format!("error: {message}")),
+        }
+    }
+}
+
+fn update_console_panel(
+    console: Res<Console>,
+    mut panel_q: Query<&mut Style, With<ConsolePanel>>,
+    mut text_q: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for mut style in panel_q.iter_mut() {
+        style.display = if console.open { Display::Flex } else { Display::None };
+    }
+    let start = console.history.len().saturating_sub(VISIBLE_LINES);
+    let mut text = console.history[start..].join("\n");
+    text.push_str(&format!("\n> {}_", console.input));
+    for mut console_text in text_q.iter_mut() {
+        console_text.sections[0].value = text.clone();
+    }
+}
+
+fn help_command(In(_): In<Vec<String>>, commands: Res<ConsoleCommands>) -> CommandResult {
+    Ok(commands.0.values().map(|command| command.usage).collect::<Vec<_>>().join("\n"))
+}
+
+fn clear_command(In(_): In<Vec<String>>, mut console: ResMut<Console>) -> CommandResult {
+    console.history.clear();
+    Ok(String::new())
+}
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<ConsoleCommands>()
+            .register_action(Action::ToggleConsole, &[KeyCode::Backquote])
+            .register_console_command("help", "help: list commands", help_command)
+            .register_console_command("clear", "clear: clear the console", clear_command)
+            .add_systems(Startup, setup_console)
+            .add_systems(PreUpdate, console_input.after(InputSystem))
+            .add_systems(
+                Update,
+                (run_console_commands, update_console_panel)
+                    .chain()
+                    .run_if(in_state(crate::loading::AppState::InGame)),
+            );
+    }
+}
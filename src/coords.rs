@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+use hexx::{Hex, HexLayout, Vec2 as HexVec2};
+
+// Conversions between hex cells and Bevy world positions. hexx carries its own
+// Vec2 type, so every round trip through it goes through here instead of being
+// spelled out at each call site.
+
+pub fn to_hex_vec(pos: Vec2) -> HexVec2 {
+    HexVec2::new(pos.x, pos.y)
+}
+
+pub fn from_hex_vec(pos: HexVec2) -> Vec2 {
+    Vec2::new(pos.x, pos.y)
+}
+
+pub trait HexLayoutExt {
+    // Center of `hex` in world space
+    fn hex_to_world(&self, hex: Hex) -> Vec2;
+    // Cell containing the world position `pos`
+    fn world_to_hex(&self, pos: Vec2) -> Hex;
+    // Corners of `hex` in world space, in hexx's winding order
+    fn world_corners(&self, hex: Hex) -> [Vec2; 6];
+}
+
+impl HexLayoutExt for HexLayout {
+    fn hex_to_world(&self, hex: Hex) -> Vec2 {
+        from_hex_vec(self.hex_to_world_pos(hex))
+    }
+
+    fn world_to_hex(&self, pos: Vec2) -> Hex {
+        self.world_pos_to_hex(to_hex_vec(pos))
+    }
+
+    fn world_corners(&self, hex: Hex) -> [Vec2; 6] {
+        self.hex_corners(hex).map(from_hex_vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexx::HexOrientation;
+
+    fn layouts() -> Vec<HexLayout> {
+        let mut layouts = Vec::new();
+        for orientation in [HexOrientation::Pointy, HexOrientation::Flat] {
+            for scale in [HexVec2::splat(1.0), HexVec2::splat(20.0), HexVec2::new(32.0, 12.5)] {
+                for origin in [HexVec2::ZERO, HexVec2::new(-137.0, 54.5)] {
+                    layouts.push(HexLayout { orientation, scale, origin });
+                }
+            }
+        }
+        layouts
+    }
+
+    fn hexes() -> impl Iterator<Item = Hex> {
+        Hex::ZERO.spiral_range(0..25).chain([Hex::new(1000, -400), Hex::new(-731, 2048)])
+    }
+
+    #[test]
+    fn hex_round_trips_through_world() {
+        for layout in layouts() {
+            for hex in hexes() {
+                assert_eq!(layout.world_to_hex(layout.hex_to_world(hex)), hex, "{layout:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn points_inside_a_cell_map_back_to_it() {
+        for layout in layouts() {
+            for hex in hexes() {
+                let center = layout.hex_to_world(hex);
+                for corner in layout.world_corners(hex) {
+                    // Anything strictly between the center and a corner is inside the cell
+                    for t in [0.1, 0.5, 0.9] {
+                        let pos = center.lerp(corner, t);
+                        assert_eq!(layout.world_to_hex(pos), hex, "{layout:?} at {pos}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_are_one_cell_apart() {
+        for layout in layouts() {
+            for hex in hexes() {
+                let center = layout.hex_to_world(hex);
+                for neighbor in hex.all_neighbors() {
+                    let midpoint = (center + layout.hex_to_world(neighbor)) / 2.0;
+                    // Step just short of the shared edge on either side
+                    assert_eq!(layout.world_to_hex(center.lerp(midpoint, 0.9)), hex);
+                    assert_eq!(layout.world_to_hex(midpoint.lerp(layout.hex_to_world(neighbor), 0.1)), neighbor);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vec_conversions_are_lossless() {
+        for pos in [Vec2::ZERO, Vec2::new(1.5, -2.25), Vec2::new(-1e6, 3e-4)] {
+            assert_eq!(from_hex_vec(to_hex_vec(pos)), pos);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use hexx::Hex;
+
+use crate::collision;
+use crate::coords::HexLayoutExt;
+use crate::health::Died;
+use crate::interactions::FoodSource;
+use crate::spatial::SpatialIndex;
+use crate::MapLayout;
+
+// Remains. Anything with health that dies (ants, brood, predators) leaves a
+// corpse on the center of its hex, worth part of what it was as food: one
+// per `HEALTH_PER_FOOD` of its maximum health. A corpse is a food source like
+// any pile, so ants pick from it on contact and idle workers go scavenging
+// (corpses first, since they don't last). It rots, losing a food every
+// `DECAY_SECS` and fading as it does, and is gone once eaten or rotted away.
+// A second death on the same hex adds to the corpse already there. Corpses
+// have a hex index of their own, kept apart from the unit index so picking
+// and unit queries never see them.
+
+const HEALTH_PER_FOOD: f32 = 10.0;
+const DECAY_SECS: f32 = 20.0;
+// Largest corpse, so a dead anteater doesn't outlast the match
+const MAX_CORPSE_FOOD: u32 = 20;
+
+#[derive(Component)]
+pub struct Corpse {
+    // Most food the corpse has held, for fading it as it shrinks
+    peak: u32,
+    decay: Timer,
+}
+
+// Which hex each corpse lies on
+#[derive(Resource, Default)]
+pub struct Corpses(SpatialIndex);
+
+impl Corpses {
+    pub fn at(&self, hex: Hex) -> Option<Entity> {
+        self.0.at(hex).first().copied()
+    }
+}
+
+fn corpse_color(food: u32, peak: u32) -> Color {
+    let fade = 0.4 + 0.6 * food as f32 / peak.max(1) as f32;
+    Color::from(Srgba::hex("6D4C41").unwrap()).with_alpha(fade)
+}
+
+fn leave_remains(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    mut corpses: ResMut<Corpses>,
+    mut died: EventReader<Died>,
+    mut corpse_q: Query<(&mut Corpse, &mut FoodSource)>,
+) {
+    for death in died.read() {
+        let food = (death.max_health / HEALTH_PER_FOOD).ceil() as u32;
+        if food == 0 {
+            continue;
+        }
+        let hex = layout.0.world_to_hex(death.pos);
+        if let Some((mut corpse, mut source)) = corpses.at(hex).and_then(|entity| corpse_q.get_mut(entity).ok()) {
+            source.amount = (source.amount + food).min(MAX_CORPSE_FOOD);
+            corpse.peak = corpse.peak.max(source.amount);
+            continue;
+        }
+
+        let food = food.min(MAX_CORPSE_FOOD);
+        let entity = commands
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: corpse_color(food, food),
+                        custom_size: Some(Vec2::new(10.0, 10.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(layout.0.hex_to_world(hex).extend(0.4)),
+                    ..default()
+                },
+                Collider::ball(6.0),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                collision::sensor_collision_groups(),
+                FoodSource { amount: food },
+                Corpse { peak: food, decay: Timer::from_seconds(DECAY_SECS, TimerMode::Repeating) },
+            ))
+            .id();
+        corpses.0.insert(entity, hex);
+    }
+}
+
+fn decay_corpses(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut corpse_q: Query<(Entity, &mut Corpse, &mut FoodSource, &mut Sprite)>,
+) {
+    for (entity, mut corpse, mut source, mut sprite) in corpse_q.iter_mut() {
+        if corpse.decay.tick(time.delta()).just_finished() {
+            source.amount = source.amount.saturating_sub(1);
+        }
+        if source.amount == 0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let color = corpse_color(source.amount, corpse.peak);
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+// Eaten corpses are despawned by the food pickup, rotted ones here
+fn unindex_corpses(mut corpses: ResMut<Corpses>, mut removed: RemovedComponents<Corpse>) {
+    for entity in removed.read() {
+        corpses.0.remove(entity);
+    }
+}
+
+pub struct CorpsesPlugin;
+
+impl Plugin for CorpsesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Corpses>()
+            .add_systems(Update, (unindex_corpses, leave_remains, decay_corpses).chain());
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{MainCamera, MapLayout};
+
+// The part of the world the camera can see, for skipping gizmo overlays that
+// would land off screen. Sprites and the grid chunk meshes don't need it:
+// Bevy already culls those by their bounds. Taken from the camera as it was
+// last rendered and padded by a couple of hexes, so a frame of camera movement
+// never reveals an undrawn edge.
+
+const PADDING_HEXES: f32 = 2.0;
+
+#[derive(Resource)]
+pub struct CameraView(Rect);
+
+// Everything counts as visible until the camera has been seen
+impl Default for CameraView {
+    fn default() -> Self {
+        Self(Rect { min: Vec2::NEG_INFINITY, max: Vec2::INFINITY })
+    }
+}
+
+impl CameraView {
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.0.contains(point)
+    }
+
+    // Whether the segment's bounding box touches the view; cheap and never
+    // drops a segment that is actually visible
+    pub fn overlaps_segment(&self, a: Vec2, b: Vec2) -> bool {
+        a.min(b).cmple(self.0.max).all() && a.max(b).cmpge(self.0.min).all()
+    }
+
+    // A circle touches the view when its center lies in the view grown by its
+    // radius (slightly generous at the corners)
+    pub fn overlaps_circle(&self, center: Vec2, radius: f32) -> bool {
+        Self(self.0.inflate(radius)).contains(center)
+    }
+}
+
+fn update_camera_view(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    layout: Res<MapLayout>,
+    mut view: ResMut<CameraView>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_q.get_single()) else {
+        return;
+    };
+    let corner = |pos| camera.viewport_to_world_2d(camera_transform, pos);
+    let (Some(min), Some(max)) = (corner(Vec2::new(0.0, window.height())), corner(Vec2::new(window.width(), 0.0))) else {
+        return;
+    };
+    let padding = layout.0.scale.max_element() * 2.0 * PADDING_HEXES;
+    view.0 = Rect::from_corners(min, max).inflate(padding);
+}
+
+pub struct CullingPlugin;
+
+impl Plugin for CullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraView>().add_systems(PreUpdate, update_camera_view);
+    }
+}
@@ -0,0 +1,220 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use crate::health::Health;
+use crate::interactions::FoodStores;
+use crate::predators::Predator;
+use crate::Ant;
+
+// Deterministic simulation, for lockstep and replays. `--deterministic`
+// makes every frame exactly one fixed tick of virtual time (and Rapier a
+// fixed step, see `game_speed`), so nothing the simulation does depends on
+// frame rate. All gameplay randomness comes from `SimRng`, seeded with
+// `--seed N`; containers the simulation iterates are ordered or only ever
+// looked up. Two runs with the same seed and inputs then produce the same
+// world on the same build and platform (`enhanced-determinism` extends that
+// across platforms). `SimState::checksum` fingerprints the world after each
+// tick; `--checksum-log PATH` writes one line per tick for diffing runs.
+
+pub const TICK_SECS: f32 = 1.0 / 60.0;
+const CHECKSUM_LOG_EVERY: u64 = 600;
+
+#[derive(Resource, Default)]
+pub struct Determinism {
+    pub enabled: bool,
+    // Recorded with flagged sessions (see `session_replay`)
+    #[cfg(feature = "admin")]
+    pub seed: u64,
+}
+
+// The only random source gameplay may use
+#[derive(Resource)]
+pub struct SimRng(pub StdRng);
+
+impl SimRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+// Everything a tick can change that the checksum covers
+#[derive(SystemParam)]
+pub struct SimState<'w, 's> {
+    unit_q: Query<'w, 's, (Entity, &'static Transform, &'static Health), Or<(With<Ant>, With<Predator>)>>,
+    stores: Option<Res<'w, FoodStores>>,
+}
+
+impl SimState<'_, '_> {
+    // FNV-1a over every unit's id, position and health, in entity order, and
+    // the food stores in player order. Floats are hashed by their bits, so
+    // any drift at all shows up.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = Fnv1a::default();
+        let mut units: Vec<_> = self.unit_q.iter().collect();
+        units.sort_by_key(|(entity, _, _)| *entity);
+        for (entity, transform, health) in units {
+            hash.write(entity.to_bits());
+            hash.write(transform.translation.x.to_bits().into());
+            hash.write(transform.translation.y.to_bits().into());
+            hash.write(health.current.to_bits().into());
+        }
+        if let Some(stores) = &self.stores {
+            let mut stores: Vec<_> = stores.0.iter().collect();
+            stores.sort();
+            for (player, food) in stores {
+                hash.write(player.0.into());
+                hash.write((*food).into());
+            }
+        }
+        hash.0
+    }
+}
+
+// FNV-1a, fed whole u64s; also fingerprints match rules (see `mutators`)
+pub struct Fnv1a(pub u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub fn write(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ChecksumLog {
+    pub tick: u64,
+    pub last: u64,
+    file: Option<BufWriter<File>>,
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2).find(|pair| pair[0] == name).map(|pair| pair[1].clone())
+}
+
+pub fn record_checksum(state: SimState, mut log: ResMut<ChecksumLog>) {
+    let checksum = state.checksum();
+    log.tick += 1;
+    log.last = checksum;
+    let tick = log.tick;
+    if let Some(file) = &mut log.file {
+        if let Err(err) = writeln!(file, "{tick} {checksum:016x}") {
+            warn!("Checksum log stopped: {err}");
+            log.file = None;
+        }
+    }
+    if tick.is_multiple_of(CHECKSUM_LOG_EVERY) {
+        info!("Tick {tick} checksum {checksum:016x}");
+        if let Some(file) = &mut log.file {
+            let _ = file.flush();
+        }
+    }
+}
+
+pub struct DeterminismPlugin;
+
+impl Plugin for DeterminismPlugin {
+    fn build(&self, app: &mut App) {
+        let enabled = std::env::args().any(|arg| arg == "--deterministic");
+        if !enabled {
+            app.init_resource::<Determinism>().insert_resource(SimRng(StdRng::from_entropy()));
+            return;
+        }
+
+        let seed = arg_value("--seed").and_then(|seed| seed.parse().ok()).unwrap_or(0);
+        let file = arg_value("--checksum-log").and_then(|path| match File::create(&path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(err) => {
+                warn!("Could not create checksum log {path}: {err}");
+                None
+            }
+        });
+        info!("Deterministic simulation, seed {seed}");
+        app.insert_resource(Determinism {
+            enabled,
+            #[cfg(feature = "admin")]
+            seed,
+        })
+            .insert_resource(SimRng::seeded(seed))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(TICK_SECS)))
+            .insert_resource(ChecksumLog { file, ..default() })
+            .add_systems(Last, record_checksum.run_if(in_state(crate::loading::AppState::InGame)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    // Stand-in for gameplay: random walks driven by `SimRng` and virtual time
+    fn wander(time: Res<Time>, mut rng: ResMut<SimRng>, mut unit_q: Query<(&mut Transform, &mut Health), With<Ant>>) {
+        for (mut transform, mut health) in unit_q.iter_mut() {
+            let step = Vec2::new(rng.0.gen_range(-1.0..1.0), rng.0.gen_range(-1.0..1.0)) * 50.0;
+            transform.translation += (step * time.delta_seconds()).extend(0.0);
+            if rng.0.gen_bool(0.05) {
+                health.current -= 1.0;
+            }
+        }
+    }
+
+    fn spawn_units(mut commands: Commands) {
+        for i in 0..20 {
+            commands.spawn((Ant, Transform::from_xyz(i as f32 * 10.0, 0.0, 1.0), Health::new(10.0)));
+        }
+    }
+
+    fn checksums(seed: u64, ticks: usize) -> Vec<u64> {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(TICK_SECS)))
+            .insert_resource(SimRng::seeded(seed))
+            .init_resource::<ChecksumLog>()
+            .add_systems(Startup, spawn_units)
+            .add_systems(Update, wander)
+            .add_systems(Last, record_checksum);
+        (0..ticks)
+            .map(|_| {
+                app.update();
+                app.world().resource::<ChecksumLog>().last
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_checksum_every_tick() {
+        assert_eq!(checksums(7, 120), checksums(7, 120));
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        assert_ne!(checksums(7, 120).last(), checksums(8, 120).last());
+    }
+
+    #[test]
+    fn checksum_sees_any_change() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).init_resource::<ChecksumLog>().add_systems(Last, record_checksum);
+        let unit = app.world_mut().spawn((Ant, Transform::default(), Health::new(10.0))).id();
+        app.update();
+        let before = app.world().resource::<ChecksumLog>().last;
+
+        app.world_mut().get_mut::<Transform>(unit).unwrap().translation.x = f32::EPSILON;
+        app.update();
+        assert_ne!(app.world().resource::<ChecksumLog>().last, before);
+    }
+}
@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+
+use crate::burrow::{FastTravel, InTunnel};
+use crate::coords::HexLayoutExt;
+use crate::game_commands::{AttackMove, AttackTarget, GameCommand};
+use crate::health::Health;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::net::LocalPlayer;
+use crate::pathfinding::PathQueue;
+use crate::scoring::Scores;
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::spatial::SpatialIndex;
+use crate::units::UnitDefinitions;
+use crate::veterancy::Experience;
+use crate::{Ant, Caste, Faction, HoldPosition, MapLayout, Path, Patrol, Relation, Selected, TargetPosition, Teams};
+
+// Digging in. Soldiers (any caste whose unit definition has `can_dig_in`)
+// dig into the hex they stand on when Z is pressed with them selected, and Z
+// again brings them out. Digging takes `DIG_SECS`, in plain sight, and any
+// order calls it off. Once dug in a soldier shows as a mound of dirt to its
+// own side, is gone from enemy view unless an enemy ant stands right next to
+// it (see `vision`), is passed over by predators and ignores move and attack
+// orders. An enemy ant stepping next to it springs an ambush: the soldier
+// bursts out, bites for `AMBUSH_DAMAGE` on the spot and goes for it.
+
+const DIG_SECS: f32 = 2.0;
+const AMBUSH_DAMAGE: f32 = 15.0;
+// Enemies this many hexes away or closer spring an ambush
+const AMBUSH_RADIUS: u32 = 1;
+const MOUND_RADIUS: f32 = 8.0;
+
+// Digging into the ground; becomes `DugIn` when the timer runs out
+#[derive(Component)]
+pub struct Digging(Timer);
+
+#[derive(Component)]
+pub struct DugIn;
+
+// Dirt over a digging or dug-in unit, a child of it
+#[derive(Component)]
+struct Mound;
+
+#[derive(Resource)]
+struct MoundAssets {
+    mesh: Mesh2dHandle,
+    digging: Handle<ColorMaterial>,
+    dug_in: Handle<ColorMaterial>,
+}
+
+fn setup_mound_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let dirt = Color::from(Srgba::hex("7A5C3A").unwrap());
+    commands.insert_resource(MoundAssets {
+        mesh: Mesh2dHandle(meshes.add(Circle::new(MOUND_RADIUS))),
+        digging: materials.add(ColorMaterial::from(dirt.with_alpha(0.4))),
+        dug_in: materials.add(ColorMaterial::from(dirt.with_alpha(0.9))),
+    });
+}
+
+fn remove_mound(
+    commands: &mut Commands,
+    unit: Entity,
+    children_q: &Query<&Children>,
+    mound_q: &Query<(), With<Mound>>,
+) {
+    let Ok(children) = children_q.get(unit) else {
+        return;
+    };
+    for &child in children.iter().filter(|&&child| mound_q.contains(child)) {
+        commands.entity(child).despawn_recursive();
+    }
+}
+
+// Z digs the selected soldiers in, or brings them out if they all are already
+fn dig_in_key(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    local_player: Res<LocalPlayer>,
+    definitions: Res<UnitDefinitions>,
+    selected_q: Query<(Entity, &Caste, Has<DugIn>, Has<Digging>), With<Selected>>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::DigIn) {
+        return;
+    }
+    let diggers: Vec<_> = selected_q.iter().filter(|(_, caste, ..)| definitions.get(**caste).can_dig_in).collect();
+    if diggers.is_empty() {
+        return;
+    }
+    let player = local_player.0;
+    let units = diggers.iter().map(|&(entity, ..)| entity).collect();
+    if diggers.iter().all(|&(_, _, dug_in, digging)| dug_in || digging) {
+        game_commands.send(GameCommand::DigOut { player, units });
+    } else {
+        game_commands.send(GameCommand::DigIn { player, units });
+    }
+}
+
+// `GameCommand::DigIn` and `DigOut`; any other order to a digging unit calls
+// the digging off
+fn execute_dig_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    assets: Res<MoundAssets>,
+    definitions: Res<UnitDefinitions>,
+    mut path_queue: ResMut<PathQueue>,
+    mut unit_q: Query<
+        (&Faction, &Caste, &Transform, &mut TargetPosition, &mut Path, Has<DugIn>, Has<Digging>),
+        (With<Ant>, Without<InTunnel>),
+    >,
+    children_q: Query<&Children>,
+    mound_q: Query<(), With<Mound>>,
+) {
+    for command in game_commands.read() {
+        let (player, units, dig_in) = match command {
+            GameCommand::DigIn { player, units } => (*player, units, true),
+            GameCommand::DigOut { player, units } => (*player, units, false),
+            GameCommand::Move { player, units, .. }
+            | GameCommand::Attack { player, units, .. }
+            | GameCommand::Stop { player, units }
+            | GameCommand::Hold { player, units } => {
+                for &entity in units {
+                    if unit_q.get(entity).is_ok_and(|(faction, .., digging)| faction.0 == *player && digging) {
+                        commands.entity(entity).remove::<Digging>();
+                        remove_mound(&mut commands, entity, &children_q, &mound_q);
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        for &entity in units {
+            let Ok((faction, caste, transform, mut target, mut path, dug_in, digging)) = unit_q.get_mut(entity) else {
+                continue;
+            };
+            if faction.0 != player || !definitions.get(*caste).can_dig_in {
+                continue;
+            }
+            if !dig_in {
+                if dug_in || digging {
+                    commands.entity(entity).remove::<(DugIn, Digging)>();
+                    remove_mound(&mut commands, entity, &children_q, &mound_q);
+                }
+                continue;
+            }
+            if dug_in || digging {
+                continue;
+            }
+            // Stop on the spot and start digging
+            target.0 = transform.translation.truncate();
+            path.waypoints.clear();
+            path_queue.cancel(entity);
+            let mound = commands
+                .spawn((
+                    MaterialMesh2dBundle {
+                        mesh: assets.mesh.clone(),
+                        material: assets.digging.clone(),
+                        // Over the unit's sprite
+                        transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                        ..default()
+                    },
+                    Mound,
+                ))
+                .id();
+            commands
+                .entity(entity)
+                .remove::<(Patrol, FastTravel, HoldPosition, AttackTarget, AttackMove)>()
+                .insert(Digging(Timer::from_seconds(DIG_SECS, TimerMode::Once)))
+                .add_child(mound);
+        }
+    }
+}
+
+fn finish_digging(
+    mut commands: Commands,
+    time: Res<Time>,
+    assets: Res<MoundAssets>,
+    mut digging_q: Query<(Entity, &mut Digging, &Children)>,
+    mut mound_q: Query<&mut Handle<ColorMaterial>, With<Mound>>,
+) {
+    for (entity, mut digging, children) in digging_q.iter_mut() {
+        if !digging.0.tick(time.delta()).finished() {
+            continue;
+        }
+        commands.entity(entity).remove::<Digging>().insert(DugIn);
+        for &child in children.iter() {
+            if let Ok(mut material) = mound_q.get_mut(child) {
+                *material = assets.dug_in.clone();
+            }
+        }
+    }
+}
+
+// A dug-in soldier with an enemy ant next to it bursts out and bites the
+// closest one
+fn spring_ambushes(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    teams: Res<Teams>,
+    spatial_index: Res<SpatialIndex>,
+    mut scores: ResMut<Scores>,
+    mut soldier_q: Query<(Entity, &Faction, &Transform, &mut TargetPosition, &mut Path), With<DugIn>>,
+    mut enemy_q: Query<(&Faction, &Transform, &mut Health), (With<Ant>, Without<DugIn>, Without<InTunnel>)>,
+    children_q: Query<&Children>,
+    mound_q: Query<(), With<Mound>>,
+    mut sounds: EventWriter<PlaySound>,
+    mut experience: EventWriter<Experience>,
+) {
+    for (entity, faction, transform, mut target, mut path) in soldier_q.iter_mut() {
+        let pos = transform.translation.truncate();
+        let hex = layout.0.world_to_hex(pos);
+        let prey = spatial_index
+            .within(hex, AMBUSH_RADIUS)
+            .filter_map(|unit| enemy_q.get(unit).ok().map(|(enemy_faction, enemy, _)| (unit, enemy_faction, enemy)))
+            .filter(|(_, enemy_faction, _)| teams.relation(faction.0, enemy_faction.0) == Relation::Enemy)
+            .map(|(unit, _, enemy)| (unit, enemy.translation.truncate()))
+            .min_by(|a, b| a.1.distance_squared(pos).total_cmp(&b.1.distance_squared(pos)));
+        let Some((prey, prey_pos)) = prey else {
+            continue;
+        };
+
+        if let Ok((_, _, mut health)) = enemy_q.get_mut(prey) {
+            let was_alive = !health.is_dead();
+            health.damage(AMBUSH_DAMAGE);
+            if was_alive && health.is_dead() {
+                scores.add_kill(faction.0);
+                experience.send(Experience::Kill(entity));
+            }
+        }
+        sounds.send(PlaySound::at(SoundEffect::Hit, prey_pos));
+        // Out of the ground and straight at it; the fight starts on contact
+        commands.entity(entity).remove::<DugIn>();
+        remove_mound(&mut commands, entity, &children_q, &mound_q);
+        target.0 = prey_pos;
+        path.waypoints.clear();
+    }
+}
+
+pub struct DigInPlugin;
+
+impl Plugin for DigInPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_action(Action::DigIn, &[KeyCode::KeyZ])
+            .add_systems(Startup, setup_mound_assets)
+            .add_systems(
+                Update,
+                (
+                    dig_in_key.before(crate::dispatch_pointer_actions),
+                    (execute_dig_commands, finish_digging, spring_ambushes)
+                        .chain()
+                        .after(crate::dispatch_pointer_actions)
+                        .after(crate::spatial::update_spatial_index),
+                ),
+            );
+    }
+}
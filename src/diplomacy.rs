@@ -0,0 +1,468 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::ant_sprites::AntSprites;
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::factions::FactionColors;
+use crate::interactions::FoodStores;
+use crate::locale::Locale;
+use crate::net::{IncomingMessage, LocalPlayer, NetConnection, NetMessage, OutgoingMessage, PlayerId};
+use crate::{Caste, Faction, Queen, Relation, Teams};
+
+// Diplomacy between colonies. A player proposes a non-aggression pact, an
+// alliance or a shipment of food to another colony; the other side accepts or
+// declines, and a pact can be ended by either side at any time. Nothing is
+// settled between the two players: proposals and answers go to the authority
+// (the server, or this client offline), which checks them, passes them on and
+// enforces the outcome. A pact changes how the two colonies relate (see
+// `Teams`): at peace they can't fight, as allies they also share vision.
+// Accepted food leaves the giver's store at once and is carried over by a
+// carrier ant, reaching the other store when it arrives at that queen.
+// Proposals and news are listed on the right; `propose`, `accept` and
+// `decline` in the console make and answer them.
+
+// Carrier ants' walking speed, px/s
+const CARRIER_SPEED: f32 = 60.0;
+// News lines kept on screen
+const NOTICES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Offer {
+    NonAggression,
+    Alliance,
+    // Food from the proposer's store, as a gift or in return for something
+    // agreed on elsewhere
+    Food(u32),
+    // Ends whatever pact there is; needs no answer
+    EndPact,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pact {
+    NonAggression,
+    Alliance,
+}
+
+impl Pact {
+    fn relation(self) -> Relation {
+        match self {
+            Pact::NonAggression => Relation::Neutral,
+            Pact::Alliance => Relation::Ally,
+        }
+    }
+}
+
+// Things the local player does, from the console or the interface
+#[derive(Event, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum DiplomacyAction {
+    Propose { to: PlayerId, offer: Offer },
+    Answer { proposal: u32, accept: bool },
+}
+
+impl DiplomacyAction {
+    fn message(self) -> NetMessage {
+        match self {
+            DiplomacyAction::Propose { to, offer } => NetMessage::Propose { to, offer },
+            DiplomacyAction::Answer { proposal, accept } => NetMessage::Answer { proposal, accept },
+        }
+    }
+}
+
+struct PendingProposal {
+    from: PlayerId,
+    to: PlayerId,
+    offer: Offer,
+}
+
+#[derive(Resource, Default)]
+struct Diplomacy {
+    // Authority side: proposals waiting for an answer, by id
+    pending: BTreeMap<u32, PendingProposal>,
+    next_proposal: u32,
+    // Proposals waiting for our answer, by id
+    inbox: BTreeMap<u32, (PlayerId, Offer)>,
+    // Latest news for us, newest last
+    notices: Vec<String>,
+}
+
+impl Diplomacy {
+    fn notify(&mut self, notice: String) {
+        self.notices.push(notice);
+        let excess = self.notices.len().saturating_sub(NOTICES);
+        self.notices.drain(..excess);
+    }
+}
+
+fn colony_name(locale: &Locale, player: PlayerId) -> String {
+    locale.format("score.colony", &[("player", &player.0)])
+}
+
+fn offer_text(locale: &Locale, offer: Offer) -> String {
+    match offer {
+        Offer::NonAggression => locale.get("diplomacy.offer-peace").to_string(),
+        Offer::Alliance => locale.get("diplomacy.offer-alliance").to_string(),
+        Offer::Food(food) => locale.format("diplomacy.offer-food", &[("food", &food)]),
+        Offer::EndPact => locale.get("diplomacy.offer-end").to_string(),
+    }
+}
+
+// A food shipment on its way to the receiving queen
+#[derive(Component)]
+struct FoodCarrier {
+    to: PlayerId,
+    food: u32,
+    // Only the authority's carriers put food in the store
+    credit: bool,
+}
+
+// Everything needed to act on what the authority says to us
+struct Receiver<'a> {
+    local_player: PlayerId,
+    authority: bool,
+    locale: &'a Locale,
+    diplomacy: &'a mut Diplomacy,
+    teams: &'a mut Teams,
+    shipments: &'a mut Vec<(PlayerId, PlayerId, u32, bool)>,
+}
+
+impl Receiver<'_> {
+    fn receive(&mut self, message: &NetMessage) {
+        let locale = self.locale;
+        match *message {
+            NetMessage::Proposal { proposal, from, offer } => {
+                self.diplomacy.inbox.insert(proposal, (from, offer));
+                let notice = locale.format(
+                    "diplomacy.proposed",
+                    &[("colony", &colony_name(locale, from)), ("offer", &offer_text(locale, offer))],
+                );
+                self.diplomacy.notify(notice);
+            }
+            NetMessage::ProposalAnswered { by, offer, accept } => {
+                let key = if accept { "diplomacy.accepted" } else { "diplomacy.declined" };
+                let notice =
+                    locale.format(key, &[("colony", &colony_name(locale, by)), ("offer", &offer_text(locale, offer))]);
+                self.diplomacy.notify(notice);
+            }
+            NetMessage::PactChanged { a, b, pact } => {
+                self.teams.set_pact(a, b, pact.map(Pact::relation));
+                let other = if a == self.local_player {
+                    b
+                } else if b == self.local_player {
+                    a
+                } else {
+                    return;
+                };
+                let key = match pact {
+                    Some(Pact::NonAggression) => "diplomacy.at-peace",
+                    Some(Pact::Alliance) => "diplomacy.allied",
+                    None => "diplomacy.pact-ended",
+                };
+                self.diplomacy.notify(locale.format(key, &[("colony", &colony_name(locale, other))]));
+            }
+            NetMessage::FoodShipped { from, to, food } => self.shipments.push((from, to, food, self.authority)),
+            _ => {}
+        }
+    }
+
+    // Authority side: what it says reaches us directly and everyone else over the wire
+    fn tell(&mut self, outgoing: &mut EventWriter<OutgoingMessage>, to: Option<PlayerId>, message: NetMessage) {
+        if to.is_none_or(|to| to == self.local_player) {
+            self.receive(&message);
+        }
+        if to != Some(self.local_player) {
+            outgoing.send(OutgoingMessage { to, message });
+        }
+    }
+}
+
+// Sends our own actions to the authority, plays the authority's part when
+// we're it and acts on what the authority tells us
+fn run_diplomacy(
+    connection: Res<NetConnection>,
+    local_player: Res<LocalPlayer>,
+    locale: Res<Locale>,
+    mut actions: EventReader<DiplomacyAction>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+    mut diplomacy: ResMut<Diplomacy>,
+    mut teams: ResMut<Teams>,
+    mut stores: ResMut<FoodStores>,
+    mut commands: Commands,
+    sprites: Res<AntSprites>,
+    colors: Res<FactionColors>,
+    queen_q: Query<(&Transform, &Faction), With<Queen>>,
+) {
+    let authority = !connection.online;
+    let mut requests: Vec<(PlayerId, NetMessage)> = Vec::new();
+    for &action in actions.read() {
+        if let DiplomacyAction::Answer { proposal, .. } = action {
+            diplomacy.inbox.remove(&proposal);
+        }
+        if authority {
+            requests.push((local_player.0, action.message()));
+        } else {
+            outgoing.send(OutgoingMessage::broadcast(action.message()));
+        }
+    }
+
+    let mut shipments = Vec::new();
+    let mut receiver = Receiver {
+        local_player: local_player.0,
+        authority,
+        locale: &locale,
+        diplomacy: &mut diplomacy,
+        teams: &mut teams,
+        shipments: &mut shipments,
+    };
+    for message in incoming.read() {
+        match message.message {
+            NetMessage::Propose { .. } | NetMessage::Answer { .. } if authority => {
+                requests.push((message.from, message.message.clone()));
+            }
+            // Only the authority speaks for the others
+            _ if authority => {}
+            ref message => receiver.receive(message),
+        }
+    }
+
+    for (from, request) in requests {
+        match request {
+            NetMessage::Propose { to, offer } => {
+                let relation = receiver.teams.relation(from, to);
+                let pact = receiver.teams.pact(from, to);
+                let valid = match offer {
+                    Offer::NonAggression => relation == Relation::Enemy,
+                    Offer::Alliance => matches!(relation, Relation::Enemy | Relation::Neutral),
+                    Offer::Food(food) => {
+                        relation != Relation::Own
+                            && food > 0
+                            && stores.0.get(&from).is_some_and(|&stored| stored >= food)
+                    }
+                    Offer::EndPact => pact.is_some(),
+                };
+                if !valid {
+                    receiver.tell(
+                        &mut outgoing,
+                        Some(from),
+                        NetMessage::ProposalAnswered { by: to, offer, accept: false },
+                    );
+                } else if offer == Offer::EndPact {
+                    receiver.tell(&mut outgoing, None, NetMessage::PactChanged { a: from, b: to, pact: None });
+                } else {
+                    let proposal = receiver.diplomacy.next_proposal;
+                    receiver.diplomacy.next_proposal = proposal.wrapping_add(1);
+                    receiver.diplomacy.pending.insert(proposal, PendingProposal { from, to, offer });
+                    receiver.tell(&mut outgoing, Some(to), NetMessage::Proposal { proposal, from, offer });
+                }
+            }
+            NetMessage::Answer { proposal, accept } => {
+                // Only the colony a proposal was made to can answer it
+                if receiver.diplomacy.pending.get(&proposal).is_none_or(|pending| pending.to != from) {
+                    continue;
+                }
+                let Some(PendingProposal { from: proposer, to, offer }) = receiver.diplomacy.pending.remove(&proposal)
+                else {
+                    continue;
+                };
+                let mut accept = accept;
+                if accept {
+                    match offer {
+                        Offer::NonAggression | Offer::Alliance => {
+                            let pact = if offer == Offer::Alliance { Pact::Alliance } else { Pact::NonAggression };
+                            receiver.tell(
+                                &mut outgoing,
+                                None,
+                                NetMessage::PactChanged { a: proposer, b: to, pact: Some(pact) },
+                            );
+                        }
+                        Offer::Food(food) => {
+                            // The store may have been spent since it was offered
+                            let stored = stores.0.entry(proposer).or_default();
+                            if *stored >= food {
+                                *stored -= food;
+                                receiver.tell(
+                                    &mut outgoing,
+                                    None,
+                                    NetMessage::FoodShipped { from: proposer, to, food },
+                                );
+                            } else {
+                                accept = false;
+                            }
+                        }
+                        Offer::EndPact => {}
+                    }
+                }
+                receiver.tell(&mut outgoing, Some(proposer), NetMessage::ProposalAnswered { by: to, offer, accept });
+            }
+            _ => {}
+        }
+    }
+
+    for (from, to, food, credit) in shipments {
+        let queen = |player: PlayerId| {
+            queen_q
+                .iter()
+                .find(|(_, faction)| faction.0 == player)
+                .map(|(transform, _)| transform.translation.truncate())
+        };
+        let (Some(start), Some(_)) = (queen(from), queen(to)) else {
+            // Nowhere to walk between; the food goes straight over
+            if credit {
+                *stores.0.entry(to).or_default() += food;
+            }
+            continue;
+        };
+        let (texture, atlas) = sprites.for_caste(Caste::Worker);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite { color: colors.get(from), custom_size: Some(Vec2::splat(10.0)), ..default() },
+                texture,
+                transform: Transform::from_translation(start.extend(1.5)),
+                ..default()
+            },
+            atlas,
+            FoodCarrier { to, food, credit },
+        ));
+    }
+}
+
+// Carriers walk straight to the receiving queen and hand over the food
+fn move_food_carriers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut stores: ResMut<FoodStores>,
+    queen_q: Query<(&Transform, &Faction), (With<Queen>, Without<FoodCarrier>)>,
+    mut carrier_q: Query<(Entity, &FoodCarrier, &mut Transform)>,
+) {
+    for (entity, carrier, mut transform) in carrier_q.iter_mut() {
+        let destination =
+            queen_q.iter().find(|(_, faction)| faction.0 == carrier.to).map(|(queen, _)| queen.translation.truncate());
+        let pos = transform.translation.truncate();
+        let step = CARRIER_SPEED * time.delta_seconds();
+        match destination {
+            Some(destination) if pos.distance(destination) > step => {
+                let next = pos + (destination - pos).normalize() * step;
+                transform.translation = next.extend(transform.translation.z);
+            }
+            // Arrived, or the queen is gone and the food with her colony
+            _ => {
+                if carrier.credit && destination.is_some() {
+                    *stores.0.entry(carrier.to).or_default() += carrier.food;
+                }
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct DiplomacyText;
+
+fn setup_diplomacy_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(40.0),
+            right: Val::Px(8.0),
+            ..default()
+        }),
+        DiplomacyText,
+    ));
+}
+
+fn update_diplomacy_text(
+    diplomacy: Res<Diplomacy>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<DiplomacyText>>,
+) {
+    if !diplomacy.is_changed() && !locale.is_changed() {
+        return;
+    }
+    let mut lines: Vec<String> = diplomacy
+        .inbox
+        .iter()
+        .map(|(&proposal, &(from, offer))| {
+            locale.format(
+                "diplomacy.inbox-row",
+                &[("id", &proposal), ("colony", &colony_name(&locale, from)), ("offer", &offer_text(&locale, offer))],
+            )
+        })
+        .collect();
+    if !lines.is_empty() {
+        lines.push(locale.get("diplomacy.answer-hint").to_string());
+    }
+    lines.extend(diplomacy.notices.iter().cloned());
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+// `propose 1000 peace`, `propose 1000 alliance`, `propose 1000 food 5`, `propose 1000 end`
+fn propose_command(
+    In(args): In<Vec<String>>,
+    local_player: Res<LocalPlayer>,
+    mut actions: EventWriter<DiplomacyAction>,
+) -> CommandResult {
+    let usage = || "usage: propose <player> <peace|alliance|food <amount>|end>".to_string();
+    let to = PlayerId(args.first().and_then(|player| player.parse().ok()).ok_or_else(usage)?);
+    let offer = match (args.get(1).map(String::as_str), args.get(2)) {
+        (Some("peace"), None) => Offer::NonAggression,
+        (Some("alliance"), None) => Offer::Alliance,
+        (Some("food"), Some(amount)) => Offer::Food(amount.parse().map_err(|_| usage())?),
+        (Some("end"), None) => Offer::EndPact,
+        _ => return Err(usage()),
+    };
+    if to == local_player.0 {
+        return Err("that's your own colony".to_string());
+    }
+    actions.send(DiplomacyAction::Propose { to, offer });
+    Ok(format!("proposed {offer:?} to player {}", to.0))
+}
+
+fn answer(args: &[String], diplomacy: &Diplomacy, accept: bool) -> Result<DiplomacyAction, String> {
+    let [proposal] = args else {
+        return Err(format!("usage: {} <proposal>", if accept { "accept" } else { "decline" }));
+    };
+    let proposal: u32 = proposal.parse().map_err(|_| format!("not a proposal: {proposal}"))?;
+    if !diplomacy.inbox.contains_key(&proposal) {
+        return Err(format!("no proposal {proposal} waiting for an answer"));
+    }
+    Ok(DiplomacyAction::Answer { proposal, accept })
+}
+
+fn accept_command(
+    In(args): In<Vec<String>>,
+    diplomacy: Res<Diplomacy>,
+    mut actions: EventWriter<DiplomacyAction>,
+) -> CommandResult {
+    actions.send(answer(&args, &diplomacy, true)?);
+    Ok("accepted".to_string())
+}
+
+fn decline_command(
+    In(args): In<Vec<String>>,
+    diplomacy: Res<Diplomacy>,
+    mut actions: EventWriter<DiplomacyAction>,
+) -> CommandResult {
+    actions.send(answer(&args, &diplomacy, false)?);
+    Ok("declined".to_string())
+}
+
+pub struct DiplomacyPlugin;
+
+impl Plugin for DiplomacyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Diplomacy>()
+            .add_event::<DiplomacyAction>()
+            .register_console_command(
+                "propose",
+                "propose <player> <peace|alliance|food <amount>|end>: offer a pact or food, or end a pact",
+                propose_command,
+            )
+            .register_console_command("accept", "accept <proposal>: accept a proposal", accept_command)
+            .register_console_command("decline", "decline <proposal>: decline a proposal", decline_command)
+            .add_systems(Startup, setup_diplomacy_text)
+            .add_systems(Update, (run_diplomacy, move_food_carriers, update_diplomacy_text).chain());
+    }
+}
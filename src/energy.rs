@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use hexx::Hex;
+use std::collections::BTreeMap;
+
+use crate::coords::HexLayoutExt;
+use crate::health::{Health, HealthBarGizmos, BAR_GAP, BAR_WIDTH};
+use crate::interactions::{Carrying, FoodStores};
+use crate::net::PlayerId;
+use crate::pathfinding::{PathPriority, PathQueue};
+use crate::{Ant, Faction, MainCamera, MapLayout, Path, Queen, Selected};
+
+// Energy, the colony's upkeep. Every ant but the queen burns a little just
+// by living, more while walking and more again while carrying food. Below
+// `HUNGRY_SHARE` it slows down; below `RETURN_SHARE` it heads back to its
+// queen whenever it has nothing else to do. An ant next to its queen eats one
+// food from the colony's store and is full again, so gathering has to keep
+// up with the mouths it feeds. Out of energy, an ant starves: it loses
+// health until it dies or gets to eat. The energy bar sits over the health
+// bar of selected and hungry ants.
+
+const MAX_ENERGY: f32 = 100.0;
+const TICK_SECS: f32 = 0.5;
+// Energy per second spent living, walking and carrying
+const IDLE_DRAIN: f32 = 0.2;
+const WALK_DRAIN: f32 = 0.5;
+const CARRY_DRAIN: f32 = 0.3;
+// Slower than this counts as standing still
+const WALKING_SPEED: f32 = 5.0;
+const HUNGRY_SHARE: f32 = 0.3;
+// Speed left to a hungry ant
+const HUNGRY_SPEED: f32 = 0.6;
+const RETURN_SHARE: f32 = 0.2;
+// Ants eat once they're this hungry and this close (in hexes) to their queen
+const EAT_SHARE: f32 = 0.5;
+const EAT_RANGE: u32 = 2;
+const FOOD_PER_MEAL: u32 = 1;
+const STARVE_DAMAGE_PER_SEC: f32 = 1.0;
+
+#[derive(Component)]
+pub struct Energy {
+    pub current: f32,
+}
+
+impl Energy {
+    pub fn fraction(&self) -> f32 {
+        (self.current / MAX_ENERGY).clamp(0.0, 1.0)
+    }
+
+    pub fn is_hungry(&self) -> bool {
+        self.fraction() < HUNGRY_SHARE
+    }
+
+    // Multiplier on the ant's walking speed
+    pub fn speed_factor(&self) -> f32 {
+        if self.is_hungry() {
+            HUNGRY_SPEED
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Resource)]
+struct EnergyTick(Timer);
+
+fn attach_energy(mut commands: Commands, new_ants: Query<Entity, (Added<Ant>, Without<Queen>)>) {
+    for entity in new_ants.iter() {
+        commands.entity(entity).insert(Energy { current: MAX_ENERGY });
+    }
+}
+
+fn update_energy(
+    time: Res<Time>,
+    mut tick: ResMut<EnergyTick>,
+    layout: Res<MapLayout>,
+    mut stores: ResMut<FoodStores>,
+    mut path_queue: ResMut<PathQueue>,
+    queen_q: Query<(&Transform, &Faction), With<Queen>>,
+    mut ant_q: Query<
+        (Entity, &Transform, &Faction, &Velocity, &Path, &mut Energy, &mut Health, Has<Carrying>),
+        Without<Queen>,
+    >,
+) {
+    if !tick.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let nests: BTreeMap<PlayerId, Hex> = queen_q
+        .iter()
+        .map(|(transform, faction)| (faction.0, layout.0.world_to_hex(transform.translation.truncate())))
+        .collect();
+
+    for (entity, transform, faction, velocity, path, mut energy, mut health, carrying) in ant_q.iter_mut() {
+        let mut drain = IDLE_DRAIN;
+        if velocity.linvel.length() > WALKING_SPEED {
+            drain += WALK_DRAIN;
+        }
+        if carrying {
+            drain += CARRY_DRAIN;
+        }
+        energy.current = (energy.current - drain * TICK_SECS).max(0.0);
+        if energy.current <= 0.0 {
+            health.damage(STARVE_DAMAGE_PER_SEC * TICK_SECS);
+        }
+
+        // A colony without a queen has nowhere to eat
+        let Some(&nest) = nests.get(&faction.0) else {
+            continue;
+        };
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        let distance = hex.unsigned_distance_to(nest);
+        if distance <= EAT_RANGE && energy.fraction() < EAT_SHARE {
+            let food = stores.0.entry(faction.0).or_default();
+            if *food >= FOOD_PER_MEAL {
+                *food -= FOOD_PER_MEAL;
+                energy.current = MAX_ENERGY;
+            }
+        } else if distance > EAT_RANGE
+            && energy.fraction() < RETURN_SHARE
+            && path.waypoints.is_empty()
+            && !path_queue.is_pending(entity)
+        {
+            // Next to the queen, on the side the ant comes from
+            let Some(spot) = nest.line_to(hex).nth(1) else {
+                continue;
+            };
+            path_queue.request(entity, hex, spot, PathPriority::Ai);
+        }
+    }
+}
+
+fn draw_energy_bars(
+    mut gizmos: Gizmos<HealthBarGizmos>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    ant_q: Query<(&Energy, &GlobalTransform, Option<&Sprite>, &ViewVisibility, Has<Selected>)>,
+) {
+    let Ok(projection) = camera_q.get_single() else {
+        return;
+    };
+    let scale = projection.scale;
+    let background = Color::from(Srgba::hex("222222").unwrap());
+    let color = Color::from(Srgba::hex("42A5F5").unwrap());
+
+    for (energy, transform, sprite, visibility, selected) in ant_q.iter() {
+        if !visibility.get() || (!selected && !energy.is_hungry()) {
+            continue;
+        }
+        let pos = transform.translation().truncate();
+        let half_height = sprite.and_then(|sprite| sprite.custom_size).map_or(5.0, |size| size.y / 2.0);
+        // One gap above where the health bar goes
+        let left = pos + Vec2::new(-BAR_WIDTH / 2.0 * scale, half_height + 2.0 * BAR_GAP * scale);
+        let right = left + Vec2::X * BAR_WIDTH * scale;
+        gizmos.line_2d(left, right, background);
+        if energy.current > 0.0 {
+            gizmos.line_2d(left, left.lerp(right, energy.fraction()), color);
+        }
+    }
+}
+
+pub struct EnergyPlugin;
+
+impl Plugin for EnergyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EnergyTick(Timer::from_seconds(TICK_SECS, TimerMode::Repeating)))
+            .add_systems(Update, (attach_energy, update_energy, draw_energy_bars));
+    }
+}
@@ -0,0 +1,374 @@
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::corpses::Corpse;
+use crate::coords::HexLayoutExt;
+use crate::health::Died;
+use crate::interactions::FoodSource;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::loading::AppState;
+use crate::locale::{Locale, Localized};
+use crate::net::{LocalPlayer, PlayerId};
+use crate::scoring::player_name;
+use crate::territory::Territory;
+use crate::vision::Vision;
+use crate::{Ant, Caste, Faction, MainCamera, MapLayout};
+
+// Event log: what happened during the match, newest first, each entry with
+// the game time and the place it happened. Our units raised and lost, other
+// colonies' units dying in sight, food piles running out and territory lost
+// are recorded into `EventLog`, a bounded history of plain, serializable
+// entries, so a replay or the server can read it just as the panel does; the
+// admin interface serves it at `GET /events`. M opens the panel, the mouse
+// wheel scrolls it and clicking an entry jumps the camera there.
+
+// Entries kept; older ones are dropped
+const MAX_ENTRIES: usize = 200;
+// Entries shown at once in the panel
+const VISIBLE_ROWS: usize = 12;
+// Touchpads scroll in pixels; this many scroll the panel by one entry
+const PIXELS_PER_ROW: f32 = 20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoggedEvent {
+    UnitRaised { caste: Caste, player: PlayerId },
+    UnitDied { caste: Caste, player: PlayerId },
+    FoodDepleted,
+    // `to` is the colony that took the most of it, if any did
+    TerritoryLost { hexes: usize, to: Option<PlayerId> },
+}
+
+impl LoggedEvent {
+    fn describe(&self, locale: &Locale, local_player: PlayerId) -> String {
+        match *self {
+            LoggedEvent::UnitRaised { caste, .. } => {
+                locale.format("log.unit-raised", &[("caste", &locale.get(caste.name_key()))])
+            }
+            LoggedEvent::UnitDied { caste, player } if player == local_player => {
+                locale.format("log.unit-lost", &[("caste", &locale.get(caste.name_key()))])
+            }
+            LoggedEvent::UnitDied { caste, player } => locale.format(
+                "log.unit-killed",
+                &[("caste", &locale.get(caste.name_key())), ("player", &player_name(locale, local_player, player))],
+            ),
+            LoggedEvent::FoodDepleted => locale.get("log.food-depleted").to_string(),
+            LoggedEvent::TerritoryLost { hexes, to: None } => {
+                locale.format("log.territory-lost", &[("hexes", &hexes)])
+            }
+            LoggedEvent::TerritoryLost { hexes, to: Some(to) } => locale.format(
+                "log.territory-taken",
+                &[("hexes", &hexes), ("player", &player_name(locale, local_player, to))],
+            ),
+        }
+    }
+
+    fn color(&self, local_player: PlayerId) -> Color {
+        let hex = match *self {
+            LoggedEvent::UnitRaised { .. } => "81C784",
+            LoggedEvent::UnitDied { player, .. } if player == local_player => "E57373",
+            LoggedEvent::UnitDied { .. } => "FFD54F",
+            LoggedEvent::FoodDepleted => "BDBDBD",
+            LoggedEvent::TerritoryLost { .. } => "E57373",
+        };
+        Color::from(Srgba::hex(hex).unwrap())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    // Game seconds since the session started
+    pub time_secs: f64,
+    pub pos: Vec2,
+    pub event: LoggedEvent,
+}
+
+#[derive(Resource, Default)]
+pub struct EventLog {
+    entries: VecDeque<LogEntry>,
+}
+
+impl EventLog {
+    pub fn record(&mut self, time_secs: f64, pos: Vec2, event: LoggedEvent) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry { time_secs, pos, event });
+    }
+
+    // Oldest first
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &LogEntry> + '_ {
+        self.entries.iter()
+    }
+}
+
+// The units on the map when the match starts aren't news, so the first batch
+// is skipped
+fn log_units_raised(
+    time: Res<Time>,
+    local_player: Res<LocalPlayer>,
+    new_units: Query<(&Caste, &Faction, &Transform), Added<Ant>>,
+    mut started: Local<bool>,
+    mut log: ResMut<EventLog>,
+) {
+    if !*started {
+        *started = true;
+        return;
+    }
+    for (&caste, faction, transform) in new_units.iter() {
+        if faction.0 == local_player.0 {
+            let event = LoggedEvent::UnitRaised { caste, player: faction.0 };
+            log.record(time.elapsed_seconds_f64(), transform.translation.truncate(), event);
+        }
+    }
+}
+
+// Ours wherever they fall, anyone else's only where we can see
+fn log_deaths(
+    time: Res<Time>,
+    local_player: Res<LocalPlayer>,
+    layout: Res<MapLayout>,
+    vision: Res<Vision>,
+    mut died: EventReader<Died>,
+    mut log: ResMut<EventLog>,
+) {
+    for death in died.read() {
+        let Some((caste, player)) = death.unit else {
+            continue;
+        };
+        if player == local_player.0 || vision.reveals(layout.0.world_to_hex(death.pos)) {
+            log.record(time.elapsed_seconds_f64(), death.pos, LoggedEvent::UnitDied { caste, player });
+        }
+    }
+}
+
+// Corpses rotting away aren't worth a line
+fn log_food_depleted(
+    time: Res<Time>,
+    new_piles: Query<(Entity, &Transform), (Added<FoodSource>, Without<Corpse>)>,
+    mut removed: RemovedComponents<FoodSource>,
+    mut piles: Local<HashMap<Entity, Vec2>>,
+    mut log: ResMut<EventLog>,
+) {
+    for (entity, transform) in new_piles.iter() {
+        piles.insert(entity, transform.translation.truncate());
+    }
+    for entity in removed.read() {
+        if let Some(pos) = piles.remove(&entity) {
+            log.record(time.elapsed_seconds_f64(), pos, LoggedEvent::FoodDepleted);
+        }
+    }
+}
+
+// One entry per territory update that costs us hexes, placed on the first of them
+fn log_territory_lost(
+    time: Res<Time>,
+    local_player: Res<LocalPlayer>,
+    layout: Res<MapLayout>,
+    territory: Res<Territory>,
+    mut held: Local<HashSet<Hex>>,
+    mut log: ResMut<EventLog>,
+) {
+    if !territory.is_changed() {
+        return;
+    }
+    let now: HashSet<Hex> =
+        territory.hexes().filter(|&(_, owner)| owner == local_player.0).map(|(hex, _)| hex).collect();
+    let mut lost: Vec<Hex> = held.difference(&now).copied().collect();
+    *held = now;
+    if lost.is_empty() {
+        return;
+    }
+    lost.sort_by_key(|hex| (hex.x, hex.y));
+
+    let mut takers: BTreeMap<PlayerId, usize> = BTreeMap::new();
+    for &hex in &lost {
+        if let Some(owner) = territory.owner(hex) {
+            *takers.entry(owner).or_default() += 1;
+        }
+    }
+    let to = takers.into_iter().max_by_key(|&(_, count)| count).map(|(player, _)| player);
+    let event = LoggedEvent::TerritoryLost { hexes: lost.len(), to };
+    log.record(time.elapsed_seconds_f64(), layout.0.hex_to_world(lost[0]), event);
+}
+
+#[derive(Resource, Default)]
+struct EventLogPanel {
+    open: bool,
+    // Entries scrolled past from the newest
+    scroll: usize,
+}
+
+#[derive(Component)]
+struct EventLogRoot;
+
+// Container the entry rows are rebuilt into
+#[derive(Component)]
+struct EventLogRows;
+
+#[derive(Component)]
+struct EventLogRow(Vec2);
+
+fn setup_event_log_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(20.0),
+                    left: Val::Px(8.0),
+                    width: Val::Px(340.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.85).into(),
+                ..default()
+            },
+            // Lets the pointer input skip clicks that land on the panel
+            Interaction::default(),
+            EventLogRoot,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 18.0, color: Color::WHITE, ..default() }),
+                Localized("log.title"),
+            ));
+            panel.spawn((
+                NodeBundle {
+                    style: Style { flex_direction: FlexDirection::Column, row_gap: Val::Px(2.0), ..default() },
+                    ..default()
+                },
+                EventLogRows,
+            ));
+        });
+}
+
+fn toggle_event_log(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut panel: ResMut<EventLogPanel>,
+    mut style_q: Query<&mut Style, With<EventLogRoot>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleEventLog) {
+        return;
+    }
+    panel.open = !panel.open;
+    panel.scroll = 0;
+    for mut style in style_q.iter_mut() {
+        style.display = if panel.open { Display::Flex } else { Display::None };
+    }
+}
+
+fn scroll_event_log(
+    mut wheel: EventReader<MouseWheel>,
+    log: Res<EventLog>,
+    mut panel: ResMut<EventLogPanel>,
+    // The rows are buttons, which keep the pointer from the panel under them
+    panel_q: Query<&Interaction, Or<(With<EventLogRoot>, With<EventLogRow>)>>,
+) {
+    let hovered = panel_q.iter().any(|interaction| *interaction != Interaction::None);
+    let rows: f32 = wheel
+        .read()
+        .filter(|_| panel.open && hovered)
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_ROW,
+        })
+        .sum();
+    if rows == 0.0 {
+        return;
+    }
+    // Scrolling down goes back in time
+    let max_scroll = log.entries.len().saturating_sub(VISIBLE_ROWS);
+    let scroll = (panel.scroll as f32 - rows.round()).clamp(0.0, max_scroll as f32) as usize;
+    if panel.scroll != scroll {
+        panel.scroll = scroll;
+    }
+}
+
+fn refresh_event_log(
+    mut commands: Commands,
+    panel: Res<EventLogPanel>,
+    log: Res<EventLog>,
+    locale: Res<Locale>,
+    local_player: Res<LocalPlayer>,
+    rows_q: Query<Entity, With<EventLogRows>>,
+) {
+    if !panel.open || !(panel.is_changed() || log.is_changed() || locale.is_changed()) {
+        return;
+    }
+    let Ok(rows) = rows_q.get_single() else {
+        return;
+    };
+    commands.entity(rows).despawn_descendants();
+    commands.entity(rows).with_children(|rows| {
+        if log.entries.is_empty() {
+            rows.spawn(TextBundle::from_section(
+                locale.get("log.empty"),
+                TextStyle { font_size: 14.0, color: Color::srgb(0.6, 0.6, 0.6), ..default() },
+            ));
+        }
+        for entry in log.entries().rev().skip(panel.scroll).take(VISIBLE_ROWS) {
+            let secs = entry.time_secs as u64;
+            let description = entry.event.describe(&locale, local_player.0);
+            let label = format!("[{:02}:{:02}] {description}", secs / 60, secs % 60);
+            rows.spawn((
+                ButtonBundle {
+                    style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+                    background_color: Color::srgb(0.2, 0.2, 0.2).into(),
+                    ..default()
+                },
+                EventLogRow(entry.pos),
+            ))
+            .with_children(|button| {
+                button.spawn(TextBundle::from_section(
+                    label,
+                    TextStyle { font_size: 14.0, color: entry.event.color(local_player.0), ..default() },
+                ));
+            });
+        }
+    });
+}
+
+fn jump_to_log_entry(
+    row_q: Query<(&Interaction, &EventLogRow), Changed<Interaction>>,
+    mut camera_q: Query<&mut Transform, With<MainCamera>>,
+) {
+    for (interaction, row) in row_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        for mut transform in camera_q.iter_mut() {
+            transform.translation.x = row.0.x;
+            transform.translation.y = row.0.y;
+        }
+    }
+}
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EventLog>()
+            .init_resource::<EventLogPanel>()
+            .register_action(Action::ToggleEventLog, &[KeyCode::KeyM])
+            .add_systems(Startup, setup_event_log_panel)
+            .add_systems(
+                Update,
+                (
+                    (log_units_raised, log_deaths, log_food_depleted, log_territory_lost)
+                        .run_if(in_state(AppState::InGame)),
+                    toggle_event_log,
+                    scroll_event_log,
+                    refresh_event_log,
+                    jump_to_log_entry,
+                )
+                    .chain(),
+            );
+    }
+}
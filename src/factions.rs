@@ -0,0 +1,71 @@
+use bevy::color::Mix;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::net::{LocalPlayer, PlayerId};
+use crate::{Faction, Selected};
+
+// Faction colors. Every player gets a color from the palette the first time
+// one of their units shows up (the local player first, so ours is always the
+// same), and their units' sprites are tinted with it on top of the caste
+// color from `units.ron`. Selection rings and order lines use the color of
+// the player giving the orders. Only our own units can be selected; see
+// `UnitPicker`, and `drop_foreign_selection` for when control changes hands.
+
+const PALETTE: [&str; 8] = ["FFD600", "E53935", "1E88E5", "43A047", "8E24AA", "FB8C00", "00ACC1", "D81B60"];
+// How far unit sprites are pulled from their caste color towards the faction's
+const FACTION_TINT: f32 = 0.45;
+
+#[derive(Resource, Default)]
+pub struct FactionColors {
+    // Palette index per player, in order of appearance
+    seats: BTreeMap<PlayerId, usize>,
+}
+
+impl FactionColors {
+    pub fn get(&self, player: PlayerId) -> Color {
+        let seat = self.seats.get(&player).copied().unwrap_or(0);
+        Srgba::hex(PALETTE[seat % PALETTE.len()]).map_or(Color::WHITE, Color::from)
+    }
+
+    fn seat(&mut self, player: PlayerId) {
+        let next = self.seats.len();
+        self.seats.entry(player).or_insert(next);
+    }
+}
+
+fn seat_local_player(local_player: Res<LocalPlayer>, mut colors: ResMut<FactionColors>) {
+    colors.seat(local_player.0);
+}
+
+fn tint_new_units(mut colors: ResMut<FactionColors>, mut unit_q: Query<(&Faction, &mut Sprite), Added<Faction>>) {
+    for (faction, mut sprite) in unit_q.iter_mut() {
+        colors.seat(faction.0);
+        let tint = Srgba::from(colors.get(faction.0));
+        sprite.color = Srgba::from(sprite.color).mix(&tint, FACTION_TINT).into();
+    }
+}
+
+// Taking over another colony (sandbox) leaves the old one's units selected
+fn drop_foreign_selection(
+    mut commands: Commands,
+    local_player: Res<LocalPlayer>,
+    selected_q: Query<(Entity, &Faction), With<Selected>>,
+) {
+    for (entity, faction) in selected_q.iter() {
+        if faction.0 != local_player.0 {
+            commands.entity(entity).remove::<Selected>();
+        }
+    }
+}
+
+pub struct FactionsPlugin;
+
+impl Plugin for FactionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FactionColors>()
+            .add_systems(Startup, seat_local_player)
+            .add_systems(PreUpdate, drop_foreign_selection.run_if(resource_changed::<LocalPlayer>))
+            .add_systems(PostUpdate, tint_new_units);
+    }
+}
@@ -0,0 +1,449 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::burrow::{self, BurrowNetwork, FastTravel, InTunnel};
+use crate::coords::HexLayoutExt;
+use crate::dig_in::DugIn;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::net::{LocalPlayer, PlayerId};
+use crate::order_feedback::{self, OrderWindUp};
+use crate::pathfinding::{Passability, PathPriority, PathQueue};
+use crate::spatial::SpatialIndex;
+#[cfg(feature = "sandbox")]
+use crate::sandbox::SandboxCommand;
+use crate::structures::StructureKind;
+use crate::{Ant, Caste, Faction, HoldPosition, MapLayout, Path, Patrol, Relation, Selected, TargetPosition, Teams};
+
+// Everything a player does to the world goes through a `GameCommand`. Input
+// never touches units itself: pointer actions, the battle panel and the build
+// menu turn into commands, and so do group orders from the network and unit
+// orders from scripts. The systems here carry them out (Build is carried out
+// by `structures`, Harvest by `harvest`, digging in by `dig_in` and Crown by
+// `succession`), so an order takes the same path whoever gave it. A command
+// names the player it's given for, and only that player's units obey it.
+// An attack order sticks: the units keep chasing the target until it dies or
+// they are given another order. An attack-move (holding Ctrl) walks to the
+// destination but goes after any enemy that comes within reach on the way, as
+// patrolling units do on their loop; both pick their route up again once the
+// enemy is dead or has got away. R stops the selection where it stands and H
+// holds it there.
+
+// Farthest ring around the destination a group order spreads units over
+const FORMATION_RINGS: u32 = 10;
+// An attacker is routed again once its target is more than this many hexes
+// from where the attacker is headed
+const CHASE_SLACK: u32 = 1;
+// Attack-moving and patrolling units go after enemies this many hexes away...
+const ACQUIRE_HEXES: u32 = 4;
+// ...and give up on one that gets farther than this from them
+const LEASH_HEXES: u32 = 8;
+// Closer than this (px) to its target, a unit has stopped
+const ARRIVAL_RADIUS: f32 = 2.0;
+
+// The unit an attack order sent this one after. Removed by any other order and
+// when the target is gone.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AttackTarget(pub Entity);
+
+// Attack-move order: the unit is headed for its spot at `destination` and
+// takes on enemies met on the way. `resume` is set while it's off chasing one,
+// so it walks on to its spot afterwards.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AttackMove {
+    destination: Vec2,
+    resume: bool,
+}
+
+#[derive(Event, Clone, Debug, Serialize, Deserialize)]
+pub enum GameCommand {
+    // Local only: which of our units are selected
+    Select { units: Vec<Entity>, mode: SelectMode },
+    Move { player: PlayerId, units: Vec<Entity>, destination: Vec2, mode: MoveMode },
+    // Chase the target; the fight starts on contact (see `interactions`)
+    Attack { player: PlayerId, units: Vec<Entity>, target: Entity },
+    // Drop every order and stand still
+    Stop { player: PlayerId, units: Vec<Entity> },
+    // Stop, then stay on the spot until the next move order (see `HoldPosition`)
+    Hold { player: PlayerId, units: Vec<Entity> },
+    Build { player: PlayerId, kind: StructureKind, hex: Hex },
+    // Mark a harvest zone over these hexes and assign the units to it
+    Harvest { player: PlayerId, units: Vec<Entity>, hexes: Vec<Hex> },
+    // Remove the player's harvest zone covering this hex
+    ClearHarvestZone { player: PlayerId, hex: Hex },
+    // Dig the units into the ground where they stand, or back out (see `dig_in`)
+    DigIn { player: PlayerId, units: Vec<Entity> },
+    DigOut { player: PlayerId, units: Vec<Entity> },
+    // Make the princess the colony's new queen (see `succession`)
+    Crown { player: PlayerId, unit: Entity },
+    // God-mode tools, carried out by `sandbox`
+    #[cfg(feature = "sandbox")]
+    Sandbox(SandboxCommand),
+}
+
+impl GameCommand {
+    // The player the command is given for; none for a selection, which only
+    // changes what we see, or a sandbox tool, which can reach any colony.
+    // Only the session recorder asks (see `session_replay`)
+    #[cfg(feature = "admin")]
+    pub fn player(&self) -> Option<PlayerId> {
+        match self {
+            GameCommand::Select { .. } => None,
+            #[cfg(feature = "sandbox")]
+            GameCommand::Sandbox(_) => None,
+            GameCommand::Move { player, .. }
+            | GameCommand::Attack { player, .. }
+            | GameCommand::Stop { player, .. }
+            | GameCommand::Hold { player, .. }
+            | GameCommand::Build { player, .. }
+            | GameCommand::Harvest { player, .. }
+            | GameCommand::ClearHarvestZone { player, .. }
+            | GameCommand::DigIn { player, .. }
+            | GameCommand::DigOut { player, .. }
+            | GameCommand::Crown { player, .. } => Some(*player),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelectMode {
+    // Exactly these units end up selected
+    Replace,
+    Add,
+    // Selected ones are deselected, the rest selected
+    Toggle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveMode {
+    // Spread over the hexes around the destination; cancels patrols and tunnel trips
+    Direct,
+    // Like `Direct`, but fighting any enemy that comes within reach on the way
+    AttackMove,
+    // Add the destination to the units' patrol loop
+    Patrol,
+    // Take the tunnel that ends at the entrance on the destination, or have
+    // the workers dig one out to it (see `burrow`)
+    Tunnel,
+}
+
+fn execute_selection(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    local_player: Res<LocalPlayer>,
+    faction_q: Query<&Faction>,
+    selected_q: Query<Entity, With<Selected>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Select { units, mode } = command else {
+            continue;
+        };
+        let units: HashSet<Entity> = units
+            .iter()
+            .copied()
+            .filter(|&entity| faction_q.get(entity).is_ok_and(|faction| faction.0 == local_player.0))
+            .collect();
+
+        if *mode == SelectMode::Replace {
+            for entity in selected_q.iter().filter(|entity| !units.contains(entity)) {
+                commands.entity(entity).remove::<Selected>();
+            }
+        }
+        for entity in units {
+            if *mode == SelectMode::Toggle && selected_q.contains(entity) {
+                commands.entity(entity).remove::<Selected>();
+            } else {
+                commands.entity(entity).insert(Selected);
+            }
+        }
+    }
+}
+
+pub fn execute_unit_orders(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    layout: Res<MapLayout>,
+    network: Res<BurrowNetwork>,
+    passability: Passability,
+    mut path_queue: ResMut<PathQueue>,
+    ant_q: Query<(Entity, &Faction, &Transform, &TargetPosition), With<Ant>>,
+    caste_q: Query<&Caste>,
+    target_q: Query<&Transform, With<Faction>>,
+    dug_in_q: Query<(), With<DugIn>>,
+    mut patrol_q: Query<&mut Patrol>,
+    mut wind_up_q: Query<&mut OrderWindUp>,
+) {
+    for command in game_commands.read() {
+        let (player, units, destination, mode, attack) = match command {
+            GameCommand::Move { player, units, destination, mode } => (*player, units, *destination, *mode, None),
+            GameCommand::Attack { player, units, target } => {
+                let Ok(transform) = target_q.get(*target) else {
+                    continue;
+                };
+                (*player, units, transform.translation.truncate(), MoveMode::Direct, Some(*target))
+            }
+            _ => continue,
+        };
+
+        // The ordered units that are the player's own, and the hex each stands
+        // on. Dug-in units stay where they are.
+        let ordered: Vec<(Entity, Hex)> = units
+            .iter()
+            .filter(|&&entity| !dug_in_q.contains(entity))
+            .filter_map(|&entity| ant_q.get(entity).ok())
+            .filter(|(_, faction, _, _)| faction.0 == player)
+            .map(|(entity, _, transform, _)| (entity, layout.0.world_to_hex(transform.translation.truncate())))
+            .collect();
+        if ordered.is_empty() {
+            continue;
+        }
+
+        // Acknowledge the order on every ordered unit right away; a move
+        // order also ends holding position, any chase and any attack-move
+        for &(entity, _) in &ordered {
+            order_feedback::play_wind_up(&mut commands, &mut wind_up_q, entity);
+            match attack {
+                Some(target) => commands.entity(entity).remove::<(HoldPosition, AttackMove)>().insert(AttackTarget(target)),
+                None => commands.entity(entity).remove::<(HoldPosition, AttackTarget, AttackMove)>(),
+            };
+        }
+
+        match mode {
+            MoveMode::Direct | MoveMode::AttackMove => order_move(
+                destination,
+                &ordered,
+                mode == MoveMode::AttackMove,
+                &mut commands,
+                &ant_q,
+                &mut path_queue,
+                &layout,
+            ),
+            MoveMode::Patrol => {
+                order_patrol(destination, &ordered, &mut commands, &mut patrol_q, &mut path_queue, &layout)
+            }
+            MoveMode::Tunnel => burrow::order_tunnel(
+                destination,
+                player,
+                &ordered,
+                |entity| caste_q.get(entity).is_ok_and(|caste| *caste == Caste::Worker),
+                |hex| passability.passable(hex),
+                &mut commands,
+                &network,
+                &mut path_queue,
+                &layout.0,
+            ),
+        }
+    }
+}
+
+// Moves the units to the centers of the free hexes closest to the destination,
+// one unit per hex. Hexes other units are already headed for count as taken.
+fn order_move(
+    destination: Vec2,
+    ordered: &[(Entity, Hex)],
+    attack_move: bool,
+    commands: &mut Commands,
+    ant_q: &Query<(Entity, &Faction, &Transform, &TargetPosition), With<Ant>>,
+    path_queue: &mut PathQueue,
+    layout: &MapLayout,
+) {
+    let moving: HashSet<Entity> = ordered.iter().map(|&(entity, _)| entity).collect();
+    let mut occupied: HashSet<Hex> = ant_q
+        .iter()
+        .filter(|(entity, ..)| !moving.contains(entity))
+        .map(|(_, _, _, target)| layout.0.world_to_hex(target.0))
+        .collect();
+
+    let spots = layout
+        .0
+        .world_to_hex(destination)
+        .spiral_range(0..FORMATION_RINGS)
+        .filter(|&hex| occupied.insert(hex));
+    for (&(entity, from), spot) in ordered.iter().zip(spots) {
+        // Route is computed by the pathfinding queue within its per-frame budget
+        path_queue.request(entity, from, spot, PathPriority::PlayerOrder);
+        // A plain move order cancels any patrol or tunnel trip in progress
+        commands.entity(entity).remove::<(Patrol, FastTravel)>();
+        if attack_move {
+            commands.entity(entity).insert(AttackMove { destination: layout.0.hex_to_world(spot), resume: false });
+        }
+    }
+}
+
+// Every ordered unit shares the same patrol loop. A new patrol starts from the
+// unit's own hex, so the loop brings it back where it started.
+fn order_patrol(
+    destination: Vec2,
+    ordered: &[(Entity, Hex)],
+    commands: &mut Commands,
+    patrol_q: &mut Query<&mut Patrol>,
+    path_queue: &mut PathQueue,
+    layout: &MapLayout,
+) {
+    let target_hex = layout.0.world_to_hex(destination);
+    let point = layout.0.hex_to_world(target_hex);
+    for &(entity, from) in ordered {
+        if let Ok(mut patrol) = patrol_q.get_mut(entity) {
+            patrol.points.push(point);
+            continue;
+        }
+        path_queue.request(entity, from, target_hex, PathPriority::PlayerOrder);
+        commands.entity(entity).remove::<FastTravel>().insert(Patrol {
+            points: vec![layout.0.hex_to_world(from), point],
+            next: 0,
+        });
+    }
+}
+
+// Attack-moving and patrolling units go after the nearest enemy within reach,
+// setting their route aside until it's dealt with
+fn acquire_targets(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    teams: Res<Teams>,
+    spatial_index: Res<SpatialIndex>,
+    mut hunter_q: Query<
+        (Entity, &Faction, &Transform, Option<&mut AttackMove>),
+        (With<Ant>, Or<(With<AttackMove>, With<Patrol>)>, Without<AttackTarget>, Without<InTunnel>),
+    >,
+    enemy_q: Query<&Faction, (With<Ant>, Without<InTunnel>)>,
+) {
+    for (entity, faction, transform, attack_move) in hunter_q.iter_mut() {
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        let enemy = spatial_index.within(hex, ACQUIRE_HEXES).find(|&other| {
+            enemy_q.get(other).is_ok_and(|other_faction| teams.relation(faction.0, other_faction.0) == Relation::Enemy)
+        });
+        let Some(enemy) = enemy else {
+            continue;
+        };
+        commands.entity(entity).insert(AttackTarget(enemy));
+        if let Some(mut attack_move) = attack_move {
+            attack_move.resume = true;
+        }
+    }
+}
+
+// Attackers follow their target as it moves: whenever it has wandered off from
+// where an attacker is headed, the attacker is routed to the target's hex.
+// Attack-moving and patrolling units only follow so far before going back to
+// their route.
+fn chase_attack_targets(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    mut path_queue: ResMut<PathQueue>,
+    attacker_q: Query<(Entity, &AttackTarget, &Transform, &TargetPosition, &Path, Has<AttackMove>, Has<Patrol>)>,
+    target_q: Query<&Transform, With<Faction>>,
+) {
+    for (entity, attack, transform, target, path, attack_moving, patrolling) in attacker_q.iter() {
+        let Ok(target_transform) = target_q.get(attack.0) else {
+            commands.entity(entity).remove::<AttackTarget>();
+            continue;
+        };
+        let from = layout.0.world_to_hex(transform.translation.truncate());
+        let goal = layout.0.world_to_hex(target_transform.translation.truncate());
+        if (attack_moving || patrolling) && from.unsigned_distance_to(goal) > LEASH_HEXES {
+            commands.entity(entity).remove::<AttackTarget>();
+            continue;
+        }
+        if path_queue.is_pending(entity) {
+            continue;
+        }
+        let heading = layout.0.world_to_hex(path.waypoints.back().copied().unwrap_or(target.0));
+        if heading.unsigned_distance_to(goal) > CHASE_SLACK {
+            path_queue.request(entity, from, goal, PathPriority::PlayerOrder);
+        }
+    }
+}
+
+// Once an attack-moving unit comes to a stop it either heads on to its spot
+// after a chase or, having got there (or as close as it can), is done
+fn advance_attack_moves(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    mut path_queue: ResMut<PathQueue>,
+    mut unit_q: Query<(Entity, &mut AttackMove, &Transform, &TargetPosition, &Path), Without<AttackTarget>>,
+) {
+    for (entity, mut attack_move, transform, target, path) in unit_q.iter_mut() {
+        let here = transform.translation.truncate();
+        if path_queue.is_pending(entity) || !path.waypoints.is_empty() || here.distance(target.0) > ARRIVAL_RADIUS {
+            continue;
+        }
+        if attack_move.resume {
+            attack_move.resume = false;
+            let to = layout.0.world_to_hex(attack_move.destination);
+            path_queue.request(entity, layout.0.world_to_hex(here), to, PathPriority::PlayerOrder);
+        } else {
+            commands.entity(entity).remove::<AttackMove>();
+        }
+    }
+}
+
+// Stop and Hold: the units drop their route, patrol, tunnel trip, chase and
+// any route still being searched for, and stand where they are
+fn execute_stop_orders(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    mut path_queue: ResMut<PathQueue>,
+    mut unit_q: Query<(&Faction, &Transform, &mut TargetPosition, &mut Path), With<Ant>>,
+) {
+    for command in game_commands.read() {
+        let (player, units, hold) = match command {
+            GameCommand::Stop { player, units } => (*player, units, false),
+            GameCommand::Hold { player, units } => (*player, units, true),
+            _ => continue,
+        };
+        for &entity in units {
+            let Ok((faction, transform, mut target, mut path)) = unit_q.get_mut(entity) else {
+                continue;
+            };
+            if faction.0 != player {
+                continue;
+            }
+            let here = transform.translation.truncate();
+            target.0 = here;
+            path.waypoints.clear();
+            path_queue.cancel(entity);
+            commands.entity(entity).remove::<(Patrol, FastTravel, AttackTarget, AttackMove)>();
+            if hold {
+                commands.entity(entity).insert(HoldPosition(here));
+            }
+        }
+    }
+}
+
+// R and H order the selection to stop or hold position
+fn stop_and_hold_keys(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    local_player: Res<LocalPlayer>,
+    selected_q: Query<Entity, With<Selected>>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    let player = local_player.0;
+    if bindings.just_pressed(&keyboard_input, Action::StopUnits) {
+        game_commands.send(GameCommand::Stop { player, units: selected_q.iter().collect() });
+    }
+    if bindings.just_pressed(&keyboard_input, Action::HoldPosition) {
+        game_commands.send(GameCommand::Hold { player, units: selected_q.iter().collect() });
+    }
+}
+
+pub struct GameCommandsPlugin;
+
+impl Plugin for GameCommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GameCommand>()
+            // Stop is on R rather than the usual S, which pans the camera with WASD
+            .register_action(Action::StopUnits, &[KeyCode::KeyR])
+            .register_action(Action::HoldPosition, &[KeyCode::KeyH])
+            .add_systems(
+                Update,
+                (
+                    stop_and_hold_keys.before(crate::dispatch_pointer_actions),
+                    (execute_selection, execute_unit_orders, execute_stop_orders).after(crate::dispatch_pointer_actions),
+                    (acquire_targets, chase_attack_targets, advance_attack_moves).chain().after(execute_unit_orders),
+                ),
+            );
+    }
+}
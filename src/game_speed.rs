@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::determinism::{Determinism, TICK_SECS};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+
+// Pause and fast-forward. The multiplier is applied to virtual time, which
+// every gameplay timer and movement system already reads through `Time`;
+// Rapier's step limit is raised to match so physics keeps up instead of
+// capping each frame at 1x (in deterministic mode it takes exactly one fixed
+// step per frame instead). Camera, UI and animation run on real time and stay
+// responsive while paused. 1/2/3 pick 1x/2x/4x, F5 pauses.
+
+pub const SPEEDS: [f32; 3] = [1.0, 2.0, 4.0];
+// Longest physics step at 1x; faster speeds take several of these per frame
+const BASE_MAX_DT: f32 = 1.0 / 60.0;
+
+#[derive(Resource)]
+pub struct GameSpeed {
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        Self { speed: 1.0, paused: false }
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum SpeedButton {
+    Pause,
+    Speed(f32),
+}
+
+fn setup_speed_bar(mut commands: Commands) {
+    let buttons = [
+        (SpeedButton::Pause, "||"),
+        (SpeedButton::Speed(SPEEDS[0]), "1x"),
+        (SpeedButton::Speed(SPEEDS[1]), "2x"),
+        (SpeedButton::Speed(SPEEDS[2]), "4x"),
+    ];
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(8.0),
+                    left: Val::Percent(50.0),
+                    column_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            },
+            Interaction::default(),
+        ))
+        .with_children(|bar| {
+            for (button, label) in buttons {
+                bar.spawn((
+                    ButtonBundle {
+                        style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+                        background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                        ..default()
+                    },
+                    button,
+                ))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle { font_size: 14.0, color: Color::WHITE, ..default() },
+                    ));
+                });
+            }
+        });
+}
+
+fn speed_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    button_q: Query<(&Interaction, &SpeedButton), Changed<Interaction>>,
+    mut game_speed: ResMut<GameSpeed>,
+) {
+    let pressed_buttons = button_q
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| *button);
+    let keys = [
+        (Action::PauseGame, SpeedButton::Pause),
+        (Action::SpeedNormal, SpeedButton::Speed(SPEEDS[0])),
+        (Action::SpeedFast, SpeedButton::Speed(SPEEDS[1])),
+        (Action::SpeedFastest, SpeedButton::Speed(SPEEDS[2])),
+    ]
+    .into_iter()
+    .filter(|(action, _)| bindings.just_pressed(&keyboard_input, *action))
+    .map(|(_, button)| button);
+
+    for button in pressed_buttons.chain(keys) {
+        match button {
+            SpeedButton::Pause => game_speed.paused = !game_speed.paused,
+            // Picking a speed also resumes
+            SpeedButton::Speed(speed) => {
+                game_speed.speed = speed;
+                game_speed.paused = false;
+            }
+        }
+    }
+}
+
+fn apply_game_speed(
+    game_speed: Res<GameSpeed>,
+    determinism: Res<Determinism>,
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if !game_speed.is_changed() {
+        return;
+    }
+    if game_speed.paused {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+    time.set_relative_speed(game_speed.speed);
+    // A fixed step doesn't look at the clock, so it has to be stopped outright
+    rapier_config.physics_pipeline_active = !game_speed.paused;
+    let substeps = game_speed.speed.ceil().max(1.0) as usize;
+    rapier_config.timestep_mode = if determinism.enabled {
+        TimestepMode::Fixed { dt: TICK_SECS * game_speed.speed, substeps }
+    } else {
+        TimestepMode::Variable { max_dt: BASE_MAX_DT * game_speed.speed, time_scale: 1.0, substeps }
+    };
+}
+
+fn update_speed_bar(game_speed: Res<GameSpeed>, mut button_q: Query<(&SpeedButton, &mut BackgroundColor)>) {
+    if !game_speed.is_changed() {
+        return;
+    }
+    for (button, mut background) in button_q.iter_mut() {
+        let active = match *button {
+            SpeedButton::Pause => game_speed.paused,
+            SpeedButton::Speed(speed) => !game_speed.paused && speed == game_speed.speed,
+        };
+        *background = if active { Color::srgb(0.3, 0.45, 0.3) } else { Color::srgb(0.25, 0.25, 0.25) }.into();
+    }
+}
+
+fn set_command(In(args): In<Vec<String>>, mut game_speed: ResMut<GameSpeed>) -> CommandResult {
+    match args.as_slice() {
+        [setting, value] if setting == "speed" => {
+            let speed: f32 = value.parse().map_err(|_| format!("not a number: {value}"))?;
+            if speed <= 0.0 {
+                return Err("speed must be positive; use F5 to pause".to_string());
+            }
+            game_speed.speed = speed;
+            Ok(format!("game speed {speed}x"))
+        }
+        _ => Err("usage: set speed <multiplier>".to_string()),
+    }
+}
+
+pub struct GameSpeedPlugin;
+
+impl Plugin for GameSpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSpeed>()
+            .register_action(Action::PauseGame, &[KeyCode::F5, KeyCode::Pause])
+            .register_action(Action::SpeedNormal, &[KeyCode::Digit1])
+            .register_action(Action::SpeedFast, &[KeyCode::Digit2])
+            .register_action(Action::SpeedFastest, &[KeyCode::Digit3])
+            .register_console_command("set", "set speed <multiplier>: change the game speed", set_command)
+            .add_systems(Startup, setup_speed_bar)
+            .add_systems(Update, (speed_input, apply_game_speed, update_speed_bar).chain());
+    }
+}
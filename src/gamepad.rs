@@ -0,0 +1,131 @@
+use bevy::prelude::*;
+
+use crate::camera_controls::{CameraInput, ManualCameraMove, ZoomLimits};
+use crate::input::PointerAction;
+use crate::settings::Settings;
+use crate::MainCamera;
+
+// Controller support: left stick pans, triggers zoom, and the right stick
+// drives a virtual cursor. A selects (tap) or box-selects (hold and move),
+// X orders the selection to the cursor. Orders go through the same
+// `PointerAction` dispatch as mouse and touch. Pan and zoom speeds are the
+// keyboard's, from `Settings::camera`.
+
+// Screen-space cursor speed; scaled by zoom so it feels the same at any zoom level
+const CURSOR_SPEED: f32 = 400.0;
+const STICK_DEADZONE: f32 = 0.15;
+
+#[derive(Resource, Default)]
+struct GamepadCursor {
+    world_pos: Option<Vec2>,
+    drag_start: Option<Vec2>,
+}
+
+fn stick(axes: &Axis<GamepadAxis>, gamepad: Gamepad, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+    let value = Vec2::new(
+        axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.0),
+        axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.0),
+    );
+    if value.length() < STICK_DEADZONE { Vec2::ZERO } else { value }
+}
+
+fn gamepad_camera(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    button_axes: Res<Axis<GamepadButton>>,
+    time: Res<Time<Real>>,
+    limits: Res<ZoomLimits>,
+    settings: Res<Settings>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    mut moved: EventWriter<ManualCameraMove>,
+) {
+    let (mut transform, mut projection) = camera_q.single_mut();
+
+    for gamepad in gamepads.iter() {
+        let pan = stick(&axes, gamepad, GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+        transform.translation += (pan * settings.camera.pan_speed * time.delta_seconds()).extend(0.0);
+
+        let trigger = |button| button_axes.get(GamepadButton::new(gamepad, button)).unwrap_or(0.0);
+        let zoom = trigger(GamepadButtonType::LeftTrigger2) - trigger(GamepadButtonType::RightTrigger2);
+        projection.scale = limits.clamp(projection.scale + zoom * settings.camera.zoom_speed * time.delta_seconds());
+        if pan != Vec2::ZERO || zoom != 0.0 {
+            moved.send(ManualCameraMove);
+        }
+    }
+}
+
+fn gamepad_cursor(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    time: Res<Time<Real>>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut cursor: ResMut<GamepadCursor>,
+    mut actions: EventWriter<PointerAction>,
+) {
+    let Ok((camera_transform, projection)) = camera_q.get_single() else {
+        return;
+    };
+    let camera_pos = camera_transform.translation.truncate();
+
+    for gamepad in gamepads.iter() {
+        let aim = stick(&axes, gamepad, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+        // Cursor appears at screen center the first time the stick is used
+        if aim != Vec2::ZERO && cursor.world_pos.is_none() {
+            cursor.world_pos = Some(camera_pos);
+        }
+        let Some(mut world_pos) = cursor.world_pos else {
+            continue;
+        };
+        world_pos += aim * CURSOR_SPEED * projection.scale * time.delta_seconds();
+        cursor.world_pos = Some(world_pos);
+
+        let select = GamepadButton::new(gamepad, GamepadButtonType::South);
+        if buttons.just_pressed(select) {
+            cursor.drag_start = Some(world_pos);
+        }
+        if buttons.just_released(select) {
+            if let Some(start) = cursor.drag_start.take() {
+                // Same slop as the mouse, measured on screen rather than in the world
+                let action = if start.distance(world_pos) / projection.scale < crate::TAP_SLOP_PX {
+                    PointerAction::Select { world_pos, additive: true }
+                } else {
+                    PointerAction::BoxSelect { start, end: world_pos, additive: true }
+                };
+                actions.send(action);
+            }
+        }
+
+        if buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::West)) {
+            actions.send(PointerAction::Command { world_pos });
+        }
+    }
+}
+
+fn draw_gamepad_cursor(mut gizmos: Gizmos, cursor: Res<GamepadCursor>) {
+    let Some(pos) = cursor.world_pos else {
+        return;
+    };
+    gizmos.circle_2d(pos, 6.0, Color::WHITE);
+    gizmos.line_2d(pos - Vec2::X * 10.0, pos + Vec2::X * 10.0, Color::WHITE);
+    gizmos.line_2d(pos - Vec2::Y * 10.0, pos + Vec2::Y * 10.0, Color::WHITE);
+
+    if let Some(start) = cursor.drag_start {
+        gizmos.rect_2d((start + pos) / 2.0, 0.0, (start - pos).abs(), Color::WHITE);
+    }
+}
+
+pub struct GamepadPlugin;
+
+impl Plugin for GamepadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GamepadCursor>().add_systems(
+            Update,
+            (
+                gamepad_camera.in_set(CameraInput),
+                gamepad_cursor.before(crate::dispatch_pointer_actions),
+                draw_gamepad_cursor,
+            ),
+        );
+    }
+}
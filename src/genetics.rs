@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::determinism::SimRng;
+use crate::health::Health;
+use crate::interactions::FoodStores;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::upgrades::{ColonyUpgrades, Upgrade};
+use crate::{Ant, Faction, Queen};
+
+// Genetics. Every colony has a gene pool: the average vigor (health), speed
+// and longevity (lifespan) of its ants, each a multiplier around 1. A newly
+// raised ant rolls its own genes close to the pool's, and the pool then
+// drifts a little towards the newborn, so over many generations colonies
+// wander apart. The rolls lean towards what the colony invests in: each level
+// of the toughness and worker speed upgrades pulls vigor and speed up, and a
+// well-stocked food store pulls longevity up. Pools are colony data that
+// outlive the session and are kept in the world save (see `save`). `genes`
+// prints ours.
+
+// Largest difference between a newborn's genes and the pool's, either way
+const MUTATION: f32 = 0.08;
+// Share of the way the pool moves towards each newborn
+const DRIFT: f32 = 0.1;
+// Lean per upgrade level, and for a colony storing at least `WELL_FED` food
+const UPGRADE_LEAN: f32 = 0.01;
+const WELL_FED: u32 = 30;
+const FED_LEAN: f32 = 0.02;
+const MIN_GENE: f32 = 0.5;
+const MAX_GENE: f32 = 1.5;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Genes {
+    pub vigor: f32,
+    pub speed: f32,
+    pub longevity: f32,
+}
+
+impl Default for Genes {
+    fn default() -> Self {
+        Self { vigor: 1.0, speed: 1.0, longevity: 1.0 }
+    }
+}
+
+impl Genes {
+    fn clamped(self) -> Self {
+        Self {
+            vigor: self.vigor.clamp(MIN_GENE, MAX_GENE),
+            speed: self.speed.clamp(MIN_GENE, MAX_GENE),
+            longevity: self.longevity.clamp(MIN_GENE, MAX_GENE),
+        }
+    }
+
+    // `share` of the way from these genes to `other`
+    fn towards(self, other: Genes, share: f32) -> Self {
+        let step = |from: f32, to: f32| from + (to - from) * share;
+        Self {
+            vigor: step(self.vigor, other.vigor),
+            speed: step(self.speed, other.speed),
+            longevity: step(self.longevity, other.longevity),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct GenePool(BTreeMap<PlayerId, Genes>);
+
+impl GenePool {
+    pub fn get(&self, player: PlayerId) -> Genes {
+        self.0.get(&player).copied().unwrap_or_default()
+    }
+
+    // Pools as the world save keeps them, by player number
+    pub fn to_saved(&self) -> BTreeMap<u32, Genes> {
+        self.0.iter().map(|(player, &genes)| (player.0, genes)).collect()
+    }
+
+    pub fn from_saved(pools: BTreeMap<u32, Genes>) -> Self {
+        Self(pools.into_iter().map(|(player, genes)| (PlayerId(player), genes.clamped())).collect())
+    }
+}
+
+// A newborn's genes, and the pool drifting towards them
+fn roll_genes(
+    mut commands: Commands,
+    mut rng: ResMut<SimRng>,
+    mut pool: ResMut<GenePool>,
+    upgrades: Res<ColonyUpgrades>,
+    stores: Res<FoodStores>,
+    mut new_ants: Query<(Entity, &Faction, &mut Health), (Added<Ant>, Without<Queen>)>,
+) {
+    for (entity, faction, mut health) in new_ants.iter_mut() {
+        let player = faction.0;
+        let fed = stores.0.get(&player).is_some_and(|&food| food >= WELL_FED);
+        let lean = Genes {
+            vigor: UPGRADE_LEAN * upgrades.level(player, Upgrade::Toughness) as f32,
+            speed: UPGRADE_LEAN * upgrades.level(player, Upgrade::WorkerSpeed) as f32,
+            longevity: if fed { FED_LEAN } else { 0.0 },
+        };
+        let mut roll = |mean: f32, lean: f32| mean + lean + rng.0.gen_range(-MUTATION..=MUTATION);
+        let parent = pool.get(player);
+        let genes = Genes {
+            vigor: roll(parent.vigor, lean.vigor),
+            speed: roll(parent.speed, lean.speed),
+            longevity: roll(parent.longevity, lean.longevity),
+        }
+        .clamped();
+        pool.0.insert(player, parent.towards(genes, DRIFT));
+
+        health.max *= genes.vigor;
+        health.current *= genes.vigor;
+        commands.entity(entity).insert(genes);
+    }
+}
+
+fn genes_command(In(_args): In<Vec<String>>, pool: Res<GenePool>, local_player: Res<LocalPlayer>) -> CommandResult {
+    let genes = pool.get(local_player.0);
+    Ok(format!("vigor {:.2}, speed {:.2}, longevity {:.2}", genes.vigor, genes.speed, genes.longevity))
+}
+
+pub struct GeneticsPlugin;
+
+impl Plugin for GeneticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GenePool>()
+            .register_console_command("genes", "genes: your colony's gene pool", genes_command)
+            .add_systems(Update, roll_genes);
+    }
+}
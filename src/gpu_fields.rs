@@ -0,0 +1,824 @@
+use bevy::prelude::*;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_asset::{RenderAssetUsages, RenderAssets};
+use bevy::render::render_graph::{self, RenderGraph, RenderLabel};
+use bevy::render::render_resource::binding_types::{texture_2d, texture_storage_2d, uniform_buffer};
+use bevy::render::render_resource::*;
+use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
+use bevy::render::texture::GpuImage;
+use bevy::render::{Render, RenderApp, RenderSet};
+use bevy::sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle};
+use hexx::Hex;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::coords::HexLayoutExt;
+use crate::determinism::Determinism;
+use crate::factions::FactionColors;
+use crate::loading::LoadingAssets;
+use crate::lobby::MatchSettings;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::{territory, vision, MapLayout, MapSize};
+
+// Pheromone and sight fields on the GPU. Over a large map, fading every hex's
+// pheromone and expanding every ant's sight on the CPU dominates the tick, so
+// where the GPU can run compute shaders both fields live in textures, one
+// texel per hex: `territory` and `vision` only upload where the ants are,
+// and `shaders/fields.wgsl` fades, deposits and spreads the pheromone and
+// works out the hexes in sight. The results are read back a few times a
+// second, which is as often as territory and fog are worked out anyway, and
+// the overlay (`pheromones` in the console) draws straight from the textures.
+//
+// The CPU path stays for everything else: WebGL has no compute shaders, a
+// deterministic run can't depend on GPU float math or on when a readback
+// lands, and a match with more colonies than the texture has room for falls
+// back to it for good.
+
+const FIELDS_SHADER: &str = "shaders/fields.wgsl";
+const OVERLAY_SHADER: &str = "shaders/field_overlay.wgsl";
+const WORKGROUP_SIZE: u32 = 8;
+// Colonies the pheromone texture has room for: one per channel, side by side
+// in two halves
+const SLOTS: usize = 8;
+// Above the ground and rocks, below structures, food and units
+const OVERLAY_Z: f32 = 0.2;
+// Rows copied out of a texture start this many bytes apart, at least
+const ROW_ALIGNMENT: u32 = 256;
+
+// Hexes along each side of the square of texels covering a map
+fn side(radius: u32) -> usize {
+    (radius.max(1) * 2 - 1) as usize
+}
+
+// Texel of a hex, if it lies in the square around the map
+fn texel(radius: u32, hex: Hex) -> Option<(usize, usize)> {
+    let offset = radius.max(1) as i32 - 1;
+    let range = 0..side(radius) as i32;
+    let (x, y) = (hex.x + offset, hex.y + offset);
+    (range.contains(&x) && range.contains(&y)).then_some((x as usize, y as usize))
+}
+
+fn hex_at(radius: u32, x: usize, y: usize) -> Hex {
+    let offset = radius.max(1) as i32 - 1;
+    Hex::new(x as i32 - offset, y as i32 - offset)
+}
+
+// Index of a colony's value at a texel in the pheromone texture's floats
+fn pheromone_index(side: usize, (x, y): (usize, usize), slot: usize) -> usize {
+    (y * side * 2 + x + slot / 4 * side) * 4 + slot % 4
+}
+
+// The pheromone texture read back, as `territory` keeps it
+fn decode_pheromone(radius: u32, slots: &[PlayerId], texels: &[f32]) -> HashMap<Hex, BTreeMap<PlayerId, f32>> {
+    let side = side(radius);
+    let mut pheromone: HashMap<Hex, BTreeMap<PlayerId, f32>> = HashMap::new();
+    for (slot, &player) in slots.iter().enumerate() {
+        for y in 0..side {
+            for x in 0..side {
+                let strength = texels.get(pheromone_index(side, (x, y), slot)).copied().unwrap_or(0.0);
+                if strength > 0.0 {
+                    pheromone.entry(hex_at(radius, x, y)).or_default().insert(player, strength);
+                }
+            }
+        }
+    }
+    pheromone
+}
+
+fn decode_visible(radius: u32, texels: &[f32]) -> HashSet<Hex> {
+    let side = side(radius);
+    texels
+        .iter()
+        .enumerate()
+        .filter(|(_, &seen)| seen > 0.0)
+        .map(|(index, _)| hex_at(radius, index % side, index / side))
+        .collect()
+}
+
+fn floats_to_bytes(floats: &[f32]) -> Vec<u8> {
+    floats.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+#[derive(Clone)]
+struct FieldImages {
+    // Ants on each hex per colony, queens apart: what this tick deposits
+    walkers: Handle<Image>,
+    nests: Handle<Image>,
+    // Pheromone per hex and colony, each written from the other in turn
+    pheromone: [Handle<Image>; 2],
+    // Ants on each hex that see for our team, and the hexes in their sight
+    sight: Handle<Image>,
+    visible: Handle<Image>,
+}
+
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct GpuFields {
+    // The GPU can run the compute passes
+    supported: bool,
+    // Textures for the current map, while the fields are on the GPU
+    images: Option<FieldImages>,
+    radius: u32,
+    // Which pheromone texture holds the latest field
+    front: usize,
+    // Passes to run this frame
+    step_pheromone: bool,
+    step_reveal: bool,
+    // Colony whose pheromone each channel holds
+    slots: Vec<PlayerId>,
+}
+
+impl GpuFields {
+    pub fn active(&self) -> bool {
+        self.images.is_some()
+    }
+
+    fn slot(&self, player: PlayerId) -> Option<usize> {
+        self.slots.iter().position(|&slotted| slotted == player)
+    }
+
+    // Uploads this tick's ants (hex, colony, queen) for the pheromone pass.
+    // False if the fields aren't on the GPU, so the caller fades and deposits
+    // itself; a colony beyond the texture's room turns them off for good.
+    pub fn deposit(&mut self, images: &mut Assets<Image>, marks: &[(Hex, PlayerId, bool)]) -> bool {
+        let Some(handles) = self.images.clone() else {
+            return false;
+        };
+        for &(_, player, _) in marks {
+            if self.slot(player).is_some() {
+                continue;
+            }
+            if self.slots.len() == SLOTS {
+                warn!("More than {SLOTS} colonies; pheromone goes back to the CPU");
+                self.images = None;
+                return false;
+            }
+            self.slots.push(player);
+        }
+        let side = side(self.radius);
+        let mut walkers = vec![0.0; side * side * 2 * 4];
+        let mut nests = walkers.clone();
+        for &(hex, player, queen) in marks {
+            let (Some(texel), Some(slot)) = (texel(self.radius, hex), self.slot(player)) else {
+                continue;
+            };
+            let counts = if queen { &mut nests } else { &mut walkers };
+            counts[pheromone_index(side, texel, slot)] += 1.0;
+        }
+        for (handle, counts) in [(&handles.walkers, walkers), (&handles.nests, nests)] {
+            if let Some(image) = images.get_mut(handle) {
+                image.data = floats_to_bytes(&counts);
+            }
+        }
+        self.front = 1 - self.front;
+        self.step_pheromone = true;
+        true
+    }
+
+    // Uploads the hexes our team's ants stand on for the sight pass. False
+    // if the fields aren't on the GPU.
+    pub fn reveal(&mut self, images: &mut Assets<Image>, watched: impl Iterator<Item = Hex>) -> bool {
+        let Some(handles) = &self.images else {
+            return false;
+        };
+        let side = side(self.radius);
+        let mut sight = vec![0.0; side * side];
+        for (x, y) in watched.filter_map(|hex| texel(self.radius, hex)) {
+            sight[y * side + x] = 1.0;
+        }
+        if let Some(image) = images.get_mut(&handles.sight) {
+            image.data = floats_to_bytes(&sight);
+        }
+        self.step_reveal = true;
+        true
+    }
+}
+
+// What the GPU worked out, read back, raw
+struct Readback {
+    radius: u32,
+    pheromone: Option<Vec<f32>>,
+    visible: Option<Vec<f32>>,
+}
+
+// The latest fields read back from the GPU
+#[derive(Resource)]
+pub struct FieldReadbacks {
+    receiver: Mutex<Receiver<Readback>>,
+    pheromone: Option<HashMap<Hex, BTreeMap<PlayerId, f32>>>,
+    visible: HashSet<Hex>,
+}
+
+impl FieldReadbacks {
+    // Pheromone read back since last asked, if any
+    pub fn take_pheromone(&mut self) -> Option<HashMap<Hex, BTreeMap<PlayerId, f32>>> {
+        self.pheromone.take()
+    }
+
+    // Hexes our team's ants saw as of the last readback
+    pub fn visible(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.visible.iter().copied()
+    }
+}
+
+fn receive_readbacks(fields: Res<GpuFields>, mut readbacks: ResMut<FieldReadbacks>) {
+    let received: Vec<Readback> = readbacks.receiver.lock().unwrap().try_iter().collect();
+    // Anything from before the map changed is stale
+    for readback in received.into_iter().filter(|readback| readback.radius == fields.radius) {
+        if let Some(texels) = readback.pheromone {
+            readbacks.pheromone = Some(decode_pheromone(fields.radius, &fields.slots, &texels));
+        }
+        if let Some(texels) = readback.visible {
+            readbacks.visible = decode_visible(fields.radius, &texels);
+        }
+    }
+}
+
+// A step's passes run in the frame it's asked for, and only then
+fn clear_field_steps(mut fields: ResMut<GpuFields>) {
+    if fields.step_pheromone || fields.step_reveal {
+        fields.step_pheromone = false;
+        fields.step_reveal = false;
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct FieldOverlayMaterial {
+    #[uniform(0)]
+    color: LinearRgba,
+    // x: the half of the pheromone texture our colony is in, y: its channel,
+    // z: 1 to shade hexes out of sight, w: pheromone shown at full strength
+    #[uniform(1)]
+    params: Vec4,
+    #[texture(2, sample_type = "float", filterable = false)]
+    pheromone: Option<Handle<Image>>,
+    #[texture(3, sample_type = "float", filterable = false)]
+    visible: Option<Handle<Image>>,
+}
+
+impl Material2d for FieldOverlayMaterial {
+    fn fragment_shader() -> ShaderRef {
+        OVERLAY_SHADER.into()
+    }
+}
+
+#[derive(Component)]
+struct FieldOverlay;
+
+#[derive(Resource, Default)]
+struct OverlayState {
+    shown: bool,
+    material: Handle<FieldOverlayMaterial>,
+}
+
+fn field_image(width: usize, height: usize, format: TextureFormat, texel_bytes: usize, written: bool) -> Image {
+    // Inputs are rewritten from here every tick; outputs only exist on the GPU
+    let (asset_usage, usage) = if written {
+        (RenderAssetUsages::RENDER_WORLD, TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC)
+    } else {
+        (RenderAssetUsages::default(), TextureUsages::empty())
+    };
+    let size = Extent3d { width: width as u32, height: height as u32, depth_or_array_layers: 1 };
+    let mut image = Image::new_fill(size, TextureDimension::D2, &vec![0; texel_bytes], format, asset_usage);
+    image.texture_descriptor.usage |= usage;
+    image
+}
+
+// A hexagon per hex, every vertex carrying the hex's texel so the overlay
+// shader reads one value per hex
+fn build_overlay_mesh(layout: &hexx::HexLayout, map_size: &MapSize) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    for hex in map_size.hexes() {
+        let Some((x, y)) = texel(map_size.radius, hex) else {
+            continue;
+        };
+        let center = layout.hex_to_world(hex);
+        let corners = layout.world_corners(hex);
+        for i in 0..6 {
+            let (start, end) = (corners[i], corners[(i + 1) % 6]);
+            positions.extend([[center.x, center.y, 0.0], [start.x, start.y, 0.0], [end.x, end.y, 0.0]]);
+            uvs.extend([[x as f32 + 0.5, y as f32 + 0.5]; 3]);
+        }
+    }
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+}
+
+fn setup_field_overlay(mut commands: Commands, asset_server: Res<AssetServer>, mut loading: ResMut<LoadingAssets>) {
+    loading.queue(asset_server.load::<Shader>(FIELDS_SHADER));
+    loading.queue(asset_server.load::<Shader>(OVERLAY_SHADER));
+    commands.init_resource::<OverlayState>();
+}
+
+// New textures and overlay whenever the map changes size
+fn sync_field_images(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    determinism: Res<Determinism>,
+    mut fields: ResMut<GpuFields>,
+    mut overlay: ResMut<OverlayState>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<FieldOverlayMaterial>>,
+    overlay_q: Query<Entity, With<FieldOverlay>>,
+) {
+    if !map_size.is_changed() && !layout.is_changed() {
+        return;
+    }
+    for entity in overlay_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    if !fields.supported || determinism.enabled {
+        return;
+    }
+
+    let radius = map_size.radius;
+    let side = side(radius);
+    let pheromone = field_image(side * 2, side, TextureFormat::Rgba32Float, 16, true);
+    *fields = GpuFields {
+        supported: true,
+        images: Some(FieldImages {
+            walkers: images.add(field_image(side * 2, side, TextureFormat::Rgba32Float, 16, false)),
+            nests: images.add(field_image(side * 2, side, TextureFormat::Rgba32Float, 16, false)),
+            pheromone: [images.add(pheromone.clone()), images.add(pheromone)],
+            sight: images.add(field_image(side, side, TextureFormat::R32Float, 4, false)),
+            visible: images.add(field_image(side, side, TextureFormat::R32Float, 4, true)),
+        }),
+        radius,
+        ..default()
+    };
+
+    overlay.material = materials.add(FieldOverlayMaterial::default());
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(build_overlay_mesh(&layout.0, &map_size)).into(),
+            material: overlay.material.clone(),
+            transform: Transform::from_xyz(0.0, 0.0, OVERLAY_Z),
+            visibility: if overlay.shown { Visibility::Inherited } else { Visibility::Hidden },
+            ..default()
+        },
+        FieldOverlay,
+    ));
+}
+
+// Points the overlay at the latest field and at our colony's channel
+fn sync_field_overlay(
+    fields: Res<GpuFields>,
+    overlay: Res<OverlayState>,
+    local_player: Res<LocalPlayer>,
+    colors: Res<FactionColors>,
+    settings: Res<MatchSettings>,
+    mut materials: ResMut<Assets<FieldOverlayMaterial>>,
+) {
+    if !fields.is_changed() || !overlay.shown {
+        return;
+    }
+    let (Some(images), Some(material)) = (&fields.images, materials.get_mut(&overlay.material)) else {
+        return;
+    };
+    let fog = if settings.mutators.fog() { 1.0 } else { 0.0 };
+    // Until our ants have left any trail there is nothing of ours to show
+    let (half, channel, full) = match fields.slot(local_player.0) {
+        Some(slot) => ((slot / 4) as f32, (slot % 4) as f32, territory::CLAIM_STRENGTH),
+        None => (0.0, 0.0, f32::MAX),
+    };
+    material.color = colors.get(local_player.0).into();
+    material.params = Vec4::new(half, channel, fog, full);
+    material.pheromone = Some(images.pheromone[fields.front].clone());
+    material.visible = Some(images.visible.clone());
+}
+
+fn pheromones_command(
+    In(_args): In<Vec<String>>,
+    fields: Res<GpuFields>,
+    mut overlay: ResMut<OverlayState>,
+    mut overlay_q: Query<&mut Visibility, With<FieldOverlay>>,
+) -> CommandResult {
+    if !fields.active() {
+        return Err("the pheromone overlay needs the fields on the GPU, and this session runs them on the CPU".to_string());
+    }
+    overlay.shown = !overlay.shown;
+    for mut visibility in overlay_q.iter_mut() {
+        *visibility = if overlay.shown { Visibility::Inherited } else { Visibility::Hidden };
+    }
+    Ok(format!("pheromone overlay {}", if overlay.shown { "on" } else { "off" }))
+}
+
+#[derive(Resource)]
+struct FieldsPipeline {
+    layout: BindGroupLayout,
+    pheromone: CachedComputePipelineId,
+    reveal: CachedComputePipelineId,
+}
+
+impl FromWorld for FieldsPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(
+            "fields",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::WriteOnly),
+                    texture_2d(TextureSampleType::Float { filterable: false }),
+                    texture_storage_2d(TextureFormat::R32Float, StorageTextureAccess::WriteOnly),
+                    uniform_buffer::<UVec4>(false),
+                    uniform_buffer::<Vec4>(false),
+                ),
+            ),
+        );
+        let shader = world.load_asset(FIELDS_SHADER);
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(Cow::from(entry_point)),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::from(entry_point),
+            })
+        };
+        let (pheromone, reveal) = (queue("pheromone"), queue("reveal"));
+        FieldsPipeline { layout, pheromone, reveal }
+    }
+}
+
+// Sizes (side, nest radius, sight radius) and rates (decay, forget below,
+// deposit, neighbor deposit) for the passes
+#[derive(Resource, Default)]
+struct FieldParams {
+    sizes: UniformBuffer<UVec4>,
+    rates: UniformBuffer<Vec4>,
+}
+
+// This frame's bindings, and the textures it writes
+#[derive(Resource)]
+struct FieldBindGroup {
+    bind_group: BindGroup,
+    pheromone: Texture,
+    visible: Texture,
+}
+
+fn prepare_field_bind_group(
+    mut commands: Commands,
+    pipeline: Res<FieldsPipeline>,
+    mut params: ResMut<FieldParams>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    fields: Res<GpuFields>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    commands.remove_resource::<FieldBindGroup>();
+    let Some(images) = &fields.images else {
+        return;
+    };
+    let [walkers, nests, front, back, sight, visible] =
+        [&images.walkers, &images.nests, &images.pheromone[fields.front], &images.pheromone[1 - fields.front], &images.sight, &images.visible]
+            .map(|handle| gpu_images.get(handle));
+    let (Some(walkers), Some(nests), Some(front), Some(back), Some(sight), Some(visible)) =
+        (walkers, nests, front, back, sight, visible)
+    else {
+        return;
+    };
+
+    params.sizes.set(UVec4::new(side(fields.radius) as u32, territory::NEST_RADIUS, vision::SIGHT_RADIUS, 0));
+    params.rates.set(Vec4::new(territory::DECAY, territory::FORGET_BELOW, territory::DEPOSIT, territory::NEIGHBOR_DEPOSIT));
+    params.sizes.write_buffer(&render_device, &render_queue);
+    params.rates.write_buffer(&render_device, &render_queue);
+    let (Some(sizes), Some(rates)) = (params.sizes.binding(), params.rates.binding()) else {
+        return;
+    };
+    // The step reads the previous field and writes the one now in front
+    let bind_group = render_device.create_bind_group(
+        "fields",
+        &pipeline.layout,
+        &BindGroupEntries::sequential((
+            &walkers.texture_view,
+            &nests.texture_view,
+            &back.texture_view,
+            &front.texture_view,
+            &sight.texture_view,
+            &visible.texture_view,
+            sizes,
+            rates,
+        )),
+    );
+    commands.insert_resource(FieldBindGroup {
+        bind_group,
+        pheromone: front.texture.clone(),
+        visible: visible.texture.clone(),
+    });
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct FieldsLabel;
+
+#[derive(Default)]
+struct FieldsNode {
+    ready: bool,
+}
+
+impl render_graph::Node for FieldsNode {
+    fn update(&mut self, world: &mut World) {
+        let Some(pipeline) = world.get_resource::<FieldsPipeline>() else {
+            return;
+        };
+        let pipeline_cache = world.resource::<PipelineCache>();
+        self.ready = [pipeline.pheromone, pipeline.reveal]
+            .into_iter()
+            .all(|id| matches!(pipeline_cache.get_compute_pipeline_state(id), CachedPipelineState::Ok(_)));
+    }
+
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let (Some(fields), Some(bind_group)) = (world.get_resource::<GpuFields>(), world.get_resource::<FieldBindGroup>())
+        else {
+            return Ok(());
+        };
+        if !self.ready {
+            return Ok(());
+        }
+        let pipeline = world.resource::<FieldsPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let groups = |texels: usize| (texels as u32).div_ceil(WORKGROUP_SIZE);
+        let side = side(fields.radius);
+
+        let mut pass = render_context.command_encoder().begin_compute_pass(&ComputePassDescriptor::default());
+        pass.set_bind_group(0, &bind_group.bind_group, &[]);
+        if fields.step_pheromone {
+            if let Some(pheromone) = pipeline_cache.get_compute_pipeline(pipeline.pheromone) {
+                pass.set_pipeline(pheromone);
+                pass.dispatch_workgroups(groups(side * 2), groups(side), 1);
+            }
+        }
+        if fields.step_reveal {
+            if let Some(reveal) = pipeline_cache.get_compute_pipeline(pipeline.reveal) {
+                pass.set_pipeline(reveal);
+                pass.dispatch_workgroups(groups(side), groups(side), 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Where in the staging buffer a texture was copied to
+struct Region {
+    offset: u64,
+    width: u32,
+    height: u32,
+    texel_bytes: u32,
+}
+
+impl Region {
+    fn padded_row(&self) -> u32 {
+        (self.width * self.texel_bytes).next_multiple_of(ROW_ALIGNMENT)
+    }
+
+    fn size(&self) -> u64 {
+        u64::from(self.padded_row() * self.height)
+    }
+
+    fn read(&self, bytes: &[u8]) -> Vec<f32> {
+        let row = (self.width * self.texel_bytes) as usize;
+        (0..self.height as usize)
+            .flat_map(|y| {
+                let start = self.offset as usize + y * self.padded_row() as usize;
+                bytes[start..start + row].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            })
+            .collect()
+    }
+}
+
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+// A readback on its way back from the GPU
+struct Staged {
+    buffer: Buffer,
+    state: Arc<AtomicU8>,
+    radius: u32,
+    pheromone: Option<Region>,
+    visible: Option<Region>,
+}
+
+#[derive(Resource)]
+struct ReadbackState {
+    sender: Sender<Readback>,
+    staged: Option<Staged>,
+}
+
+// Hands the last readback to the main world once the GPU has mapped it, and
+// copies out what this frame's passes wrote. One readback is in flight at a
+// time; steps that run meanwhile are only seen through the next one.
+fn read_back_fields(
+    mut state: ResMut<ReadbackState>,
+    fields: Option<Res<GpuFields>>,
+    bind_group: Option<Res<FieldBindGroup>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    if let Some(staged) = &state.staged {
+        render_device.poll(Maintain::Poll);
+        match staged.state.load(Ordering::Acquire) {
+            MAP_PENDING => return,
+            MAP_DONE => {
+                let readback = {
+                    let bytes = staged.buffer.slice(..).get_mapped_range();
+                    Readback {
+                        radius: staged.radius,
+                        pheromone: staged.pheromone.as_ref().map(|region| region.read(&bytes)),
+                        visible: staged.visible.as_ref().map(|region| region.read(&bytes)),
+                    }
+                };
+                staged.buffer.unmap();
+                // The main world only goes away when the app does
+                let _ = state.sender.send(readback);
+            }
+            _ => warn!("Reading the fields back from the GPU failed"),
+        }
+        state.staged = None;
+    }
+
+    let (Some(fields), Some(bind_group)) = (fields, bind_group) else {
+        return;
+    };
+    if !fields.step_pheromone && !fields.step_reveal {
+        return;
+    }
+    let side = side(fields.radius) as u32;
+    let pheromone = fields.step_pheromone.then_some(Region { offset: 0, width: side * 2, height: side, texel_bytes: 16 });
+    let offset = pheromone.as_ref().map_or(0, Region::size);
+    let visible = fields.step_reveal.then_some(Region { offset, width: side, height: side, texel_bytes: 4 });
+    let size = pheromone.iter().chain(&visible).map(|region| region.offset + region.size()).max().unwrap_or(0);
+
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("field readback"),
+        size,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor { label: Some("field readback") });
+    for (texture, region) in [(&bind_group.pheromone, &pheromone), (&bind_group.visible, &visible)] {
+        let Some(region) = region else {
+            continue;
+        };
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: region.offset,
+                    bytes_per_row: Some(region.padded_row()),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d { width: region.width, height: region.height, depth_or_array_layers: 1 },
+        );
+    }
+    render_queue.submit([encoder.finish()]);
+
+    let map_state = Arc::new(AtomicU8::new(MAP_PENDING));
+    let callback_state = map_state.clone();
+    buffer.slice(..).map_async(MapMode::Read, move |result| {
+        callback_state.store(if result.is_ok() { MAP_DONE } else { MAP_FAILED }, Ordering::Release);
+    });
+    state.staged = Some(Staged { buffer, state: map_state, radius: fields.radius, pheromone, visible });
+}
+
+pub struct GpuFieldsPlugin;
+
+impl Plugin for GpuFieldsPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        app.init_resource::<GpuFields>()
+            .insert_resource(FieldReadbacks { receiver: Mutex::new(receiver), pheromone: None, visible: HashSet::new() })
+            .add_plugins((ExtractResourcePlugin::<GpuFields>::default(), Material2dPlugin::<FieldOverlayMaterial>::default()))
+            .register_console_command("pheromones", "pheromones: toggle the pheromone overlay", pheromones_command)
+            .add_systems(Startup, setup_field_overlay)
+            .add_systems(First, clear_field_steps)
+            .add_systems(PreUpdate, receive_readbacks)
+            .add_systems(Update, (sync_field_images, sync_field_overlay).chain());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .insert_resource(ReadbackState { sender, staged: None })
+            .init_resource::<FieldParams>()
+            .add_systems(
+                Render,
+                (
+                    prepare_field_bind_group
+                        .in_set(RenderSet::PrepareBindGroups)
+                        .run_if(resource_exists::<FieldsPipeline>.and_then(resource_exists::<GpuFields>)),
+                    read_back_fields.in_set(RenderSet::Cleanup),
+                ),
+            );
+        let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        render_graph.add_node(FieldsLabel, FieldsNode::default());
+        render_graph.add_node_edge(FieldsLabel, bevy::render::graph::CameraDriverLabel);
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        // Devices without compute shaders (WebGL) report no room for a workgroup
+        if render_app.world().resource::<RenderDevice>().limits().max_compute_workgroup_size_x == 0 {
+            info!("No compute shaders; pheromone and sight stay on the CPU");
+            return;
+        }
+        render_app.init_resource::<FieldsPipeline>();
+        app.world_mut().resource_mut::<GpuFields>().supported = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_hex_of_the_map_has_its_own_texel() {
+        let radius = 5;
+        let map = MapSize { radius };
+        let texels: HashSet<(usize, usize)> = map.hexes().filter_map(|hex| texel(radius, hex)).collect();
+        assert_eq!(texels.len(), map.hexes().count());
+        for hex in map.hexes() {
+            let (x, y) = texel(radius, hex).unwrap();
+            assert_eq!(hex_at(radius, x, y), hex);
+        }
+        assert_eq!(texel(radius, Hex::new(5, 0)), None);
+    }
+
+    #[test]
+    fn pheromone_read_back_lands_on_the_right_hex_and_colony() {
+        let radius = 4;
+        let side = side(radius);
+        let slots: Vec<PlayerId> = (0..SLOTS as u32).map(PlayerId).collect();
+        let mut texels = vec![0.0; side * side * 2 * 4];
+        let hex = Hex::new(-2, 1);
+        // One colony in each half of the texture
+        texels[pheromone_index(side, texel(radius, hex).unwrap(), 1)] = 3.0;
+        texels[pheromone_index(side, texel(radius, hex).unwrap(), 6)] = 0.5;
+
+        let pheromone = decode_pheromone(radius, &slots, &texels);
+        assert_eq!(pheromone.len(), 1);
+        assert_eq!(pheromone[&hex], BTreeMap::from([(PlayerId(1), 3.0), (PlayerId(6), 0.5)]));
+    }
+
+    #[test]
+    fn visible_read_back_keeps_only_the_hexes_in_sight() {
+        let radius = 3;
+        let side = side(radius);
+        let mut texels = vec![0.0; side * side];
+        let (x, y) = texel(radius, Hex::new(1, -1)).unwrap();
+        texels[y * side + x] = 1.0;
+        assert_eq!(decode_visible(radius, &texels), HashSet::from([Hex::new(1, -1)]));
+    }
+
+    #[test]
+    fn readback_regions_skip_the_row_padding() {
+        let region = Region { offset: 0, width: 3, height: 2, texel_bytes: 4 };
+        let padded = region.padded_row() as usize;
+        let mut bytes = vec![0; padded * 2];
+        for (y, row) in [[1.0f32, 2.0, 3.0], [4.0, 5.0, 6.0]].iter().enumerate() {
+            bytes[y * padded..y * padded + 12].copy_from_slice(&floats_to_bytes(row));
+        }
+        assert_eq!(region.read(&bytes), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn a_colony_past_the_texture_sends_pheromone_back_to_the_cpu() {
+        let mut images = Assets::<Image>::default();
+        let walkers = images.add(field_image(2, 1, TextureFormat::Rgba32Float, 16, false));
+        let handles = FieldImages {
+            walkers: walkers.clone(),
+            nests: walkers.clone(),
+            pheromone: [walkers.clone(), walkers.clone()],
+            sight: walkers.clone(),
+            visible: walkers,
+        };
+        let mut fields = GpuFields { supported: true, images: Some(handles), radius: 1, ..default() };
+        let crowd: Vec<_> = (0..SLOTS as u32).map(|player| (Hex::ZERO, PlayerId(player), false)).collect();
+        assert!(fields.deposit(&mut images, &crowd));
+        assert!(fields.step_pheromone);
+
+        assert!(!fields.deposit(&mut images, &[(Hex::ZERO, PlayerId(99), false)]));
+        assert!(!fields.active());
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use hexx::Hex;
+use std::collections::{HashMap, VecDeque};
+
+use crate::coords::HexLayoutExt;
+use crate::culling::CameraView;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::pathfinding::Passability;
+use crate::spatial::SpatialIndex;
+use crate::{MainCamera, MapLayout, MapSize};
+
+// Grid debug view (debug-tools builds), toggled with F4. Every on-screen hex
+// is labelled with its axial coordinates and its step cost to the hex under
+// the mouse; hexes with units are outlined orange and impassable ones red,
+// and arrows show which way a route toward the mouse hex leaves each hex.
+
+// Step costs beyond this aren't computed, which bounds the flow field
+const MAX_FLOW_COST: u32 = 40;
+const LABEL_Z: f32 = 5.0;
+
+#[derive(Resource, Default)]
+struct GridDebug {
+    enabled: bool,
+}
+
+#[derive(Component)]
+struct HexLabel(Hex);
+
+// Step cost from every reachable hex to `goal`, by breadth-first search over
+// passable hexes: the same unit-cost graph pathfinding searches
+fn flow_field(goal: Hex, passability: &Passability) -> HashMap<Hex, u32> {
+    let mut costs = HashMap::from([(goal, 0)]);
+    let mut frontier = VecDeque::from([goal]);
+    while let Some(hex) = frontier.pop_front() {
+        let cost = costs[&hex];
+        if cost >= MAX_FLOW_COST {
+            continue;
+        }
+        for neighbor in hex.all_neighbors() {
+            if passability.passable(neighbor) && !costs.contains_key(&neighbor) {
+                costs.insert(neighbor, cost + 1);
+                frontier.push_back(neighbor);
+            }
+        }
+    }
+    costs
+}
+
+fn toggle_grid_debug(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut debug: ResMut<GridDebug>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    label_q: Query<Entity, With<HexLabel>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleGridDebug) {
+        return;
+    }
+    debug.enabled = !debug.enabled;
+    if !debug.enabled {
+        for entity in label_q.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+    for hex in map_size.hexes() {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section("", TextStyle { font_size: 8.0, color: Color::WHITE, ..default() }),
+                transform: Transform::from_translation(layout.0.hex_to_world(hex).extend(LABEL_Z)),
+                ..default()
+            },
+            HexLabel(hex),
+        ));
+    }
+}
+
+fn draw_grid_debug(
+    mut gizmos: Gizmos,
+    debug: Res<GridDebug>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    layout: Res<MapLayout>,
+    view: Res<CameraView>,
+    passability: Passability,
+    spatial_index: Res<SpatialIndex>,
+    mut label_q: Query<(&HexLabel, &mut Text, &mut Visibility)>,
+) {
+    if !debug.enabled {
+        return;
+    }
+    let window = windows.single();
+    let (camera, camera_transform) = camera_q.single();
+
+    let goal = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .map(|pos| layout.0.world_to_hex(pos));
+    let costs = goal.filter(|&goal| passability.passable(goal)).map(|goal| flow_field(goal, &passability)).unwrap_or_default();
+
+    let blocked_color = Color::srgba(1.0, 0.2, 0.2, 0.8);
+    let occupied_color = Color::srgba(1.0, 0.6, 0.1, 0.8);
+    let flow_color = Color::srgba(0.4, 0.8, 1.0, 0.6);
+
+    for (label, mut text, mut visibility) in label_q.iter_mut() {
+        let hex = label.0;
+        let center = layout.0.hex_to_world(hex);
+        if !view.contains(center) {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Visible;
+
+        let cost = costs.get(&hex);
+        text.sections[0].value = match cost {
+            Some(cost) => format!("{},{}\n{cost}", hex.x, hex.y),
+            None => format!("{},{}", hex.x, hex.y),
+        };
+
+        let outline = if !passability.passable(hex) {
+            Some(blocked_color)
+        } else if !spatial_index.at(hex).is_empty() {
+            Some(occupied_color)
+        } else {
+            None
+        };
+        if let Some(color) = outline {
+            // Inset so neighbouring outlines don't overlap
+            let corners = layout.0.world_corners(hex).map(|corner| center.lerp(corner, 0.85));
+            for i in 0..6 {
+                gizmos.line_2d(corners[i], corners[(i + 1) % 6], color);
+            }
+        }
+
+        // Downhill neighbour: the next step of a route toward the goal
+        let Some(&cost) = cost.filter(|&&cost| cost > 0) else {
+            continue;
+        };
+        if let Some(next) = hex.all_neighbors().into_iter().find(|neighbor| costs.get(neighbor) == Some(&(cost - 1))) {
+            let towards = layout.0.hex_to_world(next);
+            gizmos.arrow_2d(center.lerp(towards, 0.15), center.lerp(towards, 0.45), flow_color);
+        }
+    }
+}
+
+pub struct GridDebugPlugin;
+
+impl Plugin for GridDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GridDebug>()
+            .register_action(Action::ToggleGridDebug, &[KeyCode::F4])
+            .add_systems(
+                Update,
+                (toggle_grid_debug, draw_grid_debug).chain().run_if(in_state(crate::loading::AppState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,365 @@
+use bevy::prelude::*;
+use bevy::render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages};
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::window::PrimaryWindow;
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::burrow::FastTravel;
+use crate::coords::HexLayoutExt;
+use crate::game_commands::{AttackMove, AttackTarget, GameCommand};
+use crate::input::pointer_over_ui;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::net::{LocalPlayer, PlayerId};
+use crate::pathfinding::{Passability, PathPriority, PathQueue};
+use crate::photo_mode::HudOnly;
+use crate::{Ant, Caste, Faction, HoldPosition, MainCamera, MapLayout, MapSize, Patrol, Selected};
+
+// Harvest zones. F starts marking one: drag a box over the map (a click marks
+// a single hex) and the selected workers are assigned to it. Harvesters go to
+// the zone and from then on fetch any food that lies or turns up inside it,
+// however far from them, and carry it back to the queen; with nothing to
+// fetch they wait in the zone. The fetching itself is the idle worker loop
+// (see `idle_workers`), narrowed to the zone. Clicking an existing zone of
+// ours in marking mode removes it, and any other order to a harvester takes
+// it off its zone. Our zones are tinted on the map and kept in the world save
+// (see `save`), so they're still there next time; which workers were
+// assigned isn't, as units aren't.
+
+// A zone as it's saved
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedZone {
+    owner: PlayerId,
+    hexes: Vec<Hex>,
+}
+
+pub struct HarvestZone {
+    pub owner: PlayerId,
+    pub hexes: HashSet<Hex>,
+}
+
+#[derive(Resource, Default)]
+pub struct HarvestZones {
+    zones: BTreeMap<u32, HarvestZone>,
+    next_id: u32,
+}
+
+impl HarvestZones {
+    pub fn contains(&self, zone: u32, hex: Hex) -> bool {
+        self.zones.get(&zone).is_some_and(|zone| zone.hexes.contains(&hex))
+    }
+
+    // Which of `player`'s zones covers `hex`, if any
+    pub fn zone_at(&self, player: PlayerId, hex: Hex) -> Option<u32> {
+        self.zones.iter().find(|(_, zone)| zone.owner == player && zone.hexes.contains(&hex)).map(|(&id, _)| id)
+    }
+
+    // The hex of the zone closest to `from` that `usable` accepts
+    pub fn closest_hex(&self, zone: u32, from: Hex, usable: impl Fn(Hex) -> bool) -> Option<Hex> {
+        let zone = self.zones.get(&zone)?;
+        zone.hexes
+            .iter()
+            .copied()
+            .filter(|&hex| usable(hex))
+            .min_by_key(|hex| (from.unsigned_distance_to(*hex), hex.x, hex.y))
+    }
+
+    fn add(&mut self, owner: PlayerId, hexes: HashSet<Hex>) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.zones.insert(id, HarvestZone { owner, hexes });
+        id
+    }
+
+    pub fn from_saved(saved: Vec<SavedZone>) -> Self {
+        let mut zones = Self::default();
+        for zone in saved {
+            zones.add(zone.owner, zone.hexes.into_iter().collect());
+        }
+        zones
+    }
+
+    // Zones as the world save keeps them, hexes sorted so saves are stable
+    pub fn to_saved(&self) -> Vec<SavedZone> {
+        self.zones
+            .values()
+            .map(|zone| {
+                let mut hexes: Vec<Hex> = zone.hexes.iter().copied().collect();
+                hexes.sort_by_key(|hex| (hex.x, hex.y));
+                SavedZone { owner: zone.owner, hexes }
+            })
+            .collect()
+    }
+}
+
+// A worker assigned to the harvest zone with this id
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Harvester(pub u32);
+
+// Zone marking mode. While active, clicks and drags mark a zone instead of
+// selecting or ordering units.
+#[derive(Resource, Default)]
+pub struct HarvestPlacement {
+    active: bool,
+    // Where the drag started, in world space
+    start: Option<Vec2>,
+}
+
+impl HarvestPlacement {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+#[derive(Component)]
+struct ZoneOverlay;
+
+// Hexes of the map inside the box from `start` to `end`, and the two hexes
+// the box starts and ends on, so a click marks the hex clicked
+fn box_hexes(layout: &MapLayout, map_size: &MapSize, start: Vec2, end: Vec2) -> Vec<Hex> {
+    let rect = Rect::from_corners(start, end);
+    let ends = [layout.0.world_to_hex(start), layout.0.world_to_hex(end)];
+    map_size.hexes().filter(|&hex| ends.contains(&hex) || rect.contains(layout.0.hex_to_world(hex))).collect()
+}
+
+fn pointer_world_pos(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera_q: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    touches: &Touches,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = camera_q.single();
+    windows
+        .single()
+        .cursor_position()
+        .or_else(|| touches.first_pressed_position())
+        .or_else(|| touches.iter_just_released().next().map(|touch| touch.position()))
+        .and_then(|screen| camera.viewport_to_world_2d(camera_transform, screen))
+}
+
+fn toggle_harvest_mode(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut placement: ResMut<HarvestPlacement>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::HarvestZone) {
+        placement.active = !placement.active;
+        placement.start = None;
+    } else if placement.active
+        && (mouse_input.just_pressed(MouseButton::Right)
+            || bindings.just_pressed(&keyboard_input, Action::CancelPlacement))
+    {
+        placement.active = false;
+        placement.start = None;
+    }
+}
+
+// A drag marks a zone for the selected workers; a click on one of our zones
+// removes it. Either way marking ends.
+fn mark_harvest_zone(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    ui_q: Query<&Interaction, With<Node>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    local_player: Res<LocalPlayer>,
+    zones: Res<HarvestZones>,
+    selected_q: Query<Entity, With<Selected>>,
+    mut placement: ResMut<HarvestPlacement>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !placement.active {
+        return;
+    }
+    let Some(world_pos) = pointer_world_pos(&windows, &camera_q, &touches) else {
+        return;
+    };
+    let pressed = mouse_input.just_pressed(MouseButton::Left) || touches.any_just_pressed();
+    if pressed && !pointer_over_ui(&ui_q) {
+        placement.start = Some(world_pos);
+    }
+    if !(mouse_input.just_released(MouseButton::Left) || touches.any_just_released()) {
+        return;
+    }
+    let Some(start) = placement.start.take() else {
+        return;
+    };
+    placement.active = false;
+
+    let player = local_player.0;
+    let hex = layout.0.world_to_hex(world_pos);
+    if layout.0.world_to_hex(start) == hex && zones.zone_at(player, hex).is_some() {
+        game_commands.send(GameCommand::ClearHarvestZone { player, hex });
+        return;
+    }
+    let hexes = box_hexes(&layout, &map_size, start, world_pos);
+    game_commands.send(GameCommand::Harvest { player, units: selected_q.iter().collect(), hexes });
+}
+
+// `GameCommand::Harvest` and `ClearHarvestZone`, and any other order to a
+// harvester taking it off its zone
+fn execute_harvest_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    passability: Passability,
+    mut path_queue: ResMut<PathQueue>,
+    mut zones: ResMut<HarvestZones>,
+    worker_q: Query<(&Transform, &Faction, &Caste), With<Ant>>,
+    harvester_q: Query<(), With<Harvester>>,
+) {
+    for command in game_commands.read() {
+        match command {
+            GameCommand::Harvest { player, units, hexes } => {
+                let hexes: HashSet<Hex> = hexes.iter().copied().filter(|&hex| map_size.contains(hex)).collect();
+                if hexes.is_empty() {
+                    continue;
+                }
+                let zone = zones.add(*player, hexes);
+                for &entity in units {
+                    let Ok((transform, faction, caste)) = worker_q.get(entity) else {
+                        continue;
+                    };
+                    if faction.0 != *player || *caste != Caste::Worker {
+                        continue;
+                    }
+                    let from = layout.0.world_to_hex(transform.translation.truncate());
+                    if let Some(to) = zones.closest_hex(zone, from, |hex| passability.passable(hex)) {
+                        path_queue.request(entity, from, to, PathPriority::Ai);
+                    }
+                    commands.entity(entity).remove::<(Patrol, FastTravel, HoldPosition, AttackTarget, AttackMove)>().insert(Harvester(zone));
+                }
+            }
+            GameCommand::ClearHarvestZone { player, hex } => {
+                if let Some(zone) = zones.zone_at(*player, *hex) {
+                    zones.zones.remove(&zone);
+                }
+            }
+            GameCommand::Move { units, .. }
+            | GameCommand::Attack { units, .. }
+            | GameCommand::Stop { units, .. }
+            | GameCommand::Hold { units, .. } => {
+                for &entity in units.iter().filter(|&&entity| harvester_q.contains(entity)) {
+                    commands.entity(entity).remove::<Harvester>();
+                }
+            }
+            GameCommand::Select { .. }
+            | GameCommand::Build { .. }
+            | GameCommand::DigIn { .. }
+            | GameCommand::DigOut { .. }
+            | GameCommand::Crown { .. } => {}
+            #[cfg(feature = "sandbox")]
+            GameCommand::Sandbox(_) => {}
+        }
+    }
+}
+
+// Harvesters whose zone was removed go back to being ordinary workers
+fn release_harvesters(mut commands: Commands, zones: Res<HarvestZones>, harvester_q: Query<(Entity, &Harvester)>) {
+    if !zones.is_changed() {
+        return;
+    }
+    for (entity, harvester) in harvester_q.iter() {
+        if !zones.zones.contains_key(&harvester.0) {
+            commands.entity(entity).remove::<Harvester>();
+        }
+    }
+}
+
+fn build_zone_mesh(layout: &MapLayout, hexes: &HashSet<Hex>) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for &hex in hexes {
+        let center = layout.0.hex_to_world(hex);
+        let corners = layout.0.world_corners(hex);
+        for i in 0..6 {
+            for point in [center, corners[i], corners[(i + 1) % 6]] {
+                positions.push([point.x, point.y, 0.0]);
+            }
+        }
+    }
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
+
+// Our zones are tinted meshes, rebuilt only when the zones change
+fn sync_zone_overlay(
+    mut commands: Commands,
+    zones: Res<HarvestZones>,
+    layout: Res<MapLayout>,
+    local_player: Res<LocalPlayer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    overlay_q: Query<Entity, With<ZoneOverlay>>,
+) {
+    if !zones.is_changed() && !layout.is_changed() {
+        return;
+    }
+
+    for entity in overlay_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    let material = materials.add(ColorMaterial::from(Color::from(Srgba::hex("C0CA33").unwrap()).with_alpha(0.2)));
+    for zone in zones.zones.values().filter(|zone| zone.owner == local_player.0) {
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(build_zone_mesh(&layout, &zone.hexes)).into(),
+                material: material.clone(),
+                // Over the grid, under food and units
+                transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                ..default()
+            },
+            ZoneOverlay,
+            HudOnly,
+        ));
+    }
+}
+
+// Outline of the hexes the zone being marked would cover
+fn draw_marking(
+    mut gizmos: Gizmos,
+    placement: Res<HarvestPlacement>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+) {
+    if !placement.active {
+        return;
+    }
+    let Some(world_pos) = pointer_world_pos(&windows, &camera_q, &touches) else {
+        return;
+    };
+    let color = Color::from(Srgba::hex("C0CA33").unwrap());
+    for hex in box_hexes(&layout, &map_size, placement.start.unwrap_or(world_pos), world_pos) {
+        let corners = layout.0.world_corners(hex);
+        for i in 0..6 {
+            gizmos.line_2d(corners[i], corners[(i + 1) % 6], color);
+        }
+    }
+}
+
+pub struct HarvestZonesPlugin;
+
+impl Plugin for HarvestZonesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HarvestPlacement>()
+            .init_resource::<HarvestZones>()
+            .register_action(Action::HarvestZone, &[KeyCode::KeyF])
+            .add_systems(
+                Update,
+                (
+                    (mark_harvest_zone, draw_marking).chain().before(crate::ant_input),
+                    // After `ant_input`, so the right click that cancels marking isn't also an order
+                    toggle_harvest_mode.after(crate::ant_input),
+                    execute_harvest_commands.after(crate::dispatch_pointer_actions),
+                    (release_harvesters, sync_zone_overlay).after(execute_harvest_commands),
+                ),
+            );
+    }
+}
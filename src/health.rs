@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+
+use crate::net::PlayerId;
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::{Caste, Faction, MainCamera, Selected};
+
+// Hit points, and the small bar drawn over a unit that is hurt or selected.
+// Bars are sized in screen pixels so they read the same at every zoom level.
+
+pub const BAR_WIDTH: f32 = 16.0;
+const BAR_THICKNESS: f32 = 3.0;
+// Gap between the top of the sprite and the bar, in screen pixels
+pub const BAR_GAP: f32 = 4.0;
+
+#[derive(Component, Clone, Copy)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    // Sandbox toggle: damage is ignored while set
+    pub invulnerable: bool,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max, invulnerable: false }
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        if self.invulnerable {
+            return;
+        }
+        self.current = (self.current - amount).max(0.0);
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max <= 0.0 {
+            return 0.0;
+        }
+        (self.current / self.max).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct HealthBarGizmos;
+
+fn configure_health_bar_gizmos(mut config_store: ResMut<GizmoConfigStore>) {
+    let (config, _) = config_store.config_mut::<HealthBarGizmos>();
+    config.line_width = BAR_THICKNESS;
+}
+
+fn bar_color(fraction: f32) -> Color {
+    let hex = if fraction > 0.6 {
+        "4CAF50"
+    } else if fraction > 0.3 {
+        "FFC107"
+    } else {
+        "F44336"
+    };
+    Color::from(Srgba::hex(hex).unwrap())
+}
+
+fn draw_health_bars(
+    mut gizmos: Gizmos<HealthBarGizmos>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    unit_q: Query<(&Health, &GlobalTransform, Option<&Sprite>, &ViewVisibility, Has<Selected>)>,
+) {
+    let Ok(projection) = camera_q.get_single() else {
+        return;
+    };
+    let scale = projection.scale;
+    let background = Color::from(Srgba::hex("222222").unwrap());
+
+    for (health, transform, sprite, visibility, selected) in unit_q.iter() {
+        let fraction = health.fraction();
+        if !visibility.get() || (fraction >= 1.0 && !selected) {
+            continue;
+        }
+
+        let pos = transform.translation().truncate();
+        let half_height = sprite.and_then(|sprite| sprite.custom_size).map_or(5.0, |size| size.y / 2.0);
+        let left = pos + Vec2::new(-BAR_WIDTH / 2.0 * scale, half_height + BAR_GAP * scale);
+        let right = left + Vec2::X * BAR_WIDTH * scale;
+        gizmos.line_2d(left, right, background);
+        if fraction > 0.0 {
+            gizmos.line_2d(left, left.lerp(right, fraction), bar_color(fraction));
+        }
+    }
+}
+
+// Something with health died here
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Died {
+    pub pos: Vec2,
+    pub max_health: f32,
+    // Caste and colony, if it was an ant
+    pub unit: Option<(Caste, PlayerId)>,
+}
+
+// Anything whose health runs out is removed, along with its children
+fn despawn_dead(
+    mut commands: Commands,
+    q: Query<(Entity, &Health, &GlobalTransform, Option<&Caste>, Option<&Faction>), Changed<Health>>,
+    mut sounds: EventWriter<PlaySound>,
+    mut died: EventWriter<Died>,
+) {
+    for (entity, health, transform, caste, faction) in q.iter() {
+        if health.is_dead() {
+            let pos = transform.translation().truncate();
+            commands.entity(entity).despawn_recursive();
+            sounds.send(PlaySound::at(SoundEffect::Death, pos));
+            let unit = caste.zip(faction).map(|(caste, faction)| (*caste, faction.0));
+            died.send(Died { pos, max_health: health.max, unit });
+        }
+    }
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_gizmo_group::<HealthBarGizmos>()
+            .add_event::<Died>()
+            .add_systems(Startup, configure_health_bar_gizmos)
+            .add_systems(Update, (despawn_dead, draw_health_bars));
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use hexx::{Hex, HexLayout};
+
+use crate::coords::HexLayoutExt;
+use crate::input::PointerAction;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::{MainCamera, MapLayout};
+
+// Optional grid cursor for precise orders: toggled with C, moved one hex at a
+// time with I/J/K/L (or the D-pad), Space selects and Enter orders the
+// selection to the highlighted hex. Goes through the same `PointerAction`
+// dispatch as mouse and touch.
+
+#[derive(Resource, Default)]
+struct HexCursor {
+    hex: Option<Hex>,
+    // Alternates NE/NW (SE/SW) so repeated up/down moves stay in a straight column
+    zig: bool,
+}
+
+// Neighbor of `hex` whose world-space direction best matches `direction`
+fn step(layout: &HexLayout, hex: Hex, direction: Vec2) -> Hex {
+    let origin = layout.hex_to_world(hex);
+    let score = |neighbor: &Hex| (layout.hex_to_world(*neighbor) - origin).normalize().dot(direction);
+    hex.all_neighbors()
+        .into_iter()
+        .max_by(|a, b| score(a).total_cmp(&score(b)))
+        .unwrap_or(hex)
+}
+
+fn hex_cursor_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    camera_q: Query<&Transform, With<MainCamera>>,
+    layout: Res<MapLayout>,
+    mut cursor: ResMut<HexCursor>,
+    mut actions: EventWriter<PointerAction>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::ToggleHexCursor) {
+        cursor.hex = match cursor.hex {
+            Some(_) => None,
+            // Start under the middle of the screen
+            None => {
+                let center = camera_q.single().translation;
+                Some(layout.0.world_to_hex(center.truncate()))
+            }
+        };
+    }
+    let Some(hex) = cursor.hex else {
+        return;
+    };
+
+    let dpad = |button| {
+        gamepads
+            .iter()
+            .any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button)))
+    };
+    let pressed = |action, button| bindings.just_pressed(&keyboard_input, action) || dpad(button);
+
+    // Up/down get a slight sideways bias that flips every step
+    let zig = if cursor.zig { 0.1 } else { -0.1 };
+    let direction = if pressed(Action::HexCursorUp, GamepadButtonType::DPadUp) {
+        Some(Vec2::new(zig, 1.0))
+    } else if pressed(Action::HexCursorDown, GamepadButtonType::DPadDown) {
+        Some(Vec2::new(zig, -1.0))
+    } else if pressed(Action::HexCursorLeft, GamepadButtonType::DPadLeft) {
+        Some(Vec2::NEG_X)
+    } else if pressed(Action::HexCursorRight, GamepadButtonType::DPadRight) {
+        Some(Vec2::X)
+    } else {
+        None
+    };
+    if let Some(direction) = direction {
+        cursor.hex = Some(step(&layout.0, hex, direction.normalize()));
+        cursor.zig = !cursor.zig;
+    }
+
+    let world_pos = layout.0.hex_to_world(cursor.hex.unwrap_or(hex));
+    if bindings.just_pressed(&keyboard_input, Action::HexCursorSelect) {
+        actions.send(PointerAction::Select { world_pos, additive: true });
+    }
+    if bindings.just_pressed(&keyboard_input, Action::HexCursorCommand) {
+        actions.send(PointerAction::Command { world_pos });
+    }
+}
+
+fn draw_hex_cursor(mut gizmos: Gizmos, cursor: Res<HexCursor>, layout: Res<MapLayout>) {
+    let Some(hex) = cursor.hex else {
+        return;
+    };
+    let cursor_color = Color::from(Srgba::hex("00FFFF").unwrap());
+    let corners = layout.0.world_corners(hex);
+    for i in 0..6 {
+        gizmos.line_2d(corners[i], corners[(i + 1) % 6], cursor_color);
+    }
+}
+
+pub struct HexCursorPlugin;
+
+impl Plugin for HexCursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HexCursor>()
+            .register_action(Action::ToggleHexCursor, &[KeyCode::KeyC])
+            .register_action(Action::HexCursorUp, &[KeyCode::KeyI])
+            .register_action(Action::HexCursorDown, &[KeyCode::KeyK])
+            .register_action(Action::HexCursorLeft, &[KeyCode::KeyJ])
+            .register_action(Action::HexCursorRight, &[KeyCode::KeyL])
+            .register_action(Action::HexCursorSelect, &[KeyCode::Space])
+            .register_action(Action::HexCursorCommand, &[KeyCode::Enter])
+            .add_systems(
+                Update,
+                (hex_cursor_input.before(crate::dispatch_pointer_actions), draw_hex_cursor),
+            );
+    }
+}
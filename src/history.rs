@@ -0,0 +1,278 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::burrow::BurrowNetwork;
+use crate::coords::HexLayoutExt;
+use crate::interactions::{Engaged, FoodStores};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::locale::Locale;
+use crate::net::LocalPlayer;
+use crate::predators::Predator;
+use crate::{Ant, Caste, Faction, MapLayout, Queen, Relation, Teams};
+
+// Colony history: notable moments are sent as `ColonyEvent`s, the structured
+// log other systems (notifications, achievements) can listen to as well, and
+// appended to a timeline shown in the history panel and exported on exit.
+
+const HISTORY_PATH: &str = "saves/history.ron";
+// Food deliveries are recorded every time the store passes a multiple of this
+const FOOD_MILESTONE: u32 = 10;
+// A predator this many hexes from our queen is raiding the colony...
+const RAID_RANGE: u32 = 6;
+// ...and a raid is over once the colony has been left alone this long
+const RAID_OVER_SECS: f32 = 10.0;
+
+#[derive(Event, Clone, Debug, Serialize, Deserialize)]
+pub enum ColonyEvent {
+    Founded,
+    FirstOfCaste(Caste),
+    Expanded { entrances: usize },
+    FoodMilestone(u32),
+    // A predator raid or an attack by enemy ants ended with the queen alive
+    RaidSurvived,
+}
+
+impl ColonyEvent {
+    fn describe(&self, locale: &Locale) -> String {
+        match self {
+            ColonyEvent::Founded => locale.get("history.founded").to_string(),
+            ColonyEvent::FirstOfCaste(caste) => {
+                locale.format("history.first-of-caste", &[("caste", &locale.get(caste.name_key()))])
+            }
+            ColonyEvent::Expanded { entrances } => locale.format("history.expanded", &[("entrances", entrances)]),
+            ColonyEvent::FoodMilestone(food) => locale.format("history.food-milestone", &[("food", food)]),
+            ColonyEvent::RaidSurvived => locale.get("history.raid-survived").to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    // Game seconds since the session started
+    pub time_secs: f64,
+    pub event: ColonyEvent,
+}
+
+#[derive(Resource, Default)]
+pub struct ColonyHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+// What gets written to disk: the timeline plus a few end-of-session stats
+#[derive(Serialize)]
+struct HistoryExport<'a> {
+    entries: &'a [HistoryEntry],
+    food_stored: u32,
+    units: usize,
+}
+
+#[derive(Resource, Default)]
+struct HistoryPanel {
+    open: bool,
+}
+
+#[derive(Component)]
+struct HistoryPanelText;
+
+fn detect_new_castes(
+    local_player: Res<LocalPlayer>,
+    new_units: Query<(&Caste, &Faction), Added<Ant>>,
+    mut seen: Local<Vec<Caste>>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    for (&caste, faction) in new_units.iter() {
+        if faction.0 != local_player.0 || seen.contains(&caste) {
+            continue;
+        }
+        // The colony starts the moment its queen exists
+        if caste == Caste::Queen && seen.is_empty() {
+            events.send(ColonyEvent::Founded);
+        }
+        seen.push(caste);
+        events.send(ColonyEvent::FirstOfCaste(caste));
+    }
+}
+
+fn detect_expansion(
+    network: Res<BurrowNetwork>,
+    mut known: Local<Option<usize>>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    if !network.is_changed() {
+        return;
+    }
+    let entrances = network.entrance_count();
+    // The first count is the starting map, not an expansion
+    if known.is_some_and(|known| entrances > known) {
+        events.send(ColonyEvent::Expanded { entrances });
+    }
+    *known = Some(entrances);
+}
+
+fn detect_food_milestones(
+    stores: Res<FoodStores>,
+    local_player: Res<LocalPlayer>,
+    mut last_milestone: Local<u32>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    if !stores.is_changed() {
+        return;
+    }
+    let food = stores.0.get(&local_player.0).copied().unwrap_or(0);
+    let milestone = food / FOOD_MILESTONE * FOOD_MILESTONE;
+    if milestone > *last_milestone {
+        *last_milestone = milestone;
+        events.send(ColonyEvent::FoodMilestone(milestone));
+    }
+}
+
+// A raid lasts while predators prowl near our queen or enemy ants are biting
+// ours. `calm_secs` is how long it has been quiet since, while one is on.
+fn detect_raids(
+    time: Res<Time>,
+    layout: Res<MapLayout>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+    queen_q: Query<(&Faction, &Transform), With<Queen>>,
+    predator_q: Query<&Transform, With<Predator>>,
+    attacker_q: Query<(&Engaged, &Faction)>,
+    ant_q: Query<&Faction, With<Ant>>,
+    mut calm_secs: Local<Option<f32>>,
+    mut events: EventWriter<ColonyEvent>,
+) {
+    let to_hex = |transform: &Transform| layout.0.world_to_hex(transform.translation.truncate());
+    let our_queen = queen_q.iter().find(|(faction, _)| faction.0 == local_player.0);
+    let Some(queen_hex) = our_queen.map(|(_, transform)| to_hex(transform)) else {
+        // Without a queen there's nothing left to survive
+        *calm_secs = None;
+        return;
+    };
+
+    let hunted = predator_q.iter().any(|transform| to_hex(transform).unsigned_distance_to(queen_hex) <= RAID_RANGE);
+    let bitten = attacker_q.iter().any(|(engaged, faction)| {
+        teams.relation(local_player.0, faction.0) == Relation::Enemy
+            && ant_q.get(engaged.0).is_ok_and(|target| target.0 == local_player.0)
+    });
+    if hunted || bitten {
+        *calm_secs = Some(0.0);
+    } else if let Some(calm) = calm_secs.as_mut() {
+        *calm += time.delta_seconds();
+        if *calm >= RAID_OVER_SECS {
+            *calm_secs = None;
+            events.send(ColonyEvent::RaidSurvived);
+        }
+    }
+}
+
+fn record_colony_events(
+    time: Res<Time>,
+    mut events: EventReader<ColonyEvent>,
+    mut history: ResMut<ColonyHistory>,
+) {
+    for event in events.read() {
+        history.entries.push(HistoryEntry {
+            time_secs: time.elapsed_seconds_f64(),
+            event: event.clone(),
+        });
+    }
+}
+
+fn setup_history_panel(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle { font_size: 14.0, color: Color::WHITE, ..default() },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.0),
+            left: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            display: Display::None,
+            ..default()
+        })
+        .with_background_color(Color::srgba(0.1, 0.1, 0.1, 0.85)),
+        HistoryPanelText,
+    ));
+}
+
+fn update_history_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut panel: ResMut<HistoryPanel>,
+    history: Res<ColonyHistory>,
+    locale: Res<Locale>,
+    mut text_q: Query<(&mut Text, &mut Style), With<HistoryPanelText>>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::ToggleHistory) {
+        panel.open = !panel.open;
+    }
+    if !panel.is_changed() && !history.is_changed() && !locale.is_changed() {
+        return;
+    }
+
+    let mut lines = vec![locale.get("history.title").to_string()];
+    for entry in history.entries.iter() {
+        let secs = entry.time_secs as u64;
+        lines.push(format!("[{:02}:{:02}] {}", secs / 60, secs % 60, entry.event.describe(&locale)));
+    }
+    for (mut text, mut style) in text_q.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+        style.display = if panel.open { Display::Flex } else { Display::None };
+    }
+}
+
+fn export_history_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    history: Res<ColonyHistory>,
+    stores: Res<FoodStores>,
+    local_player: Res<LocalPlayer>,
+    unit_q: Query<&Faction, With<Ant>>,
+) {
+    if exit_events.read().next().is_none() {
+        return;
+    }
+    let export = HistoryExport {
+        entries: &history.entries,
+        food_stored: stores.0.get(&local_player.0).copied().unwrap_or(0),
+        units: unit_q.iter().filter(|faction| faction.0 == local_player.0).count(),
+    };
+    let result = ron::ser::to_string_pretty(&export, PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            fs::create_dir_all("saves").map_err(|err| err.to_string())?;
+            fs::write(HISTORY_PATH, contents).map_err(|err| err.to_string())
+        });
+    if let Err(err) = result {
+        warn!("Failed to export colony history: {err}");
+    }
+}
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ColonyEvent>()
+            .init_resource::<ColonyHistory>()
+            .init_resource::<HistoryPanel>()
+            .register_action(Action::ToggleHistory, &[KeyCode::KeyY])
+            .add_systems(Startup, setup_history_panel)
+            .add_systems(
+                Update,
+                (
+                    (detect_new_castes, detect_expansion, detect_food_milestones, detect_raids),
+                    record_colony_events,
+                    update_history_panel,
+                )
+                    .chain(),
+            );
+
+        // The web build has no filesystem to export to
+        if cfg!(feature = "native") {
+            app.add_systems(Last, export_history_on_exit);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use hexx::Hex;
+use std::collections::BTreeMap;
+
+use crate::burrow::BurrowNetwork;
+use crate::coords::HexLayoutExt;
+use crate::corpses::Corpses;
+use crate::input::pointer_over_ui;
+use crate::interactions::FoodSource;
+use crate::locale::Locale;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::rocks::Rocks;
+use crate::scoring::player_name;
+use crate::spatial::SpatialIndex;
+use crate::structures::{Structure, Structures};
+use crate::territory::Territory;
+use crate::vision::InFog;
+use crate::weather::Flooded;
+use crate::{Caste, Faction, MainCamera, MapLayout, MapSize};
+
+// The hex under the mouse: outlined on the map, with a small tooltip next to
+// the cursor giving its coordinates, what the ground is, whose territory it
+// is and what stands on it, so it's clear which cell a click will target.
+// Enemy units hidden in the fog aren't listed. Touch screens have no hover
+// and show nothing; neither does a cursor over the UI.
+
+// Tooltip offset from the cursor, in screen pixels
+const TOOLTIP_OFFSET: Vec2 = Vec2::new(16.0, 16.0);
+
+#[derive(Resource, Default)]
+struct HoveredHex {
+    hex: Option<Hex>,
+    // Cursor position in the window, for placing the tooltip
+    cursor: Vec2,
+}
+
+fn track_hovered_hex(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    ui_q: Query<&Interaction, With<Node>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    mut hovered: ResMut<HoveredHex>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_q.get_single()) else {
+        return;
+    };
+    let hit = window.cursor_position().filter(|_| !pointer_over_ui(&ui_q)).and_then(|cursor| {
+        let world_pos = camera.viewport_to_world_2d(camera_transform, cursor)?;
+        Some((layout.0.world_to_hex(world_pos), cursor))
+    });
+    let hex = hit.map(|(hex, _)| hex).filter(|&hex| map_size.contains(hex));
+    if hovered.hex != hex {
+        hovered.hex = hex;
+    }
+    if let Some((_, cursor)) = hit {
+        if hovered.cursor != cursor {
+            hovered.cursor = cursor;
+        }
+    }
+}
+
+fn draw_hovered_hex(mut gizmos: Gizmos, hovered: Res<HoveredHex>, layout: Res<MapLayout>) {
+    let Some(hex) = hovered.hex else {
+        return;
+    };
+    let color = Color::WHITE.with_alpha(0.9);
+    let center = layout.0.hex_to_world(hex);
+    // A second, slightly smaller outline thickens it without a gizmo group of its own
+    for shrink in [1.0, 0.92] {
+        let corners = layout.0.world_corners(hex).map(|corner| center + (corner - center) * shrink);
+        for i in 0..6 {
+            gizmos.line_2d(corners[i], corners[(i + 1) % 6], color);
+        }
+    }
+}
+
+#[derive(SystemParam)]
+struct HexContents<'w, 's> {
+    rocks: Res<'w, Rocks>,
+    flooded: Res<'w, Flooded>,
+    network: Res<'w, BurrowNetwork>,
+    territory: Res<'w, Territory>,
+    structures: Res<'w, Structures>,
+    corpses: Res<'w, Corpses>,
+    spatial_index: Res<'w, SpatialIndex>,
+    layout: Res<'w, MapLayout>,
+    structure_q: Query<'w, 's, &'static Structure>,
+    unit_q: Query<'w, 's, (&'static Caste, &'static Faction), Without<InFog>>,
+    food_q: Query<'w, 's, (&'static FoodSource, &'static Transform)>,
+}
+
+impl HexContents<'_, '_> {
+    fn describe(&self, hex: Hex, locale: &Locale, local_player: PlayerId) -> Vec<String> {
+        let mut lines = vec![locale.format("hover.coords", &[("q", &hex.x), ("r", &hex.y)])];
+
+        let ground = if self.rocks.contains(hex) {
+            "hover.rock"
+        } else if self.flooded.contains(hex) {
+            "hover.flooded"
+        } else if self.network.is_entrance(hex) {
+            "hover.entrance"
+        } else if self.network.is_dug(hex) {
+            "hover.tunnel"
+        } else {
+            "hover.ground"
+        };
+        lines.push(locale.get(ground).to_string());
+        if let Some(owner) = self.territory.owner(hex) {
+            let owner = player_name(locale, local_player, owner);
+            lines.push(locale.format("hover.territory", &[("player", &owner)]));
+        }
+
+        if let Some(structure) = self.structures.at(hex).and_then(|entity| self.structure_q.get(entity).ok()) {
+            lines.push(locale.get(structure.kind.name_key()).to_string());
+        }
+        let food: u32 = self
+            .food_q
+            .iter()
+            .filter(|(_, transform)| self.layout.0.world_to_hex(transform.translation.truncate()) == hex)
+            .map(|(source, _)| source.amount)
+            .sum();
+        if food > 0 {
+            lines.push(locale.format("hover.food", &[("amount", &food)]));
+        }
+        if self.corpses.at(hex).is_some() {
+            lines.push(locale.get("hover.corpse").to_string());
+        }
+
+        // Units grouped by colony and caste, ours first
+        let mut units: BTreeMap<(bool, PlayerId, &str), usize> = BTreeMap::new();
+        for &entity in self.spatial_index.at(hex) {
+            if let Ok((caste, faction)) = self.unit_q.get(entity) {
+                *units.entry((faction.0 != local_player, faction.0, caste.name_key())).or_default() += 1;
+            }
+        }
+        for ((_, player, caste), count) in units {
+            let player = player_name(locale, local_player, player);
+            let caste = locale.get(caste);
+            lines.push(locale.format("hover.units", &[("count", &count), ("caste", &caste), ("player", &player)]));
+        }
+        lines
+    }
+}
+
+#[derive(Component)]
+struct HoverTooltip;
+
+fn setup_hover_tooltip(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 13.0, color: Color::WHITE, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(4.0)),
+                display: Display::None,
+                ..default()
+            })
+            .with_background_color(Color::srgba(0.1, 0.1, 0.1, 0.85)),
+        HoverTooltip,
+    ));
+}
+
+fn update_hover_tooltip(
+    hovered: Res<HoveredHex>,
+    contents: HexContents,
+    locale: Res<Locale>,
+    local_player: Res<LocalPlayer>,
+    mut tooltip_q: Query<(&mut Text, &mut Style), With<HoverTooltip>>,
+) {
+    let Ok((mut text, mut style)) = tooltip_q.get_single_mut() else {
+        return;
+    };
+    let Some(hex) = hovered.hex else {
+        if style.display != Display::None {
+            style.display = Display::None;
+        }
+        return;
+    };
+    let value = contents.describe(hex, &locale, local_player.0).join("\n");
+    if text.sections[0].value != value {
+        text.sections[0].value = value;
+    }
+    let left = Val::Px(hovered.cursor.x + TOOLTIP_OFFSET.x);
+    let top = Val::Px(hovered.cursor.y + TOOLTIP_OFFSET.y);
+    if style.display != Display::Flex || style.left != left || style.top != top {
+        style.display = Display::Flex;
+        style.left = left;
+        style.top = top;
+    }
+}
+
+pub struct HoverPlugin;
+
+impl Plugin for HoverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoveredHex>()
+            .add_systems(Startup, setup_hover_tooltip)
+            .add_systems(Update, (track_hovered_hex, draw_hovered_hex, update_hover_tooltip).chain());
+    }
+}
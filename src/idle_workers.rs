@@ -0,0 +1,317 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::HashMap;
+
+use crate::burrow::{FastTravel, InTunnel};
+use crate::coords::HexLayoutExt;
+use crate::corpses::Corpse;
+use crate::game_commands::{GameCommand, SelectMode};
+use crate::harvest::{HarvestZones, Harvester};
+use crate::interactions::{Carrying, Engaged, FoodSource};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::loading::AppState;
+use crate::locale::Locale;
+use crate::net::LocalPlayer;
+use crate::pathfinding::{Passability, PathPriority, PathQueue};
+use crate::structures::Structure;
+use crate::{Ant, Caste, Faction, HoldPosition, MainCamera, MapLayout, Path, Patrol, Queen, TargetPosition};
+
+// Idle worker manager. Our workers that have stood around with no order for
+// a moment pick up the best task near them: raising an unfinished structure
+// of ours, or fetching food from a pile or corpse, the nearest first with
+// building preferred and corpses next. A worker that comes back with food
+// takes it to our queen, which makes gathering a loop that runs until the
+// piles nearby are gone. Tasks are claimed, so a pile or site only draws a
+// few workers at a time. There are no dig sites to hand out: tunnels aren't
+// dug by workers. Patrolling, holding, fighting and tunnelling ants are busy,
+// and an order from the player drops the task. The workers left with nothing
+// to do are counted on the HUD, and period selects the next one and centers
+// the camera on it. Workers assigned to a harvest zone (see `harvest`) only
+// fetch food from inside their zone, however far, go straight back to it
+// after a delivery and never count as idle.
+
+// Seconds a worker stands idle before it's given a task, so a player's stop
+// order isn't overridden on the spot
+const IDLE_GRACE_SECS: f32 = 2.0;
+// Farthest a task may be from the worker, in hexes
+const TASK_RADIUS: u32 = 8;
+// Workers a task draws at once
+const GATHERERS_PER_PILE: usize = 2;
+const BUILDERS_PER_SITE: usize = 3;
+// Extra hexes a pile counts as being away, so a site at the same distance wins
+const GATHER_PENALTY: u32 = 2;
+// Corpses rot, so they're fetched before a pile as far away
+const SCAVENGE_PENALTY: u32 = 1;
+// Closer than this (px) to its target, a worker has arrived
+const ARRIVAL_RADIUS: f32 = 2.0;
+
+// What an auto-assigned worker was sent to do
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+enum Task {
+    Gather(Entity),
+    Build(Entity),
+    Deliver,
+}
+
+#[derive(Resource, Default)]
+struct IdleWorkers {
+    // When each idle worker was last seen busy
+    since: HashMap<Entity, f32>,
+    // Idle workers no task was found for, oldest first
+    unassigned: Vec<Entity>,
+    // Index into `unassigned` of the worker the next key press selects
+    cycle: usize,
+}
+
+type WorkerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static TargetPosition,
+        &'static Path,
+        &'static Faction,
+        &'static Caste,
+        Has<Carrying>,
+        Option<&'static Task>,
+        Option<&'static Harvester>,
+    ),
+    (With<Ant>, Without<Patrol>, Without<HoldPosition>, Without<Engaged>, Without<FastTravel>, Without<InTunnel>),
+>;
+
+fn assign_idle_workers(
+    mut commands: Commands,
+    time: Res<Time>,
+    local_player: Res<LocalPlayer>,
+    layout: Res<MapLayout>,
+    passability: Passability,
+    mut path_queue: ResMut<PathQueue>,
+    mut idle_workers: ResMut<IdleWorkers>,
+    zones: Res<HarvestZones>,
+    worker_q: WorkerQuery,
+    task_q: Query<(Entity, &Task)>,
+    food_q: Query<(Entity, &FoodSource, &Transform, Has<Corpse>)>,
+    structure_q: Query<(Entity, &Structure, &Faction)>,
+    queen_q: Query<(&Transform, &Faction), With<Queen>>,
+) {
+    let now = time.elapsed_seconds();
+    let player = local_player.0;
+    let sites: Vec<(Entity, Hex)> = structure_q
+        .iter()
+        .filter(|(_, structure, faction)| faction.0 == player && !structure.is_built())
+        .map(|(entity, structure, _)| (entity, structure.hex()))
+        .collect();
+
+    // Standing next to a site means building it, which isn't idle. A worker
+    // whose task is done (food picked up, or delivered) is idle right away,
+    // even if it's still pressing on towards the spot it was sent to.
+    let mut idle: Vec<(Entity, Hex, bool, bool, Option<u32>)> = worker_q
+        .iter()
+        .filter(|(_, _, _, _, faction, caste, ..)| faction.0 == player && **caste == Caste::Worker)
+        .filter_map(|(entity, transform, target, path, _, _, carrying, task, harvester)| {
+            let done = match task {
+                Some(Task::Gather(_)) => carrying,
+                Some(Task::Deliver) => !carrying,
+                Some(Task::Build(_)) | None => false,
+            };
+            let at_rest = path.waypoints.is_empty()
+                && !path_queue.is_pending(entity)
+                && transform.translation.truncate().distance(target.0) <= ARRIVAL_RADIUS;
+            let hex = layout.0.world_to_hex(transform.translation.truncate());
+            (done || at_rest).then(|| (entity, hex, carrying, done, harvester.map(|harvester| harvester.0)))
+        })
+        .filter(|&(_, hex, _, _, zone)| {
+            zone.is_some() || sites.iter().all(|&(_, site)| hex.unsigned_distance_to(site) > 1)
+        })
+        .collect();
+    idle.sort_by_key(|&(entity, ..)| entity);
+    idle_workers.since.retain(|entity, _| idle.iter().any(|&(idle_entity, ..)| idle_entity == *entity));
+    for &(entity, ..) in &idle {
+        idle_workers.since.entry(entity).or_insert(now);
+    }
+
+    // Piles and sites already taken by workers on their way or at work
+    let mut claims: HashMap<Entity, usize> = HashMap::new();
+    for (entity, task) in task_q.iter() {
+        if idle.iter().any(|&(idle_entity, ..)| idle_entity == entity) {
+            continue;
+        }
+        if let Task::Gather(target) | Task::Build(target) = *task {
+            *claims.entry(target).or_default() += 1;
+        }
+    }
+
+    let mut unassigned = Vec::new();
+    for (entity, from, carrying, done, zone) in idle {
+        if !done && zone.is_none() && now - idle_workers.since[&entity] < IDLE_GRACE_SECS {
+            continue;
+        }
+        let task = if carrying {
+            queen_q
+                .iter()
+                .filter(|(_, faction)| faction.0 == player)
+                .map(|(transform, _)| layout.0.world_to_hex(transform.translation.truncate()))
+                .min_by_key(|queen| from.unsigned_distance_to(*queen))
+                .map(|queen| (Task::Deliver, queen))
+        } else {
+            let piles = food_q
+                .iter()
+                .filter(|(pile, source, ..)| {
+                    source.amount > 0 && claims.get(pile).copied().unwrap_or(0) < GATHERERS_PER_PILE
+                })
+                .map(|(pile, _, transform, corpse)| {
+                    let hex = layout.0.world_to_hex(transform.translation.truncate());
+                    let penalty = if corpse { SCAVENGE_PENALTY } else { GATHER_PENALTY };
+                    (from.unsigned_distance_to(hex), penalty, Task::Gather(pile), hex)
+                });
+            // Builders stand on the free hex around the site closest to them
+            let builds = sites
+                .iter()
+                .filter(|_| zone.is_none())
+                .filter(|(site, _)| claims.get(site).copied().unwrap_or(0) < BUILDERS_PER_SITE)
+                .filter_map(|&(site, hex)| {
+                    let spot = hex
+                        .all_neighbors()
+                        .into_iter()
+                        .filter(|&spot| passability.passable(spot))
+                        .min_by_key(|spot| from.unsigned_distance_to(*spot))?;
+                    Some((from.unsigned_distance_to(spot), 0, Task::Build(site), spot))
+                });
+            piles
+                .chain(builds)
+                .filter(|&(distance, _, _, hex)| match zone {
+                    Some(zone) => zones.contains(zone, hex),
+                    None => distance <= TASK_RADIUS,
+                })
+                .min_by_key(|&(distance, penalty, ..)| distance + penalty)
+                .map(|(_, _, task, hex)| (task, hex))
+        };
+
+        let Some((task, to)) = task else {
+            // Nothing left to do: harvesters head back to their zone to wait,
+            // others stop where they are rather than keep pushing on
+            if done {
+                let back = zone.and_then(|zone| zones.closest_hex(zone, from, |hex| passability.passable(hex)));
+                path_queue.request(entity, from, back.unwrap_or(from), PathPriority::IdleWander);
+                commands.entity(entity).remove::<Task>();
+            }
+            if zone.is_none() {
+                unassigned.push((idle_workers.since[&entity], entity));
+            }
+            continue;
+        };
+        if let Task::Gather(target) | Task::Build(target) = task {
+            *claims.entry(target).or_default() += 1;
+        }
+        path_queue.request(entity, from, to, PathPriority::IdleWander);
+        commands.entity(entity).insert(task);
+        idle_workers.since.remove(&entity);
+    }
+
+    unassigned.sort_by(|a, b| a.0.total_cmp(&b.0));
+    idle_workers.unassigned = unassigned.into_iter().map(|(_, entity)| entity).collect();
+}
+
+// A player's order replaces whatever task the units had
+fn drop_overridden_tasks(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    task_q: Query<(), With<Task>>,
+) {
+    for command in game_commands.read() {
+        let units = match command {
+            GameCommand::Move { units, .. }
+            | GameCommand::Attack { units, .. }
+            | GameCommand::Stop { units, .. }
+            | GameCommand::Hold { units, .. }
+            | GameCommand::Harvest { units, .. } => units,
+            GameCommand::Select { .. }
+            | GameCommand::Build { .. }
+            | GameCommand::ClearHarvestZone { .. }
+            | GameCommand::DigIn { .. }
+            | GameCommand::DigOut { .. }
+            | GameCommand::Crown { .. } => continue,
+            #[cfg(feature = "sandbox")]
+            GameCommand::Sandbox(_) => continue,
+        };
+        for &entity in units.iter().filter(|&&entity| task_q.contains(entity)) {
+            commands.entity(entity).remove::<Task>();
+        }
+    }
+}
+
+// Period selects the next idle worker and centers the camera on it
+fn cycle_idle_workers(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut idle_workers: ResMut<IdleWorkers>,
+    worker_q: Query<&Transform, (With<Ant>, Without<MainCamera>)>,
+    mut camera_q: Query<&mut Transform, With<MainCamera>>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::NextIdleWorker) || idle_workers.unassigned.is_empty() {
+        return;
+    }
+    let index = idle_workers.cycle % idle_workers.unassigned.len();
+    idle_workers.cycle = index + 1;
+    let worker = idle_workers.unassigned[index];
+    let Ok(transform) = worker_q.get(worker) else {
+        return;
+    };
+    game_commands.send(GameCommand::Select { units: vec![worker], mode: SelectMode::Replace });
+    if let Ok(mut camera) = camera_q.get_single_mut() {
+        camera.translation.x = transform.translation.x;
+        camera.translation.y = transform.translation.y;
+    }
+}
+
+#[derive(Component)]
+struct IdleWorkerCounter;
+
+fn setup_idle_counter(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 16.0, color: Color::WHITE, ..default() }).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Percent(30.0),
+            ..default()
+        }),
+        IdleWorkerCounter,
+    ));
+}
+
+fn update_idle_counter(
+    idle_workers: Res<IdleWorkers>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<IdleWorkerCounter>>,
+) {
+    let count = idle_workers.unassigned.len();
+    let value = if count == 0 { String::new() } else { locale.format("idle.workers", &[("count", &count)]) };
+    for mut text in text_q.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+pub struct IdleWorkersPlugin;
+
+impl Plugin for IdleWorkersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<IdleWorkers>()
+            .register_action(Action::NextIdleWorker, &[KeyCode::Period])
+            .add_systems(Startup, setup_idle_counter)
+            .add_systems(
+                Update,
+                (
+                    drop_overridden_tasks,
+                    assign_idle_workers.run_if(in_state(AppState::InGame)),
+                    cycle_idle_workers,
+                    update_idle_counter,
+                )
+                    .chain()
+                    .after(crate::move_ants),
+            );
+    }
+}
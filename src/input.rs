@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::settings::Settings;
+
+// Which control scheme pointer input is interpreted with. Both feed the same
+// `PointerAction`s, so selection and ordering code doesn't care which is active.
+// The active one is `Settings::input_scheme`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputScheme {
+    // Single button: tap a unit to toggle it, tap ground to order, drag is additive
+    #[default]
+    Touch,
+    // Desktop RTS: left click/drag selects (Shift adds), right click orders
+    ClassicRts,
+}
+
+impl InputScheme {
+    pub fn other(self) -> Self {
+        match self {
+            InputScheme::Touch => InputScheme::ClassicRts,
+            InputScheme::ClassicRts => InputScheme::Touch,
+        }
+    }
+}
+
+// A finished pointer gesture, already translated by the active scheme
+#[derive(Event, Clone, Copy, Debug)]
+pub enum PointerAction {
+    // Click/tap on a unit or empty ground with the select button
+    Select { world_pos: Vec2, additive: bool },
+    BoxSelect { start: Vec2, end: Vec2, additive: bool },
+    // Ground order; modifiers decide whether it's a move, patrol or tunnel trip
+    Command { world_pos: Vec2 },
+}
+
+fn toggle_input_scheme(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut settings: ResMut<Settings>,
+) {
+    if bindings.just_pressed(&keyboard_input, Action::ToggleInputScheme) {
+        settings.input_scheme = settings.input_scheme.other();
+        info!("Input scheme: {:?}", settings.input_scheme);
+    }
+}
+
+// Pointer is over an interactive UI node, so the press belongs to the UI, not the map
+pub fn pointer_over_ui(interaction_q: &Query<&Interaction, With<Node>>) -> bool {
+    interaction_q.iter().any(|interaction| *interaction != Interaction::None)
+}
+
+pub struct InputSchemePlugin;
+
+impl Plugin for InputSchemePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_action(Action::ToggleInputScheme, &[KeyCode::F2])
+            .add_systems(Update, toggle_input_scheme.before(crate::ant_input));
+    }
+}
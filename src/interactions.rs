@@ -0,0 +1,266 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use hexx::Hex;
+use std::collections::HashMap;
+
+use crate::collision;
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::coords::HexLayoutExt;
+use crate::health::Health;
+use crate::lobby::MatchSettings;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::predators::Predator;
+use crate::scoring::Scores;
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::units::UnitStats;
+use crate::upgrades::ColonyUpgrades;
+use crate::veterancy::{Experience, Veterancy};
+use crate::{Ant, Caste, Faction, MapLayout, Queen, Relation, Teams};
+
+// Gameplay driven by Rapier sensor events instead of per-frame distance checks:
+// - a unit touching a food pile picks some up,
+// - a carrying unit touching its own queen (or storage chamber) drops the food
+//   into the colony store,
+// - a unit whose engagement range touches an enemy engages it, and bites it
+//   until one of them leaves or dies, then turns on the next enemy still in
+//   range. Predators are engaged by `predators` but bitten here all the same.
+
+const ENGAGE_RADIUS: f32 = 15.0;
+const FOOD_PER_PILE: u32 = 20;
+// Where the starting food piles lie
+pub const FOOD_PILE_HEXES: [Hex; 3] = [Hex::new(4, -1), Hex::new(-3, 5), Hex::new(2, 4)];
+
+#[derive(Component)]
+pub struct FoodSource {
+    pub amount: u32,
+}
+
+#[derive(Component)]
+pub struct Carrying {
+    pub food: u32,
+}
+
+// Enemy this unit is fighting; combat resolution reads this
+#[derive(Component)]
+pub struct Engaged(pub Entity);
+
+// Sensor child around every unit, used for engagement and (on queens) deposits
+#[derive(Component)]
+struct UnitSensor;
+
+// Sensor where a colony's carriers can unload besides their queen
+#[derive(Component)]
+pub struct FoodDropOff(pub PlayerId);
+
+// Food delivered to each player's queen
+#[derive(Resource, Default)]
+pub struct FoodStores(pub HashMap<PlayerId, u32>);
+
+fn spawn_food_sources(mut commands: Commands, layout: Res<MapLayout>, settings: Res<MatchSettings>) {
+    let amount = (FOOD_PER_PILE as f32 * settings.mutators.food_multiplier()).round() as u32;
+    for hex in FOOD_PILE_HEXES {
+        spawn_food_source(&mut commands, layout.0.hex_to_world(hex), amount.max(1));
+    }
+}
+
+pub fn spawn_food_source(commands: &mut Commands, pos: Vec2, amount: u32) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::from(Srgba::hex("7CB342").unwrap()),
+                custom_size: Some(Vec2::new(12.0, 12.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(pos.extend(0.5)),
+            ..default()
+        },
+        Collider::ball(6.0),
+        Sensor,
+        ActiveEvents::COLLISION_EVENTS,
+        collision::sensor_collision_groups(),
+        FoodSource { amount },
+    ));
+}
+
+fn attach_unit_sensors(mut commands: Commands, new_units: Query<Entity, Added<Ant>>) {
+    for entity in new_units.iter() {
+        let sensor = commands
+            .spawn((
+                Collider::ball(ENGAGE_RADIUS),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                collision::sensor_collision_groups(),
+                TransformBundle::default(),
+                UnitSensor,
+            ))
+            .id();
+        commands.entity(entity).add_child(sensor);
+    }
+}
+
+fn handle_sensor_events(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    mut stores: ResMut<FoodStores>,
+    mut scores: ResMut<Scores>,
+    teams: Res<Teams>,
+    upgrades: Res<ColonyUpgrades>,
+    rapier_context: Res<RapierContext>,
+    mut food_q: Query<&mut FoodSource>,
+    drop_off_q: Query<&FoodDropOff>,
+    sensor_q: Query<(Entity, &Parent), With<UnitSensor>>,
+    unit_q: Query<(&Faction, Option<&Queen>, Option<&Carrying>, Option<&Engaged>), With<Ant>>,
+    predator_q: Query<(), With<Predator>>,
+    mut experience: EventWriter<Experience>,
+) {
+    // Another enemy unit still inside the sensor, to fight once `gone` is
+    let next_enemy = |sensor: Entity, faction: PlayerId, gone: Entity| {
+        rapier_context
+            .intersection_pairs_with(sensor)
+            .filter(|&(_, _, intersecting)| intersecting)
+            .map(|(a, b, _)| if a == sensor { b } else { a })
+            .filter(|&other| other != gone)
+            .find(|&other| {
+                unit_q
+                    .get(other)
+                    .is_ok_and(|(other_faction, ..)| teams.relation(faction, other_faction.0) == Relation::Enemy)
+            })
+    };
+
+    for event in collisions.read() {
+        let (a, b, started) = match *event {
+            CollisionEvent::Started(a, b, _) => (a, b, true),
+            CollisionEvent::Stopped(a, b, _) => (a, b, false),
+        };
+
+        // Either collider can be the sensor; the other one is a unit body
+        for (sensor, body) in [(a, b), (b, a)] {
+            let Ok((body_faction, _, carrying, _)) = unit_q.get(body) else {
+                continue;
+            };
+
+            if let Ok(mut food) = food_q.get_mut(sensor) {
+                if started && carrying.is_none() && food.amount > 0 {
+                    let taken = food.amount.min(upgrades.carry_capacity(body_faction.0));
+                    food.amount -= taken;
+                    commands.entity(body).insert(Carrying { food: taken });
+                    if food.amount == 0 {
+                        commands.entity(sensor).despawn_recursive();
+                    }
+                }
+                continue;
+            }
+
+            if let Ok(drop_off) = drop_off_q.get(sensor) {
+                if let Some(carrying) = carrying.filter(|_| started && drop_off.0 == body_faction.0) {
+                    *stores.0.entry(body_faction.0).or_default() += carrying.food;
+                    scores.add_food(body_faction.0, carrying.food);
+                    commands.entity(body).remove::<Carrying>();
+                    experience.send(Experience::Delivery(body));
+                }
+                continue;
+            }
+
+            let Ok(owner) = sensor_q.get(sensor).map(|(_, parent)| parent.get()) else {
+                continue;
+            };
+            let Ok((owner_faction, is_queen, _, engaged)) = unit_q.get(owner) else {
+                continue;
+            };
+
+            match teams.relation(owner_faction.0, body_faction.0) {
+                Relation::Own => {
+                    let Some(carrying) = carrying.filter(|_| started && is_queen.is_some()) else {
+                        continue;
+                    };
+                    *stores.0.entry(body_faction.0).or_default() += carrying.food;
+                    scores.add_food(body_faction.0, carrying.food);
+                    commands.entity(body).remove::<Carrying>();
+                    experience.send(Experience::Delivery(body));
+                }
+                Relation::Ally | Relation::Neutral => {}
+                Relation::Enemy => {
+                    if started && engaged.is_none() {
+                        commands.entity(owner).insert(Engaged(body));
+                    } else if !started && engaged.is_some_and(|engaged| engaged.0 == body) {
+                        match next_enemy(sensor, owner_faction.0, body) {
+                            Some(next) => commands.entity(owner).insert(Engaged(next)),
+                            None => commands.entity(owner).remove::<Engaged>(),
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    // A target that died was despawned without ever leaving the sensor
+    for (sensor, owner) in sensor_q.iter() {
+        let Ok((faction, _, _, Some(engaged))) = unit_q.get(owner.get()) else {
+            continue;
+        };
+        if unit_q.contains(engaged.0) || predator_q.contains(engaged.0) {
+            continue;
+        }
+        if let Some(next) = next_enemy(sensor, faction.0, engaged.0) {
+            commands.entity(owner.get()).insert(Engaged(next));
+        }
+    }
+}
+
+// Engaged ants bite their target for as long as it's there. Engagements only
+// ever start against enemies, but the relation is checked again here so that
+// allies can never hurt each other whatever put them at odds. Predators belong
+// to no colony and are everyone's enemy.
+fn resolve_engagements(
+    mut commands: Commands,
+    time: Res<Time>,
+    teams: Res<Teams>,
+    mut scores: ResMut<Scores>,
+    stats: UnitStats,
+    attacker_q: Query<(Entity, &Engaged, &Faction, &Caste, Option<&Veterancy>)>,
+    mut target_q: Query<(Option<&Faction>, &Transform, &mut Health), Or<(With<Ant>, With<Predator>)>>,
+    mut sounds: EventWriter<PlaySound>,
+    mut experience: EventWriter<Experience>,
+) {
+    for (attacker, engaged, faction, caste, veterancy) in attacker_q.iter() {
+        let Ok((target_faction, transform, mut health)) = target_q.get_mut(engaged.0) else {
+            commands.entity(attacker).remove::<Engaged>();
+            continue;
+        };
+        if target_faction.is_some_and(|target_faction| teams.relation(faction.0, target_faction.0) != Relation::Enemy) {
+            commands.entity(attacker).remove::<Engaged>();
+            continue;
+        }
+        let was_alive = !health.is_dead();
+        health.damage(stats.bite(*caste, veterancy) * time.delta_seconds());
+        if was_alive && health.is_dead() {
+            scores.add_kill(faction.0);
+            experience.send(Experience::Kill(attacker));
+        }
+        sounds.send(PlaySound::at(SoundEffect::Hit, transform.translation.truncate()));
+    }
+}
+
+fn give_command(In(args): In<Vec<String>>, local_player: Res<LocalPlayer>, mut stores: ResMut<FoodStores>) -> CommandResult {
+    let [resource, amount] = args.as_slice() else {
+        return Err("usage: give food <amount>".to_string());
+    };
+    if resource != "food" {
+        return Err(format!("can't give {resource}"));
+    }
+    let amount: u32 = amount.parse().map_err(|_| format!("not a number: {amount}"))?;
+    let food = stores.0.entry(local_player.0).or_default();
+    *food += amount;
+    Ok(format!("food store now {food}"))
+}
+
+pub struct InteractionsPlugin;
+
+impl Plugin for InteractionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FoodStores>()
+            .register_console_command("give", "give food <amount>: add food to your store", give_command)
+            .add_systems(OnEnter(crate::loading::AppState::InGame), spawn_food_sources)
+            .add_systems(Update, (attach_unit_sensors, handle_sensor_events, resolve_engagements).chain());
+    }
+}
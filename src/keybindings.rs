@@ -0,0 +1,207 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::storage::storage;
+
+// Action-mapping layer: systems ask "is PanLeft pressed?" instead of checking
+// raw `KeyCode`s, and players rebind keys in `settings.ron` without recompiling.
+
+pub const SETTINGS_PATH: &str = "settings.ron";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    AddToSelection,
+    PatrolModifier,
+    AttackMoveModifier,
+    TunnelModifier,
+    ToggleInputScheme,
+    ShareCamera,
+    FollowCamera,
+    StopFollowing,
+    ToggleHexCursor,
+    HexCursorUp,
+    HexCursorDown,
+    HexCursorLeft,
+    HexCursorRight,
+    HexCursorSelect,
+    HexCursorCommand,
+    ToggleOrderOfBattle,
+    ToggleHistory,
+    ToggleUpgrades,
+    ToggleSandbox,
+    SandboxApply,
+    PrioritizeBrood,
+    ToggleBuildMenu,
+    CancelPlacement,
+    ToggleMetrics,
+    ToggleConsole,
+    ToggleGridDebug,
+    PauseGame,
+    SpeedNormal,
+    SpeedFast,
+    SpeedFastest,
+    ToggleSettings,
+    ToggleScores,
+    StopUnits,
+    HoldPosition,
+    Screenshot,
+    ToggleTimelapse,
+    JumpToAlert,
+    Ping,
+    NextIdleWorker,
+    ToggleEventLog,
+    PhotoMode,
+    HarvestZone,
+    DigIn,
+    CrownPrincess,
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct KeyBindings {
+    #[serde(default)]
+    bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = (Action, &[KeyCode])> {
+        self.bindings.iter().map(|(action, keys)| (*action, keys.as_slice()))
+    }
+
+    pub fn pressed(&self, input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_pressed(&self, input: &ButtonInput<KeyCode>, action: Action) -> bool {
+        input.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    // Default keys for an action; a binding loaded from the settings file wins
+    fn register(&mut self, action: Action, keys: &[KeyCode]) {
+        self.bindings.entry(action).or_insert_with(|| keys.to_vec());
+    }
+
+    fn load() -> Self {
+        let Some(contents) = storage().read(SETTINGS_PATH) else {
+            return Self::default();
+        };
+        match ron::from_str(&contents) {
+            Ok(bindings) => bindings,
+            Err(err) => {
+                warn!("Ignoring {SETTINGS_PATH}: {err}");
+                Self::default()
+            }
+        }
+    }
+}
+
+// Keyboard state and bindings in one system parameter, for systems that only
+// need to ask about actions
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    keyboard: Res<'w, ButtonInput<KeyCode>>,
+    bindings: Res<'w, KeyBindings>,
+}
+
+impl ActionInput<'_> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.bindings.pressed(&self.keyboard, action)
+    }
+}
+
+// Lets each plugin register the actions it owns along with their default keys
+pub trait KeyBindingsAppExt {
+    fn register_action(&mut self, action: Action, keys: &[KeyCode]) -> &mut Self;
+}
+
+impl KeyBindingsAppExt for App {
+    fn register_action(&mut self, action: Action, keys: &[KeyCode]) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(KeyBindings::load)
+            .register(action, keys);
+        self
+    }
+}
+
+pub struct KeyBindingsPlugin;
+
+impl Plugin for KeyBindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_action(Action::PanLeft, &[KeyCode::KeyA, KeyCode::ArrowLeft])
+            .register_action(Action::PanRight, &[KeyCode::KeyD, KeyCode::ArrowRight])
+            .register_action(Action::PanUp, &[KeyCode::KeyW, KeyCode::ArrowUp])
+            .register_action(Action::PanDown, &[KeyCode::KeyS, KeyCode::ArrowDown])
+            .register_action(Action::ZoomIn, &[KeyCode::KeyE])
+            .register_action(Action::ZoomOut, &[KeyCode::KeyQ])
+            .register_action(Action::AddToSelection, &[KeyCode::ShiftLeft, KeyCode::ShiftRight])
+            .register_action(Action::PatrolModifier, &[KeyCode::KeyP])
+            .register_action(Action::AttackMoveModifier, &[KeyCode::ControlLeft, KeyCode::ControlRight])
+            .register_action(Action::TunnelModifier, &[KeyCode::KeyT]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> KeyBindings {
+        ron::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn shipped_settings_parse() {
+        let bindings = parse(include_str!("../settings.ron"));
+        assert_eq!(bindings.keys(Action::PanLeft), [KeyCode::KeyA, KeyCode::ArrowLeft]);
+        assert_eq!(bindings.keys(Action::StopUnits), [KeyCode::KeyR]);
+    }
+
+    #[test]
+    fn shipped_settings_only_share_escape() {
+        let bindings = parse(include_str!("../settings.ron"));
+        let mut actions_per_key: HashMap<KeyCode, Vec<Action>> = HashMap::new();
+        for (action, keys) in bindings.all() {
+            for &key in keys {
+                actions_per_key.entry(key).or_default().push(action);
+            }
+        }
+        for (key, actions) in actions_per_key {
+            assert!(key == KeyCode::Escape || actions.len() == 1, "{key:?} is bound to {actions:?}");
+        }
+    }
+
+    #[test]
+    fn a_loaded_binding_wins_over_the_default() {
+        let mut bindings = parse("(bindings: { StopUnits: [KeyK] })");
+        bindings.register(Action::StopUnits, &[KeyCode::KeyR]);
+        bindings.register(Action::HoldPosition, &[KeyCode::KeyH]);
+        assert_eq!(bindings.keys(Action::StopUnits), [KeyCode::KeyK]);
+        assert_eq!(bindings.keys(Action::HoldPosition), [KeyCode::KeyH]);
+    }
+
+    #[test]
+    fn settings_without_bindings_keep_every_default() {
+        let mut bindings = parse("(language: \"de\")");
+        assert!(bindings.keys(Action::ZoomIn).is_empty());
+        bindings.register(Action::ZoomIn, &[KeyCode::KeyE]);
+        assert_eq!(bindings.keys(Action::ZoomIn), [KeyCode::KeyE]);
+    }
+
+    #[test]
+    fn an_unbound_action_has_no_keys() {
+        let bindings = parse("(bindings: { DigIn: [] })");
+        let input = ButtonInput::<KeyCode>::default();
+        assert!(bindings.keys(Action::DigIn).is_empty());
+        assert!(!bindings.pressed(&input, Action::DigIn));
+    }
+}
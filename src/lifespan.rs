@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::determinism::SimRng;
+use crate::genetics::Genes;
+use crate::health::Health;
+use crate::units::UnitDefinitions;
+use crate::{Ant, Caste};
+
+// Old age. Castes with a `lifespan` in the unit definitions die once they
+// have lived that long, give or take `LIFESPAN_SPREAD` per ant so a brood
+// raised together doesn't drop dead together, and longer or shorter still
+// with its longevity gene (see `genetics`). The body is left behind as a
+// corpse (see `corpses`). The queen has no lifespan; a colony keeps its
+// numbers up only as long as she keeps laying (see `brood`).
+
+// Share of the lifespan an ant's own may differ from its caste's, either way
+const LIFESPAN_SPREAD: f32 = 0.15;
+
+#[derive(Component)]
+pub struct Age {
+    pub seconds: f32,
+    // This ant's lifespan as a multiple of its caste's
+    scale: f32,
+}
+
+fn attach_age(mut commands: Commands, mut rng: ResMut<SimRng>, new_ants: Query<Entity, Added<Ant>>) {
+    for entity in new_ants.iter() {
+        let scale = 1.0 + rng.0.gen_range(-LIFESPAN_SPREAD..=LIFESPAN_SPREAD);
+        commands.entity(entity).insert(Age { seconds: 0.0, scale });
+    }
+}
+
+fn age_ants(
+    time: Res<Time>,
+    definitions: Res<UnitDefinitions>,
+    mut ant_q: Query<(&mut Age, &mut Health, &Caste, Option<&Genes>)>,
+) {
+    for (mut age, mut health, caste, genes) in ant_q.iter_mut() {
+        age.seconds += time.delta_seconds();
+        let Some(lifespan) = definitions.get(*caste).lifespan else {
+            continue;
+        };
+        let longevity = genes.map_or(1.0, |genes| genes.longevity);
+        if age.seconds < lifespan * age.scale * longevity || health.is_dead() {
+            continue;
+        }
+        let max = health.max;
+        health.damage(max);
+    }
+}
+
+pub struct LifespanPlugin;
+
+impl Plugin for LifespanPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (attach_age, age_ants));
+    }
+}
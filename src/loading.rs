@@ -0,0 +1,135 @@
+use bevy::asset::{LoadState, UntypedAssetId};
+use bevy::prelude::*;
+
+// Everything the game needs on disk (sprites, audio, maps, mod content) is
+// queued into `LoadingAssets` during startup and loaded asynchronously behind
+// a progress bar; the world is only populated once we enter `InGame`, after
+// the pre-game lobby (see `lobby`).
+
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppState {
+    #[default]
+    Loading,
+    Lobby,
+    InGame,
+}
+
+#[derive(Resource, Default)]
+pub struct LoadingAssets {
+    handles: Vec<UntypedHandle>,
+}
+
+impl LoadingAssets {
+    // Keep an asset loading (and alive) until the loading screen is done with it
+    pub fn queue(&mut self, handle: impl Into<UntypedHandle>) {
+        self.handles.push(handle.into());
+    }
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingBarFill;
+
+#[derive(Component)]
+struct LoadingText;
+
+fn setup_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                },
+                background_color: Color::from(Srgba::hex("2B2B2B").unwrap()).into(),
+                ..default()
+            },
+            LoadingScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "Loading...",
+                    TextStyle { font_size: 24.0, color: Color::WHITE, ..default() },
+                ),
+                LoadingText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(16.0),
+                        ..default()
+                    },
+                    background_color: Color::from(Srgba::hex("444444").unwrap()).into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn((
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::from(Srgba::hex("8B4513").unwrap()).into(),
+                            ..default()
+                        },
+                        LoadingBarFill,
+                    ));
+                });
+        });
+}
+
+fn update_loading(
+    asset_server: Res<AssetServer>,
+    loading: Res<LoadingAssets>,
+    mut fill_q: Query<&mut Style, With<LoadingBarFill>>,
+    mut text_q: Query<&mut Text, With<LoadingText>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // Failed assets count as done so a missing file can't hang the game
+    let is_done = |id: UntypedAssetId| {
+        asset_server.is_loaded_with_dependencies(id)
+            || matches!(asset_server.get_load_state(id), Some(LoadState::Failed(_)))
+    };
+    let total = loading.handles.len();
+    let done = loading.handles.iter().filter(|handle| is_done(handle.id())).count();
+    let progress = if total == 0 { 1.0 } else { done as f32 / total as f32 };
+
+    for mut style in fill_q.iter_mut() {
+        style.width = Val::Percent(progress * 100.0);
+    }
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = format!("Loading... {:.0}%", progress * 100.0);
+    }
+
+    if done == total {
+        next_state.set(AppState::Lobby);
+    }
+}
+
+fn cleanup_loading_screen(mut commands: Commands, screen_q: Query<Entity, With<LoadingScreen>>) {
+    for entity in screen_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .init_resource::<LoadingAssets>()
+            .add_systems(Startup, setup_loading_screen)
+            .add_systems(Update, update_loading.run_if(in_state(AppState::Loading)))
+            .add_systems(OnExit(AppState::Loading), cleanup_loading_screen);
+    }
+}
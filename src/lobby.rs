@@ -0,0 +1,476 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bots::{Bots, WanderBot};
+use crate::game_speed::{GameSpeed, SPEEDS};
+use crate::loading::AppState;
+use crate::locale::{Locale, Localized};
+use crate::mutators::{Mutator, Mutators};
+use crate::net::{IncomingMessage, LocalPlayer, NetConnection, NetMessage, OutgoingMessage, PlayerId};
+use crate::units::UnitDefinitions;
+use crate::MapSize;
+
+// Pre-game lobby. Once loading is done the game waits here instead of going
+// straight into the match. Offline, or as the server, we host: the host picks
+// the map, how many colonies play, how many of them are AI opponents, the
+// game speed and the match's mutators (see `mutators`), and starts the match
+// once everyone who joined is ready. A client sends `JoinLobby` with the
+// checksum of its rules, and the host only seats it if that matches its own.
+// A seated client sees the host's choices as they change (`LobbyState`) and
+// readies up. `StartMatch` carries the final settings to every client; each
+// side applies them as it enters the game, so everyone plays the same match.
+// Changing a setting un-readies every guest.
+// `--skip-lobby` starts right away with the defaults (and `--bots N`).
+
+const MIN_PLAYERS: u32 = 2;
+const MAX_PLAYERS: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchMap {
+    Small,
+    Medium,
+    Large,
+}
+
+impl MatchMap {
+    const ALL: [MatchMap; 3] = [MatchMap::Small, MatchMap::Medium, MatchMap::Large];
+
+    fn name_key(self) -> &'static str {
+        match self {
+            MatchMap::Small => "lobby.map-small",
+            MatchMap::Medium => "lobby.map-medium",
+            MatchMap::Large => "lobby.map-large",
+        }
+    }
+
+    // Map radius in hexes (see `MapSize`)
+    fn radius(self) -> u32 {
+        match self {
+            MatchMap::Small => 10,
+            MatchMap::Medium => 16,
+            MatchMap::Large => 24,
+        }
+    }
+}
+
+// The rules of the match, chosen by the host
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchSettings {
+    pub map: MatchMap,
+    // Colonies in the match, the host's included
+    pub players: u32,
+    // How many of them bots play
+    pub ai: u32,
+    pub speed: f32,
+    pub mutators: Mutators,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self { map: MatchMap::Small, players: MIN_PLAYERS, ai: 0, speed: SPEEDS[0], mutators: Mutators::default() }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Lobby {
+    // Players who joined the host, and whether each is ready
+    guests: Vec<(PlayerId, bool)>,
+    // Joining: the host turned us away for playing by other rules
+    refused: bool,
+}
+
+impl Lobby {
+    fn all_ready(&self) -> bool {
+        self.guests.iter().all(|&(_, ready)| ready)
+    }
+}
+
+// One adjustable line on the lobby screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LobbyRow {
+    Map,
+    Players,
+    Ai,
+    Speed,
+    Mutator(Mutator),
+}
+
+impl LobbyRow {
+    const ALL: [LobbyRow; 9] = [
+        LobbyRow::Map,
+        LobbyRow::Players,
+        LobbyRow::Ai,
+        LobbyRow::Speed,
+        LobbyRow::Mutator(Mutator::DoubleSpeedAnts),
+        LobbyRow::Mutator(Mutator::NoFog),
+        LobbyRow::Mutator(Mutator::ScarceFood),
+        LobbyRow::Mutator(Mutator::GiantPredators),
+        LobbyRow::Mutator(Mutator::FragileQueens),
+    ];
+
+    fn label_key(self) -> &'static str {
+        match self {
+            LobbyRow::Map => "lobby.map",
+            LobbyRow::Players => "lobby.players",
+            LobbyRow::Ai => "lobby.ai",
+            LobbyRow::Speed => "lobby.speed",
+            LobbyRow::Mutator(mutator) => mutator.name_key(),
+        }
+    }
+
+    fn value(self, settings: &MatchSettings, locale: &Locale) -> String {
+        match self {
+            LobbyRow::Map => locale.get(settings.map.name_key()).to_string(),
+            LobbyRow::Players => settings.players.to_string(),
+            LobbyRow::Ai => settings.ai.to_string(),
+            LobbyRow::Speed => format!("{}x", settings.speed),
+            LobbyRow::Mutator(mutator) => {
+                locale.get(if settings.mutators.contains(mutator) { "settings.on" } else { "settings.off" }).to_string()
+            }
+        }
+    }
+
+    // One step up or down; there is always a seat for the host and every guest
+    fn step(self, settings: &mut MatchSettings, guests: u32, up: bool) {
+        let humans = 1 + guests;
+        match self {
+            LobbyRow::Map => {
+                let current = MatchMap::ALL.iter().position(|&map| map == settings.map).unwrap_or(0);
+                let step = if up { 1 } else { MatchMap::ALL.len() - 1 };
+                settings.map = MatchMap::ALL[(current + step) % MatchMap::ALL.len()];
+            }
+            LobbyRow::Players => {
+                let players = if up { settings.players + 1 } else { settings.players.saturating_sub(1) };
+                settings.players = players.clamp(MIN_PLAYERS.max(humans), MAX_PLAYERS.max(humans));
+                settings.ai = settings.ai.min(settings.players - humans);
+            }
+            LobbyRow::Ai => {
+                let ai = if up { settings.ai + 1 } else { settings.ai.saturating_sub(1) };
+                settings.ai = ai.min(settings.players.saturating_sub(humans));
+            }
+            LobbyRow::Speed => {
+                let current = SPEEDS.iter().position(|&speed| speed == settings.speed).unwrap_or(0);
+                let step = if up { 1 } else { SPEEDS.len() - 1 };
+                settings.speed = SPEEDS[(current + step) % SPEEDS.len()];
+            }
+            LobbyRow::Mutator(mutator) => settings.mutators.toggle(mutator),
+        }
+    }
+}
+
+#[derive(Component)]
+struct LobbyScreen;
+
+#[derive(Component)]
+struct LobbyValue(LobbyRow);
+
+#[derive(Component)]
+struct LobbyButton {
+    row: LobbyRow,
+    up: bool,
+}
+
+// Who is in the match, one line each
+#[derive(Component)]
+struct LobbyRoster;
+
+// Start for the host, ready/not ready for a client
+#[derive(Component)]
+struct StartButton;
+
+// `--bots N` fills the first AI seats
+pub fn init_match_settings(bots: Res<Bots>, mut settings: ResMut<MatchSettings>) {
+    settings.ai = bots.players().count() as u32;
+    settings.players = settings.players.max(settings.ai + 1);
+}
+
+fn enter_lobby(
+    mut commands: Commands,
+    connection: Res<NetConnection>,
+    definitions: Res<UnitDefinitions>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    // A replayed session brings its own settings (see `session_replay`)
+    if std::env::args().any(|arg| arg == "--skip-lobby" || arg == "--replay-session") {
+        next_state.set(AppState::InGame);
+        return;
+    }
+    let host = !connection.online;
+    if !host {
+        outgoing.send(OutgoingMessage::broadcast(NetMessage::JoinLobby { rules: definitions.rules_checksum() }));
+    }
+
+    let text =
+        |value: &str| TextBundle::from_section(value, TextStyle { font_size: 16.0, color: Color::WHITE, ..default() });
+    let button = || ButtonBundle {
+        style: Style { padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)), ..default() },
+        background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                background_color: Color::from(Srgba::hex("2B2B2B").unwrap()).into(),
+                ..default()
+            },
+            Interaction::default(),
+            LobbyScreen,
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 24.0, color: Color::WHITE, ..default() }),
+                Localized("lobby.title"),
+            ));
+            for row in LobbyRow::ALL {
+                screen
+                    .spawn(NodeBundle {
+                        style: Style { column_gap: Val::Px(6.0), align_items: AlignItems::Center, ..default() },
+                        ..default()
+                    })
+                    .with_children(|line| {
+                        line.spawn(NodeBundle { style: Style { width: Val::Px(150.0), ..default() }, ..default() })
+                            .with_children(|label| {
+                                label.spawn((text(""), Localized(row.label_key())));
+                            });
+                        // Only the host gets to change anything
+                        if host {
+                            for (up, symbol) in [(false, "-"), (true, "+")] {
+                                line.spawn((button(), LobbyButton { row, up })).with_children(|button| {
+                                    button.spawn(text(symbol));
+                                });
+                            }
+                        }
+                        line.spawn(NodeBundle { style: Style { width: Val::Px(90.0), ..default() }, ..default() })
+                            .with_children(|value| {
+                                value.spawn((text(""), LobbyValue(row)));
+                            });
+                    });
+            }
+            screen.spawn((text(""), LobbyRoster));
+            screen.spawn((button(), StartButton)).with_children(|button| {
+                button.spawn(text(""));
+            });
+        });
+}
+
+fn exit_lobby(mut commands: Commands, screen_q: Query<Entity, With<LobbyScreen>>) {
+    for entity in screen_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Host side: seat joining players while there is room, track who is ready
+// and keep every guest up to date
+fn host_lobby(
+    connection: Res<NetConnection>,
+    settings: Res<MatchSettings>,
+    definitions: Res<UnitDefinitions>,
+    mut lobby: ResMut<Lobby>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    if connection.online {
+        return;
+    }
+    for message in incoming.read() {
+        let seated = lobby.guests.iter().position(|&(player, _)| player == message.from);
+        match (&message.message, seated) {
+            (NetMessage::JoinLobby { rules }, None) if *rules != definitions.rules_checksum() => {
+                info!("Player {} plays by other rules; not seated", message.from.0);
+                outgoing.send(OutgoingMessage::to(message.from, NetMessage::LobbyRefused));
+            }
+            (NetMessage::JoinLobby { .. }, None) => {
+                let humans = 1 + lobby.guests.len() as u32;
+                if humans + settings.ai < settings.players {
+                    lobby.guests.push((message.from, false));
+                } else {
+                    info!("Lobby full; player {} can't join", message.from.0);
+                }
+            }
+            (NetMessage::SetReady { ready }, Some(index)) => lobby.guests[index].1 = *ready,
+            _ => {}
+        }
+    }
+    if lobby.is_changed() || settings.is_changed() {
+        let guests = lobby.guests.clone();
+        outgoing.send(OutgoingMessage::broadcast(NetMessage::LobbyState { settings: *settings, guests }));
+    }
+}
+
+// Client side: mirror the host's lobby and follow it into the match
+fn follow_host(
+    connection: Res<NetConnection>,
+    mut settings: ResMut<MatchSettings>,
+    mut lobby: ResMut<Lobby>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !connection.online {
+        return;
+    }
+    for message in incoming.read() {
+        match &message.message {
+            NetMessage::LobbyState { settings: chosen, guests } => {
+                *settings = *chosen;
+                lobby.guests = guests.clone();
+            }
+            // Stay out of a match played by other rules
+            NetMessage::StartMatch { .. } if lobby.refused => {}
+            NetMessage::StartMatch { settings: chosen } => {
+                *settings = *chosen;
+                next_state.set(AppState::InGame);
+            }
+            NetMessage::LobbyRefused => {
+                warn!("The host plays by other rules (a different build or unit file)");
+                lobby.refused = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn press_lobby_buttons(
+    connection: Res<NetConnection>,
+    local_player: Res<LocalPlayer>,
+    button_q: Query<(&Interaction, &LobbyButton), Changed<Interaction>>,
+    start_q: Query<&Interaction, (With<StartButton>, Changed<Interaction>)>,
+    mut settings: ResMut<MatchSettings>,
+    mut lobby: ResMut<Lobby>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let before = *settings;
+        button.row.step(&mut settings, lobby.guests.len() as u32, button.up);
+        if *settings != before {
+            // Guests ready up for the rules they saw
+            for (_, ready) in lobby.guests.iter_mut() {
+                *ready = false;
+            }
+        }
+    }
+
+    if !start_q.iter().any(|interaction| *interaction == Interaction::Pressed) {
+        return;
+    }
+    if !connection.online {
+        if lobby.all_ready() {
+            outgoing.send(OutgoingMessage::broadcast(NetMessage::StartMatch { settings: *settings }));
+            next_state.set(AppState::InGame);
+        }
+        return;
+    }
+    let ready = lobby.guests.iter().any(|&(player, ready)| player == local_player.0 && ready);
+    outgoing.send(OutgoingMessage::broadcast(NetMessage::SetReady { ready: !ready }));
+}
+
+fn update_lobby_screen(
+    connection: Res<NetConnection>,
+    local_player: Res<LocalPlayer>,
+    settings: Res<MatchSettings>,
+    lobby: Res<Lobby>,
+    locale: Res<Locale>,
+    mut value_q: Query<(&LobbyValue, &mut Text), Without<LobbyRoster>>,
+    mut roster_q: Query<&mut Text, With<LobbyRoster>>,
+    start_q: Query<&Children, With<StartButton>>,
+    mut text_q: Query<&mut Text, (Without<LobbyValue>, Without<LobbyRoster>)>,
+) {
+    if !settings.is_changed() && !lobby.is_changed() && !locale.is_changed() {
+        return;
+    }
+    for (value, mut text) in value_q.iter_mut() {
+        text.sections[0].value = value.0.value(&settings, &locale);
+    }
+
+    let status = |ready: bool| locale.get(if ready { "lobby.ready" } else { "lobby.not-ready" });
+    let mut lines = vec![locale.get("lobby.host").to_string()];
+    if lobby.refused {
+        lines.push(locale.get("lobby.refused").to_string());
+    }
+    for &(player, ready) in &lobby.guests {
+        let key = if player == local_player.0 { "lobby.you" } else { "lobby.guest" };
+        lines.push(locale.format(key, &[("id", &player.0), ("status", &status(ready))]));
+    }
+    for n in 1..=settings.ai {
+        lines.push(locale.format("lobby.ai-player", &[("n", &n)]));
+    }
+    let seated = 1 + lobby.guests.len() as u32 + settings.ai;
+    for _ in seated..settings.players {
+        lines.push(locale.get("lobby.open").to_string());
+    }
+    for mut text in roster_q.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+
+    let label = if !connection.online {
+        locale.get(if lobby.all_ready() { "lobby.start" } else { "lobby.waiting" })
+    } else if lobby.guests.iter().any(|&(player, ready)| player == local_player.0 && ready) {
+        locale.get("lobby.unready")
+    } else {
+        locale.get("lobby.ready-up")
+    };
+    for children in start_q.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_q.get_mut(child) {
+                text.sections[0].value = label.to_string();
+            }
+        }
+    }
+}
+
+// Runs as the match starts, before anything is spawned
+pub fn apply_match_settings(
+    settings: Res<MatchSettings>,
+    connection: Res<NetConnection>,
+    mut map_size: ResMut<MapSize>,
+    mut game_speed: ResMut<GameSpeed>,
+    mut bots: ResMut<Bots>,
+) {
+    map_size.radius = settings.map.radius();
+    game_speed.speed = settings.speed;
+    // The server runs the AI opponents
+    if connection.online {
+        return;
+    }
+    let mut joined: Vec<PlayerId> = bots.players().collect();
+    while joined.len() > settings.ai as usize {
+        if let Some(player) = joined.pop() {
+            bots.kick(player);
+        }
+    }
+    for _ in joined.len()..settings.ai as usize {
+        bots.join(WanderBot::default());
+    }
+}
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchSettings>()
+            .init_resource::<Lobby>()
+            .add_systems(Startup, init_match_settings)
+            .add_systems(OnEnter(AppState::Lobby), enter_lobby.after(crate::units::apply_unit_definitions))
+            .add_systems(
+                Update,
+                (host_lobby, follow_host, press_lobby_buttons, update_lobby_screen)
+                    .chain()
+                    .run_if(in_state(AppState::Lobby)),
+            )
+            .add_systems(OnExit(AppState::Lobby), exit_lobby)
+            .add_systems(OnEnter(AppState::InGame), apply_match_settings.before(crate::spawn_units));
+    }
+}
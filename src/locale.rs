@@ -0,0 +1,178 @@
+use bevy::asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::loading::LoadingAssets;
+use crate::settings::Settings;
+
+// Player-facing text comes from locale files instead of string literals:
+// `assets/locales/<language>.locale.ron` maps keys like `"upgrades.title"` to
+// the text to show, with `{name}` placeholders filled in by `Locale::format`.
+// Every listed language is loaded behind the loading screen, so switching
+// (settings screen or `language <code>` in the console) is instant, and edits
+// to a file are picked up while playing. A key missing from the chosen
+// language falls back to English, then to the key itself.
+//
+// Fixed labels only need a `Localized` component; text built from game state
+// is formatted by its own system, which also reruns when `Locale` changes.
+// Debug tools (console, metrics, grid overlay, sandbox) stay in English.
+
+pub const FALLBACK_LANGUAGE: &str = "en";
+// Languages with a locale file, in the order the settings screen cycles them
+pub const LANGUAGES: [&str; 2] = ["en", "de"];
+
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+struct LocaleFile(HashMap<String, String>);
+
+#[derive(Debug)]
+enum LocaleFileError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl std::fmt::Display for LocaleFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocaleFileError::Io(err) => write!(f, "could not read locale: {err}"),
+            LocaleFileError::Ron(err) => write!(f, "could not parse locale: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LocaleFileError {}
+
+impl From<std::io::Error> for LocaleFileError {
+    fn from(err: std::io::Error) -> Self {
+        LocaleFileError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for LocaleFileError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        LocaleFileError::Ron(err)
+    }
+}
+
+#[derive(Default)]
+struct LocaleFileLoader;
+
+impl AssetLoader for LocaleFileLoader {
+    type Asset = LocaleFile;
+    type Settings = ();
+    type Error = LocaleFileError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<LocaleFile, LocaleFileError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["locale.ron"]
+    }
+}
+
+#[derive(Resource, Default)]
+struct LocaleFiles(HashMap<&'static str, Handle<LocaleFile>>);
+
+// The strings of the chosen language, with English filling any gaps
+#[derive(Resource, Default)]
+pub struct Locale {
+    strings: HashMap<String, String>,
+}
+
+impl Locale {
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map_or(key, String::as_str)
+    }
+
+    // `get` with each `{name}` replaced by its argument
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut text = self.get(key).to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{name}}}"), &value.to_string());
+        }
+        text
+    }
+}
+
+// A fixed label; its text is the key's translation in the current language
+#[derive(Component)]
+pub struct Localized(pub &'static str);
+
+fn load_locale_files(asset_server: Res<AssetServer>, mut files: ResMut<LocaleFiles>, mut loading: ResMut<LoadingAssets>) {
+    for language in LANGUAGES {
+        let handle = asset_server.load::<LocaleFile>(format!("locales/{language}.locale.ron"));
+        loading.queue(handle.clone());
+        files.0.insert(language, handle);
+    }
+}
+
+fn rebuild_locale(
+    settings: Res<Settings>,
+    files: Res<LocaleFiles>,
+    assets: Res<Assets<LocaleFile>>,
+    mut events: EventReader<AssetEvent<LocaleFile>>,
+    mut locale: ResMut<Locale>,
+    mut language: Local<Option<String>>,
+) {
+    let reloaded = events.read().any(|event| {
+        matches!(event, AssetEvent::LoadedWithDependencies { .. } | AssetEvent::Modified { .. })
+    });
+    if !reloaded && language.as_deref() == Some(settings.language.as_str()) {
+        return;
+    }
+    *language = Some(settings.language.clone());
+
+    let strings_of = |language: &str| files.0.get(language).and_then(|handle| assets.get(handle)).map(|file| &file.0);
+    let mut strings = strings_of(FALLBACK_LANGUAGE).cloned().unwrap_or_default();
+    match strings_of(&settings.language) {
+        Some(chosen) => strings.extend(chosen.iter().map(|(key, text)| (key.clone(), text.clone()))),
+        None if settings.language != FALLBACK_LANGUAGE => warn!("No locale loaded for {:?}", settings.language),
+        None => {}
+    }
+    locale.strings = strings;
+}
+
+fn apply_localized_text(locale: Res<Locale>, mut text_q: Query<(Ref<Localized>, &mut Text)>) {
+    for (localized, mut text) in text_q.iter_mut() {
+        if locale.is_changed() || localized.is_added() {
+            text.sections[0].value = locale.get(localized.0).to_string();
+        }
+    }
+}
+
+fn language_command(In(args): In<Vec<String>>, mut settings: ResMut<Settings>) -> CommandResult {
+    let [language] = args.as_slice() else {
+        return Err(format!("usage: language {}", LANGUAGES.join("|")));
+    };
+    if !LANGUAGES.contains(&language.as_str()) {
+        return Err(format!("no {language} locale; available: {}", LANGUAGES.join(", ")));
+    }
+    settings.language = language.clone();
+    Ok(format!("language {language}"))
+}
+
+pub struct LocalePlugin;
+
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<LocaleFile>()
+            .init_asset_loader::<LocaleFileLoader>()
+            .init_resource::<LocaleFiles>()
+            .init_resource::<Locale>()
+            .register_console_command("language", "language <code>: switch the interface language", language_command)
+            .add_systems(Startup, load_locale_files)
+            .add_systems(PreUpdate, rebuild_locale)
+            .add_systems(Update, apply_localized_text);
+    }
+}
@@ -0,0 +1,200 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::HashMap;
+
+use crate::burrow::InTunnel;
+use crate::coords::HexLayoutExt;
+use crate::culling::CameraView;
+use crate::net::LocalPlayer;
+use crate::settings::Settings;
+use crate::vision::InFog;
+use crate::{chunk_of, Ant, Faction, HexGridMesh, MainCamera, MapLayout, MapSize, Relation, Teams};
+
+// Zoom-dependent level of detail, picked from how many pixels a hex covers at
+// the camera's `OrthographicProjection::scale`. Zoomed in everything is drawn
+// in full. Further out the hex grid gives way to chunk borders and ants become
+// dots colored by who they belong to; furthest out each chunk shows one marker
+// per side, sized by how many ants it holds. Sprites are only hidden, never
+// despawned, and idle animation pauses while they are. Enemies in the fog of
+// war stay hidden at every level; see `vision`.
+
+// On-screen hex size (px) below which detail drops, and above which it comes
+// back; the gap keeps a zoom hovering at the boundary from flickering
+const DOTS_BELOW: f32 = 12.0;
+const FULL_ABOVE: f32 = 15.0;
+const CLUSTERS_BELOW: f32 = 4.0;
+const DOTS_ABOVE: f32 = 5.0;
+// Screen-space sizes (px) of the simplified markers
+const DOT_RADIUS: f32 = 2.5;
+const CLUSTER_RADIUS_PER_SQRT_ANT: f32 = 2.0;
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DetailLevel {
+    #[default]
+    Full,
+    Dots,
+    Clusters,
+}
+
+impl DetailLevel {
+    fn for_hex_pixels(self, hex_px: f32) -> Self {
+        match self {
+            Self::Full if hex_px < DOTS_BELOW => Self::Dots.for_hex_pixels(hex_px),
+            Self::Dots if hex_px > FULL_ABOVE => Self::Full,
+            Self::Dots if hex_px < CLUSTERS_BELOW => Self::Clusters,
+            Self::Clusters if hex_px > DOTS_ABOVE => Self::Dots.for_hex_pixels(hex_px),
+            level => level,
+        }
+    }
+}
+
+// Run condition for systems only worth running at full detail
+pub fn full_detail(level: Res<DetailLevel>) -> bool {
+    *level == DetailLevel::Full
+}
+
+fn relation_color(relation: Relation) -> Color {
+    match relation {
+        Relation::Own => Color::WHITE,
+        Relation::Ally => Color::from(Srgba::hex("3399FF").unwrap()),
+        Relation::Neutral => Color::from(Srgba::hex("B0BEC5").unwrap()),
+        Relation::Enemy => Color::from(Srgba::hex("FF3333").unwrap()),
+    }
+}
+
+fn update_detail_level(
+    layout: Res<MapLayout>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    mut level: ResMut<DetailLevel>,
+) {
+    let Ok(projection) = camera_q.get_single() else {
+        return;
+    };
+    let hex_px = layout.0.scale.max_element() * 2.0 / projection.scale;
+    let next = level.for_hex_pixels(hex_px);
+    level.set_if_neq(next);
+}
+
+// Sprites and the grid mesh are hidden below full detail, and the grid also
+// when turned off in the settings. Checked every frame rather than on change,
+// since units spawn and leave tunnels, and grid chunks appear, at any zoom.
+fn apply_detail_visibility(
+    level: Res<DetailLevel>,
+    settings: Res<Settings>,
+    mut ant_q: Query<&mut Visibility, (With<Ant>, Without<InTunnel>, Without<InFog>, Without<HexGridMesh>)>,
+    mut grid_q: Query<&mut Visibility, With<HexGridMesh>>,
+) {
+    let shown = |show: bool| if show { Visibility::Inherited } else { Visibility::Hidden };
+    let full = *level == DetailLevel::Full;
+    for mut visibility in grid_q.iter_mut() {
+        visibility.set_if_neq(shown(full && settings.show_grid));
+    }
+    if full && !level.is_changed() {
+        return;
+    }
+    for mut visibility in ant_q.iter_mut() {
+        visibility.set_if_neq(shown(full));
+    }
+}
+
+// Edges between hexes of different chunks, drawn in place of the hex grid
+#[derive(Resource, Default)]
+struct ChunkBorders(Vec<(Vec2, Vec2)>);
+
+fn update_chunk_borders(layout: Res<MapLayout>, map_size: Res<MapSize>, mut borders: ResMut<ChunkBorders>) {
+    if !layout.is_changed() && !map_size.is_changed() {
+        return;
+    }
+    let reach = layout.0.scale.max_element() * 1.01;
+    borders.0.clear();
+    for hex in map_size.hexes() {
+        // Half the directions, so each edge is only found once
+        for neighbor in hex.all_neighbors().into_iter().take(3) {
+            if !map_size.contains(neighbor) || chunk_of(hex) == chunk_of(neighbor) {
+                continue;
+            }
+            let center = layout.0.hex_to_world(neighbor);
+            let shared: Vec<Vec2> =
+                layout.0.world_corners(hex).into_iter().filter(|corner| corner.distance(center) <= reach).collect();
+            if let [a, b] = shared[..] {
+                borders.0.push((a, b));
+            }
+        }
+    }
+}
+
+fn draw_chunk_borders(
+    level: Res<DetailLevel>,
+    settings: Res<Settings>,
+    borders: Res<ChunkBorders>,
+    view: Res<CameraView>,
+    mut gizmos: Gizmos,
+) {
+    if *level == DetailLevel::Full || !settings.show_grid {
+        return;
+    }
+    let color = Color::from(Srgba::hex("444444").unwrap());
+    for &(a, b) in borders.0.iter().filter(|&&(a, b)| view.overlaps_segment(a, b)) {
+        gizmos.line_2d(a, b, color);
+    }
+}
+
+fn draw_unit_markers(
+    mut gizmos: Gizmos,
+    level: Res<DetailLevel>,
+    layout: Res<MapLayout>,
+    teams: Res<Teams>,
+    local_player: Res<LocalPlayer>,
+    view: Res<CameraView>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    ant_q: Query<(&Transform, &Faction), (With<Ant>, Without<InTunnel>, Without<InFog>)>,
+) {
+    let Ok(projection) = camera_q.get_single() else {
+        return;
+    };
+    match *level {
+        DetailLevel::Full => {}
+        DetailLevel::Dots => {
+            let radius = DOT_RADIUS * projection.scale;
+            for (transform, faction) in ant_q.iter() {
+                let pos = transform.translation.truncate();
+                if view.overlaps_circle(pos, radius) {
+                    gizmos.circle_2d(pos, radius, relation_color(teams.relation(local_player.0, faction.0)));
+                }
+            }
+        }
+        DetailLevel::Clusters => {
+            let mut clusters: HashMap<(Hex, Relation), (Vec2, u32)> = HashMap::new();
+            for (transform, faction) in ant_q.iter() {
+                let pos = transform.translation.truncate();
+                let key = (chunk_of(layout.0.world_to_hex(pos)), teams.relation(local_player.0, faction.0));
+                let (sum, count) = clusters.entry(key).or_default();
+                *sum += pos;
+                *count += 1;
+            }
+            for ((_, relation), (sum, count)) in clusters {
+                let (center, radius) = (sum / count as f32, CLUSTER_RADIUS_PER_SQRT_ANT * (count as f32).sqrt() * projection.scale);
+                if view.overlaps_circle(center, radius) {
+                    gizmos.circle_2d(center, radius, relation_color(relation));
+                }
+            }
+        }
+    }
+}
+
+pub struct LodPlugin;
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DetailLevel>().init_resource::<ChunkBorders>().add_systems(
+            Update,
+            (
+                update_detail_level,
+                apply_detail_visibility.after(crate::sync_hex_grid_mesh),
+                (update_chunk_borders, draw_chunk_borders).chain(),
+                draw_unit_markers,
+            )
+                .chain(),
+        );
+    }
+}
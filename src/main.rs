@@ -1,10 +1,198 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+// Bevy systems take their resources and queries as arguments, so long
+// parameter lists and nested query types are the norm here
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages},
+    sprite::MaterialMesh2dBundle,
+    time::Real,
+    window::PrimaryWindow,
+};
 use bevy_rapier2d::prelude::*;
 use hexx::{Hex, HexLayout, HexOrientation, Vec2 as HexVec2};
-use std::collections::{HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+#[cfg(feature = "admin")]
+mod admin;
+mod alerts;
+mod ant_sprites;
+mod battle_panel;
+mod bots;
+mod brood;
+mod burrow;
+mod camera_controls;
+mod camera_share;
+mod capture;
+mod collision;
+mod console;
+mod coords;
+mod corpses;
+mod culling;
+mod determinism;
+mod dig_in;
+mod diplomacy;
+mod energy;
+mod event_log;
+mod factions;
+mod game_commands;
+mod game_speed;
+mod gamepad;
+mod genetics;
+mod gpu_fields;
+#[cfg(feature = "debug-tools")]
+mod grid_debug;
+mod harvest;
+mod health;
+mod hex_cursor;
+mod history;
+mod hover;
+mod idle_workers;
+mod input;
+mod interactions;
+mod keybindings;
+mod lifespan;
+mod loading;
+mod lobby;
+mod locale;
+mod lod;
+mod metrics;
+mod movement;
+mod mutators;
+mod music;
+mod net;
+mod objectives;
+mod order_ack;
+mod order_feedback;
+mod order_inspector;
+mod outline;
+mod pathfinding;
+mod photo_mode;
+mod placement;
+mod predators;
+mod rocks;
+#[cfg(feature = "sandbox")]
+mod sandbox;
+mod save;
+mod scenario;
+mod scoring;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod selection_ring;
+#[cfg(feature = "admin")]
+mod session_replay;
+mod settings;
+mod sfx;
+mod spatial;
+mod storage;
+mod stress;
+mod structures;
+mod succession;
+mod synth;
+mod terrain_sync;
+mod territory;
+mod tick_loop;
+mod touch_camera;
+mod units;
+mod upgrades;
+mod veterancy;
+mod victory;
+mod vision;
+mod weather;
+#[cfg(target_arch = "wasm32")]
+mod web;
+mod zones;
+
+use alerts::AlertsPlugin;
+use ant_sprites::AntSpritesPlugin;
+use battle_panel::BattlePanelPlugin;
+use bots::BotsPlugin;
+use brood::BroodPlugin;
+use burrow::BurrowPlugin;
+use camera_controls::{CameraControlsPlugin, CameraInput, ManualCameraMove, ZoomLimits};
+use camera_share::CameraSharePlugin;
+use capture::CapturePlugin;
+use collision::CollisionPlugin;
+use console::{CommandResult, ConsoleAppExt, ConsolePlugin};
+use corpses::CorpsesPlugin;
+use culling::{CameraView, CullingPlugin};
+use coords::HexLayoutExt;
+use determinism::DeterminismPlugin;
+use dig_in::DigInPlugin;
+use diplomacy::DiplomacyPlugin;
+use energy::{Energy, EnergyPlugin};
+use event_log::EventLogPlugin;
+use factions::{FactionColors, FactionsPlugin};
+use game_commands::{AttackTarget, GameCommand, GameCommandsPlugin, MoveMode, SelectMode};
+use game_speed::GameSpeedPlugin;
+use gamepad::GamepadPlugin;
+use genetics::{Genes, GeneticsPlugin};
+use gpu_fields::GpuFieldsPlugin;
+use harvest::{HarvestPlacement, HarvestZonesPlugin};
+use health::HealthPlugin;
+use hex_cursor::HexCursorPlugin;
+use history::HistoryPlugin;
+use hover::HoverPlugin;
+use idle_workers::IdleWorkersPlugin;
+use input::{InputScheme, InputSchemePlugin, PointerAction};
+use interactions::InteractionsPlugin;
+use keybindings::{Action, ActionInput, KeyBindings, KeyBindingsPlugin};
+use lifespan::LifespanPlugin;
+use loading::{AppState, LoadingPlugin};
+use lobby::LobbyPlugin;
+use locale::LocalePlugin;
+use lod::LodPlugin;
+use metrics::MetricsPlugin;
+use movement::MovementPlugin;
+use music::MusicPlugin;
+use net::{LocalPlayer, NetPlugin, PlayerId};
+use objectives::ObjectivesPlugin;
+use order_ack::{OrderAckPlugin, PendingOrder};
+use order_feedback::OrderFeedbackPlugin;
+use order_inspector::OrderInspectorPlugin;
+use outline::OutlinePlugin;
+use pathfinding::{PathPriority, PathQueue, PathfindingPlugin};
+use photo_mode::PhotoModePlugin;
+use placement::SpawnPlacer;
+use predators::PredatorsPlugin;
+use rocks::RocksPlugin;
+use save::WorldSavePlugin;
+use scenario::ScenarioPlugin;
+use scoring::ScoringPlugin;
+use selection_ring::SelectionRingPlugin;
+use settings::{Settings, SettingsPlugin};
+use sfx::SfxPlugin;
+use spatial::{SpatialIndex, SpatialIndexPlugin};
+use stress::StressPlugin;
+use structures::{BuildPlacement, StructuresPlugin};
+use succession::SuccessionPlugin;
+use terrain_sync::TerrainSyncPlugin;
+use territory::TerritoryPlugin;
+use tick_loop::TickLoopPlugin;
+use touch_camera::TouchCameraPlugin;
+use units::{UnitSpawner, UnitStats, UnitsPlugin};
+use upgrades::UpgradesPlugin;
+use veterancy::{Veterancy, VeterancyPlugin};
+use victory::VictoryPlugin;
+use vision::{InFog, VisionPlugin};
+use weather::WeatherPlugin;
+use zones::{SpeedModifier, ZonesPlugin};
 
 fn main() {
-    App::new()
+    // An embedding page may want to sign the player in first; it starts the
+    // game itself through `web::start`
+    #[cfg(target_arch = "wasm32")]
+    if !web::autostart() {
+        return;
+    }
+    run_game();
+}
+
+fn run_game() {
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Ant Colony MMO".to_string(),
@@ -16,13 +204,36 @@ fn main() {
             ..default()
         }))
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
-        //.add_plugins(RapierDebugRenderPlugin::default())
+        .add_plugins((LoadingPlugin, KeyBindingsPlugin, NetPlugin, PathfindingPlugin, CameraSharePlugin, BurrowPlugin, InputSchemePlugin, OutlinePlugin, WorldSavePlugin, GamepadPlugin, TouchCameraPlugin, CameraControlsPlugin, HexCursorPlugin, TerrainSyncPlugin, OrderFeedbackPlugin))
+        .add_plugins((SpatialIndexPlugin, BattlePanelPlugin, CollisionPlugin, InteractionsPlugin, HistoryPlugin, AntSpritesPlugin, HealthPlugin, SelectionRingPlugin, ObjectivesPlugin, WeatherPlugin, OrderAckPlugin, PredatorsPlugin, UpgradesPlugin, BroodPlugin, StructuresPlugin))
+        .add_plugins((ZonesPlugin, UnitsPlugin, BotsPlugin, MetricsPlugin, ConsolePlugin, GameSpeedPlugin, DeterminismPlugin, StressPlugin, LodPlugin, CullingPlugin, SfxPlugin, MusicPlugin, SettingsPlugin, LocalePlugin, TickLoopPlugin))
+        .add_plugins((FactionsPlugin, VisionPlugin, TerritoryPlugin, ScoringPlugin, GameCommandsPlugin, RocksPlugin, EnergyPlugin, LifespanPlugin, GeneticsPlugin, DiplomacyPlugin, CapturePlugin, ScenarioPlugin, VictoryPlugin, AlertsPlugin, HoverPlugin))
+        .add_plugins((MovementPlugin, OrderInspectorPlugin, IdleWorkersPlugin, CorpsesPlugin, EventLogPlugin, PhotoModePlugin, HarvestZonesPlugin, DigInPlugin, VeterancyPlugin, LobbyPlugin, SuccessionPlugin, GpuFieldsPlugin))
         .init_resource::<SelectionState>()
+        .insert_resource(Teams::from_args())
+        .add_event::<PointerAction>()
+        .register_console_command("teleport", "teleport <q> <r>: move the selection to a hex", teleport_command)
         .init_gizmo_group::<DashedGizmos>()
         .add_systems(Startup, (setup_camera, setup_physics, configure_gizmos))
-        .add_systems(Startup, (setup_hex_grid, spawn_units).chain())
-        .add_systems(Update, (camera_movement, move_ants, ant_input, draw_selection_visuals, draw_selection_box, draw_hex_grid))
-        .run();
+        .add_systems(Startup, setup_hex_grid)
+        // Units need their assets, so the world is populated once loading finishes
+        .add_systems(OnEnter(AppState::InGame), spawn_units.after(units::apply_unit_definitions))
+        .add_systems(Update, (camera_movement.in_set(CameraInput), move_ants, (ant_input, dispatch_pointer_actions).chain(), draw_selection_visuals, draw_selection_box, sync_hex_grid_mesh));
+
+    #[cfg(feature = "debug-tools")]
+    app.add_plugins((RapierDebugRenderPlugin::default(), grid_debug::GridDebugPlugin));
+    #[cfg(feature = "sandbox")]
+    app.add_plugins(sandbox::SandboxPlugin);
+    #[cfg(feature = "scripting")]
+    app.add_plugins(scripting::ScriptingPlugin);
+    #[cfg(feature = "admin")]
+    app.add_plugins((admin::AdminPlugin, session_replay::SessionReplayPlugin));
+    #[cfg(feature = "webrtc")]
+    app.add_plugins(net::webrtc::WebRtcPlugin);
+    #[cfg(target_arch = "wasm32")]
+    app.add_plugins(web::WebPlugin);
+
+    app.run();
 }
 
 #[derive(Default, Reflect, GizmoConfigGroup)]
@@ -47,12 +258,145 @@ struct MainCamera;
 #[derive(Resource)]
 struct MapLayout(HexLayout);
 
+// Hexes are grouped into chunks (large hexes of this radius) for saving and streaming
+const CHUNK_RADIUS: u32 = 4;
+
+fn chunk_of(hex: Hex) -> Hex {
+    hex.to_lower_res(CHUNK_RADIUS)
+}
+
+// The playable map: every hex within `radius` rings of the origin (exclusive)
+#[derive(Resource)]
+struct MapSize {
+    radius: u32,
+}
+
+impl MapSize {
+    fn hexes(&self) -> impl Iterator<Item = Hex> {
+        Hex::ZERO.spiral_range(0..self.radius)
+    }
+
+    fn contains(&self, hex: Hex) -> bool {
+        hex.unsigned_distance_to(Hex::ZERO) < self.radius
+    }
+
+    // World-space bounding box of the map, corners included
+    fn world_rect(&self, layout: &HexLayout) -> Rect {
+        let outer_ring = Hex::ZERO.ring(self.radius.saturating_sub(1));
+        let mut rect = Rect::from_center_size(Vec2::ZERO, Vec2::ZERO);
+        for hex in outer_ring {
+            for corner in layout.world_corners(hex) {
+                rect = rect.union_point(corner);
+            }
+        }
+        rect
+    }
+}
+
 #[derive(Component)]
 struct Ant;
 
 #[derive(Component)]
 struct Queen;
 
+// Owning player of a unit
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+struct Faction(PlayerId);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Relation {
+    Own,
+    Ally,
+    // Bound by a non-aggression pact: no fighting, but no shared vision either
+    Neutral,
+    Enemy,
+}
+
+// Team per player; players without an entry are on a team of their own.
+// Allies share vision (see `vision`), never fight each other and aren't slowed
+// on each other's home turf. Teams are fixed before the game starts: each
+// `--team 0,1000` argument puts the listed players on one team (bots are
+// numbered from 1000 in the order they join). During the game colonies on
+// different teams can still make pacts with each other (see `diplomacy`).
+#[derive(Resource, Default)]
+struct Teams {
+    teams: HashMap<PlayerId, u32>,
+    // Ally or Neutral, per pair of players in id order
+    pacts: HashMap<(PlayerId, PlayerId), Relation>,
+}
+
+impl Teams {
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let mut teams = HashMap::new();
+        let lists = args.windows(2).filter(|pair| pair[0] == "--team").map(|pair| &pair[1]);
+        for (team, list) in lists.enumerate() {
+            for player in list.split(',') {
+                match player.trim().parse() {
+                    Ok(id) => {
+                        teams.insert(PlayerId(id), team as u32);
+                    }
+                    Err(_) => warn!("--team {list}: {player:?} is not a player id"),
+                }
+            }
+        }
+        Self { teams, pacts: HashMap::new() }
+    }
+
+    fn relation(&self, from: PlayerId, to: PlayerId) -> Relation {
+        if from == to {
+            return Relation::Own;
+        }
+        match (self.teams.get(&from), self.teams.get(&to)) {
+            (Some(a), Some(b)) if a == b => Relation::Ally,
+            _ => self.pact(from, to).unwrap_or(Relation::Enemy),
+        }
+    }
+
+    fn pact(&self, a: PlayerId, b: PlayerId) -> Option<Relation> {
+        self.pacts.get(&(a.min(b), a.max(b))).copied()
+    }
+
+    // Makes or (with `None`) ends the pact between two players
+    fn set_pact(&mut self, a: PlayerId, b: PlayerId, pact: Option<Relation>) {
+        let key = (a.min(b), a.max(b));
+        match pact {
+            Some(relation) => self.pacts.insert(key, relation),
+            None => self.pacts.remove(&key),
+        };
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+enum Caste {
+    Queen,
+    Worker,
+    Soldier,
+}
+
+impl Caste {
+    const ALL: [Caste; 3] = [Caste::Queen, Caste::Worker, Caste::Soldier];
+
+    // Lowercase name as typed in commands, e.g. "worker"
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "queen" => Some(Caste::Queen),
+            "worker" => Some(Caste::Worker),
+            "soldier" => Some(Caste::Soldier),
+            _ => None,
+        }
+    }
+
+    // Locale key of the caste's display name
+    fn name_key(self) -> &'static str {
+        match self {
+            Caste::Queen => "caste.queen",
+            Caste::Worker => "caste.worker",
+            Caste::Soldier => "caste.soldier",
+        }
+    }
+}
+
 #[derive(Component)]
 struct TargetPosition(Vec2);
 
@@ -64,10 +408,63 @@ struct Path {
 #[derive(Component)]
 struct Selected;
 
+// Looping patrol order: once the current path is exhausted the unit walks to
+// `points[next]` and keeps cycling through the list until given a new order.
+#[derive(Component)]
+struct Patrol {
+    points: Vec<Vec2>,
+    next: usize,
+}
+
+// Hold position: the unit stays on this spot, dropping any route it's given
+// and walking back if it's pushed off, until it gets a new move order
+#[derive(Component)]
+struct HoldPosition(Vec2);
+
 #[derive(Resource, Default)]
 struct SelectionState {
     start_pos: Option<Vec2>,
     drag_current: Option<Vec2>,
+    // Where the press began on screen, and when (real seconds)
+    start_screen: Option<Vec2>,
+    pressed_at: f64,
+    // The pointer has moved far enough from the press to draw a box
+    dragging: bool,
+    touch_drag: TouchDrag,
+    // Last tapped unit and when (real seconds), for double-tap detection
+    last_tap: Option<(Entity, f64)>,
+    // A two-finger gesture is in progress; stays set until every finger lifts
+    multi_touch: bool,
+}
+
+// What a one-finger drag on a touch screen does. A finger that moves right
+// away pans the camera (see `touch_camera`); one held still for a moment
+// first draws a selection box instead.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+enum TouchDrag {
+    #[default]
+    Undecided,
+    Pan,
+    Select,
+}
+
+// Max gap between two taps on the same unit to count as a double tap
+const DOUBLE_TAP_SECS: f64 = 0.3;
+// Pointer travel, in screen pixels, under which a press and release is a tap
+// whatever the zoom level
+pub(crate) const TAP_SLOP_PX: f32 = 5.0;
+// A finger held still this long starts a selection box instead of panning
+const LONG_PRESS_SECS: f64 = 0.4;
+
+impl SelectionState {
+    // Forget the press in progress, if any
+    fn clear_press(&mut self) {
+        self.start_pos = None;
+        self.drag_current = None;
+        self.start_screen = None;
+        self.dragging = false;
+        self.touch_drag = TouchDrag::Undecided;
+    }
 }
 
 fn setup_camera(mut commands: Commands) {
@@ -83,32 +480,42 @@ fn setup_physics(mut rapier_config: ResMut<RapierConfiguration>) {
 
 fn camera_movement(
     mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
-    time: Res<Time>,
+    zoom_limits: Res<ZoomLimits>,
+    settings: Res<Settings>,
+    // Real time, so the camera still moves while the game is paused
+    time: Res<Time<Real>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut moved: EventWriter<ManualCameraMove>,
 ) {
     let (mut transform, mut projection) = camera_query.single_mut();
-    let speed = 500.0;
-    let zoom_speed = 1.0;
+    let speed = settings.camera.pan_speed;
+    let zoom_speed = settings.camera.zoom_speed;
 
-    if keyboard_input.pressed(KeyCode::ArrowLeft) || keyboard_input.pressed(KeyCode::KeyA) {
+    if bindings.pressed(&keyboard_input, Action::PanLeft) {
         transform.translation.x -= speed * time.delta_seconds();
     }
-    if keyboard_input.pressed(KeyCode::ArrowRight) || keyboard_input.pressed(KeyCode::KeyD) {
+    if bindings.pressed(&keyboard_input, Action::PanRight) {
         transform.translation.x += speed * time.delta_seconds();
     }
-    if keyboard_input.pressed(KeyCode::ArrowUp) || keyboard_input.pressed(KeyCode::KeyW) {
+    if bindings.pressed(&keyboard_input, Action::PanUp) {
         transform.translation.y += speed * time.delta_seconds();
     }
-    if keyboard_input.pressed(KeyCode::ArrowDown) || keyboard_input.pressed(KeyCode::KeyS) {
+    if bindings.pressed(&keyboard_input, Action::PanDown) {
         transform.translation.y -= speed * time.delta_seconds();
     }
 
-    if keyboard_input.pressed(KeyCode::KeyQ) {
+    if bindings.pressed(&keyboard_input, Action::ZoomOut) {
         projection.scale += zoom_speed * time.delta_seconds();
     }
-    if keyboard_input.pressed(KeyCode::KeyE) {
+    if bindings.pressed(&keyboard_input, Action::ZoomIn) {
         projection.scale -= zoom_speed * time.delta_seconds();
-        projection.scale = projection.scale.max(0.1);
+    }
+    projection.scale = zoom_limits.clamp(projection.scale);
+
+    let camera_keys = [Action::PanLeft, Action::PanRight, Action::PanUp, Action::PanDown, Action::ZoomIn, Action::ZoomOut];
+    if camera_keys.into_iter().any(|action| bindings.pressed(&keyboard_input, action)) {
+        moved.send(ManualCameraMove);
     }
 }
 
@@ -120,96 +527,158 @@ fn setup_hex_grid(mut commands: Commands) {
     };
 
     commands.insert_resource(MapLayout(layout));
+    commands.insert_resource(MapSize { radius: 10 });
 }
 
-fn draw_hex_grid(mut gizmos: Gizmos, layout: Res<MapLayout>) {
-    let hex_coords = Hex::ZERO.spiral_range(0..10);
-    for hex in hex_coords {
-        let corners = layout.0.hex_corners(hex);
+// The static grid is line-list meshes built once, not per-frame gizmos;
+// gizmos are kept for dynamic overlays only. One mesh per chunk, so Bevy's
+// culling skips the ones off screen.
+#[derive(Component)]
+struct HexGridMesh;
+
+fn build_hex_grid_mesh(layout: &HexLayout, hexes: &[Hex]) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for &hex in hexes {
+        let corners = layout.world_corners(hex);
         for i in 0..6 {
             let start = corners[i];
             let end = corners[(i + 1) % 6];
-            // Convert hexx::Vec2 to bevy::Vec2 to resolve crate version mismatch
-            let start_bevy = Vec2::new(start.x, start.y);
-            let end_bevy = Vec2::new(end.x, end.y);
-            gizmos.line_2d(start_bevy, end_bevy, Color::from(Srgba::hex("444444").unwrap()));
+            positions.push([start.x, start.y, 0.0]);
+            positions.push([end.x, end.y, 0.0]);
         }
     }
-}
-
-fn spawn_units(mut commands: Commands, layout: Res<MapLayout>) {
-    // Spawn Queen (Gold, bigger, immobile) at 0,0 (Hex ZERO)
-    let queen_color = Color::from(Srgba::hex("8B4513").unwrap()); // SaddleBrown for Queen
-    let queen_hex = Hex::ZERO;
-    let queen_pos = layout.0.hex_to_world_pos(queen_hex);
-    let queen_vec = Vec2::new(queen_pos.x, queen_pos.y);
 
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: queen_color,
-                custom_size: Some(Vec2::new(20.0, 20.0)), // Smaller Queen (was 25.0)
-                ..default()
-            },
-            transform: Transform::from_xyz(queen_vec.x, queen_vec.y, 1.0),
-            ..default()
-        },
-        RigidBody::Fixed, // Immobile
-        Collider::ball(12.5),
-        Ant,
-        Queen,
-        TargetPosition(queen_vec),
-        Path::default(),
-    ));
+    Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
 
-    // Spawn Worker Ants
-    let worker_color = Color::from(Srgba::hex("8B4513").unwrap()); // SaddleBrown
-    // Spawn 3 workers in the first ring
-    let worker_hexes = Hex::ZERO.ring(1).take(3);
-    
-    for hex in worker_hexes {
-        let pos = layout.0.hex_to_world_pos(hex);
-        let vec = Vec2::new(pos.x, pos.y);
+// Rebuild the grid mesh only when the map itself changes
+fn sync_hex_grid_mesh(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    grid_q: Query<Entity, With<HexGridMesh>>,
+) {
+    if !layout.is_changed() && !map_size.is_changed() {
+        return;
+    }
 
+    for entity in grid_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    let mut chunks: HashMap<Hex, Vec<Hex>> = HashMap::new();
+    for hex in map_size.hexes() {
+        chunks.entry(chunk_of(hex)).or_default().push(hex);
+    }
+    let material = materials.add(ColorMaterial::from(Color::from(Srgba::hex("444444").unwrap())));
+    for hexes in chunks.values() {
         commands.spawn((
-            SpriteBundle {
-                sprite: Sprite {
-                    color: worker_color,
-                    custom_size: Some(Vec2::new(10.0, 10.0)),
-                    ..default()
-                },
-                transform: Transform::from_xyz(vec.x, vec.y, 1.0),
+            MaterialMesh2dBundle {
+                mesh: meshes.add(build_hex_grid_mesh(&layout.0, hexes)).into(),
+                material: material.clone(),
                 ..default()
             },
-            RigidBody::Dynamic,
-            // Make units Sensors to avoid physical collision/locking
-            Sensor,
-            Collider::ball(5.0),
-            Velocity::zero(),
-            Damping { linear_damping: 20.0, angular_damping: 1.0 },
-            Ant,
-            TargetPosition(vec),
-            Path::default(),
+            HexGridMesh,
         ));
     }
 }
 
-fn ant_input(
+fn spawn_units(
     mut commands: Commands,
+    layout: Res<MapLayout>,
+    placer: SpawnPlacer,
+    spawner: UnitSpawner,
+    local_player: Res<LocalPlayer>,
+) {
+    let faction = Faction(local_player.0);
+
+    // Queen at the center of the map
+    let queen_hex = Hex::ZERO;
+    spawner.spawn(&mut commands, Caste::Queen, faction, layout.0.hex_to_world(queen_hex));
+
+    // Spawn 3 workers and 2 soldiers in the free hexes closest to the queen
+    let mut spots = placer.around(queen_hex);
+    spots.reserve(queen_hex);
+
+    let castes = [Caste::Worker, Caste::Worker, Caste::Worker, Caste::Soldier, Caste::Soldier];
+    for (caste, (_, vec)) in castes.into_iter().zip(spots) {
+        spawner.spawn(&mut commands, caste, faction, vec);
+    }
+}
+
+fn teleport_command(
+    In(args): In<Vec<String>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    mut selected_q: Query<(&mut Transform, &mut TargetPosition, &mut Path), With<Selected>>,
+) -> CommandResult {
+    let [q, r] = args.as_slice() else {
+        return Err("usage: teleport <q> <r>".to_string());
+    };
+    let (Ok(q), Ok(r)) = (q.parse(), r.parse()) else {
+        return Err(format!("not a hex: {q} {r}"));
+    };
+    let hex = Hex::new(q, r);
+    if !map_size.contains(hex) {
+        return Err(format!("({q}, {r}) is off the map"));
+    }
+    let pos = layout.0.hex_to_world(hex);
+    let mut count = 0;
+    for (mut transform, mut target, mut path) in selected_q.iter_mut() {
+        transform.translation = pos.extend(transform.translation.z);
+        target.0 = pos;
+        path.waypoints.clear();
+        count += 1;
+    }
+    Ok(format!("teleported {count} units to ({q}, {r})"))
+}
+
+fn ant_input(
     mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
     touches: Res<Touches>,
+    settings: Res<Settings>,
     windows: Query<&Window, With<PrimaryWindow>>,
     camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
-    mut ant_q: Query<(Entity, &mut TargetPosition, &Transform, &mut Path), With<Ant>>,
+    picker: UnitPicker,
+    ui_q: Query<&Interaction, With<Node>>,
+    placement: Res<BuildPlacement>,
+    harvest_placement: Res<HarvestPlacement>,
+    real_time: Res<Time<Real>>,
     mut selection_state: ResMut<SelectionState>,
-    selected_q: Query<Entity, With<Selected>>,
-    layout: Res<MapLayout>,
+    mut actions: EventWriter<PointerAction>,
 ) {
+    // Clicks place the structure or mark the zone instead; see `structures` and `harvest`
+    if placement.is_active() || harvest_placement.is_active() {
+        selection_state.clear_press();
+        return;
+    }
+
+    // Two-finger gestures belong to the camera and must never select anything,
+    // including when the fingers lift one at a time
+    let touch_count = touches.iter().count();
+    if touch_count >= 2 {
+        selection_state.multi_touch = true;
+    }
+    if selection_state.multi_touch {
+        selection_state.clear_press();
+        if touch_count == 0 {
+            selection_state.multi_touch = false;
+        }
+        return;
+    }
+
     let window = windows.single();
     let cursor_pos = if let Some(pos) = window.cursor_position() {
         pos
     } else if let Some(touch) = touches.first_pressed_position() {
         touch
+    } else if let Some(touch) = touches.iter_just_released().next() {
+        // The lifted finger is no longer pressed, but its tap still counts
+        touch.position()
     } else {
         return; // No input
     };
@@ -221,305 +690,329 @@ fn ant_input(
         return;
     };
 
+    let scheme = settings.input_scheme;
+    let shift = bindings.pressed(&keyboard_input, Action::AddToSelection);
+    // Holding an order modifier makes a touch tap an order even on top of a unit
+    let order_modifier = bindings.pressed(&keyboard_input, Action::PatrolModifier)
+        || bindings.pressed(&keyboard_input, Action::TunnelModifier)
+        || bindings.pressed(&keyboard_input, Action::AttackMoveModifier);
+
     // Handle Input
-    if mouse_input.just_pressed(MouseButton::Left) || touches.any_just_pressed() {
-        selection_state.start_pos = Some(world_pos);
-        selection_state.drag_current = Some(world_pos);
+    // Presses that land on a UI panel belong to the UI, not the map
+    let pressed = mouse_input.just_pressed(MouseButton::Left) || touches.any_just_pressed();
+    if (pressed || mouse_input.just_pressed(MouseButton::Right)) && input::pointer_over_ui(&ui_q) {
+        return;
     }
 
-    if mouse_input.pressed(MouseButton::Left) || touches.iter().count() > 0 {
+    let now = real_time.elapsed_seconds_f64();
+    if pressed {
+        selection_state.start_pos = Some(world_pos);
         selection_state.drag_current = Some(world_pos);
+        selection_state.start_screen = Some(cursor_pos);
+        selection_state.pressed_at = now;
+        selection_state.dragging = false;
+        selection_state.touch_drag = TouchDrag::Undecided;
     }
 
-    if mouse_input.just_released(MouseButton::Left) || touches.any_just_released() {
-        if let Some(start) = selection_state.start_pos {
-            let dist = start.distance(world_pos);
-            
-            if dist < 5.0 {
-                // CLICK / TAP
-                handle_click(
-                    world_pos, 
-                    &mut commands, 
-                    &mut ant_q, 
-                    &selected_q,
-                    &layout.0
-                );
-            } else {
-                // DRAG / BOX SELECT
-                handle_box_select(
-                    start, 
-                    world_pos, 
-                    &mut commands, 
-                    &ant_q,
-                    &selected_q,
-                    &layout.0
-                );
+    if mouse_input.pressed(MouseButton::Left) || touch_count > 0 {
+        selection_state.drag_current = Some(world_pos);
+        if let Some(start_screen) = selection_state.start_screen {
+            let moved = start_screen.distance(cursor_pos) >= TAP_SLOP_PX;
+            if touch_count > 0 && selection_state.touch_drag == TouchDrag::Undecided {
+                if moved {
+                    selection_state.touch_drag = TouchDrag::Pan;
+                } else if now - selection_state.pressed_at >= LONG_PRESS_SECS {
+                    selection_state.touch_drag = TouchDrag::Select;
+                }
             }
+            selection_state.dragging = moved && (touch_count == 0 || selection_state.touch_drag == TouchDrag::Select);
         }
-        selection_state.start_pos = None;
-        selection_state.drag_current = None;
     }
-}
 
-fn handle_click(
-    world_pos: Vec2,
-    commands: &mut Commands,
-    ant_q: &mut Query<(Entity, &mut TargetPosition, &Transform, &mut Path), With<Ant>>,
-    selected_q: &Query<Entity, With<Selected>>,
-    layout: &HexLayout,
-) {
-    // 1. Check for unit in the clicked hex
-    let mut hit_unit = None;
-
-    // Convert world_pos to hex to check which cell we clicked
-    let hex_vec = HexVec2::new(world_pos.x, world_pos.y);
-    let clicked_hex = layout.world_pos_to_hex(hex_vec);
-
-    // Find if any ant is in this hex (based on their transform/target)
-    // We check transform position to see if they are "visually" in the hex
-    for (entity, _, transform, _) in ant_q.iter() {
-        let pos = transform.translation.truncate();
-        let ant_hex_vec = HexVec2::new(pos.x, pos.y);
-        let ant_hex = layout.world_pos_to_hex(ant_hex_vec);
-        
-        if ant_hex == clicked_hex {
-            hit_unit = Some(entity);
-            break; 
+    let touch_released = touches.any_just_released();
+    if mouse_input.just_released(MouseButton::Left) || touch_released {
+        if let (Some(start), Some(start_screen)) = (selection_state.start_pos, selection_state.start_screen) {
+            let is_tap = start_screen.distance(cursor_pos) < TAP_SLOP_PX;
+            // A finger only draws a box after a long press; any other drag panned the camera
+            let boxed = !touch_released || selection_state.touch_drag == TouchDrag::Select;
+
+            // Translate the gesture according to the active scheme
+            let action = match scheme {
+                _ if !is_tap && !boxed => None,
+                _ if !is_tap => Some(PointerAction::BoxSelect {
+                    start,
+                    end: world_pos,
+                    additive: scheme == InputScheme::Touch || shift,
+                }),
+                InputScheme::Touch if order_modifier || picker.own_unit_at(world_pos).is_none() => {
+                    Some(PointerAction::Command { world_pos })
+                }
+                InputScheme::Touch => Some(PointerAction::Select { world_pos, additive: true }),
+                InputScheme::ClassicRts => Some(PointerAction::Select { world_pos, additive: shift }),
+            };
+            if let Some(action) = action {
+                actions.send(action);
+            }
         }
+        selection_state.clear_press();
     }
 
-    if let Some(entity) = hit_unit {
-        // TOGGLE SELECTION:
-        // If the entity is already selected, deselect it.
-        // If it's not selected, select it (and clear others if we want single select, but user asked for toggle behavior).
-        // Based on user request "tap a unit to deselect it", we imply a toggle or multi-select mode?
-        // "if i select a unit ,i cai an still dratgg a new window to grup selet other units and its additiive, i will select all three, then if i tap one of the selected, it deselcted"
-        
-        if selected_q.contains(entity) {
-            commands.entity(entity).remove::<Selected>();
-        } else {
-            // If we are just clicking one unit, do we clear others? The prompt implies "additive" behavior for the drag window,
-            // but usually a single click replaces selection unless shift is held. 
-            // However, the user says "tap a unit to deselect it".
-            // Let's assume:
-            // 1. Click on unselected -> Select ONLY that one (Standard RTS)
-            // 2. Click on selected -> Deselect that one (User Request)
-            // BUT user also mentioned "additive" drag.
-            // Let's try this:
-            // Single click adds/toggles if it's a toggle, or replaces if it's a new selection?
-            // Re-reading: "tap a unit to deselect it... if i select a unit... then if i tap one of the selected, it deselcted"
-            // This implies clicking a selected unit deselects it.
-            // What if I click an unselected unit? Usually that clears and selects new. 
-            // But if the user wants to "drag a new window... and its additive", that's about the window.
-            
-            // Let's implement: 
-            // - Click selected: Deselect it.
-            // - Click unselected: Select it (and clear others? Standard behavior says yes, unless we are in a special mode).
-            // Let's stick to standard RTS + the requested "deselect on click":
-            // If I click an unselected unit, I probably want to select it. If I didn't hold shift, I probably want to select ONLY it.
-            
-            // However, to support the "workflow" described: 
-            // 1. Select one.
-            // 2. Drag select more (additive).
-            // 3. Tap one to deselect.
-            
-            // If I click an unselected unit without shift, standard is "Clear all, select this".
-            // If I click a selected unit without shift, standard is "Select only this" (if multiple selected) or "Nothing" (if only one).
-            // The user specifically wants "tap to deselect".
-            
-            // Interpretation: Single click always toggles? That's mobile-friendly.
-            // Let's try: Single click toggles selection state of the target. Does NOT clear others.
-            // This fits "additive" workflow best without modifier keys.
-            commands.entity(entity).insert(Selected);
-        }
-    } else {
-        // Move Selected Units to Center of Hexes, avoiding overlap
-        
-        // Identify Occupied Hexes (Targets of non-selected units)
-        let mut occupied: HashSet<Hex> = HashSet::new();
-        for (entity, target, _, _) in ant_q.iter() {
-             // Don't mark current targets of selected units as occupied, 
-             // because they are about to move (or stay if we click same spot)
-             if selected_q.contains(entity) { continue; }
-
-             // Convert target Vec2 to Hex
-             let t_vec = HexVec2::new(target.0.x, target.0.y);
-             let hex = layout.world_pos_to_hex(t_vec);
-             occupied.insert(hex);
-        }
+    if scheme == InputScheme::ClassicRts && mouse_input.just_pressed(MouseButton::Right) {
+        actions.send(PointerAction::Command { world_pos });
+    }
+}
 
-        // Determine Target Hex for click
-        let target_pos_vec = HexVec2::new(world_pos.x, world_pos.y);
-        let target_hex = layout.world_pos_to_hex(target_pos_vec);
-        
-        let selected_entities: Vec<Entity> = selected_q.iter().collect();
-        if selected_entities.is_empty() { return; }
+// Command dispatch: every input source (mouse, touch, gamepad) ends up here
+// and is turned into `GameCommand`s for the local player; see `game_commands`
+fn dispatch_pointer_actions(
+    mut actions: EventReader<PointerAction>,
+    mut game_commands: EventWriter<GameCommand>,
+    action_input: ActionInput,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    ant_q: Query<(Entity, &Transform), With<Ant>>,
+    caste_q: Query<(Entity, &Caste, &Transform)>,
+    mut selection_state: ResMut<SelectionState>,
+    selected_q: Query<Entity, With<Selected>>,
+    picker: UnitPicker,
+    layout: Res<MapLayout>,
+    real_time: Res<Time<Real>>,
+) {
+    let window = windows.single();
+    let (camera, camera_transform) = camera_q.single();
 
-        let mut available_hexes = Vec::new();
-        let candidates = target_hex.spiral_range(0..10); 
-        
-        for hex in candidates {
-            // Allow moving to same hex multiple times if needed, OR just ignore occupancy for now?
-            // User said: "you should be able to walk through a cell taht a unit is in!!"
-            // This implies we shouldn't block movement based on occupancy, OR we should just treat it as soft collision.
-            // The previous logic was: "occupied.contains(&hex)".
-            // Let's RELAX this. If we relax it, units might stack.
-            // "units gets locked together when they corss paths"
-            // This is likely due to physics collisions (Rapier).
-            // We should probably use sensor colliders or collision groups to avoid units pushing each other?
-            // But for now, let's remove the strict "occupied" check for target assignment so they can at least try to go there.
-            // Actually, spiral_range assignment is for formation.
-            
-            // Let's keep formation logic but MAYBE allow overlap if space is tight?
-            // Or maybe the user means transient pathing?
-            // "you should be able to walk through a cell taht a unit is in" -> This suggests pathfinding issue or physics issue.
-            // If it's physics, they bump.
-            // If it's this logic, they can't target the same cell.
-            
-            // Let's Keep formation but allow moving through.
-            // The issue "locked together when they cross paths" is definitely physics.
-            
-            if !occupied.contains(&hex) {
-                available_hexes.push(hex);
-                occupied.insert(hex); 
-                if available_hexes.len() >= selected_entities.len() {
-                    break;
+    for &action in actions.read() {
+        let command = match action {
+            PointerAction::Select { world_pos, additive } => {
+                if is_double_tap(world_pos, &picker, &mut selection_state, real_time.elapsed_seconds_f64()) {
+                    // DOUBLE TAP: select all of that caste on screen
+                    let view_min = camera.viewport_to_world_2d(camera_transform, Vec2::new(0.0, window.height()));
+                    let view_max = camera.viewport_to_world_2d(camera_transform, Vec2::new(window.width(), 0.0));
+                    let (Some(view_min), Some(view_max), Some(tapped)) = (view_min, view_max, picker.own_unit_at(world_pos)) else {
+                        continue;
+                    };
+                    GameCommand::Select {
+                        units: same_caste_on_screen(tapped, view_min, view_max, &caste_q, &picker),
+                        mode: SelectMode::Add,
+                    }
+                } else {
+                    // A tap toggles the tapped unit; otherwise the click replaces
+                    // the selection, and clicking empty ground clears it
+                    GameCommand::Select {
+                        units: picker.own_unit_at(world_pos).into_iter().collect(),
+                        mode: if additive { SelectMode::Toggle } else { SelectMode::Replace },
+                    }
                 }
             }
-        }
-
-        // Assign Targets
-        let mut moved_any = false;
-        for (i, entity) in selected_entities.iter().enumerate() {
-            if let Some(dest_hex) = available_hexes.get(i) {
-                if let Ok((_, mut target, transform, mut path)) = ant_q.get_mut(*entity) {
-                     let current_pos_vec = transform.translation.truncate();
-                     let current_hex = layout.world_pos_to_hex(HexVec2::new(current_pos_vec.x, current_pos_vec.y));
-                     
-                     // Generate path using line_to (grid walking)
-                     let route: Vec<Vec2> = current_hex.line_to(*dest_hex)
-                        .skip(1) // Skip start
-                        .map(|h| {
-                            let p = layout.hex_to_world_pos(h);
-                            Vec2::new(p.x, p.y)
-                        })
-                        .collect();
-                     
-                     path.waypoints = VecDeque::from(route);
-                     
-                     // Set initial target
-                     if let Some(first) = path.waypoints.pop_front() {
-                         target.0 = first;
-                     } else {
-                         // Already there or path empty
-                         let pos = layout.hex_to_world_pos(*dest_hex);
-                         target.0 = Vec2::new(pos.x, pos.y);
-                     }
-                     
-                     moved_any = true;
+            PointerAction::BoxSelect { start, end, additive } => {
+                // DRAG / BOX SELECT
+                GameCommand::Select {
+                    units: units_in_box(start, end, &ant_q, &picker, &layout.0),
+                    mode: if additive { SelectMode::Toggle } else { SelectMode::Replace },
                 }
             }
-        }
-        
-        if !moved_any {
-             for sel in selected_q.iter() {
-                commands.entity(sel).remove::<Selected>();
+            PointerAction::Command { world_pos } => {
+                let units: Vec<Entity> = selected_q.iter().collect();
+                if units.is_empty() {
+                    continue;
+                }
+                let player = picker.local_player.0;
+                if let Some(target) = picker.enemy_unit_at(world_pos) {
+                    GameCommand::Attack { player, units, target }
+                } else {
+                    // Holding T: tap a tunnel entrance to fast travel there,
+                    // or open ground to have the workers dig a tunnel to it;
+                    // holding P turns ground clicks into patrol waypoints and
+                    // holding Ctrl into attack-moves
+                    let mode = if action_input.pressed(Action::TunnelModifier) {
+                        MoveMode::Tunnel
+                    } else if action_input.pressed(Action::PatrolModifier) {
+                        MoveMode::Patrol
+                    } else if action_input.pressed(Action::AttackMoveModifier) {
+                        MoveMode::AttackMove
+                    } else {
+                        MoveMode::Direct
+                    };
+                    GameCommand::Move { player, units, destination: world_pos, mode }
+                }
             }
-        }
+        };
+        game_commands.send(command);
     }
 }
 
-fn handle_box_select(
+// Picks the unit whose collider is under a point, so picking matches what's
+// drawn whatever the hex size. `None` means the point is on empty ground.
+// Selecting goes through `own_unit_at`: other players' units can be hovered
+// but never selected or ordered, and enemies can be attacked.
+#[derive(SystemParam)]
+struct UnitPicker<'w, 's> {
+    rapier_context: Res<'w, RapierContext>,
+    spatial_index: Res<'w, SpatialIndex>,
+    local_player: Res<'w, LocalPlayer>,
+    faction_q: Query<'w, 's, &'static Faction>,
+    fog_q: Query<'w, 's, (), With<InFog>>,
+    teams: Res<'w, Teams>,
+}
+
+impl UnitPicker<'_, '_> {
+    fn is_own(&self, entity: Entity) -> bool {
+        self.faction_q.get(entity).is_ok_and(|faction| faction.0 == self.local_player.0)
+    }
+
+    fn own_unit_at(&self, world_pos: Vec2) -> Option<Entity> {
+        self.unit_at(world_pos).filter(|&entity| self.is_own(entity))
+    }
+
+    // An enemy unit under the point, as a target to attack
+    fn enemy_unit_at(&self, world_pos: Vec2) -> Option<Entity> {
+        self.unit_at(world_pos).filter(|&entity| {
+            self.faction_q
+                .get(entity)
+                .is_ok_and(|faction| self.teams.relation(self.local_player.0, faction.0) == Relation::Enemy)
+        })
+    }
+
+    fn unit_at(&self, world_pos: Vec2) -> Option<Entity> {
+        let mut hit = None;
+        self.rapier_context.intersections_with_point(world_pos, QueryFilter::default(), |entity| {
+            // Only units are indexed; keep looking past any other collider
+            // and enemies hidden in the fog of war
+            if self.spatial_index.contains(entity) && !self.fog_q.contains(entity) {
+                hit = Some(entity);
+                return false;
+            }
+            true
+        });
+        hit
+    }
+}
+
+// Records the tap and reports whether it completes a double tap on the same unit
+fn is_double_tap(
+    world_pos: Vec2,
+    picker: &UnitPicker,
+    selection_state: &mut SelectionState,
+    now: f64,
+) -> bool {
+    let hit = picker.own_unit_at(world_pos);
+    let double_tap = match (hit, selection_state.last_tap) {
+        (Some(entity), Some((last_entity, last_time))) => {
+            entity == last_entity && now - last_time <= DOUBLE_TAP_SECS
+        }
+        _ => false,
+    };
+
+    // A completed double tap shouldn't also start the next one
+    selection_state.last_tap = if double_tap { None } else { hit.map(|entity| (entity, now)) };
+    double_tap
+}
+
+// Double tap: every one of our units of the tapped unit's caste inside the camera view
+fn same_caste_on_screen(
+    tapped: Entity,
+    view_min: Vec2,
+    view_max: Vec2,
+    caste_q: &Query<(Entity, &Caste, &Transform)>,
+    picker: &UnitPicker,
+) -> Vec<Entity> {
+    let Ok((_, &caste, _)) = caste_q.get(tapped) else {
+        return Vec::new();
+    };
+
+    caste_q
+        .iter()
+        .filter(|&(entity, &other_caste, transform)| {
+            let pos = transform.translation.truncate();
+            let on_screen = pos.cmpge(view_min).all() && pos.cmple(view_max).all();
+            other_caste == caste && on_screen && picker.is_own(entity)
+        })
+        .map(|(entity, ..)| entity)
+        .collect()
+}
+
+// Our units whose cell the box touches
+fn units_in_box(
     start: Vec2,
     end: Vec2,
-    commands: &mut Commands,
-    ant_q: &Query<(Entity, &mut TargetPosition, &Transform, &mut Path), With<Ant>>,
-    selected_q: &Query<Entity, With<Selected>>,
+    ant_q: &Query<(Entity, &Transform), With<Ant>>,
+    picker: &UnitPicker,
     layout: &HexLayout,
-) {
+) -> Vec<Entity> {
     let min = start.min(end);
     let max = start.max(end);
 
-    // Toggle selection for units inside the box
-    for (entity, _, transform, _) in ant_q.iter() {
-        let pos = transform.translation.truncate();
-        // Convert unit position to hex center to check if that hex is touched by the box?
-        // OR: Check if the hex center is inside the box.
-        // Ideally, if the box touches the unit's cell, it should select.
-        // But checking if a rectangle intersects a hexagon is complex.
-        // A simpler approximation: Check if the unit's hex center is inside the box.
-        // This is what we did before with `pos`.
-        // If "hit box should be the whole cell", it means if I drag over ANY part of the cell, it selects.
-        // That means intersection of Box vs Hexagon.
-        
-        // Approximate: Check if Box intersects Circle (Radius ~ Hex Size)
-        // Hex radius is layout.scale.x (20.0)
-        // Box is min/max.
-        
-        let hex_radius = layout.scale.x;
-        // Clamp point in box to find closest point to circle center
-        let closest_x = pos.x.clamp(min.x, max.x);
-        let closest_y = pos.y.clamp(min.y, max.y);
-        
-        let closest = Vec2::new(closest_x, closest_y);
-        let dist_sq = pos.distance_squared(closest);
-        
-        if dist_sq < (hex_radius * hex_radius) {
-            if selected_q.contains(entity) {
-                commands.entity(entity).remove::<Selected>();
-            } else {
-                commands.entity(entity).insert(Selected);
-            }
-        } 
-    }
+    // Intersecting the box with each hexagon is more than we need: treat the
+    // cell as a circle of the hex radius around the unit and check the closest
+    // point of the box against it
+    let hex_radius = layout.scale.x;
+    ant_q
+        .iter()
+        .filter(|&(entity, transform)| {
+            let pos = transform.translation.truncate();
+            let closest = pos.clamp(min, max);
+            picker.is_own(entity) && pos.distance_squared(closest) < hex_radius * hex_radius
+        })
+        .map(|(entity, _)| entity)
+        .collect()
 }
 
 fn draw_selection_visuals(
     mut gizmos: Gizmos,
     mut dashed_gizmos: Gizmos<DashedGizmos>,
-    query: Query<(&Transform, &TargetPosition, &Path), With<Selected>>,
+    query: Query<(&Transform, &TargetPosition, &Path, Option<&Patrol>, Has<PendingOrder>), With<Selected>>,
     layout: Res<MapLayout>,
+    view: Res<CameraView>,
+    colors: Res<FactionColors>,
+    local_player: Res<LocalPlayer>,
 ) {
-    let selection_color = Color::from(Srgba::hex("FFFF00").unwrap()); // Yellow for selection
-    let path_color = Color::from(Srgba::hex("FFFF00").unwrap()); // Yellow for path
-    let target_color = Color::from(Srgba::hex("FFFF00").unwrap()); // Yellow for destination
+    // The selection ring itself is a child mesh; see `selection_ring`
+    let confirmed_color = colors.get(local_player.0); // Our faction color for path
+    let pending_color = Color::from(Srgba::hex("9E9E9E").unwrap()); // Grey until the server acks the order
+    let target_color = colors.get(local_player.0); // and destination
+
+    let patrol_color = Color::from(Srgba::hex("00BFFF").unwrap()); // Blue for patrol loop
 
-    for (transform, target, path) in query.iter() {
+    for (transform, target, path, patrol, pending) in query.iter() {
         let current_pos = transform.translation.truncate();
+        let path_color = if pending { pending_color } else { confirmed_color };
         
-        // 1. Draw Hexagonal Outline around the Unit
-        // Use the grid layout scale so it matches the cell size
-        let corners = layout.0.hex_corners(Hex::ZERO);
-         for i in 0..6 {
-            let start = corners[i];
-            let end = corners[(i + 1) % 6];
-            let start_v = Vec2::new(start.x, start.y) + current_pos;
-            let end_v = Vec2::new(end.x, end.y) + current_pos;
-            gizmos.line_2d(start_v, end_v, selection_color);
-        }
-        
-        // 2. Draw Path
+        // 1. Draw Path
         // Line from current to target (immediate)
-        dashed_gizmos.line_2d(current_pos, target.0, path_color);
+        if view.overlaps_segment(current_pos, target.0) {
+            dashed_gizmos.line_2d(current_pos, target.0, path_color);
+        }
         
         let mut prev_point = target.0;
         for &waypoint in &path.waypoints {
-             dashed_gizmos.line_2d(prev_point, waypoint, path_color);
+             if view.overlaps_segment(prev_point, waypoint) {
+                 dashed_gizmos.line_2d(prev_point, waypoint, path_color);
+             }
              prev_point = waypoint;
         }
         
-        // 3. Draw Target Hexagon (at final destination)
+        // 2. Draw Target Hexagon (at final destination)
         // Only draw if we are not already there (distance > some small amount)
         // or if there are waypoints left.
-        if !path.waypoints.is_empty() || current_pos.distance(target.0) > 2.0 {
-            let target_hex_vec = HexVec2::new(prev_point.x, prev_point.y);
-            let target_hex = layout.0.world_pos_to_hex(target_hex_vec);
+        let arrived = path.waypoints.is_empty() && current_pos.distance(target.0) <= 2.0;
+        if !arrived && view.overlaps_circle(prev_point, layout.0.scale.max_element()) {
+            let target_hex = layout.0.world_to_hex(prev_point);
             
-            let corners = layout.0.hex_corners(target_hex);
+            let corners = layout.0.world_corners(target_hex);
             for i in 0..6 {
-                let start = corners[i];
-                let end = corners[(i + 1) % 6];
-                gizmos.line_2d(Vec2::new(start.x, start.y), Vec2::new(end.x, end.y), target_color);
+                gizmos.line_2d(corners[i], corners[(i + 1) % 6], target_color);
+            }
+        }
+
+        // 3. Draw Patrol Loop (closed, so the last point links back to the first)
+        if let Some(patrol) = patrol {
+            let count = patrol.points.len();
+            for i in 0..count {
+                let start = patrol.points[i];
+                let end = patrol.points[(i + 1) % count];
+                if view.overlaps_segment(start, end) {
+                    dashed_gizmos.line_2d(start, end, patrol_color);
+                }
             }
         }
     }
@@ -527,40 +1020,56 @@ fn draw_selection_visuals(
 
 // Hack to fix color restore for Queen
 fn move_ants(
-    mut ant_q: Query<(&mut Velocity, &mut Transform, &mut TargetPosition, &mut Path), (With<Ant>, Without<Queen>)>,
+    mut ant_q: Query<(Entity, &mut Velocity, &mut Transform, &mut TargetPosition, &mut Path, Option<&mut Patrol>, Option<&HoldPosition>, Option<&SpeedModifier>, Option<&Energy>, Option<&Genes>, Option<&Veterancy>, &Faction, &Caste, Has<AttackTarget>), (With<Ant>, Without<Queen>)>,
+    mut path_queue: ResMut<PathQueue>,
+    layout: Res<MapLayout>,
+    stats: UnitStats,
 ) {
-    let speed = 100.0;
     let arrival_radius = 2.0;
     
-    for (mut velocity, mut transform, mut target, mut path) in ant_q.iter_mut() {
+    for (entity, mut velocity, mut transform, mut target, mut path, patrol, hold, speed_modifier, energy, genes, veterancy, faction, caste, chasing) in ant_q.iter_mut() {
+        if let Some(hold) = hold {
+            target.0 = hold.0;
+            path.waypoints.clear();
+        }
+
+        // Zones (mud, home turf, ...), hunger and genes scale the unit's speed (caste, upgrades and rank)
+        let speed = stats.speed(*caste, faction.0, veterancy)
+            * speed_modifier.map_or(1.0, |modifier| modifier.0)
+            * energy.map_or(1.0, Energy::speed_factor)
+            * genes.map_or(1.0, |genes| genes.speed);
         let delta = target.0 - transform.translation.truncate();
         let distance = delta.length();
 
         if distance > arrival_radius {
             let direction = delta.normalize();
+            // Facing follows velocity; see `ant_sprites`
             velocity.linvel = direction * speed;
-            
-             // Rotate to face direction
-            if delta.length_squared() > 0.0 {
-                 let angle = delta.y.atan2(delta.x);
-                 transform.rotation = Quat::from_rotation_z(angle);
-            }
         } else {
             // Snap to exact position to ensure centered in cell
             transform.translation.x = target.0.x;
             transform.translation.y = target.0.y;
+
+            // Patrolling units queue up the next leg of the loop once the path
+            // runs dry, unless they're off after an enemy
+            if path.waypoints.is_empty() && !path_queue.is_pending(entity) && !chasing {
+                if let Some(mut patrol) = patrol {
+                    if !patrol.points.is_empty() {
+                        let leg_end = patrol.points[patrol.next % patrol.points.len()];
+                        patrol.next = (patrol.next + 1) % patrol.points.len();
+
+                        let from = layout.0.world_to_hex(target.0);
+                        let to = layout.0.world_to_hex(leg_end);
+                        path_queue.request(entity, from, to, PathPriority::Ai);
+                    }
+                }
+            }
             
             // Check for next waypoint
             if let Some(next_pos) = path.waypoints.pop_front() {
                 target.0 = next_pos;
                 // Continue moving immediately
                 let delta = target.0 - transform.translation.truncate();
-                // Rotate to face direction
-                if delta.length_squared() > 0.0 {
-                     let angle = delta.y.atan2(delta.x);
-                     transform.rotation = Quat::from_rotation_z(angle);
-                }
-
                 let direction = delta.normalize_or_zero();
                 velocity.linvel = direction * speed;
             } else {
@@ -579,8 +1088,8 @@ fn draw_selection_box(
         let center = (start + current) / 2.0;
         let size = (start - current).abs();
         
-        // Only draw if it looks like a drag (> 5.0 distance)
-        if start.distance(current) > 5.0 {
+        // Only draw if it looks like a drag rather than a tap
+        if state.dragging {
             gizmos.rect_2d(center, 0.0, size, Color::WHITE);
         }
     }
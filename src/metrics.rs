@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+#[cfg(feature = "admin")]
+use std::fmt::Write;
+
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::net::{OutgoingMessage, PlayerId};
+use crate::pathfinding::{PathBudget, PathQueue};
+use crate::tick_loop::TickStats;
+use crate::{Ant, Faction};
+
+// Simulation metrics: frame time, tick rate and overruns, pathfinding time,
+// entity counts and bytes sent per client, gathered once per frame into `Metrics`. F3 shows them in an
+// overlay; `Metrics::prometheus` renders them for a scraper (the admin
+// endpoint serves it at /metrics).
+
+const OVERLAY_REFRESH_SECS: f32 = 0.5;
+// Weight of the newest frame in the smoothed tick time
+const SMOOTHING: f32 = 0.05;
+
+#[derive(Resource, Default)]
+pub struct Metrics {
+    pub tick_secs: f32,
+    pub tick_secs_smoothed: f32,
+    // Ticks per second actually reached, and ticks over budget so far
+    pub tick_rate: f32,
+    pub tick_overruns: u64,
+    pub pathfinding_secs: f32,
+    // Currently allowed, after cuts for overruns
+    pub pathfinding_budget_secs: f32,
+    pub path_requests_pending: usize,
+    pub entities: usize,
+    pub units_by_player: BTreeMap<PlayerId, usize>,
+    // Estimated bytes sent, per recipient; `None` is broadcasts
+    pub bytes_sent: BTreeMap<Option<PlayerId>, u64>,
+}
+
+impl Metrics {
+    // Prometheus text exposition format
+    #[cfg(feature = "admin")]
+    pub fn prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE colony_tick_seconds gauge\ncolony_tick_seconds {}", self.tick_secs);
+        let _ = writeln!(out, "# TYPE colony_tick_rate_hz gauge\ncolony_tick_rate_hz {}", self.tick_rate);
+        let _ = writeln!(out, "# TYPE colony_tick_overruns_total counter\ncolony_tick_overruns_total {}", self.tick_overruns);
+        let _ = writeln!(out, "# TYPE colony_pathfinding_seconds gauge\ncolony_pathfinding_seconds {}", self.pathfinding_secs);
+        let _ = writeln!(
+            out,
+            "# TYPE colony_pathfinding_budget_seconds gauge\ncolony_pathfinding_budget_seconds {}",
+            self.pathfinding_budget_secs
+        );
+        let _ = writeln!(out, "# TYPE colony_path_requests_pending gauge\ncolony_path_requests_pending {}", self.path_requests_pending);
+        let _ = writeln!(out, "# TYPE colony_entities gauge\ncolony_entities {}", self.entities);
+        let _ = writeln!(out, "# TYPE colony_units gauge");
+        for (player, count) in &self.units_by_player {
+            let _ = writeln!(out, "colony_units{{player=\"{}\"}} {count}", player.0);
+        }
+        let _ = writeln!(out, "# TYPE colony_net_bytes_sent_total counter");
+        for (client, bytes) in &self.bytes_sent {
+            let client = client.map_or("all".to_string(), |player| player.0.to_string());
+            let _ = writeln!(out, "colony_net_bytes_sent_total{{client=\"{client}\"}} {bytes}");
+        }
+        out
+    }
+}
+
+#[derive(Component)]
+struct MetricsOverlay;
+
+#[derive(Resource)]
+struct OverlayRefresh(Timer);
+
+fn collect_metrics(
+    time: Res<Time<Real>>,
+    tick_stats: Res<TickStats>,
+    path_queue: Res<PathQueue>,
+    path_budget: Res<PathBudget>,
+    mut outgoing: EventReader<OutgoingMessage>,
+    entity_q: Query<Entity>,
+    unit_q: Query<&Faction, With<Ant>>,
+    mut metrics: ResMut<Metrics>,
+) {
+    let tick_secs = time.delta_seconds();
+    metrics.tick_secs = tick_secs;
+    metrics.tick_secs_smoothed += (tick_secs - metrics.tick_secs_smoothed) * SMOOTHING;
+    metrics.tick_rate = tick_stats.effective_rate;
+    metrics.tick_overruns = tick_stats.overruns;
+    metrics.pathfinding_secs = path_queue.last_tick_time().as_secs_f32();
+    metrics.pathfinding_budget_secs = path_budget.micros_per_tick as f32 / 1_000_000.0;
+    metrics.path_requests_pending = path_queue.pending();
+    metrics.entities = entity_q.iter().len();
+
+    metrics.units_by_player.clear();
+    for faction in unit_q.iter() {
+        *metrics.units_by_player.entry(faction.0).or_default() += 1;
+    }
+    for sent in outgoing.read() {
+        *metrics.bytes_sent.entry(sent.to).or_default() += sent.message.approx_size() as u64;
+    }
+}
+
+fn setup_metrics_overlay(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                right: Val::Px(8.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                display: Display::None,
+                ..default()
+            })
+            .with_background_color(Color::srgba(0.1, 0.1, 0.1, 0.85)),
+        MetricsOverlay,
+    ));
+}
+
+fn toggle_metrics_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut style_q: Query<&mut Style, With<MetricsOverlay>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleMetrics) {
+        return;
+    }
+    for mut style in style_q.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn update_metrics_overlay(
+    time: Res<Time<Real>>,
+    mut refresh: ResMut<OverlayRefresh>,
+    metrics: Res<Metrics>,
+    mut overlay_q: Query<(&mut Text, &Style), With<MetricsOverlay>>,
+) {
+    if !refresh.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    for (mut text, style) in overlay_q.iter_mut() {
+        if style.display == Display::None {
+            continue;
+        }
+        let units: usize = metrics.units_by_player.values().sum();
+        let bytes: u64 = metrics.bytes_sent.values().sum();
+        text.sections[0].value = format!(
+            "tick {:.1} ms ({:.0}/s, {} overruns)\npathfinding {:.2}/{:.2} ms ({} queued)\nentities {}\nunits {} ({} players)\nsent {:.1} KiB",
+            metrics.tick_secs_smoothed * 1000.0,
+            metrics.tick_rate,
+            metrics.tick_overruns,
+            metrics.pathfinding_secs * 1000.0,
+            metrics.pathfinding_budget_secs * 1000.0,
+            metrics.path_requests_pending,
+            metrics.entities,
+            units,
+            metrics.units_by_player.len(),
+            bytes as f32 / 1024.0,
+        );
+    }
+}
+
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Metrics>()
+            .insert_resource(OverlayRefresh(Timer::from_seconds(OVERLAY_REFRESH_SECS, TimerMode::Repeating)))
+            .register_action(Action::ToggleMetrics, &[KeyCode::F3])
+            .add_systems(Startup, setup_metrics_overlay)
+            .add_systems(Update, (toggle_metrics_overlay, update_metrics_overlay))
+            .add_systems(Last, collect_metrics);
+    }
+}
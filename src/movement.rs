@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::interactions::Engaged;
+use crate::Selected;
+
+// Which physics body drives a mobile unit. A dynamic body is pushed back by
+// rock, structures and the map edge and eased by damping, which costs a
+// solver step per ant and makes crowds jitter. A kinematic one moves exactly
+// at the velocity `move_ants` gives it and only touches other colliders
+// through sensors, so it is far cheaper for thousands of ants; pathfinding
+// already keeps them off rock. `Auto` stays kinematic until the unit starts
+// fighting and is dynamic while it does. Each caste picks a mode in the unit
+// definitions, `movement <mode>` changes it for the selected units, and
+// `--movement <mode>` overrides it for every unit, which together with
+// `--stress` compares the two.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum MovementMode {
+    #[default]
+    Dynamic,
+    Kinematic,
+    Auto,
+}
+
+impl MovementMode {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dynamic" => Some(Self::Dynamic),
+            "kinematic" => Some(Self::Kinematic),
+            "auto" => Some(Self::Auto),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Movement(pub MovementMode);
+
+// `--movement`, for every unit regardless of its own mode
+#[derive(Resource)]
+struct MovementOverride(Option<MovementMode>);
+
+impl MovementOverride {
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let Some(name) = args.windows(2).find(|pair| pair[0] == "--movement").map(|pair| &pair[1]) else {
+            return Self(None);
+        };
+        let mode = MovementMode::from_name(name);
+        if mode.is_none() {
+            warn!("--movement {name}: use dynamic, kinematic or auto");
+        }
+        Self(mode)
+    }
+}
+
+// Kinematic bodies don't meet fixed or other kinematic ones by default, and
+// food and engagement sensors are both
+fn enable_kinematic_sensing(mut commands: Commands, new_units: Query<Entity, Added<Movement>>) {
+    for entity in new_units.iter() {
+        commands.entity(entity).insert(
+            ActiveCollisionTypes::default()
+                | ActiveCollisionTypes::KINEMATIC_KINEMATIC
+                | ActiveCollisionTypes::KINEMATIC_STATIC,
+        );
+    }
+}
+
+fn sync_bodies(
+    movement_override: Res<MovementOverride>,
+    mut unit_q: Query<(&Movement, &mut RigidBody, Has<Engaged>)>,
+) {
+    for (movement, mut body, engaged) in unit_q.iter_mut() {
+        let wanted = match movement_override.0.unwrap_or(movement.0) {
+            MovementMode::Dynamic => RigidBody::Dynamic,
+            MovementMode::Kinematic => RigidBody::KinematicVelocityBased,
+            MovementMode::Auto if engaged => RigidBody::Dynamic,
+            MovementMode::Auto => RigidBody::KinematicVelocityBased,
+        };
+        if *body != wanted {
+            *body = wanted;
+        }
+    }
+}
+
+// `movement kinematic` switches the selected units to kinematic bodies
+fn movement_command(In(args): In<Vec<String>>, mut selected_q: Query<&mut Movement, With<Selected>>) -> CommandResult {
+    let usage = || "usage: movement <dynamic|kinematic|auto>".to_string();
+    let mode = args.first().and_then(|name| MovementMode::from_name(name)).ok_or_else(usage)?;
+    let mut count = 0;
+    for mut movement in selected_q.iter_mut() {
+        movement.0 = mode;
+        count += 1;
+    }
+    if count == 0 {
+        return Err("no mobile units selected".to_string());
+    }
+    Ok(format!("{count} units now use {mode:?} movement"))
+}
+
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MovementOverride::from_args())
+            .register_console_command(
+                "movement",
+                "movement <dynamic|kinematic|auto>: physics body of the selected units",
+                movement_command,
+            )
+            .add_systems(Update, (enable_kinematic_sensing, sync_bodies).chain().after(crate::move_ants));
+    }
+}
@@ -0,0 +1,138 @@
+use bevy::audio::{AudioSinkPlayback, AudioSource, Volume};
+use bevy::prelude::*;
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::coords::HexLayoutExt;
+use crate::interactions::Engaged;
+use crate::net::LocalPlayer;
+use crate::predators::Predator;
+use crate::settings::Settings;
+use crate::spatial::SpatialIndex;
+use crate::synth::{note, synthesize, wav_bytes, Note};
+use crate::{Ant, Faction, MapLayout};
+
+// Background music and the volume mixer. Two looping tracks, calm and combat,
+// play all the time and are crossfaded: combat fades in while a predator is
+// close to one of our ants or one of our ants is fighting, or being fought by,
+// another ant, and back out a few seconds after the last contact. Music and effect volumes come from the `audio` section of
+// `settings.ron`, and `volume music|sfx <0-1>` changes them while playing.
+// Fades run on real time, so pausing the game doesn't freeze the music.
+
+// Hexes between a predator and one of our ants that count as a fight
+const COMBAT_RANGE: u32 = 2;
+// Combat music keeps playing this long after the last contact
+const COMBAT_LINGER_SECS: f32 = 6.0;
+const CROSSFADE_SECS: f32 = 2.0;
+// Music sits under the effects even at full volume
+const MUSIC_LEVEL: f32 = 0.25;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum MusicTrack {
+    Calm,
+    Combat,
+}
+
+// How far the crossfade is towards the combat track, 0..1
+#[derive(Resource, Default)]
+struct MusicMix {
+    combat: f32,
+    // Real time left before combat music starts fading out
+    linger: f32,
+}
+
+// Slow arpeggios over A minor and F major
+fn calm_notes() -> Vec<Note> {
+    [220.0, 261.6, 329.6, 261.6, 174.6, 220.0, 261.6, 220.0]
+        .into_iter()
+        .cycle()
+        .take(32)
+        .map(|hz| note(hz, hz, 0.5))
+        .collect()
+}
+
+// Driving bass line with a noise hit on every off-beat
+fn combat_notes() -> Vec<Note> {
+    [110.0, 110.0, 130.8, 98.0]
+        .into_iter()
+        .cycle()
+        .take(32)
+        .flat_map(|hz| [note(hz, hz, 0.18), note(0.0, 0.0, 0.07)])
+        .collect()
+}
+
+fn setup_music(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    for (track, notes) in [(MusicTrack::Calm, calm_notes()), (MusicTrack::Combat, combat_notes())] {
+        let bytes = wav_bytes(&synthesize(&notes));
+        commands.spawn((
+            AudioBundle {
+                source: sources.add(AudioSource { bytes: bytes.into() }),
+                // Silent until `apply_music_volume` mixes it in
+                settings: PlaybackSettings::LOOP.with_volume(Volume::ZERO),
+            },
+            track,
+        ));
+    }
+}
+
+fn update_music_mix(
+    time: Res<Time<Real>>,
+    layout: Res<MapLayout>,
+    local_player: Res<LocalPlayer>,
+    spatial_index: Res<SpatialIndex>,
+    predator_q: Query<&Transform, With<Predator>>,
+    ant_q: Query<&Faction, With<Ant>>,
+    engaged_q: Query<(&Engaged, &Faction)>,
+    mut mix: ResMut<MusicMix>,
+) {
+    let ours = |entity: Entity| ant_q.get(entity).is_ok_and(|faction| faction.0 == local_player.0);
+    let hunted = predator_q.iter().any(|transform| {
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        spatial_index.within(hex, COMBAT_RANGE).any(ours)
+    });
+    let engaged = engaged_q.iter().any(|(engaged, faction)| faction.0 == local_player.0 || ours(engaged.0));
+    let fighting = hunted || engaged;
+    let dt = time.delta_seconds();
+    mix.linger = if fighting { COMBAT_LINGER_SECS } else { (mix.linger - dt).max(0.0) };
+    let target = if mix.linger > 0.0 { 1.0 } else { 0.0 };
+    let step = dt / CROSSFADE_SECS;
+    mix.combat = if mix.combat < target { (mix.combat + step).min(target) } else { (mix.combat - step).max(target) };
+}
+
+fn apply_music_volume(settings: Res<Settings>, mix: Res<MusicMix>, sink_q: Query<(&MusicTrack, &AudioSink)>) {
+    for (track, sink) in sink_q.iter() {
+        let share = match track {
+            MusicTrack::Calm => 1.0 - mix.combat,
+            MusicTrack::Combat => mix.combat,
+        };
+        // Equal-power crossfade: no dip in loudness halfway through
+        sink.set_volume(share.sqrt() * settings.audio.music_volume * MUSIC_LEVEL);
+    }
+}
+
+fn volume_command(In(args): In<Vec<String>>, mut settings: ResMut<Settings>) -> CommandResult {
+    let [channel, value] = args.as_slice() else {
+        return Err("usage: volume music|sfx <0-1>".to_string());
+    };
+    let volume: f32 = value.parse().map_err(|_| format!("not a number: {value}"))?;
+    let volume = volume.clamp(0.0, 1.0);
+    match channel.as_str() {
+        "music" => settings.audio.music_volume = volume,
+        "sfx" => settings.audio.sfx_volume = volume,
+        _ => return Err(format!("no {channel} channel; use music or sfx")),
+    }
+    Ok(format!("{channel} volume {volume}"))
+}
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicMix>()
+            .register_console_command("volume", "volume music|sfx <0-1>: set a mixer channel", volume_command)
+            .add_systems(Startup, setup_music)
+            .add_systems(
+                Update,
+                (update_music_mix.run_if(in_state(crate::loading::AppState::InGame)), apply_music_volume).chain(),
+            );
+    }
+}
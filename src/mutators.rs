@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+
+use crate::determinism::Fnv1a;
+use crate::units::UnitDefinition;
+use crate::Caste;
+
+// Per-match mutators, toggled in the lobby. Each one is a data override laid
+// on top of the shipped definitions as the match starts: unit stats from
+// `units.ron`, the size of the food piles and the predators' stats. Nothing
+// is stored anywhere else, so a match with no mutators plays exactly as the
+// files say. `rules_checksum` fingerprints the definitions under every
+// mutator; a client sends it when joining a lobby and the host only seats
+// clients whose checksum matches its own, so everyone runs the same rules.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mutator {
+    DoubleSpeedAnts,
+    NoFog,
+    ScarceFood,
+    GiantPredators,
+    FragileQueens,
+}
+
+impl Mutator {
+    pub const ALL: [Mutator; 5] = [
+        Mutator::DoubleSpeedAnts,
+        Mutator::NoFog,
+        Mutator::ScarceFood,
+        Mutator::GiantPredators,
+        Mutator::FragileQueens,
+    ];
+
+    pub fn name_key(self) -> &'static str {
+        match self {
+            Mutator::DoubleSpeedAnts => "mutator.double-speed",
+            Mutator::NoFog => "mutator.no-fog",
+            Mutator::ScarceFood => "mutator.scarce-food",
+            Mutator::GiantPredators => "mutator.giant-predators",
+            Mutator::FragileQueens => "mutator.fragile-queens",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+// The mutators a match plays with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mutators(u8);
+
+impl Mutators {
+    pub fn contains(self, mutator: Mutator) -> bool {
+        self.0 & mutator.bit() != 0
+    }
+
+    pub fn toggle(&mut self, mutator: Mutator) {
+        self.0 ^= mutator.bit();
+    }
+
+    // A unit definition as this match plays it
+    pub fn apply(self, definition: &mut UnitDefinition) {
+        if self.contains(Mutator::DoubleSpeedAnts) {
+            definition.speed *= 2.0;
+        }
+        if self.contains(Mutator::FragileQueens) && definition.caste == Caste::Queen {
+            definition.health *= 0.25;
+        }
+    }
+
+    pub fn fog(self) -> bool {
+        !self.contains(Mutator::NoFog)
+    }
+
+    // Scales the food in every pile placed on the map
+    pub fn food_multiplier(self) -> f32 {
+        if self.contains(Mutator::ScarceFood) {
+            0.4
+        } else {
+            1.0
+        }
+    }
+
+    // Scales predators' size, health and bite
+    pub fn predator_scale(self) -> f32 {
+        if self.contains(Mutator::GiantPredators) {
+            2.0
+        } else {
+            1.0
+        }
+    }
+}
+
+// Fingerprint of the shipped definitions and of what each mutator makes of
+// them, in caste order so both sides hash the same sequence
+pub fn rules_checksum(definitions: &[UnitDefinition]) -> u64 {
+    let mut definitions = definitions.to_vec();
+    definitions.sort_by_key(|definition| definition.caste as u8);
+    let mut hash = Fnv1a::default();
+    for mutators in std::iter::once(Mutators::default()).chain(Mutator::ALL.map(|mutator| {
+        let mut mutators = Mutators::default();
+        mutators.toggle(mutator);
+        mutators
+    })) {
+        for definition in &definitions {
+            let mut definition = definition.clone();
+            mutators.apply(&mut definition);
+            hash.write(definition.caste as u64);
+            for value in [definition.size, definition.speed, definition.collider_radius, definition.damping] {
+                hash.write(value.to_bits().into());
+            }
+            for value in [definition.health, definition.bite, definition.lifespan.unwrap_or(-1.0)] {
+                hash.write(value.to_bits().into());
+            }
+            hash.write(u64::from(definition.immobile) | u64::from(definition.can_dig_in) << 1);
+        }
+        hash.write(mutators.food_multiplier().to_bits().into());
+        hash.write(mutators.predator_scale().to_bits().into());
+        hash.write(mutators.fog().into());
+    }
+    hash.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::MovementMode;
+
+    fn definition(caste: Caste, speed: f32, health: f32) -> UnitDefinition {
+        UnitDefinition {
+            caste,
+            size: 10.0,
+            color: "8B4513".to_string(),
+            speed,
+            collider_radius: 5.0,
+            damping: 20.0,
+            health,
+            bite: 4.0,
+            immobile: false,
+            movement: MovementMode::default(),
+            lifespan: None,
+            can_dig_in: false,
+        }
+    }
+
+    #[test]
+    fn no_mutators_leave_the_definitions_alone() {
+        let mut worker = definition(Caste::Worker, 100.0, 50.0);
+        Mutators::default().apply(&mut worker);
+        assert_eq!((worker.speed, worker.health), (100.0, 50.0));
+        assert!(Mutators::default().fog());
+        assert_eq!(Mutators::default().food_multiplier(), 1.0);
+    }
+
+    #[test]
+    fn toggling_twice_turns_a_mutator_off() {
+        let mut mutators = Mutators::default();
+        mutators.toggle(Mutator::ScarceFood);
+        assert!(mutators.contains(Mutator::ScarceFood));
+        assert!(!mutators.contains(Mutator::NoFog));
+        mutators.toggle(Mutator::ScarceFood);
+        assert_eq!(mutators, Mutators::default());
+    }
+
+    #[test]
+    fn fragile_queens_only_weaken_queens() {
+        let mut mutators = Mutators::default();
+        mutators.toggle(Mutator::FragileQueens);
+        let mut queen = definition(Caste::Queen, 0.0, 200.0);
+        let mut soldier = definition(Caste::Soldier, 80.0, 120.0);
+        mutators.apply(&mut queen);
+        mutators.apply(&mut soldier);
+        assert_eq!(queen.health, 50.0);
+        assert_eq!(soldier.health, 120.0);
+    }
+
+    #[test]
+    fn double_speed_doubles_every_caste() {
+        let mut mutators = Mutators::default();
+        mutators.toggle(Mutator::DoubleSpeedAnts);
+        let mut worker = definition(Caste::Worker, 100.0, 50.0);
+        mutators.apply(&mut worker);
+        assert_eq!(worker.speed, 200.0);
+    }
+
+    #[test]
+    fn checksum_ignores_file_order_but_not_stats() {
+        let units = [definition(Caste::Worker, 100.0, 50.0), definition(Caste::Queen, 0.0, 200.0)];
+        let reversed = [units[1].clone(), units[0].clone()];
+        assert_eq!(rules_checksum(&units), rules_checksum(&reversed));
+
+        let buffed = [definition(Caste::Worker, 101.0, 50.0), units[1].clone()];
+        assert_ne!(rules_checksum(&units), rules_checksum(&buffed));
+    }
+}
@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+
+use crate::diplomacy::{Offer, Pact};
+use crate::lobby::MatchSettings;
+use crate::objectives::Objective;
+use crate::Caste;
+
+#[cfg(feature = "webrtc")]
+pub mod webrtc;
+
+// Network message layer. Gameplay systems only ever send `OutgoingMessage` and
+// read `IncomingMessage`; a transport plugin carries them between players
+// without them knowing. Bots are pumped in-process (see `bots`), and the
+// `webrtc` feature adds a WebRTC data channel transport for real players (see
+// `webrtc`). A transport asks `NetConnection::channel_for` which of its
+// channels each message goes on, so browser clients get position updates over
+// a loss-tolerant channel while orders stay on the ordered one.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PlayerId(pub u32);
+
+// The player controlling this client
+#[derive(Resource, Default)]
+pub struct LocalPlayer(pub PlayerId);
+
+// The player hosting the match, who is the authority for everyone connected
+pub const HOST: PlayerId = PlayerId(0);
+
+// Session token the hosting page signed the player in with (see `web`); the
+// transport presents it to the server when connecting
+#[cfg(any(feature = "webrtc", target_arch = "wasm32"))]
+#[derive(Resource, Default)]
+pub struct PlayerToken(pub Option<String>);
+
+// Set by the transport while connected to a server; offline, the client is
+// the authority on everything
+#[derive(Resource, Default)]
+pub struct NetConnection {
+    pub online: bool,
+    // Whether the transport has an unreliable channel open next to the
+    // reliable one (a WebRTC data channel with retransmits and ordering off,
+    // or WebTransport datagrams). Without one everything goes reliable.
+    #[cfg(feature = "webrtc")]
+    pub unreliable_channel: bool,
+    // Players connected to us through the transport while we host
+    pub peers: Vec<PlayerId>,
+}
+
+#[cfg(feature = "webrtc")]
+impl NetConnection {
+    // The channel a transport should send `message` on
+    pub fn channel_for(&self, message: &NetMessage) -> Delivery {
+        if self.unreliable_channel {
+            message.delivery()
+        } else {
+            Delivery::Reliable
+        }
+    }
+}
+
+// How a message has to arrive. Orders, acks, chunks and objectives must get
+// there, once and in order. Position streams are replaced by the next update
+// anyway, so on an unreliable channel a lost packet costs nothing, instead of
+// holding back everything behind it as it does on a TCP WebSocket. Those
+// messages carry a tick so a late one that arrives after a newer one is dropped.
+#[cfg(feature = "webrtc")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    Reliable,
+    Unreliable,
+}
+
+// Whether `tick` is newer than the last one applied; ticks wrap around
+pub fn tick_is_newer(tick: u32, last: Option<u32>) -> bool {
+    last.is_none_or(|last| (tick.wrapping_sub(last) as i32) > 0)
+}
+
+// One unit as the server reports it to clients
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UnitState {
+    // Entity bits, the same ids group orders use
+    pub id: u64,
+    pub player: PlayerId,
+    pub caste: Caste,
+    pub position: Vec2,
+    pub moving: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum NetMessage {
+    // Lightweight camera snapshot, streamed while "share my view" is on
+    CameraState { tick: u32, position: Vec2, scale: f32 },
+    // Sent when "share my view" is turned off, so followers let go right away
+    CameraShareStopped,
+    // Ask for the current contents of these terrain chunks
+    SubscribeChunks { chunks: Vec<Hex> },
+    // Complete, compressed contents of one terrain chunk (see `terrain_sync`)
+    TerrainChunk { chunk: Hex, data: Vec<u8> },
+    // Today's objectives for the receiving player; replaces the previous set
+    DailyObjectives { day: u32, objectives: Vec<Objective> },
+    // Client's progress on one of today's objectives (by index)
+    ObjectiveProgress { day: u32, index: usize, progress: u32 },
+    // Server confirms an objective is complete; the client applies its reward
+    ObjectiveReward { day: u32, index: usize },
+    // Move order for a group of units (entity bits, in squad order)
+    GroupOrder { order: u32, units: Vec<u64>, destination: Vec2 },
+    // Server's verdict on a group order, one flag per squad
+    OrderAck { order: u32, squads: Vec<bool> },
+    // Every unit in the world, sent periodically by the authority
+    UnitSnapshot { tick: u32, units: Vec<UnitState> },
+    // Diplomacy (see `diplomacy`). A player proposes to the authority...
+    Propose { to: PlayerId, offer: Offer },
+    // ...which passes it on to the player it's for...
+    Proposal { proposal: u32, from: PlayerId, offer: Offer },
+    // ...who answers the authority...
+    Answer { proposal: u32, accept: bool },
+    // ...which tells the proposer how it went
+    ProposalAnswered { by: PlayerId, offer: Offer, accept: bool },
+    // Broadcast by the authority whenever a pact is made or ended
+    PactChanged { a: PlayerId, b: PlayerId, pact: Option<Pact> },
+    // Broadcast by the authority when traded food sets off
+    FoodShipped { from: PlayerId, to: PlayerId, food: u32 },
+    // "Look here": a hex pinged for allies (see `alerts`)
+    Ping { hex: Hex },
+    // Pre-game lobby (see `lobby`). A client asks the host for a seat,
+    // with the checksum of its rules (see `mutators`)...
+    JoinLobby { rules: u64 },
+    // ...which is refused if they differ from the host's...
+    LobbyRefused,
+    // ...and tells it whether it's ready to start...
+    SetReady { ready: bool },
+    // ...while the host sends the settings and who has joined whenever they change...
+    LobbyState { settings: MatchSettings, guests: Vec<(PlayerId, bool)> },
+    // ...and finally starts everyone on the same settings
+    StartMatch { settings: MatchSettings },
+}
+
+impl NetMessage {
+    #[cfg(feature = "webrtc")]
+    pub fn delivery(&self) -> Delivery {
+        match self {
+            NetMessage::CameraState { .. } | NetMessage::UnitSnapshot { .. } => Delivery::Unreliable,
+            _ => Delivery::Reliable,
+        }
+    }
+
+    // Rough encoded size in bytes (one tag byte plus the payload), for
+    // bandwidth accounting whichever transport does the real encoding
+    pub fn approx_size(&self) -> usize {
+        let payload = match self {
+            NetMessage::CameraState { .. } => 4 + 12,
+            NetMessage::CameraShareStopped => 0,
+            NetMessage::SubscribeChunks { chunks } => 4 + chunks.len() * 8,
+            NetMessage::TerrainChunk { data, .. } => 8 + 4 + data.len(),
+            NetMessage::DailyObjectives { objectives, .. } => 4 + 4 + objectives.len() * std::mem::size_of::<Objective>(),
+            NetMessage::ObjectiveProgress { .. } => 4 + 8 + 4,
+            NetMessage::ObjectiveReward { .. } => 4 + 8,
+            NetMessage::GroupOrder { units, .. } => 4 + 4 + units.len() * 8 + 8,
+            NetMessage::OrderAck { squads, .. } => 4 + 4 + squads.len().div_ceil(8),
+            NetMessage::UnitSnapshot { units, .. } => 4 + 4 + units.len() * std::mem::size_of::<UnitState>(),
+            NetMessage::Propose { .. } => 4 + 5,
+            NetMessage::Proposal { .. } => 4 + 4 + 5,
+            NetMessage::Answer { .. } => 4 + 1,
+            NetMessage::ProposalAnswered { .. } => 4 + 5 + 1,
+            NetMessage::PactChanged { .. } => 4 + 4 + 2,
+            NetMessage::FoodShipped { .. } => 4 + 4 + 4,
+            NetMessage::Ping { .. } => 8,
+            NetMessage::JoinLobby { .. } => 8,
+            NetMessage::LobbyRefused => 0,
+            NetMessage::SetReady { .. } => 1,
+            NetMessage::LobbyState { guests, .. } => {
+                std::mem::size_of::<MatchSettings>() + 4 + guests.len() * 5
+            }
+            NetMessage::StartMatch { .. } => std::mem::size_of::<MatchSettings>(),
+        };
+        1 + payload
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct OutgoingMessage {
+    // `None` broadcasts to every connected player
+    pub to: Option<PlayerId>,
+    pub message: NetMessage,
+}
+
+impl OutgoingMessage {
+    pub fn broadcast(message: NetMessage) -> Self {
+        Self { to: None, message }
+    }
+
+    pub fn to(player: PlayerId, message: NetMessage) -> Self {
+        Self { to: Some(player), message }
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct IncomingMessage {
+    pub from: PlayerId,
+    pub message: NetMessage,
+}
+
+// Hosting: asks the transport to drop a player connected through it. Only the
+// WebRTC transport has players to drop.
+#[derive(Event, Clone, Copy, Debug)]
+#[cfg_attr(not(feature = "webrtc"), allow(dead_code))]
+pub struct KickPlayer(pub PlayerId);
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LocalPlayer>()
+            .init_resource::<NetConnection>()
+            .add_event::<OutgoingMessage>()
+            .add_event::<IncomingMessage>()
+            .add_event::<KickPlayer>();
+        #[cfg(any(feature = "webrtc", target_arch = "wasm32"))]
+        app.init_resource::<PlayerToken>();
+    }
+}
@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use matchbox_socket::{MultipleChannels, PeerId, PeerState, WebRtcSocket};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{
+    Delivery, IncomingMessage, KickPlayer, LocalPlayer, NetConnection, NetMessage, OutgoingMessage, PlayerId,
+    PlayerToken, HOST,
+};
+
+// WebRTC transport (`webrtc` feature). Players meet in a room on a matchbox
+// signaling server: `--host-room <url>` hosts the match there and
+// `--join-room <url>` joins it, e.g. `--join-room ws://localhost:3536/ants`.
+// In the browser the same goes in the page address, `?join-room=<url>`.
+//
+// Every peer gets two data channels: an ordered, reliable one and one with
+// ordering and retransmits off, and each message goes on the one
+// `NetConnection::channel_for` picks. The host is the authority. It numbers
+// the players who join, and clients only ever talk to it; messages between
+// clients (a shared camera, a ping) are passed along by the host. A kicked
+// client is told so and forgotten: the host ignores anything else it sends.
+
+const RELIABLE: usize = 0;
+const UNRELIABLE: usize = 1;
+// Players who join over the network are numbered from here, after the host;
+// bots start at 1000
+const FIRST_REMOTE_PLAYER: u32 = HOST.0 + 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Host,
+    Client,
+}
+
+// What goes over a data channel, bincode encoded
+#[derive(Serialize, Deserialize)]
+enum Frame {
+    // Host to a peer that just connected: the player it plays as
+    Welcome { player: PlayerId },
+    // Client to host once welcomed, with the token the page signed it in with
+    Hello { token: Option<String> },
+    // A game message. The host fills in `from` itself for what it passes on,
+    // so a client can't speak for another player.
+    Message { from: PlayerId, to: Option<PlayerId>, message: NetMessage },
+    // Host to a peer it no longer plays with
+    Kicked,
+}
+
+#[derive(Resource)]
+struct Transport {
+    socket: WebRtcSocket<MultipleChannels>,
+    role: Role,
+    // Hosting: the player each connected peer plays as
+    players: HashMap<PeerId, PlayerId>,
+    next_player: u32,
+    // Joining: the host, once it has welcomed us
+    host: Option<PeerId>,
+    // Joining: what was sent before the host welcomed us
+    outbox: Vec<OutgoingMessage>,
+}
+
+impl Transport {
+    fn send(&mut self, peer: PeerId, frame: &Frame, delivery: Delivery) {
+        let packet = match bincode::serialize(frame) {
+            Ok(packet) => packet.into_boxed_slice(),
+            Err(err) => {
+                warn!("Could not encode a network message: {err}");
+                return;
+            }
+        };
+        let channel = match delivery {
+            Delivery::Reliable => RELIABLE,
+            Delivery::Unreliable => UNRELIABLE,
+        };
+        self.socket.channel_mut(channel).send(packet, peer);
+    }
+
+    fn peer_of(&self, player: PlayerId) -> Option<PeerId> {
+        self.players.iter().find(|&(_, &p)| p == player).map(|(&peer, _)| peer)
+    }
+}
+
+// Room to host or join, from the command line or, on the web, the page address
+#[cfg(not(target_arch = "wasm32"))]
+fn room() -> Option<(Role, String)> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2).find_map(|pair| match pair[0].as_str() {
+        "--host-room" => Some((Role::Host, pair[1].clone())),
+        "--join-room" => Some((Role::Client, pair[1].clone())),
+        _ => None,
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn room() -> Option<(Role, String)> {
+    let search = web_sys::window()?.location().search().ok()?;
+    let params = web_sys::UrlSearchParams::new_with_str(&search).ok()?;
+    params
+        .get("host-room")
+        .map(|url| (Role::Host, url))
+        .or_else(|| params.get("join-room").map(|url| (Role::Client, url)))
+}
+
+fn open_transport(mut commands: Commands, mut connection: ResMut<NetConnection>) {
+    let Some((role, url)) = room() else {
+        return;
+    };
+    let (socket, message_loop) = WebRtcSocket::builder(url.as_str())
+        .add_reliable_channel()
+        .add_unreliable_channel()
+        .build();
+    IoTaskPool::get()
+        .spawn(async move {
+            if let Err(err) = message_loop.await {
+                error!("Lost the signaling server: {err}");
+            }
+        })
+        .detach();
+
+    info!("{} room {url}", if role == Role::Host { "Hosting" } else { "Joining" });
+    // A client is online from the start, so the lobby waits for the host
+    // instead of hosting one of its own
+    connection.online = role == Role::Client;
+    connection.unreliable_channel = true;
+    commands.insert_resource(Transport {
+        socket,
+        role,
+        players: HashMap::new(),
+        next_player: FIRST_REMOTE_PLAYER,
+        host: None,
+        outbox: Vec::new(),
+    });
+}
+
+fn receive_frames(
+    mut transport: ResMut<Transport>,
+    mut connection: ResMut<NetConnection>,
+    mut local_player: ResMut<LocalPlayer>,
+    token: Res<PlayerToken>,
+    mut incoming: EventWriter<IncomingMessage>,
+) {
+    let transport = &mut *transport;
+    for (peer, state) in transport.socket.update_peers() {
+        match (transport.role, state) {
+            (Role::Host, PeerState::Connected) => {
+                let player = PlayerId(transport.next_player);
+                transport.next_player += 1;
+                transport.players.insert(peer, player);
+                transport.send(peer, &Frame::Welcome { player }, Delivery::Reliable);
+                info!("Player {} connected", player.0);
+            }
+            (Role::Host, PeerState::Disconnected) => {
+                if let Some(player) = transport.players.remove(&peer) {
+                    info!("Player {} disconnected", player.0);
+                }
+            }
+            (Role::Client, PeerState::Disconnected) if transport.host == Some(peer) => {
+                warn!("Lost the connection to the host");
+                transport.host = None;
+            }
+            // Clients are connected to each other too, but never use it
+            (Role::Client, _) => {}
+        }
+    }
+    connection.peers = transport.players.values().copied().collect();
+    connection.peers.sort();
+
+    let mut received = transport.socket.channel_mut(RELIABLE).receive();
+    received.extend(transport.socket.channel_mut(UNRELIABLE).receive());
+    for (peer, packet) in received {
+        let frame = match bincode::deserialize::<Frame>(&packet) {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!("Dropping an unreadable network message: {err}");
+                continue;
+            }
+        };
+        match (transport.role, frame) {
+            (Role::Client, Frame::Welcome { player }) => {
+                info!("Joined as player {}", player.0);
+                transport.host = Some(peer);
+                local_player.0 = player;
+                let hello = Frame::Hello { token: token.0.clone() };
+                transport.send(peer, &hello, Delivery::Reliable);
+                for queued in std::mem::take(&mut transport.outbox) {
+                    send_to_host(transport, &connection, queued);
+                }
+            }
+            (Role::Client, Frame::Message { from, message, .. }) if transport.host == Some(peer) => {
+                incoming.send(IncomingMessage { from, message });
+            }
+            (Role::Client, Frame::Kicked) if transport.host == Some(peer) => {
+                warn!("The host kicked us");
+                transport.host = None;
+            }
+            (Role::Host, Frame::Hello { token }) => {
+                // Nothing checks tokens yet; it is only noted
+                if let Some(&player) = transport.players.get(&peer) {
+                    info!("Player {} signed in: {}", player.0, token.is_some());
+                }
+            }
+            (Role::Host, Frame::Message { to, message, .. }) => {
+                let Some(&from) = transport.players.get(&peer) else {
+                    continue;
+                };
+                let delivery = connection.channel_for(&message);
+                // Pass it on to the other clients it is for
+                let others: Vec<PeerId> = match to {
+                    None => transport.players.keys().filter(|&&other| other != peer).copied().collect(),
+                    Some(to) => transport.peer_of(to).into_iter().collect(),
+                };
+                let relayed = Frame::Message { from, to, message };
+                for other in others {
+                    transport.send(other, &relayed, delivery);
+                }
+                let Frame::Message { message, .. } = relayed else {
+                    unreachable!();
+                };
+                if to.is_none_or(|to| to == local_player.0) {
+                    incoming.send(IncomingMessage { from, message });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn send_to_host(transport: &mut Transport, connection: &NetConnection, outgoing: OutgoingMessage) {
+    let Some(host) = transport.host else {
+        transport.outbox.push(outgoing);
+        return;
+    };
+    let delivery = connection.channel_for(&outgoing.message);
+    let frame = Frame::Message { from: PlayerId::default(), to: outgoing.to, message: outgoing.message };
+    transport.send(host, &frame, delivery);
+}
+
+fn send_frames(
+    mut transport: ResMut<Transport>,
+    connection: Res<NetConnection>,
+    local_player: Res<LocalPlayer>,
+    mut outgoing: EventReader<OutgoingMessage>,
+) {
+    for message in outgoing.read() {
+        match transport.role {
+            Role::Client => send_to_host(&mut transport, &connection, message.clone()),
+            Role::Host => {
+                // Players who aren't ours to reach (bots) are left to their own transport
+                let peers: Vec<PeerId> = match message.to {
+                    None => transport.players.keys().copied().collect(),
+                    Some(to) => transport.peer_of(to).into_iter().collect(),
+                };
+                let delivery = connection.channel_for(&message.message);
+                let frame = Frame::Message { from: local_player.0, to: message.to, message: message.message.clone() };
+                for peer in peers {
+                    transport.send(peer, &frame, delivery);
+                }
+            }
+        }
+    }
+}
+
+fn kick_players(mut transport: ResMut<Transport>, mut kicks: EventReader<KickPlayer>) {
+    for &KickPlayer(player) in kicks.read() {
+        let Some(peer) = transport.peer_of(player) else {
+            continue;
+        };
+        transport.send(peer, &Frame::Kicked, Delivery::Reliable);
+        transport.players.remove(&peer);
+        info!("Player {} kicked", player.0);
+    }
+}
+
+pub struct WebRtcPlugin;
+
+impl Plugin for WebRtcPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, open_transport)
+            .add_systems(PreUpdate, receive_frames.run_if(resource_exists::<Transport>))
+            .add_systems(PostUpdate, (kick_players, send_frames).chain().run_if(resource_exists::<Transport>));
+    }
+}
@@ -0,0 +1,311 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::burrow::BurrowNetwork;
+use crate::interactions::FoodStores;
+use crate::locale::Locale;
+use crate::net::{IncomingMessage, LocalPlayer, NetConnection, NetMessage, OutgoingMessage, PlayerId, HOST};
+
+// Daily objectives handed out by the authority (the server, or this client
+// offline): short goals like "gather 30 food today". The authority owns the
+// rotation, keeps each player's progress and grants the rewards; the client
+// shows the current set, counts its own progress and reports it to the
+// authority alone. A new set comes with each day by the wall clock, the same
+// for every player.
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+// Cosmetic rewards, handed out in turn
+const COSMETICS: [&str; 3] = ["golden-mandibles", "striped-abdomen", "crimson-antennae"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ObjectiveKind {
+    GatherFood,
+    DigHexes,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ObjectiveReward {
+    FoodBonus(u32),
+    Cosmetic(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Objective {
+    pub kind: ObjectiveKind,
+    pub target: u32,
+    pub reward: ObjectiveReward,
+}
+
+struct TrackedObjective {
+    objective: Objective,
+    progress: u32,
+    rewarded: bool,
+}
+
+#[derive(Resource, Default)]
+struct DailyObjectives {
+    day: u32,
+    objectives: Vec<TrackedObjective>,
+    cosmetics: Vec<String>,
+    // Food added by rewards since the last progress update, which mustn't
+    // count as gathered
+    bonus_food: u32,
+}
+
+// Authority side: today's set, and how far each player has got with it
+#[derive(Resource, Default)]
+struct ObjectiveServer {
+    day: Option<u32>,
+    objectives: Vec<Objective>,
+    // Reported progress on each objective, and whether its reward went out
+    players: HashMap<PlayerId, Vec<(u32, bool)>>,
+    // Connected players when the set was last sent, to send it to newcomers
+    peers: Vec<PlayerId>,
+    // Our own progress reports, when we're the authority
+    local_reports: Vec<(u32, usize, u32)>,
+}
+
+// Last seen totals, so progress only counts what happened after they arrived
+#[derive(Default)]
+struct ProgressBaseline {
+    food: Option<u32>,
+    dug: Option<usize>,
+}
+
+// Days since the Unix epoch, by the wall clock
+fn today() -> u32 {
+    #[cfg(not(target_arch = "wasm32"))]
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    #[cfg(target_arch = "wasm32")]
+    let secs = (js_sys::Date::now() / 1000.0) as u64;
+    (secs / SECS_PER_DAY) as u32
+}
+
+// The set for a day: the targets and rewards rotate so consecutive days differ
+fn objectives_for(day: u32) -> Vec<Objective> {
+    let food = [30, 60, 100][day as usize % 3];
+    let dig = [6, 10, 16][day as usize / 3 % 3];
+    vec![
+        Objective { kind: ObjectiveKind::GatherFood, target: food, reward: ObjectiveReward::FoodBonus(food / 2) },
+        Objective {
+            kind: ObjectiveKind::DigHexes,
+            target: dig,
+            reward: ObjectiveReward::Cosmetic(COSMETICS[day as usize % COSMETICS.len()].to_string()),
+        },
+    ]
+}
+
+// Acts on what the authority tells us
+fn receive(daily: &mut DailyObjectives, stores: &mut FoodStores, local_player: PlayerId, message: &NetMessage) {
+    match message {
+        // A new day replaces yesterday's set and its progress; the same day
+        // again (sent for a newcomer) changes nothing
+        NetMessage::DailyObjectives { day, objectives } if *day != daily.day || daily.objectives.is_empty() => {
+            daily.day = *day;
+            daily.objectives = objectives
+                .iter()
+                .cloned()
+                .map(|objective| TrackedObjective { objective, progress: 0, rewarded: false })
+                .collect();
+        }
+        NetMessage::ObjectiveReward { day, index } if *day == daily.day => {
+            let Some(tracked) = daily.objectives.get_mut(*index) else {
+                return;
+            };
+            if tracked.rewarded {
+                return;
+            }
+            tracked.rewarded = true;
+            match tracked.objective.reward.clone() {
+                ObjectiveReward::FoodBonus(food) => {
+                    *stores.0.entry(local_player).or_default() += food;
+                    daily.bonus_food += food;
+                }
+                ObjectiveReward::Cosmetic(name) => daily.cosmetics.push(name),
+            }
+        }
+        _ => {}
+    }
+}
+
+#[derive(Component)]
+struct ObjectivesText;
+
+// Client side: what the server says about our objectives. Offline, we are the
+// authority and `serve_objectives` tells us directly.
+fn receive_objectives(
+    connection: Res<NetConnection>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut daily: ResMut<DailyObjectives>,
+    mut stores: ResMut<FoodStores>,
+    local_player: Res<LocalPlayer>,
+) {
+    for message in incoming.read() {
+        if connection.online {
+            receive(&mut daily, &mut stores, local_player.0, &message.message);
+        }
+    }
+}
+
+fn track_objective_progress(
+    connection: Res<NetConnection>,
+    mut daily: ResMut<DailyObjectives>,
+    mut server: ResMut<ObjectiveServer>,
+    stores: Res<FoodStores>,
+    network: Res<BurrowNetwork>,
+    local_player: Res<LocalPlayer>,
+    mut baseline: Local<ProgressBaseline>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    let food = stores.0.get(&local_player.0).copied().unwrap_or(0);
+    let dug = network.dug_by(local_player.0);
+    let gained_food = baseline.food.map_or(0, |last| food.saturating_sub(last));
+    // Only touch the resource when there's a bonus, to keep change detection quiet
+    let bonus = if daily.bonus_food > 0 { std::mem::take(&mut daily.bonus_food) } else { 0 };
+    let gathered = gained_food.saturating_sub(bonus);
+    let dug_now = baseline.dug.map_or(0, |last| dug.saturating_sub(last));
+    baseline.food = Some(food);
+    baseline.dug = Some(dug);
+    if gathered == 0 && dug_now == 0 {
+        return;
+    }
+
+    let day = daily.day;
+    for (index, tracked) in daily.objectives.iter_mut().enumerate() {
+        let gained = match tracked.objective.kind {
+            ObjectiveKind::GatherFood => gathered,
+            ObjectiveKind::DigHexes => dug_now as u32,
+        };
+        if gained == 0 || tracked.progress >= tracked.objective.target {
+            continue;
+        }
+        tracked.progress = (tracked.progress + gained).min(tracked.objective.target);
+        // The authority decides completion from these reports and replies with the reward
+        if connection.online {
+            let progress = tracked.progress;
+            outgoing.send(OutgoingMessage::to(HOST, NetMessage::ObjectiveProgress { day, index, progress }));
+        } else {
+            server.local_reports.push((day, index, tracked.progress));
+        }
+    }
+}
+
+// Authority side: hands out each day's set (again to whoever connects), keeps
+// every player's reported progress and sends the reward once an objective is
+// met
+fn serve_objectives(
+    connection: Res<NetConnection>,
+    local_player: Res<LocalPlayer>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+    mut server: ResMut<ObjectiveServer>,
+    mut daily: ResMut<DailyObjectives>,
+    mut stores: ResMut<FoodStores>,
+) {
+    if connection.online {
+        incoming.clear();
+        return;
+    }
+    let local_player = local_player.0;
+    // What the authority says reaches us directly and everyone else over the wire
+    let mut tell = |to: Option<PlayerId>, message: NetMessage| {
+        if to.is_none_or(|to| to == local_player) {
+            receive(&mut daily, &mut stores, local_player, &message);
+        }
+        if to != Some(local_player) {
+            outgoing.send(OutgoingMessage { to, message });
+        }
+    };
+
+    let day = today();
+    if server.day != Some(day) {
+        server.day = Some(day);
+        server.objectives = objectives_for(day);
+        server.players.clear();
+        server.peers = connection.peers.clone();
+        tell(None, NetMessage::DailyObjectives { day, objectives: server.objectives.clone() });
+    } else if server.peers != connection.peers {
+        server.peers = connection.peers.clone();
+        tell(None, NetMessage::DailyObjectives { day, objectives: server.objectives.clone() });
+    }
+
+    let mut reports: Vec<(PlayerId, u32, usize, u32)> =
+        server.local_reports.drain(..).map(|(day, index, progress)| (local_player, day, index, progress)).collect();
+    for message in incoming.read() {
+        if let NetMessage::ObjectiveProgress { day, index, progress } = message.message {
+            reports.push((message.from, day, index, progress));
+        }
+    }
+
+    let server = &mut *server;
+    for (player, report_day, index, progress) in reports {
+        let Some(objective) = server.objectives.get(index).filter(|_| report_day == day) else {
+            continue;
+        };
+        let tracked = server.players.entry(player).or_insert_with(|| vec![(0, false); server.objectives.len()]);
+        let (best, rewarded) = &mut tracked[index];
+        *best = (*best).max(progress.min(objective.target));
+        if *best >= objective.target && !*rewarded {
+            *rewarded = true;
+            tell(Some(player), NetMessage::ObjectiveReward { day, index });
+        }
+    }
+}
+
+fn setup_objectives_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle { font_size: 14.0, color: Color::from(Srgba::hex("FFD54F").unwrap()), ..default() },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(32.0),
+            left: Val::Px(8.0),
+            ..default()
+        }),
+        ObjectivesText,
+    ));
+}
+
+fn update_objectives_text(
+    daily: Res<DailyObjectives>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<ObjectivesText>>,
+) {
+    if !daily.is_changed() && !locale.is_changed() {
+        return;
+    }
+
+    let mut lines = Vec::new();
+    if !daily.objectives.is_empty() {
+        lines.push(locale.format("objectives.title", &[("day", &daily.day)]));
+    }
+    for tracked in daily.objectives.iter() {
+        let goal = locale.get(match tracked.objective.kind {
+            ObjectiveKind::GatherFood => "objectives.gather-food",
+            ObjectiveKind::DigHexes => "objectives.dig-hexes",
+        });
+        let key = if tracked.rewarded { "objectives.goal-done" } else { "objectives.goal" };
+        let (progress, target) = (tracked.progress, tracked.objective.target);
+        lines.push(locale.format(key, &[("goal", &goal), ("progress", &progress), ("target", &target)]));
+    }
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DailyObjectives>()
+            .add_systems(Startup, setup_objectives_text)
+            .init_resource::<ObjectiveServer>()
+            .add_systems(
+                Update,
+                (receive_objectives, track_objective_progress, serve_objectives, update_objectives_text).chain(),
+            );
+    }
+}
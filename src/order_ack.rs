@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+
+use crate::game_commands::GameCommand;
+use crate::net::{IncomingMessage, LocalPlayer, NetConnection, NetMessage, OutgoingMessage};
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::{Path, TargetPosition};
+
+// Online, a group order is sent to the server as one message and the server
+// answers with a compact per-squad ack instead of us waiting for every unit's
+// next snapshot. Until its squad is acked a unit's path is drawn as pending;
+// a rejected squad stops where it is. Offline, orders are confirmed on the spot.
+
+// Units per squad in a group order, in selection order
+pub const SQUAD_SIZE: usize = 12;
+
+#[derive(Resource, Default)]
+struct OrderIds {
+    next: u32,
+}
+
+#[derive(Component)]
+pub struct PendingOrder {
+    order: u32,
+    squad: usize,
+}
+
+// Our own unit orders go to the server as they're carried out here; an attack
+// is sent as a move to where the target stands
+fn announce_group_orders(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    connection: Res<NetConnection>,
+    local_player: Res<LocalPlayer>,
+    mut ids: ResMut<OrderIds>,
+    target_q: Query<&Transform>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    for command in game_commands.read() {
+        let (player, units, destination) = match command {
+            GameCommand::Move { player, units, destination, .. } => (*player, units, *destination),
+            GameCommand::Attack { player, units, target } => {
+                let Ok(transform) = target_q.get(*target) else {
+                    continue;
+                };
+                (*player, units, transform.translation.truncate())
+            }
+            _ => continue,
+        };
+        if !connection.online || player != local_player.0 || units.is_empty() {
+            continue;
+        }
+
+        let order = ids.next;
+        ids.next = ids.next.wrapping_add(1);
+        for (i, &entity) in units.iter().enumerate() {
+            commands.entity(entity).insert(PendingOrder { order, squad: i / SQUAD_SIZE });
+        }
+        outgoing.send(OutgoingMessage::broadcast(NetMessage::GroupOrder {
+            order,
+            units: units.iter().map(|entity| entity.to_bits()).collect(),
+            destination,
+        }));
+    }
+}
+
+fn receive_order_acks(
+    mut commands: Commands,
+    mut incoming: EventReader<IncomingMessage>,
+    mut pending_q: Query<(Entity, &PendingOrder, &Transform, &mut TargetPosition, &mut Path)>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for message in incoming.read() {
+        let NetMessage::OrderAck { order, squads } = &message.message else {
+            continue;
+        };
+        let mut confirmed = false;
+        for (entity, pending, transform, mut target, mut path) in pending_q.iter_mut() {
+            if pending.order != *order {
+                continue;
+            }
+            let Some(&accepted) = squads.get(pending.squad) else {
+                continue;
+            };
+            if !accepted {
+                target.0 = transform.translation.truncate();
+                path.waypoints.clear();
+            }
+            confirmed |= accepted;
+            commands.entity(entity).remove::<PendingOrder>();
+        }
+        if confirmed {
+            sounds.send(PlaySound::ui(SoundEffect::OrderConfirmed));
+        }
+    }
+}
+
+pub struct OrderAckPlugin;
+
+impl Plugin for OrderAckPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OrderIds>().add_systems(
+            Update,
+            (announce_group_orders.after(crate::dispatch_pointer_actions), receive_order_acks),
+        );
+    }
+}
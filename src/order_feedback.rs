@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+// Immediate local feedback for orders: the moment an order is issued, each
+// ordered ant plays a short squash-and-stretch "leg shuffle". It only touches
+// the sprite's size, never its position, so it can cover the time until the
+// authoritative result arrives without mispredicting anything.
+
+const WIND_UP_SECS: f32 = 0.15;
+const SHUFFLES: f32 = 2.0;
+const SQUASH: f32 = 0.2;
+
+#[derive(Component)]
+pub struct OrderWindUp {
+    timer: Timer,
+    // Sprite size to return to, captured on the first animated frame
+    base_size: Option<Vec2>,
+}
+
+// Start (or restart) the wind-up on a unit that was just given an order
+pub fn play_wind_up(commands: &mut Commands, wind_up_q: &mut Query<&mut OrderWindUp>, entity: Entity) {
+    if let Ok(mut wind_up) = wind_up_q.get_mut(entity) {
+        wind_up.timer.reset();
+        return;
+    }
+    commands.entity(entity).insert(OrderWindUp {
+        timer: Timer::from_seconds(WIND_UP_SECS, TimerMode::Once),
+        base_size: None,
+    });
+}
+
+fn animate_wind_up(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut q: Query<(Entity, &mut OrderWindUp, &mut Sprite)>,
+) {
+    for (entity, mut wind_up, mut sprite) in q.iter_mut() {
+        let Some(base) = wind_up.base_size.or(sprite.custom_size) else {
+            commands.entity(entity).remove::<OrderWindUp>();
+            continue;
+        };
+        wind_up.base_size = Some(base);
+
+        if wind_up.timer.tick(time.delta()).finished() {
+            sprite.custom_size = Some(base);
+            commands.entity(entity).remove::<OrderWindUp>();
+            continue;
+        }
+
+        // Fades out over the wind-up so it settles back to the base size
+        let t = wind_up.timer.fraction();
+        let wobble = (t * SHUFFLES * TAU).sin() * SQUASH * (1.0 - t);
+        sprite.custom_size = Some(base * Vec2::new(1.0 + wobble, 1.0 - wobble));
+    }
+}
+
+pub struct OrderFeedbackPlugin;
+
+impl Plugin for OrderFeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, animate_wind_up);
+    }
+}
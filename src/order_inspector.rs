@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+
+use crate::burrow::{FastTravel, InTunnel};
+use crate::dig_in::{Digging, DugIn};
+use crate::game_commands::AttackTarget;
+use crate::harvest::Harvester;
+use crate::interactions::{Carrying, Engaged};
+use crate::locale::Locale;
+use crate::photo_mode::PhotoMode;
+use crate::{Caste, HoldPosition, MainCamera, Path, Patrol, Selected, TargetPosition};
+
+// What the selected ants are doing. A HUD panel lists each selected unit's
+// current order (idle, move, gather, harvest, attack, patrol, hold, tunnel or
+// dug in) with the waypoints it has left, and when a single unit is selected
+// its route is numbered on the map: 1 is the hex it's heading for, then every
+// waypoint in the order it will walk them, and P1, P2, ... mark its patrol
+// loop. The order is read from the unit's state rather than stored, so the
+// panel shows what the ant is actually doing, not just what it was told.
+
+// Units listed in the panel before the rest are summed up
+const MAX_LISTED: usize = 8;
+// Numbered markers drawn at once; longer routes only number their start
+const MAX_MARKERS: usize = 64;
+// Closer than this (px) to its target, a unit isn't moving
+const ARRIVAL_RADIUS: f32 = 2.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Order {
+    Idle,
+    Move,
+    Gather,
+    Harvest,
+    Attack,
+    Patrol,
+    Hold,
+    Tunnel,
+    DugIn,
+}
+
+impl Order {
+    fn key(self) -> &'static str {
+        match self {
+            Order::Idle => "orders.idle",
+            Order::Move => "orders.move",
+            Order::Gather => "orders.gather",
+            Order::Harvest => "orders.harvest",
+            Order::Attack => "orders.attack",
+            Order::Patrol => "orders.patrol",
+            Order::Hold => "orders.hold",
+            Order::Tunnel => "orders.tunnel",
+            Order::DugIn => "orders.dug-in",
+        }
+    }
+}
+
+type SelectedQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Caste,
+        &'static Transform,
+        &'static TargetPosition,
+        &'static Path,
+        Option<&'static Patrol>,
+        Option<&'static Carrying>,
+        Has<HoldPosition>,
+        Has<Harvester>,
+        Has<Engaged>,
+        Has<AttackTarget>,
+        Has<InTunnel>,
+        Has<FastTravel>,
+        Has<DugIn>,
+        Has<Digging>,
+    ),
+    With<Selected>,
+>;
+
+// Fighting outranks everything, then travelling underground, then whatever
+// the unit was told to keep doing
+fn current_order(
+    moving: bool,
+    patrolling: bool,
+    harvesting: bool,
+    carrying: bool,
+    holding: bool,
+    engaged: bool,
+    tunnelling: bool,
+) -> Order {
+    if engaged {
+        Order::Attack
+    } else if tunnelling {
+        Order::Tunnel
+    } else if holding {
+        Order::Hold
+    } else if patrolling {
+        Order::Patrol
+    } else if harvesting {
+        Order::Harvest
+    } else if carrying {
+        Order::Gather
+    } else if moving {
+        Order::Move
+    } else {
+        Order::Idle
+    }
+}
+
+fn is_moving(transform: &Transform, target: &TargetPosition, path: &Path) -> bool {
+    !path.waypoints.is_empty() || transform.translation.truncate().distance(target.0) > ARRIVAL_RADIUS
+}
+
+#[derive(Component)]
+struct OrderPanel;
+
+#[derive(Component)]
+struct WaypointMarker;
+
+fn setup_order_inspector(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(32.0),
+            left: Val::Percent(30.0),
+            ..default()
+        }),
+        OrderPanel,
+    ));
+    for _ in 0..MAX_MARKERS {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section("", TextStyle { font_size: 12.0, color: Color::WHITE, ..default() }),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            WaypointMarker,
+        ));
+    }
+}
+
+fn update_order_panel(
+    selected_q: SelectedQuery,
+    locale: Res<Locale>,
+    mut panel_q: Query<&mut Text, With<OrderPanel>>,
+) {
+    let mut lines = Vec::new();
+    let total = selected_q.iter().count();
+    if total > 1 {
+        lines.push(locale.format("orders.selected", &[("count", &total)]));
+    }
+    for (
+        caste,
+        transform,
+        target,
+        path,
+        patrol,
+        carrying,
+        holding,
+        harvesting,
+        engaged,
+        attacking,
+        in_tunnel,
+        fast_travel,
+        dug_in,
+        digging,
+    ) in selected_q.iter().take(MAX_LISTED)
+    {
+        let moving = is_moving(transform, target, path);
+        let patrolling = patrol.is_some_and(|patrol| !patrol.points.is_empty());
+        let tunnelling = in_tunnel || fast_travel;
+        // Dug in (or digging in) is all a unit does until it comes out
+        let order = if dug_in || digging {
+            Order::DugIn
+        } else {
+            current_order(moving, patrolling, harvesting, carrying.is_some(), holding, engaged || attacking, tunnelling)
+        };
+        let mut line = locale.format(
+            "orders.unit",
+            &[("caste", &locale.get(caste.name_key())), ("order", &locale.get(order.key()))],
+        );
+        if !path.waypoints.is_empty() {
+            line.push_str(&locale.format("orders.waypoints", &[("count", &path.waypoints.len())]));
+        }
+        if let Some(carrying) = carrying {
+            line.push_str(&locale.format("orders.carrying", &[("food", &carrying.food)]));
+        }
+        lines.push(line);
+    }
+    if total > MAX_LISTED {
+        lines.push(locale.format("orders.more", &[("count", &(total - MAX_LISTED))]));
+    }
+
+    let value = lines.join("\n");
+    for mut text in panel_q.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+// Numbers along the route of a lone selected unit
+fn update_waypoint_markers(
+    selected_q: Query<(&Transform, &TargetPosition, &Path, Option<&Patrol>), (With<Selected>, Without<WaypointMarker>)>,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    photo: Res<PhotoMode>,
+    mut marker_q: Query<(&mut Text, &mut Transform, &mut Visibility), With<WaypointMarker>>,
+) {
+    let mut labels: Vec<(Vec2, String, Color)> = Vec::new();
+    if let Some((transform, target, path, patrol)) = selected_q.get_single().ok().filter(|_| !photo.is_active()) {
+        let heading = transform.translation.truncate().distance(target.0) > ARRIVAL_RADIUS;
+        let stops = heading.then_some(target.0).into_iter().chain(path.waypoints.iter().copied());
+        labels.extend(stops.enumerate().map(|(i, pos)| (pos, (i + 1).to_string(), Color::WHITE)));
+        if let Some(patrol) = patrol {
+            let loop_color = Color::from(Srgba::hex("00BFFF").unwrap());
+            labels.extend(patrol.points.iter().enumerate().map(|(i, &pos)| (pos, format!("P{}", i + 1), loop_color)));
+        }
+    }
+    let scale = camera_q.get_single().map_or(1.0, |projection| projection.scale);
+
+    let mut labels = labels.into_iter().take(MAX_MARKERS);
+    for (mut text, mut transform, mut visibility) in marker_q.iter_mut() {
+        let Some((pos, label, color)) = labels.next() else {
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+            continue;
+        };
+        if text.sections[0].value != label || text.sections[0].style.color != color {
+            text.sections[0].value = label;
+            text.sections[0].style.color = color;
+        }
+        // Same size on screen at every zoom level, above units and gizmos
+        let placed = Transform::from_translation(pos.extend(5.0)).with_scale(Vec3::splat(scale));
+        if *transform != placed {
+            *transform = placed;
+        }
+        if *visibility != Visibility::Visible {
+            *visibility = Visibility::Visible;
+        }
+    }
+}
+
+pub struct OrderInspectorPlugin;
+
+impl Plugin for OrderInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_order_inspector)
+            .add_systems(Update, (update_order_panel, update_waypoint_markers));
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef};
+use bevy::sprite::{Anchor, Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::window::PrimaryWindow;
+use std::collections::HashSet;
+
+use crate::game_commands::AttackTarget;
+use crate::loading::LoadingAssets;
+use crate::net::LocalPlayer;
+use crate::photo_mode::PhotoMode;
+use crate::{Ant, Faction, MainCamera, Relation, Selected, Teams, UnitPicker};
+
+// Outline traced around the silhouette of the unit under the cursor and of
+// whatever the selection has been ordered to attack, colored by how it
+// relates to us: white for our own units, blue for allies, gray for colonies
+// we have a non-aggression pact with, red for enemies.
+//
+// The outline is a quad a little larger than the sprite, just behind it, and
+// the shader draws the pixels near the edge of the sprite's current atlas
+// frame. Only a handful of units are outlined at once, so each shown outline
+// borrows a material from a small pool that is pointed at that unit's frame
+// every frame.
+
+const OUTLINE_PADDING: f32 = 3.0;
+// Outline width, in sprite texels
+const OUTLINE_TEXELS: f32 = 1.0;
+const OUTLINE_SHADER: &str = "shaders/sprite_outline.wgsl";
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct OutlineMaterial {
+    #[uniform(0)]
+    color: LinearRgba,
+    // Frame of the sprite in the texture, in UV: xy the corner, zw the size
+    #[uniform(1)]
+    frame: Vec4,
+    // xy: padding around the sprite as a fraction of the quad,
+    // zw: outline width in UV of the texture
+    #[uniform(2)]
+    params: Vec4,
+    #[texture(3)]
+    #[sampler(4)]
+    texture: Option<Handle<Image>>,
+}
+
+impl Material2d for OutlineMaterial {
+    fn fragment_shader() -> ShaderRef {
+        OUTLINE_SHADER.into()
+    }
+}
+
+#[derive(Resource, Default)]
+struct OutlineMaterials {
+    pool: Vec<Handle<OutlineMaterial>>,
+}
+
+// Child quad of a unit that renders its outline
+#[derive(Component)]
+struct Outline;
+
+fn outline_color(relation: Relation) -> Color {
+    match relation {
+        Relation::Own => Color::WHITE,
+        Relation::Ally => Color::from(Srgba::hex("3399FF").unwrap()),
+        Relation::Neutral => Color::from(Srgba::hex("B0BEC5").unwrap()),
+        Relation::Enemy => Color::from(Srgba::hex("FF3333").unwrap()),
+    }
+}
+
+fn setup_outline_materials(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    loading.queue(asset_server.load::<Shader>(OUTLINE_SHADER));
+    commands.init_resource::<OutlineMaterials>();
+}
+
+fn attach_outlines(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, new_units: Query<(Entity, &Sprite), Added<Ant>>) {
+    for (entity, sprite) in new_units.iter() {
+        let size = sprite.custom_size.unwrap_or(Vec2::splat(10.0)) + Vec2::splat(OUTLINE_PADDING * 2.0);
+        let outline = commands
+            .spawn((
+                MaterialMesh2dBundle::<OutlineMaterial> {
+                    mesh: Mesh2dHandle(meshes.add(Rectangle::from_size(size))),
+                    // Just behind the sprite
+                    transform: Transform::from_xyz(0.0, 0.0, -0.1),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                Outline,
+            ))
+            .id();
+        commands.entity(entity).add_child(outline);
+    }
+}
+
+fn update_outlines(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    unit_q: Query<(Entity, &Faction, &Sprite, &Handle<Image>, Option<&TextureAtlas>, &Children), With<Ant>>,
+    attack_q: Query<&AttackTarget, With<Selected>>,
+    mut outline_q: Query<(&mut Visibility, &mut Transform, &mut Handle<OutlineMaterial>), With<Outline>>,
+    mut pool: ResMut<OutlineMaterials>,
+    mut materials: ResMut<Assets<OutlineMaterial>>,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    images: Res<Assets<Image>>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+    photo: Res<PhotoMode>,
+    picker: UnitPicker,
+) {
+    let (camera, camera_transform) = camera_q.single();
+    // Same picking rule as clicks, and only one unit per hover. None in photo mode.
+    let hovered_unit = windows
+        .single()
+        .cursor_position()
+        .filter(|_| !photo.is_active())
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .and_then(|pos| picker.unit_at(pos));
+    let mut outlined: HashSet<Entity> = hovered_unit.into_iter().collect();
+    if !photo.is_active() {
+        outlined.extend(attack_q.iter().map(|attack| attack.0));
+    }
+
+    let mut used = 0;
+    for (entity, faction, sprite, texture, atlas, children) in unit_q.iter() {
+        let shown = outlined.contains(&entity);
+        for &child in children.iter() {
+            let Ok((mut visibility, mut transform, mut material_handle)) = outline_q.get_mut(child) else {
+                continue;
+            };
+            if !shown {
+                if *visibility != Visibility::Hidden {
+                    *visibility = Visibility::Hidden;
+                }
+                continue;
+            }
+
+            // The frame the sprite shows right now, in texture UV
+            let texture_size = images.get(texture).map_or(Vec2::ONE, |image| image.size_f32());
+            let frame = match atlas.and_then(|atlas| Some((layouts.get(&atlas.layout)?, atlas.index))) {
+                Some((layout, index)) => layout.textures.get(index).map_or(Rect::new(0.0, 0.0, 1.0, 1.0), |rect| {
+                    Rect::from_corners(rect.min.as_vec2() / texture_size, rect.max.as_vec2() / texture_size)
+                }),
+                None => Rect::new(0.0, 0.0, 1.0, 1.0),
+            };
+            let sprite_size = sprite.custom_size.unwrap_or(Vec2::splat(10.0));
+            let quad_size = sprite_size + Vec2::splat(OUTLINE_PADDING * 2.0);
+            let padding = Vec2::splat(OUTLINE_PADDING) / quad_size;
+            let width = Vec2::splat(OUTLINE_TEXELS) / texture_size;
+
+            if pool.pool.len() == used {
+                pool.pool.push(materials.add(OutlineMaterial::default()));
+            }
+            let handle = pool.pool[used].clone();
+            used += 1;
+            if let Some(material) = materials.get_mut(&handle) {
+                material.color = outline_color(teams.relation(local_player.0, faction.0)).into();
+                material.frame = Vec4::new(frame.min.x, frame.min.y, frame.width(), frame.height());
+                material.params = Vec4::new(padding.x, padding.y, width.x, width.y);
+                material.texture = Some(texture.clone());
+            }
+            if *material_handle != handle {
+                *material_handle = handle;
+            }
+            *visibility = Visibility::Inherited;
+            // Follow the sprite when idle fidgeting shifts its anchor
+            let anchor = match sprite.anchor {
+                Anchor::Custom(offset) => offset,
+                _ => Vec2::ZERO,
+            };
+            transform.translation = (-anchor * sprite_size).extend(transform.translation.z);
+        }
+    }
+}
+
+pub struct OutlinePlugin;
+
+impl Plugin for OutlinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<OutlineMaterial>::default())
+            .add_systems(Startup, setup_outline_materials)
+            .add_systems(Update, (attach_outlines, update_outlines).chain());
+    }
+}
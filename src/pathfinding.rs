@@ -0,0 +1,366 @@
+use bevy::ecs::entity::Entities;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::tasks::AsyncComputeTaskPool;
+use bevy::tasks::{block_on, futures_lite::future, Task};
+use bevy::utils::{Duration, Instant};
+use hexx::{Hex, HexLayout};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::coords::HexLayoutExt;
+use crate::determinism::Determinism;
+use crate::rocks::Rocks;
+use crate::structures::Structures;
+use crate::weather::Flooded;
+use crate::{MapLayout, MapSize, Path, TargetPosition};
+
+mod cache;
+mod route;
+use cache::RouteCache;
+use route::hex_path;
+
+// Who asked for the path. Declaration order is the priority order:
+// player orders always jump ahead of AI requests, which beat idle wandering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathPriority {
+    IdleWander,
+    Ai,
+    PlayerOrder,
+}
+
+// Microseconds of pathfinding allowed per frame. Whatever doesn't fit is
+// deferred to the next frame so a burst of orders can't blow up frame time.
+#[derive(Resource)]
+pub struct PathBudget {
+    pub micros_per_tick: u64,
+}
+
+// Searches running on the task pool at once. The rest wait in the queue, so a
+// burst of orders is still served in priority order.
+const MAX_IN_FLIGHT: usize = 256;
+// Searches a deterministic run serves per frame, however long they take, so
+// the same inputs give the same routes on any machine
+const DETERMINISTIC_SEARCHES: usize = 32;
+
+impl Default for PathBudget {
+    fn default() -> Self {
+        Self { micros_per_tick: 2000 }
+    }
+}
+
+struct QueuedRequest {
+    entity: Entity,
+    from: Hex,
+    to: Hex,
+    priority: PathPriority,
+    seq: u64,
+}
+
+impl PartialEq for QueuedRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedRequest {}
+
+impl PartialOrd for QueuedRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedRequest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Max-heap: highest priority first, then oldest request (lowest seq) first
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PathQueue {
+    heap: BinaryHeap<QueuedRequest>,
+    // Latest request per entity, until its route is applied; older ones still
+    // in the heap or in flight are stale and dropped
+    latest: HashMap<Entity, u64>,
+    next_seq: u64,
+    // Main-thread time spent on pathfinding during the last tick
+    last_tick: Duration,
+    in_flight: usize,
+    cache: RouteCache,
+    // Searches run since startup
+    solved: u64,
+}
+
+impl PathQueue {
+    pub fn request(&mut self, entity: Entity, from: Hex, to: Hex, priority: PathPriority) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.latest.insert(entity, seq);
+        self.heap.push(QueuedRequest { entity, from, to, priority, seq });
+    }
+
+    // Drops the unit's queued or in-flight request, if any
+    pub fn cancel(&mut self, entity: Entity) {
+        self.latest.remove(&entity);
+    }
+
+    pub fn is_pending(&self, entity: Entity) -> bool {
+        self.latest.contains_key(&entity)
+    }
+
+    // Requests still waiting for a route, queued or in flight
+    pub fn pending(&self) -> usize {
+        self.latest.len()
+    }
+
+    pub fn last_tick_time(&self) -> Duration {
+        self.last_tick
+    }
+
+    pub fn solved_total(&self) -> u64 {
+        self.solved
+    }
+}
+
+// Which hexes routes may go through
+#[derive(SystemParam)]
+pub struct Passability<'w> {
+    map_size: Res<'w, MapSize>,
+    flooded: Res<'w, Flooded>,
+    structures: Res<'w, Structures>,
+    rocks: Res<'w, Rocks>,
+}
+
+impl Passability<'_> {
+    // Off-map hexes are impassable too, which keeps searches bounded
+    pub fn passable(&self, hex: Hex) -> bool {
+        self.map_size.contains(hex)
+            && !self.flooded.contains(hex)
+            && !self.structures.occupies(hex)
+            && !self.rocks.contains(hex)
+    }
+
+    fn is_changed(&self) -> bool {
+        self.map_size.is_changed() || self.flooded.is_changed() || self.structures.is_changed() || self.rocks.is_changed()
+    }
+
+    fn snapshot(&self) -> Obstacles {
+        Obstacles {
+            map_radius: self.map_size.radius,
+            blocked: self.flooded.hexes().chain(self.structures.occupied()).chain(self.rocks.hexes()).collect(),
+        }
+    }
+}
+
+// Copy of `Passability` that can be moved onto the task pool. Searches see the
+// map as it was when they started; a route through a hex flooded since then
+// is walked like any other route that becomes blocked.
+pub struct Obstacles {
+    map_radius: u32,
+    blocked: HashSet<Hex>,
+}
+
+impl Obstacles {
+    fn passable(&self, hex: Hex) -> bool {
+        hex.unsigned_distance_to(Hex::ZERO) < self.map_radius && !self.blocked.contains(&hex)
+    }
+}
+
+// A search running on the task pool. Replacing or removing the component
+// drops the task, which cancels it.
+#[derive(Component)]
+pub struct PendingPath {
+    task: Task<Vec<Hex>>,
+    seq: u64,
+    generation: u64,
+    from: Hex,
+    to: Hex,
+}
+
+// `route` includes the start hex, which the unit is already on
+fn follow_route(route: &[Hex], from: Hex, to: Hex, layout: &HexLayout, target: &mut TargetPosition, path: &mut Path) {
+    path.waypoints = route.iter().skip(1).map(|&hex| layout.hex_to_world(hex)).collect();
+    if let Some(first) = path.waypoints.pop_front() {
+        target.0 = first;
+    } else if from == to {
+        // Already there
+        target.0 = layout.hex_to_world(to);
+    }
+    // Otherwise the destination is unreachable: stay on the current target
+}
+
+// Applies finished searches, and cancels those a newer order has superseded
+pub fn apply_finished_paths(
+    mut commands: Commands,
+    mut queue: ResMut<PathQueue>,
+    layout: Res<MapLayout>,
+    entities: &Entities,
+    mut pending_q: Query<(Entity, &mut PendingPath, &mut TargetPosition, &mut Path)>,
+) {
+    let start = Instant::now();
+    queue.in_flight = 0;
+    // Units that died waiting, or whose search was replaced by a newer one,
+    // never get to clear their entries below
+    queue.latest.retain(|&entity, _| entities.contains(entity));
+    queue.cache.retain_searches(|key, searcher| {
+        pending_q
+            .get(searcher)
+            .is_ok_and(|(_, pending, ..)| RouteCache::key(pending.from, pending.to) == key)
+    });
+
+    for (entity, mut pending, mut target, mut path) in pending_q.iter_mut() {
+        let key = RouteCache::key(pending.from, pending.to);
+        if queue.latest.get(&entity) != Some(&pending.seq) {
+            queue.cache.end_search(key, entity);
+            commands.entity(entity).remove::<PendingPath>();
+            continue;
+        }
+        let Some(route) = block_on(future::poll_once(&mut pending.task)) else {
+            queue.in_flight += 1;
+            continue;
+        };
+        queue.latest.remove(&entity);
+        queue.cache.end_search(key, entity);
+        queue.solved += 1;
+        follow_route(&route, pending.from, pending.to, &layout.0, &mut target, &mut path);
+        queue.cache.insert(pending.generation, route);
+        commands.entity(entity).remove::<PendingPath>();
+    }
+    queue.last_tick = start.elapsed();
+}
+
+// Starts searches for queued requests, highest priority first, until the
+// frame budget is spent. Deterministic runs search inline instead, since a
+// task finishing a frame earlier or later would change the simulation, and
+// serve a fixed number of searches rather than however many fit in the time.
+#[cfg_attr(target_arch = "wasm32", allow(unused_mut, unused_variables))]
+pub fn process_path_requests(
+    mut commands: Commands,
+    budget: Res<PathBudget>,
+    determinism: Res<Determinism>,
+    mut queue: ResMut<PathQueue>,
+    layout: Res<MapLayout>,
+    passability: Passability,
+    mut obstacles: Local<Option<Arc<Obstacles>>>,
+    mut ant_q: Query<(&mut TargetPosition, &mut Path)>,
+) {
+    let start = Instant::now();
+    let budget = Duration::from_micros(budget.micros_per_tick);
+    if passability.is_changed() {
+        *obstacles = None;
+        queue.cache.clear();
+    }
+    let obstacles = obstacles.get_or_insert_with(|| Arc::new(passability.snapshot()));
+    // The browser's task pool runs on the main thread and hands back nothing
+    // to poll, so there routes are searched inline like deterministic ones
+    let inline = determinism.enabled || cfg!(target_arch = "wasm32");
+    let mut waiting = Vec::new();
+    let mut searched = 0;
+
+    while inline || queue.in_flight < MAX_IN_FLIGHT {
+        let Some(request) = queue.heap.pop() else {
+            break;
+        };
+        // Superseded by a newer order for the same unit
+        if queue.latest.get(&request.entity) != Some(&request.seq) {
+            continue;
+        }
+        let Ok((mut target, mut path)) = ant_q.get_mut(request.entity) else {
+            queue.latest.remove(&request.entity);
+            continue;
+        };
+
+        if let Some(route) = queue.cache.lookup(request.from, request.to, |hex| obstacles.passable(hex)) {
+            queue.latest.remove(&request.entity);
+            queue.solved += 1;
+            follow_route(&route, request.from, request.to, &layout.0, &mut target, &mut path);
+            continue;
+        }
+        let key = RouteCache::key(request.from, request.to);
+        if queue.cache.is_searching(key) {
+            waiting.push(request);
+            continue;
+        }
+
+        searched += 1;
+        if inline {
+            queue.latest.remove(&request.entity);
+            queue.solved += 1;
+            let route = hex_path(request.from, request.to, |hex| obstacles.passable(hex));
+            follow_route(&route, request.from, request.to, &layout.0, &mut target, &mut path);
+            let generation = queue.cache.generation();
+            queue.cache.insert(generation, route);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if !inline {
+            let obstacles = Arc::clone(obstacles);
+            let (from, to) = (request.from, request.to);
+            let task = AsyncComputeTaskPool::get().spawn(async move { hex_path(from, to, |hex| obstacles.passable(hex)) });
+            let generation = queue.cache.generation();
+            // Replaces (and so cancels) any older search for this unit
+            commands.entity(request.entity).insert(PendingPath { task, seq: request.seq, generation, from, to });
+            queue.cache.start_search(key, request.entity);
+            queue.in_flight += 1;
+        }
+        // Checked after the work so at least one request is served every frame,
+        // even with a budget smaller than a single search.
+        let spent = if determinism.enabled { searched >= DETERMINISTIC_SEARCHES } else { start.elapsed() >= budget };
+        if spent {
+            break;
+        }
+    }
+    queue.heap.extend(waiting);
+    queue.last_tick += start.elapsed();
+}
+
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PathBudget>()
+            .init_resource::<PathQueue>()
+            .add_systems(
+                Update,
+                (apply_finished_paths, process_path_requests)
+                    .chain()
+                    .after(crate::game_commands::execute_unit_orders)
+                    .before(crate::move_ants),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_orders_are_served_first_then_oldest_first() {
+        let mut queue = PathQueue::default();
+        let (a, b, c) = (Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3));
+        queue.request(a, Hex::ZERO, Hex::new(3, 0), PathPriority::IdleWander);
+        queue.request(b, Hex::ZERO, Hex::new(3, 0), PathPriority::PlayerOrder);
+        queue.request(c, Hex::ZERO, Hex::new(3, 0), PathPriority::PlayerOrder);
+
+        let order: Vec<Entity> = std::iter::from_fn(|| queue.heap.pop()).map(|request| request.entity).collect();
+        assert_eq!(order, vec![b, c, a]);
+    }
+
+    #[test]
+    fn a_newer_request_supersedes_the_old_one() {
+        let mut queue = PathQueue::default();
+        let unit = Entity::from_raw(1);
+        queue.request(unit, Hex::ZERO, Hex::new(3, 0), PathPriority::PlayerOrder);
+        queue.request(unit, Hex::ZERO, Hex::new(-3, 0), PathPriority::PlayerOrder);
+        assert_eq!(queue.pending(), 1);
+        assert_eq!(queue.latest.get(&unit), Some(&1));
+
+        queue.cancel(unit);
+        assert!(!queue.is_pending(unit));
+    }
+}
@@ -0,0 +1,181 @@
+use bevy::prelude::Entity;
+use hexx::Hex;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{chunk_of, CHUNK_RADIUS};
+
+// Recently found routes, keyed by the chunk they start in and their
+// destination. A unit starting anywhere in that chunk walks over to the cached
+// route and follows it the rest of the way, so a squad ordered to one spot
+// costs one search instead of one per ant. Everything is dropped whenever
+// passability changes, including the searches still running.
+
+const MAX_ROUTES: usize = 1024;
+// Furthest a unit walks to join a cached route
+const MAX_JOIN_DISTANCE: u32 = 2 * CHUNK_RADIUS;
+
+pub type RouteKey = (Hex, Hex);
+
+#[derive(Default)]
+pub struct RouteCache {
+    routes: HashMap<RouteKey, Arc<Vec<Hex>>>,
+    // Routes being searched for on the task pool, and the unit running each
+    // search. Requests sharing one wait for it to land in the cache.
+    searching: HashMap<RouteKey, Entity>,
+    // Bumped on every clear, so searches started before it aren't cached
+    generation: u64,
+}
+
+impl RouteCache {
+    pub fn key(from: Hex, to: Hex) -> RouteKey {
+        (chunk_of(from), to)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn clear(&mut self) {
+        self.routes.clear();
+        self.searching.clear();
+        self.generation += 1;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start_search(&mut self, key: RouteKey, searcher: Entity) {
+        self.searching.insert(key, searcher);
+    }
+
+    pub fn is_searching(&self, key: RouteKey) -> bool {
+        self.searching.contains_key(&key)
+    }
+
+    // Leaves the key alone if another unit has started a search for it since
+    pub fn end_search(&mut self, key: RouteKey, searcher: Entity) {
+        if self.searching.get(&key) == Some(&searcher) {
+            self.searching.remove(&key);
+        }
+    }
+
+    // Drops searches whose unit is no longer running them, because it died or
+    // was given a new order before the route came back
+    pub fn retain_searches(&mut self, mut running: impl FnMut(RouteKey, Entity) -> bool) {
+        self.searching.retain(|&key, &mut searcher| running(key, searcher));
+    }
+
+    // `route` includes its start hex; unreachable (empty) routes aren't kept
+    pub fn insert(&mut self, generation: u64, route: Vec<Hex>) {
+        let (Some(&from), Some(&to)) = (route.first(), route.last()) else {
+            return;
+        };
+        if generation != self.generation {
+            return;
+        }
+        if self.routes.len() >= MAX_ROUTES {
+            self.routes.clear();
+        }
+        self.routes.insert(Self::key(from, to), Arc::new(route));
+    }
+
+    // Route from `from` to `to` by way of a cached one, start hex included.
+    // Joins the cached route at its hex closest to `from` (the furthest along
+    // on ties) if the straight walk there is clear.
+    pub fn lookup(&self, from: Hex, to: Hex, passable: impl Fn(Hex) -> bool) -> Option<Vec<Hex>> {
+        let route = self.routes.get(&Self::key(from, to))?;
+        let (join, distance) = route
+            .iter()
+            .enumerate()
+            .map(|(i, hex)| (i, hex.unsigned_distance_to(from)))
+            .min_by_key(|&(i, distance)| (distance, std::cmp::Reverse(i)))?;
+        if distance > MAX_JOIN_DISTANCE || !from.line_to(route[join]).skip(1).all(passable) {
+            return None;
+        }
+        let mut path: Vec<Hex> = from.line_to(route[join]).collect();
+        path.extend_from_slice(&route[join + 1..]);
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight_route(from: Hex, to: Hex) -> Vec<Hex> {
+        from.line_to(to).collect()
+    }
+
+    #[test]
+    fn units_in_the_same_chunk_share_a_key() {
+        let to = Hex::new(20, -5);
+        assert_eq!(RouteCache::key(Hex::ZERO, to), RouteCache::key(Hex::new(1, 0), to));
+        assert_ne!(RouteCache::key(Hex::ZERO, to), RouteCache::key(Hex::ZERO, Hex::new(20, -4)));
+    }
+
+    #[test]
+    fn lookup_joins_a_cached_route_from_a_nearby_hex() {
+        let mut cache = RouteCache::default();
+        let to = Hex::new(10, 0);
+        cache.insert(cache.generation(), straight_route(Hex::ZERO, to));
+
+        let from = Hex::new(1, -1);
+        let path = cache.lookup(from, to, |_| true).unwrap();
+        assert_eq!(path.first(), Some(&from));
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.windows(2).all(|pair| pair[0].unsigned_distance_to(pair[1]) == 1));
+    }
+
+    #[test]
+    fn lookup_refuses_a_blocked_walk_to_the_route() {
+        let mut cache = RouteCache::default();
+        let to = Hex::new(10, 0);
+        cache.insert(cache.generation(), straight_route(Hex::ZERO, to));
+
+        // Joins at (2, 0), the furthest of the hexes two steps away, by way of (1, 1)
+        let from = Hex::new(0, 2);
+        assert!(cache.lookup(from, to, |hex| hex != Hex::new(1, 1)).is_none());
+    }
+
+    #[test]
+    fn routes_searched_before_a_clear_are_not_cached() {
+        let mut cache = RouteCache::default();
+        let to = Hex::new(10, 0);
+        let generation = cache.generation();
+        cache.clear();
+        cache.insert(generation, straight_route(Hex::ZERO, to));
+        assert!(cache.lookup(Hex::ZERO, to, |_| true).is_none());
+    }
+
+    #[test]
+    fn clear_forgets_running_searches() {
+        let mut cache = RouteCache::default();
+        let key = RouteCache::key(Hex::ZERO, Hex::new(10, 0));
+        cache.start_search(key, Entity::from_raw(1));
+        cache.clear();
+        assert!(!cache.is_searching(key));
+    }
+
+    #[test]
+    fn an_older_search_does_not_end_a_newer_one() {
+        let mut cache = RouteCache::default();
+        let key = RouteCache::key(Hex::ZERO, Hex::new(10, 0));
+        cache.start_search(key, Entity::from_raw(1));
+        cache.start_search(key, Entity::from_raw(2));
+        cache.end_search(key, Entity::from_raw(1));
+        assert!(cache.is_searching(key));
+        cache.end_search(key, Entity::from_raw(2));
+        assert!(!cache.is_searching(key));
+    }
+
+    #[test]
+    fn searches_of_dead_units_are_dropped() {
+        let mut cache = RouteCache::default();
+        let alive = RouteCache::key(Hex::ZERO, Hex::new(10, 0));
+        let dead = RouteCache::key(Hex::ZERO, Hex::new(-10, 0));
+        cache.start_search(alive, Entity::from_raw(1));
+        cache.start_search(dead, Entity::from_raw(2));
+        cache.retain_searches(|_, searcher| searcher == Entity::from_raw(1));
+        assert!(cache.is_searching(alive));
+        assert!(!cache.is_searching(dead));
+    }
+}
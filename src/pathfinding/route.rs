@@ -0,0 +1,15 @@
+use hexx::Hex;
+
+// Route search on the hex grid. Kept free of ECS types so the benchmarks in
+// `benches/` can build it on their own.
+
+// Grid-walking route between two cells, start cell included. Walks the
+// straight line when it's clear, otherwise searches around impassable hexes;
+// empty when the destination can't be reached.
+pub fn hex_path(from: Hex, to: Hex, passable: impl Fn(Hex) -> bool) -> Vec<Hex> {
+    if from.line_to(to).skip(1).all(&passable) {
+        from.line_to(to).collect()
+    } else {
+        hexx::algorithms::a_star(from, to, |_, next| passable(next).then_some(1)).unwrap_or_default()
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use hexx::Hex;
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+
+use crate::camera_controls::ZoomLimits;
+use crate::coords::HexLayoutExt;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::vision::Vision;
+use crate::{chunk_of, MainCamera, MapLayout, MapSize};
+
+// Photo mode, for screenshots and a quick look at the whole colony. F7 hides
+// the HUD, every gizmo overlay (paths, outlines, health bars, alerts) and the
+// selection rings, and fits the camera to the explored part of the map: the
+// union of every chunk our team has seen so far, or the whole map before any.
+// Pressing it again brings all of it back and returns the camera to where it
+// was. Other world-space overlays mark themselves `HudOnly` to be hidden too.
+
+// Share of the view left around the explored area
+const FIT_MARGIN: f32 = 1.05;
+
+// Hidden while photo mode is on
+#[derive(Component)]
+pub struct HudOnly;
+
+// What photo mode changed, to put back on exit
+struct SavedView {
+    translation: Vec3,
+    scale: f32,
+    // UI roots that were showing
+    ui_roots: Vec<Entity>,
+    // Whether each gizmo group was enabled
+    gizmos: HashMap<TypeId, bool>,
+}
+
+#[derive(Resource, Default)]
+pub struct PhotoMode {
+    saved: Option<SavedView>,
+    // Chunks our team has seen at least once
+    explored: HashSet<Hex>,
+}
+
+impl PhotoMode {
+    pub fn is_active(&self) -> bool {
+        self.saved.is_some()
+    }
+}
+
+fn track_explored_chunks(vision: Res<Vision>, mut photo: ResMut<PhotoMode>) {
+    if !vision.is_changed() {
+        return;
+    }
+    let seen: Vec<Hex> = vision.hexes().map(chunk_of).filter(|chunk| !photo.explored.contains(chunk)).collect();
+    if !seen.is_empty() {
+        photo.explored.extend(seen);
+    }
+}
+
+// The world rectangle covering every explored chunk's hexes on the map
+fn explored_rect(photo: &PhotoMode, layout: &MapLayout, map_size: &MapSize) -> Rect {
+    let mut hexes = map_size.hexes().filter(|&hex| photo.explored.contains(&chunk_of(hex))).peekable();
+    if hexes.peek().is_none() {
+        return map_size.world_rect(&layout.0);
+    }
+    let mut rect: Option<Rect> = None;
+    for hex in hexes {
+        for corner in layout.0.world_corners(hex) {
+            rect = Some(rect.map_or(Rect::from_center_size(corner, Vec2::ZERO), |rect| rect.union_point(corner)));
+        }
+    }
+    rect.unwrap_or_default()
+}
+
+fn toggle_photo_mode(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut photo: ResMut<PhotoMode>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    limits: Res<ZoomLimits>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    mut ui_root_q: Query<(Entity, &mut Visibility), (With<Node>, Without<Parent>)>,
+    mut hud_only_q: Query<&mut Visibility, (With<HudOnly>, Without<Node>)>,
+    mut gizmo_store: ResMut<GizmoConfigStore>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::PhotoMode) {
+        return;
+    }
+    let (Ok(window), Ok((mut transform, mut projection))) = (windows.get_single(), camera_q.get_single_mut()) else {
+        return;
+    };
+
+    if let Some(saved) = photo.saved.take() {
+        transform.translation = saved.translation;
+        projection.scale = saved.scale;
+        for entity in saved.ui_roots {
+            if let Ok((_, mut visibility)) = ui_root_q.get_mut(entity) {
+                *visibility = Visibility::Inherited;
+            }
+        }
+        for (type_id, config, _) in gizmo_store.iter_mut() {
+            if let Some(&enabled) = saved.gizmos.get(type_id) {
+                config.enabled = enabled;
+            }
+        }
+        for mut visibility in hud_only_q.iter_mut() {
+            *visibility = Visibility::Inherited;
+        }
+        return;
+    }
+
+    let mut ui_roots = Vec::new();
+    for (entity, mut visibility) in ui_root_q.iter_mut() {
+        if *visibility != Visibility::Hidden {
+            *visibility = Visibility::Hidden;
+            ui_roots.push(entity);
+        }
+    }
+    let mut gizmos = HashMap::new();
+    for (type_id, config, _) in gizmo_store.iter_mut() {
+        gizmos.insert(*type_id, config.enabled);
+        config.enabled = false;
+    }
+    for mut visibility in hud_only_q.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+    photo.saved = Some(SavedView { translation: transform.translation, scale: projection.scale, ui_roots, gizmos });
+
+    let rect = explored_rect(&photo, &layout, &map_size);
+    let screen = Vec2::new(window.width(), window.height());
+    if screen.min_element() > 0.0 {
+        let center = rect.center();
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+        projection.scale = limits.clamp((rect.size() / screen).max_element() * FIT_MARGIN);
+    }
+}
+
+// Overlays that appear while the photo is being taken stay hidden too
+fn hide_new_overlays(photo: Res<PhotoMode>, mut new_q: Query<&mut Visibility, Added<HudOnly>>) {
+    if !photo.is_active() {
+        return;
+    }
+    for mut visibility in new_q.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+pub struct PhotoModePlugin;
+
+impl Plugin for PhotoModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhotoMode>()
+            .register_action(Action::PhotoMode, &[KeyCode::F7])
+            .add_systems(Update, (track_explored_chunks, toggle_photo_mode, hide_new_overlays).chain());
+    }
+}
@@ -0,0 +1,80 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use hexx::Hex;
+use std::collections::HashSet;
+
+use crate::coords::HexLayoutExt;
+use crate::spatial::SpatialIndex;
+use crate::{MapLayout, MapSize};
+
+// One place to answer "where can I put a new unit near here?" for anything
+// that spawns or drops units: starting units, hatching, unloading, editors.
+// A hex is free when it's on the map, no unit stands in it and no solid
+// collider (terrain or otherwise) covers its center.
+
+// How many rings around the requested hex are searched before giving up
+const SEARCH_RADIUS: u32 = 10;
+
+#[derive(SystemParam)]
+pub struct SpawnPlacer<'w> {
+    layout: Res<'w, MapLayout>,
+    map_size: Res<'w, MapSize>,
+    spatial_index: Res<'w, SpatialIndex>,
+    rapier_context: Res<'w, RapierContext>,
+}
+
+impl SpawnPlacer<'_> {
+    fn world_pos(&self, hex: Hex) -> Vec2 {
+        self.layout.0.hex_to_world(hex)
+    }
+
+    fn is_free(&self, hex: Hex) -> bool {
+        if !self.map_size.contains(hex) || !self.spatial_index.at(hex).is_empty() {
+            return false;
+        }
+        let mut blocked = false;
+        self.rapier_context.intersections_with_point(self.world_pos(hex), QueryFilter::default().exclude_sensors(), |_| {
+            blocked = true;
+            false
+        });
+        !blocked
+    }
+
+    // Free spots nearest `center` first. Spots handed out are reserved for the
+    // rest of the batch, since units spawned this frame aren't indexed yet.
+    pub fn around(&self, center: Hex) -> FreeSpots<'_, '_> {
+        FreeSpots {
+            placer: self,
+            candidates: Box::new(center.spiral_range(0..=SEARCH_RADIUS)),
+            taken: HashSet::new(),
+        }
+    }
+}
+
+pub struct FreeSpots<'a, 'w> {
+    placer: &'a SpawnPlacer<'w>,
+    candidates: Box<dyn Iterator<Item = Hex> + 'a>,
+    taken: HashSet<Hex>,
+}
+
+impl FreeSpots<'_, '_> {
+    // Mark a hex as used by something placed outside this batch
+    pub fn reserve(&mut self, hex: Hex) {
+        self.taken.insert(hex);
+    }
+}
+
+impl Iterator for FreeSpots<'_, '_> {
+    type Item = (Hex, Vec2);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let placer = self.placer;
+        let taken = &mut self.taken;
+        let hex = self
+            .candidates
+            .find(|&hex| !taken.contains(&hex) && placer.is_free(hex))?;
+        taken.insert(hex);
+        Some((hex, placer.world_pos(hex)))
+    }
+}
@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use hexx::Hex;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::brood::Brood;
+use crate::burrow::InTunnel;
+use crate::collision;
+use crate::coords::HexLayoutExt;
+use crate::determinism::SimRng;
+use crate::dig_in::DugIn;
+use crate::health::Health;
+use crate::interactions::Engaged;
+use crate::lobby::MatchSettings;
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::spatial::SpatialIndex;
+use crate::{Ant, Caste, MapLayout, MapSize, Queen};
+
+// Roaming predators: every so often a spider or anteater walks in from the map
+// edge, wanders, and bites ants it runs into. Ants next to it engage it and
+// bite back like they would an enemy ant (see `interactions`), so their caste,
+// rank and colony upgrades count; once enough soldiers swarm it, it flees for
+// the edge, and it dies like any unit when its health runs out. The giant predators mutator scales their size,
+// health and bite up (see `mutators`).
+
+const SPAWN_SECS: f32 = 60.0;
+const MAX_PREDATORS: usize = 2;
+const ATTACK_SECS: f32 = 1.0;
+// This many soldiers around a predator make it run
+const SWARM_COUNT: usize = 4;
+// Wander targets are picked within this many hexes of the current one
+const WANDER_RADIUS: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PredatorKind {
+    Spider,
+    Anteater,
+}
+
+impl PredatorKind {
+    fn health(self) -> f32 {
+        match self {
+            PredatorKind::Spider => 40.0,
+            PredatorKind::Anteater => 150.0,
+        }
+    }
+
+    fn speed(self) -> f32 {
+        match self {
+            PredatorKind::Spider => 120.0,
+            PredatorKind::Anteater => 60.0,
+        }
+    }
+
+    // Damage per attack tick, and how far (in hexes) the attack reaches
+    fn attack(self) -> (f32, u32) {
+        match self {
+            PredatorKind::Spider => (15.0, 0),
+            PredatorKind::Anteater => (8.0, 1),
+        }
+    }
+
+    fn sprite(self) -> (Color, f32) {
+        match self {
+            PredatorKind::Spider => (Color::from(Srgba::hex("212121").unwrap()), 14.0),
+            PredatorKind::Anteater => (Color::from(Srgba::hex("6D5D4B").unwrap()), 24.0),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Predator {
+    kind: PredatorKind,
+    // Size, health and bite multiplier
+    scale: f32,
+    wander_target: Vec2,
+    attack_timer: Timer,
+    fleeing: bool,
+}
+
+#[derive(Resource)]
+struct PredatorSpawner(Timer);
+
+fn hex_pos(layout: &MapLayout, hex: Hex) -> Vec2 {
+    layout.0.hex_to_world(hex)
+}
+
+fn spawn_predators(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawner: ResMut<PredatorSpawner>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    settings: Res<MatchSettings>,
+    mut sim_rng: ResMut<SimRng>,
+    predator_q: Query<(), With<Predator>>,
+) {
+    if !spawner.0.tick(time.delta()).just_finished() || predator_q.iter().count() >= MAX_PREDATORS {
+        return;
+    }
+
+    let rng = &mut sim_rng.0;
+    let Some(edge) = Hex::ZERO.ring(map_size.radius.saturating_sub(1)).choose(rng) else {
+        return;
+    };
+    let kind = if rng.gen_bool(0.5) { PredatorKind::Spider } else { PredatorKind::Anteater };
+    spawn_predator(&mut commands, kind, settings.mutators.predator_scale(), hex_pos(&layout, edge));
+}
+
+// `scale` multiplies size, health and bite (see the giant predators mutator)
+pub fn spawn_predator(commands: &mut Commands, kind: PredatorKind, scale: f32, pos: Vec2) {
+    let (color, size) = kind.sprite();
+    let size = size * scale;
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite { color, custom_size: Some(Vec2::splat(size)), ..default() },
+            transform: Transform::from_xyz(pos.x, pos.y, 1.0),
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::ball(size / 2.0),
+        collision::unit_collision_groups(),
+        Velocity::zero(),
+        Damping { linear_damping: 20.0, angular_damping: 1.0 },
+        LockedAxes::ROTATION_LOCKED,
+        Health::new(kind.health() * scale),
+        Predator {
+            kind,
+            scale,
+            wander_target: pos,
+            attack_timer: Timer::from_seconds(ATTACK_SECS, TimerMode::Repeating),
+            fleeing: false,
+        },
+    ));
+}
+
+// Steering: wander between nearby hexes, or run for the map edge when swarmed
+fn steer_predators(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    mut sim_rng: ResMut<SimRng>,
+    mut predator_q: Query<(Entity, &mut Predator, &Transform, &mut Velocity)>,
+) {
+    let rng = &mut sim_rng.0;
+    for (entity, mut predator, transform, mut velocity) in predator_q.iter_mut() {
+        let pos = transform.translation.truncate();
+        let current = layout.0.world_to_hex(pos);
+        if predator.fleeing && current.unsigned_distance_to(Hex::ZERO) + 1 >= map_size.radius {
+            // Made it to the edge and escapes
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let delta = predator.wander_target - pos;
+        if delta.length() < 4.0 {
+            if let Some(next) = current
+                .range(WANDER_RADIUS)
+                .filter(|&hex| map_size.contains(hex))
+                .choose(rng)
+            {
+                predator.wander_target = hex_pos(&layout, next);
+            }
+            continue;
+        }
+
+        let speed = predator.kind.speed() * if predator.fleeing { 1.5 } else { 1.0 };
+        velocity.linvel = delta.normalize_or_zero() * speed;
+    }
+}
+
+// Ants next to a predator that aren't already fighting something take it on,
+// and let go of it once it's out of reach
+fn engage_predators(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    spatial_index: Res<SpatialIndex>,
+    predator_q: Query<(Entity, &Transform), With<Predator>>,
+    // Dug-in and underground ants are out of reach
+    ant_q: Query<Has<Engaged>, (With<Ant>, Without<Queen>, Without<DugIn>, Without<InTunnel>)>,
+    engaged_q: Query<(Entity, &Engaged, &Transform), With<Ant>>,
+) {
+    for (ant, engaged, transform) in engaged_q.iter() {
+        let Ok((_, predator_transform)) = predator_q.get(engaged.0) else {
+            continue;
+        };
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        if layout.0.world_to_hex(predator_transform.translation.truncate()).unsigned_distance_to(hex) > 1 {
+            commands.entity(ant).remove::<Engaged>();
+        }
+    }
+
+    for (predator, transform) in predator_q.iter() {
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        for ant in spatial_index.within(hex, 1) {
+            if ant_q.get(ant).is_ok_and(|engaged| !engaged) {
+                commands.entity(ant).insert(Engaged(predator));
+            }
+        }
+    }
+}
+
+// Attack ticks: the predator bites ants and brood in reach, and runs once
+// swarmed by soldiers
+fn predator_combat(
+    time: Res<Time>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    spatial_index: Res<SpatialIndex>,
+    mut predator_q: Query<(&mut Predator, &Transform), Without<Ant>>,
+    // Dug-in ants are out of reach
+    mut ant_q: Query<(&mut Health, &Caste), (With<Ant>, Without<Queen>, Without<DugIn>)>,
+    mut brood_q: Query<(&Transform, &mut Health), (With<Brood>, Without<Predator>, Without<Ant>)>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for (mut predator, transform) in predator_q.iter_mut() {
+        if !predator.attack_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let pos = transform.translation.truncate();
+        let hex = layout.0.world_to_hex(pos);
+
+        let (damage, reach) = predator.kind.attack();
+        let damage = damage * predator.scale;
+        if !predator.fleeing {
+            for ant in spatial_index.within(hex, reach) {
+                if let Ok((mut ant_health, _)) = ant_q.get_mut(ant) {
+                    ant_health.damage(damage);
+                    sounds.send(PlaySound::at(SoundEffect::Hit, pos));
+                }
+            }
+            // Unguarded brood is easy prey
+            for (brood_transform, mut brood_health) in brood_q.iter_mut() {
+                if layout.0.world_to_hex(brood_transform.translation.truncate()).unsigned_distance_to(hex) <= reach {
+                    brood_health.damage(damage);
+                    sounds.send(PlaySound::at(SoundEffect::Hit, pos));
+                }
+            }
+        }
+
+        let soldiers = spatial_index
+            .within(hex, 1)
+            .filter(|&ant| ant_q.get(ant).is_ok_and(|(_, caste)| *caste == Caste::Soldier))
+            .count();
+        if soldiers >= SWARM_COUNT && !predator.fleeing {
+            // Run straight out past the nearest edge
+            predator.fleeing = true;
+            let away = if pos == Vec2::ZERO { Vec2::X } else { pos.normalize() };
+            let map_extent = map_size.world_rect(&layout.0).half_size().max_element();
+            predator.wander_target = away * map_extent * 2.0;
+        }
+    }
+}
+
+pub struct PredatorsPlugin;
+
+impl Plugin for PredatorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PredatorSpawner(Timer::from_seconds(SPAWN_SECS, TimerMode::Repeating)))
+            .add_systems(
+                Update,
+                (
+                    spawn_predators,
+                    steer_predators,
+                    (engage_predators, predator_combat).after(crate::spatial::update_spatial_index),
+                )
+                    .run_if(in_state(crate::loading::AppState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use bevy::render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages};
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy_rapier2d::prelude::*;
+use hexx::{Hex, HexLayout};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+use crate::burrow::BurrowNetwork;
+use crate::collision;
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::coords::HexLayoutExt;
+use crate::determinism::SimRng;
+use crate::interactions::FOOD_PILE_HEXES;
+use crate::loading::AppState;
+use crate::{chunk_of, MapLayout, MapSize};
+
+// Rock: solid hexes nothing walks through or builds on. A few outcrops are
+// scattered over the map as the match starts, clear of the nest in the
+// middle, tunnel entrances and the food piles; `rock <q> <r>` adds or removes
+// one by hand. Rock is kept in the world save with the rest of its chunk (see
+// `save`), and a world that comes back with rock keeps it instead of getting
+// new outcrops.
+// Pathfinding routes around rock (see `Passability`), and for physics every
+// chunk with rock in it gets one fixed compound collider of hexagons in the
+// terrain group, so units, raycasts and anything else Rapier moves meet the
+// same walls the pathfinder does. A chunk's collider and mesh are rebuilt
+// only when rock in it changes.
+
+const OUTCROPS: usize = 4;
+// Rock hexes per outcrop, at most: its center and some of its neighbors
+const OUTCROP_SIZE: usize = 3;
+// Outcrops keep at least this many hexes away from the map center
+const NEST_CLEARANCE: u32 = 3;
+
+#[derive(Resource, Default)]
+pub struct Rocks {
+    hexes: HashSet<Hex>,
+    // Chunks whose collider and mesh are out of date
+    dirty_chunks: HashSet<Hex>,
+    // Chunks changed since the world was last saved
+    unsaved_chunks: HashSet<Hex>,
+}
+
+impl Rocks {
+    pub fn contains(&self, hex: Hex) -> bool {
+        self.hexes.contains(&hex)
+    }
+
+    pub fn hexes(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.hexes.iter().copied()
+    }
+
+    // Rock hexes inside one chunk, sorted so saves are stable
+    pub fn chunk_contents(&self, chunk: Hex) -> Vec<Hex> {
+        let mut hexes: Vec<Hex> = self.hexes().filter(|&hex| chunk_of(hex) == chunk).collect();
+        hexes.sort_by_key(|hex| (hex.x, hex.y));
+        hexes
+    }
+
+    // Overwrite one chunk with saved contents (doesn't mark it unsaved)
+    pub fn replace_chunk(&mut self, chunk: Hex, hexes: &[Hex]) {
+        self.hexes.retain(|&hex| chunk_of(hex) != chunk);
+        self.hexes.extend(hexes.iter().copied());
+        self.dirty_chunks.insert(chunk);
+    }
+
+    pub fn take_unsaved_chunks(&mut self) -> HashSet<Hex> {
+        std::mem::take(&mut self.unsaved_chunks)
+    }
+
+    fn set(&mut self, hex: Hex, rock: bool) {
+        let changed = if rock { self.hexes.insert(hex) } else { self.hexes.remove(&hex) };
+        if changed {
+            self.dirty_chunks.insert(chunk_of(hex));
+            self.unsaved_chunks.insert(chunk_of(hex));
+        }
+    }
+}
+
+fn scatter_rocks(
+    map_size: Res<MapSize>,
+    network: Res<BurrowNetwork>,
+    mut rng: ResMut<SimRng>,
+    mut rocks: ResMut<Rocks>,
+) {
+    // Already in the world save
+    if !rocks.hexes.is_empty() {
+        return;
+    }
+    let candidates: Vec<Hex> = map_size
+        .hexes()
+        .filter(|hex| hex.unsigned_distance_to(Hex::ZERO) >= NEST_CLEARANCE)
+        .collect();
+    if candidates.is_empty() {
+        return;
+    }
+    let allowed = |hex: Hex| {
+        map_size.contains(hex)
+            && hex.unsigned_distance_to(Hex::ZERO) >= NEST_CLEARANCE
+            && !network.is_entrance(hex)
+            && !FOOD_PILE_HEXES.contains(&hex)
+    };
+    for _ in 0..OUTCROPS {
+        let center = candidates[rng.0.gen_range(0..candidates.len())];
+        let outcrop = std::iter::once(center).chain(center.all_neighbors().into_iter().filter(|_| rng.0.gen_bool(0.5)));
+        for hex in outcrop.filter(|&hex| allowed(hex)).take(OUTCROP_SIZE).collect::<Vec<_>>() {
+            rocks.set(hex, true);
+        }
+    }
+}
+
+// The rock hexes of one chunk, with the collider and mesh made from them
+#[derive(Component)]
+struct RockChunk(Hex);
+
+#[derive(Resource)]
+struct RockMaterial(Handle<ColorMaterial>);
+
+fn setup_rock_material(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    let material = materials.add(ColorMaterial::from(Color::from(Srgba::hex("6D6A64").unwrap())));
+    commands.insert_resource(RockMaterial(material));
+}
+
+// One convex hexagon per rock, placed at its hex center
+fn rock_collider(layout: &HexLayout, hexes: &[Hex]) -> Option<Collider> {
+    let shapes: Vec<(Vec2, f32, Collider)> = hexes
+        .iter()
+        .filter_map(|&hex| {
+            let center = layout.hex_to_world(hex);
+            let corners = layout.world_corners(hex).map(|corner| corner - center);
+            Collider::convex_polyline(corners.to_vec()).map(|shape| (center, 0.0, shape))
+        })
+        .collect();
+    (!shapes.is_empty()).then(|| Collider::compound(shapes))
+}
+
+// Filled hexagons, as a fan of triangles around each center
+fn rock_mesh(layout: &HexLayout, hexes: &[Hex]) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for &hex in hexes {
+        let center = layout.hex_to_world(hex);
+        let corners = layout.world_corners(hex);
+        for i in 0..6 {
+            let (start, end) = (corners[i], corners[(i + 1) % 6]);
+            positions.extend([[center.x, center.y, 0.0], [start.x, start.y, 0.0], [end.x, end.y, 0.0]]);
+        }
+    }
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
+
+fn sync_rock_chunks(
+    mut commands: Commands,
+    mut rocks: ResMut<Rocks>,
+    layout: Res<MapLayout>,
+    material: Res<RockMaterial>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunk_q: Query<(Entity, &RockChunk)>,
+) {
+    if rocks.dirty_chunks.is_empty() {
+        return;
+    }
+    let dirty = std::mem::take(&mut rocks.dirty_chunks);
+    for (entity, chunk) in chunk_q.iter() {
+        if dirty.contains(&chunk.0) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let mut by_chunk: HashMap<Hex, Vec<Hex>> = HashMap::new();
+    for hex in rocks.hexes().filter(|&hex| dirty.contains(&chunk_of(hex))) {
+        by_chunk.entry(chunk_of(hex)).or_default().push(hex);
+    }
+    for (chunk, mut hexes) in by_chunk {
+        // Same shapes in the same order however the set happens to iterate
+        hexes.sort_by_key(|hex| (hex.x, hex.y));
+        let Some(collider) = rock_collider(&layout.0, &hexes) else {
+            continue;
+        };
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: meshes.add(rock_mesh(&layout.0, &hexes)).into(),
+                material: material.0.clone(),
+                // Over the grid, under everything standing on the map
+                transform: Transform::from_xyz(0.0, 0.0, 0.05),
+                ..default()
+            },
+            RigidBody::Fixed,
+            collider,
+            collision::terrain_collision_groups(),
+            RockChunk(chunk),
+        ));
+    }
+}
+
+fn rock_command(In(args): In<Vec<String>>, map_size: Res<MapSize>, mut rocks: ResMut<Rocks>) -> CommandResult {
+    let [q, r] = args.as_slice() else {
+        return Err("usage: rock <q> <r>".to_string());
+    };
+    let (Ok(q), Ok(r)) = (q.parse(), r.parse()) else {
+        return Err(format!("not a hex: {q} {r}"));
+    };
+    let hex = Hex::new(q, r);
+    if !map_size.contains(hex) {
+        return Err(format!("({q}, {r}) is off the map"));
+    }
+    let rock = !rocks.contains(hex);
+    rocks.set(hex, rock);
+    Ok(format!("({q}, {r}) is {}", if rock { "rock" } else { "clear" }))
+}
+
+pub struct RocksPlugin;
+
+impl Plugin for RocksPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rocks>()
+            .register_console_command("rock", "rock <q> <r>: add or remove rock on a hex", rock_command)
+            .add_systems(Startup, setup_rock_material)
+            // Once the match has its map size, and the save has had its say
+            .add_systems(
+                OnEnter(AppState::InGame),
+                scatter_rocks.after(crate::lobby::apply_match_settings).before(crate::save::restore_structures),
+            )
+            .add_systems(Update, sync_rock_chunks);
+    }
+}
@@ -0,0 +1,315 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+
+use crate::coords::HexLayoutExt;
+use crate::game_commands::GameCommand;
+use crate::health::Health;
+use crate::interactions::spawn_food_source;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::lobby::MatchSettings;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::predators::{self, PredatorKind};
+use crate::structures::{self, StructureKind, Structures};
+use crate::units::UnitSpawner;
+use crate::weather::{Flooded, Weather};
+use crate::{Caste, Faction, MainCamera, MapLayout, MapSize, Selected};
+
+// Sandbox toolbox (built with `--features sandbox`): F1 opens it, G applies
+// the chosen tool at the hex under the mouse. Every tool only sends a
+// `GameCommand::Sandbox`, like any other order, and `apply_sandbox_commands`
+// is the one place that changes the world, so a recorded stream of game
+// commands replays the same session, sandbox tools included.
+
+const FOOD_PER_PAINT: u32 = 10;
+// Hexes around the cursor a flood puts under water
+const FLOOD_RADIUS: u32 = 2;
+// Factions the toolbox can cycle through
+const FACTION_COUNT: u32 = 4;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SandboxCommand {
+    SpawnUnit { caste: Caste, player: PlayerId, hex: Hex },
+    // A finished structure, free of charge
+    SpawnStructure { kind: StructureKind, player: PlayerId, hex: Hex },
+    PaintFood { hex: Hex },
+    StartRain,
+    // World events, at a hex of the map
+    SpawnPredator { kind: PredatorKind, hex: Hex },
+    Flood { hex: Hex },
+    // Flips invulnerability on these units
+    ToggleInvulnerable { units: Vec<Entity> },
+    // Take over another colony: panels, upgrades and spawns follow the new player
+    ControlPlayer(PlayerId),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SandboxTool {
+    Spawn(Caste),
+    Build(StructureKind),
+    PaintFood,
+    Predator(PredatorKind),
+    Flood,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum SandboxButton {
+    Tool(SandboxTool),
+    NextFaction,
+    PlayAs,
+    Rain,
+    Invulnerable,
+}
+
+#[derive(Resource)]
+struct Sandbox {
+    tool: SandboxTool,
+    player: PlayerId,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self { tool: SandboxTool::Spawn(Caste::Worker), player: PlayerId(0) }
+    }
+}
+
+#[derive(Component)]
+struct SandboxPanel;
+
+fn setup_sandbox_panel(mut commands: Commands, local_player: Res<LocalPlayer>) {
+    commands.insert_resource(Sandbox { player: local_player.0, ..default() });
+
+    let buttons = [
+        (SandboxButton::Tool(SandboxTool::Spawn(Caste::Worker)), "Spawn worker"),
+        (SandboxButton::Tool(SandboxTool::Spawn(Caste::Soldier)), "Spawn soldier"),
+        (SandboxButton::Tool(SandboxTool::Spawn(Caste::Queen)), "Spawn queen"),
+        (SandboxButton::Tool(SandboxTool::Build(StructureKind::StorageChamber)), "Build storage chamber"),
+        (SandboxButton::Tool(SandboxTool::Build(StructureKind::Nursery)), "Build nursery"),
+        (SandboxButton::Tool(SandboxTool::Build(StructureKind::GuardPost)), "Build guard post"),
+        (SandboxButton::Tool(SandboxTool::PaintFood), "Paint food"),
+        (SandboxButton::Tool(SandboxTool::Predator(PredatorKind::Spider)), "Spawn spider"),
+        (SandboxButton::Tool(SandboxTool::Predator(PredatorKind::Anteater)), "Spawn anteater"),
+        (SandboxButton::Tool(SandboxTool::Flood), "Flood"),
+        (SandboxButton::NextFaction, ""),
+        (SandboxButton::PlayAs, "Play as faction"),
+        (SandboxButton::Rain, "Start rain"),
+        (SandboxButton::Invulnerable, "Toggle invulnerable"),
+    ];
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(48.0),
+                    left: Val::Px(8.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.85).into(),
+                ..default()
+            },
+            Interaction::default(),
+            SandboxPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section(
+                "Sandbox (G: apply at cursor)",
+                TextStyle { font_size: 18.0, color: Color::WHITE, ..default() },
+            ));
+            for (button, label) in buttons {
+                panel
+                    .spawn((
+                        ButtonBundle {
+                            style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+                            background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                            ..default()
+                        },
+                        button,
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            label,
+                            TextStyle { font_size: 14.0, color: Color::WHITE, ..default() },
+                        ));
+                    });
+            }
+        });
+}
+
+fn toggle_sandbox_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut style_q: Query<&mut Style, With<SandboxPanel>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleSandbox) {
+        return;
+    }
+    for mut style in style_q.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn sandbox_buttons(
+    button_q: Query<(&Interaction, &SandboxButton), Changed<Interaction>>,
+    selected_q: Query<Entity, With<Selected>>,
+    mut sandbox: ResMut<Sandbox>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match *button {
+            SandboxButton::Tool(tool) => sandbox.tool = tool,
+            SandboxButton::NextFaction => sandbox.player = PlayerId((sandbox.player.0 + 1) % FACTION_COUNT),
+            SandboxButton::PlayAs => {
+                game_commands.send(GameCommand::Sandbox(SandboxCommand::ControlPlayer(sandbox.player)));
+            }
+            SandboxButton::Rain => {
+                game_commands.send(GameCommand::Sandbox(SandboxCommand::StartRain));
+            }
+            SandboxButton::Invulnerable => {
+                let units = selected_q.iter().collect();
+                game_commands.send(GameCommand::Sandbox(SandboxCommand::ToggleInvulnerable { units }));
+            }
+        }
+    }
+}
+
+// Apply the current tool at the hex under the mouse
+fn sandbox_apply_at_cursor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    panel_q: Query<&Style, With<SandboxPanel>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    sandbox: Res<Sandbox>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::SandboxApply) {
+        return;
+    }
+    // Tools only work while the toolbox is open
+    if panel_q.iter().all(|style| style.display == Display::None) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera_q.get_single()) else {
+        return;
+    };
+    let Some(world_pos) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let hex = layout.0.world_to_hex(world_pos);
+    if !map_size.contains(hex) {
+        return;
+    }
+
+    let player = sandbox.player;
+    game_commands.send(GameCommand::Sandbox(match sandbox.tool {
+        SandboxTool::Spawn(caste) => SandboxCommand::SpawnUnit { caste, player, hex },
+        SandboxTool::Build(kind) => SandboxCommand::SpawnStructure { kind, player, hex },
+        SandboxTool::PaintFood => SandboxCommand::PaintFood { hex },
+        SandboxTool::Predator(kind) => SandboxCommand::SpawnPredator { kind, hex },
+        SandboxTool::Flood => SandboxCommand::Flood { hex },
+    }));
+}
+
+fn apply_sandbox_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    settings: Res<MatchSettings>,
+    spawner: UnitSpawner,
+    mut structures: ResMut<Structures>,
+    mut weather: ResMut<Weather>,
+    mut flooded: ResMut<Flooded>,
+    mut local_player: ResMut<LocalPlayer>,
+    mut health_q: Query<&mut Health>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Sandbox(command) = command else {
+            continue;
+        };
+        match *command {
+            SandboxCommand::SpawnUnit { caste, player, hex } => {
+                spawner.spawn(&mut commands, caste, Faction(player), layout.0.hex_to_world(hex));
+            }
+            SandboxCommand::SpawnStructure { kind, player, hex } => {
+                if !structures.occupies(hex) {
+                    structures::spawn_structure(&mut commands, &mut structures, &layout, kind, player, hex, 1.0);
+                }
+            }
+            SandboxCommand::PaintFood { hex } => {
+                spawn_food_source(&mut commands, layout.0.hex_to_world(hex), FOOD_PER_PAINT);
+            }
+            SandboxCommand::StartRain => weather.start_rain(),
+            SandboxCommand::SpawnPredator { kind, hex } => {
+                let scale = settings.mutators.predator_scale();
+                predators::spawn_predator(&mut commands, kind, scale, layout.0.hex_to_world(hex));
+            }
+            SandboxCommand::Flood { hex } => {
+                weather.flood(&mut flooded, hex.range(FLOOD_RADIUS).filter(|&hex| map_size.contains(hex)));
+            }
+            SandboxCommand::ToggleInvulnerable { ref units } => {
+                let mut healths = health_q.iter_many_mut(units);
+                while let Some(mut health) = healths.fetch_next() {
+                    health.invulnerable = !health.invulnerable;
+                }
+            }
+            SandboxCommand::ControlPlayer(player) => local_player.0 = player,
+        }
+    }
+}
+
+fn update_sandbox_labels(
+    sandbox: Res<Sandbox>,
+    mut button_q: Query<(&SandboxButton, &mut BackgroundColor, &Children)>,
+    mut text_q: Query<&mut Text>,
+) {
+    if !sandbox.is_changed() {
+        return;
+    }
+    for (button, mut background, children) in button_q.iter_mut() {
+        let active = *button == SandboxButton::Tool(sandbox.tool);
+        *background = if active { Color::srgb(0.3, 0.45, 0.3) } else { Color::srgb(0.25, 0.25, 0.25) }.into();
+
+        if *button == SandboxButton::NextFaction {
+            for &child in children.iter() {
+                if let Ok(mut text) = text_q.get_mut(child) {
+                    text.sections[0].value = format!("Faction: {}", sandbox.player.0);
+                }
+            }
+        }
+    }
+}
+
+pub struct SandboxPlugin;
+
+impl Plugin for SandboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_action(Action::ToggleSandbox, &[KeyCode::F1])
+            .register_action(Action::SandboxApply, &[KeyCode::KeyG])
+            .add_systems(Startup, setup_sandbox_panel)
+            .add_systems(
+                Update,
+                (
+                    (toggle_sandbox_panel, sandbox_buttons, sandbox_apply_at_cursor).chain().before(crate::dispatch_pointer_actions),
+                    apply_sandbox_commands.after(crate::dispatch_pointer_actions),
+                    update_sandbox_labels,
+                ),
+            );
+    }
+}
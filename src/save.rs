@@ -0,0 +1,303 @@
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use hexx::Hex;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::burrow::BurrowNetwork;
+use crate::genetics::{GenePool, Genes};
+use crate::harvest::{HarvestZones, SavedZone};
+use crate::net::PlayerId;
+use crate::rocks::Rocks;
+use crate::scoring::{Leaderboard, MatchEnded};
+use crate::storage::storage;
+use crate::structures::{self, Structure, StructureKind, Structures};
+use crate::veterancy::{ReturningVeterans, SavedVeteran, Veterancy};
+use crate::{chunk_of, Caste, Faction, MapLayout};
+
+// Persistent world = generated base map + one diff file per touched chunk
+// (tunnels, entrances, structures and rock). Only chunks changed since the
+// last save are rewritten, so saving a huge, mostly untouched world costs
+// next to nothing. Next to the chunks, one small file keeps the colony data
+// that outlives a match: gene pools, harvest zones, veterans and the
+// leaderboard. It's rewritten on every save. Files go through `storage`, so
+// in the browser the world is kept in localStorage and survives reloads. The
+// world is saved every few seconds, when a match ends and when the game
+// closes. A saved structure whose hex has become rock is left out.
+
+const SAVE_DIR: &str = "saves/world";
+const CHUNK_PREFIX: &str = "chunk_";
+const COLONIES_PATH: &str = "saves/world/colonies.ron";
+const AUTOSAVE_SECS: f32 = 10.0;
+
+// A structure as it's saved
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct SavedStructure {
+    hex: Hex,
+    kind: StructureKind,
+    owner: PlayerId,
+    progress: f32,
+}
+
+// Full contents of a chunk that differs from the base map
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct ChunkDiff {
+    chunk: Hex,
+    dug: Vec<Hex>,
+    // With the colony that dug each
+    entrances: Vec<(Hex, PlayerId)>,
+    // Missing from saves made before structures were saved
+    #[serde(default)]
+    structures: Vec<SavedStructure>,
+    // Likewise for rock
+    #[serde(default)]
+    rocks: Vec<Hex>,
+}
+
+impl ChunkDiff {
+    // What the world holds in `chunk` now, sorted so saves are stable
+    fn collect(
+        chunk: Hex,
+        network: &BurrowNetwork,
+        rocks: &Rocks,
+        structures: impl Iterator<Item = SavedStructure>,
+    ) -> Self {
+        let (dug, entrances) = network.chunk_contents(chunk);
+        let mut structures: Vec<SavedStructure> = structures.filter(|saved| chunk_of(saved.hex) == chunk).collect();
+        structures.sort_by_key(|saved| (saved.hex.x, saved.hex.y));
+        Self { chunk, dug, entrances, structures, rocks: rocks.chunk_contents(chunk) }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ColonyRecords {
+    #[serde(default)]
+    gene_pools: BTreeMap<u32, Genes>,
+    #[serde(default)]
+    harvest_zones: Vec<SavedZone>,
+    #[serde(default)]
+    veterans: BTreeMap<u32, Vec<SavedVeteran>>,
+    #[serde(default)]
+    leaderboard: Leaderboard,
+}
+
+// Structures read from the chunk saves, raised once the world is set up
+#[derive(Resource, Default)]
+pub struct PendingStructures(Vec<SavedStructure>);
+
+#[derive(Resource)]
+struct AutosaveTimer(Timer);
+
+fn chunk_path(chunk: Hex) -> String {
+    format!("{SAVE_DIR}/{CHUNK_PREFIX}{}_{}.ron", chunk.x, chunk.y)
+}
+
+fn write_ron(path: &str, value: &impl Serialize) -> Result<(), String> {
+    ron::ser::to_string_pretty(value, PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage().write(path, &contents))
+}
+
+// Everything the world save reads from and restores into
+#[derive(SystemParam)]
+pub struct WorldSave<'w, 's> {
+    network: ResMut<'w, BurrowNetwork>,
+    rocks: ResMut<'w, Rocks>,
+    structures: ResMut<'w, Structures>,
+    structure_q: Query<'w, 's, (&'static Structure, &'static Faction)>,
+    gene_pool: ResMut<'w, GenePool>,
+    harvest_zones: ResMut<'w, HarvestZones>,
+    returning_veterans: ResMut<'w, ReturningVeterans>,
+    veteran_q: Query<'w, 's, (&'static Faction, &'static Caste, &'static Veterancy)>,
+    leaderboard: ResMut<'w, Leaderboard>,
+}
+
+impl WorldSave<'_, '_> {
+    fn load(&mut self, pending: &mut PendingStructures) {
+        // Nothing listed for a fresh world
+        let chunks = storage().list(SAVE_DIR).into_iter().filter(|path| {
+            path.rsplit('/').next().is_some_and(|name| name.starts_with(CHUNK_PREFIX))
+        });
+        for path in chunks {
+            let diff = storage()
+                .read(&path)
+                .ok_or_else(|| "could not read it".to_string())
+                .and_then(|contents| ron::from_str::<ChunkDiff>(&contents).map_err(|err| err.to_string()));
+            match diff {
+                Ok(diff) => {
+                    self.network.replace_chunk(diff.chunk, &diff.dug, &diff.entrances);
+                    self.rocks.replace_chunk(diff.chunk, &diff.rocks);
+                    pending.0.extend(diff.structures);
+                }
+                Err(err) => warn!("Skipping chunk save {path}: {err}"),
+            }
+        }
+
+        let Some(contents) = storage().read(COLONIES_PATH) else {
+            return;
+        };
+        match ron::from_str::<ColonyRecords>(&contents) {
+            Ok(records) => {
+                *self.gene_pool = GenePool::from_saved(records.gene_pools);
+                *self.harvest_zones = HarvestZones::from_saved(records.harvest_zones);
+                *self.returning_veterans = ReturningVeterans::from_saved(records.veterans);
+                *self.leaderboard = records.leaderboard;
+            }
+            Err(err) => warn!("Ignoring {COLONIES_PATH}: {err}"),
+        }
+    }
+
+    // Writes every chunk changed since the last save and the colony records;
+    // returns how many chunks there were
+    pub fn save(&mut self) -> usize {
+        let mut dirty = self.network.take_dirty_chunks();
+        dirty.extend(self.rocks.take_unsaved_chunks());
+        dirty.extend(self.structures.take_dirty_chunks());
+        let count = dirty.len();
+
+        for chunk in dirty {
+            let structures = self.structure_q.iter().map(|(structure, faction)| SavedStructure {
+                hex: structure.hex(),
+                kind: structure.kind,
+                owner: faction.0,
+                progress: structure.progress(),
+            });
+            let diff = ChunkDiff::collect(chunk, &self.network, &self.rocks, structures);
+            if let Err(err) = write_ron(&chunk_path(chunk), &diff) {
+                warn!("Failed to save chunk ({}, {}): {err}", chunk.x, chunk.y);
+            }
+        }
+
+        let records = ColonyRecords {
+            gene_pools: self.gene_pool.to_saved(),
+            harvest_zones: self.harvest_zones.to_saved(),
+            veterans: self.returning_veterans.to_saved(self.veteran_q.iter()),
+            leaderboard: self.leaderboard.clone(),
+        };
+        if let Err(err) = write_ron(COLONIES_PATH, &records) {
+            warn!("Failed to save the colonies: {err}");
+        }
+        count
+    }
+}
+
+pub fn load_world(mut world_save: WorldSave, mut pending: ResMut<PendingStructures>) {
+    world_save.load(&mut pending);
+}
+
+pub fn restore_structures(
+    mut commands: Commands,
+    mut pending: ResMut<PendingStructures>,
+    mut structures: ResMut<Structures>,
+    rocks: Res<Rocks>,
+    layout: Res<MapLayout>,
+) {
+    for saved in pending.0.drain(..) {
+        if structures.occupies(saved.hex) || rocks.contains(saved.hex) {
+            continue;
+        }
+        structures::spawn_structure(
+            &mut commands,
+            &mut structures,
+            &layout,
+            saved.kind,
+            saved.owner,
+            saved.hex,
+            saved.progress,
+        );
+    }
+    // They are saved already
+    structures.take_dirty_chunks();
+}
+
+fn autosave_world(time: Res<Time<Real>>, mut timer: ResMut<AutosaveTimer>, mut world_save: WorldSave) {
+    if timer.0.tick(time.delta()).just_finished() {
+        world_save.save();
+    }
+}
+
+fn save_on_match_end_or_exit(
+    mut ended: EventReader<MatchEnded>,
+    mut exit_events: EventReader<AppExit>,
+    mut world_save: WorldSave,
+) {
+    let ended = ended.read().count() > 0;
+    if exit_events.read().next().is_some() || ended {
+        world_save.save();
+    }
+}
+
+pub struct WorldSavePlugin;
+
+impl Plugin for WorldSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AutosaveTimer(Timer::from_seconds(AUTOSAVE_SECS, TimerMode::Repeating)))
+            .init_resource::<PendingStructures>()
+            .add_systems(Startup, load_world)
+            // Before the colonies are placed, so nothing spawns on a structure
+            .add_systems(OnEnter(crate::loading::AppState::InGame), restore_structures.before(crate::spawn_units))
+            .add_systems(Update, autosave_world)
+            .add_systems(Last, save_on_match_end_or_exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(hex: Hex, progress: f32) -> SavedStructure {
+        SavedStructure { hex, kind: StructureKind::Nursery, owner: PlayerId(1), progress }
+    }
+
+    #[test]
+    fn collect_keeps_only_what_is_in_the_chunk() {
+        let mut network = BurrowNetwork::default();
+        let inside = Hex::new(1, 0);
+        let outside = Hex::new(30, -10);
+        assert_ne!(chunk_of(inside), chunk_of(outside));
+        network.dig(inside);
+        network.add_entrance(outside, PlayerId(1));
+        let mut rocks = Rocks::default();
+        let rock = Hex::new(-1, 0);
+        rocks.replace_chunk(chunk_of(rock), &[rock]);
+        rocks.replace_chunk(chunk_of(outside), &[outside + Hex::new(1, 0)]);
+
+        let structures = [saved(inside, 0.5), saved(outside, 1.0)].into_iter();
+        let diff = ChunkDiff::collect(chunk_of(inside), &network, &rocks, structures);
+        assert_eq!(diff.dug, vec![inside]);
+        assert!(diff.entrances.is_empty());
+        assert_eq!(diff.structures, vec![saved(inside, 0.5)]);
+        assert_eq!(diff.rocks, vec![rock]);
+    }
+
+    #[test]
+    fn collected_structures_are_sorted_by_hex() {
+        let network = BurrowNetwork::default();
+        let (a, b) = (Hex::new(0, 1), Hex::new(1, 0));
+        let diff = ChunkDiff::collect(chunk_of(a), &network, &Rocks::default(), [saved(b, 1.0), saved(a, 1.0)].into_iter());
+        assert_eq!(diff.structures, vec![saved(a, 1.0), saved(b, 1.0)]);
+    }
+
+    #[test]
+    fn chunk_diffs_round_trip_through_ron() {
+        let mut network = BurrowNetwork::default();
+        network.add_entrance(Hex::new(2, -1), PlayerId(1));
+        let chunk = chunk_of(Hex::new(2, -1));
+        let mut rocks = Rocks::default();
+        rocks.replace_chunk(chunk, &[Hex::new(3, -1)]);
+        let diff = ChunkDiff::collect(chunk, &network, &rocks, std::iter::once(saved(Hex::new(2, -1), 0.25)));
+
+        let contents = ron::ser::to_string_pretty(&diff, PrettyConfig::default()).unwrap();
+        assert_eq!(ron::from_str::<ChunkDiff>(&contents).unwrap(), diff);
+    }
+
+    #[test]
+    fn chunk_saves_without_structures_still_load() {
+        let diff: ChunkDiff = ron::from_str("(chunk: (x: 0, y: 0), dug: [(x: 1, y: 0)], entrances: [])").unwrap();
+        assert_eq!(diff.dug, vec![Hex::new(1, 0)]);
+        assert!(diff.structures.is_empty());
+        assert!(diff.rocks.is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+use bevy::asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use hexx::Hex;
+use serde::Deserialize;
+
+use crate::coords::HexLayoutExt;
+use crate::interactions::spawn_food_source;
+use crate::loading::{AppState, LoadingAssets};
+use crate::locale::Locale;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::photo_mode::PhotoMode;
+use crate::placement::SpawnPlacer;
+use crate::scoring::Scores;
+use crate::units::UnitSpawner;
+use crate::{Ant, Caste, Faction, MainCamera, MapLayout, Selected};
+
+// Scenarios: scripted sequences of steps in `assets/scenarios/*.scenario.ron`,
+// started with `--scenario <name>` (`--scenario tutorial` for the one shipped
+// here). When a step begins it spawns what it lists, marks a hex, moves the
+// camera and shows its message; it ends once its goal is met, and the next one
+// begins. Messages are locale keys, so a scenario can be translated, but text
+// that isn't a key shows as written. The current message and goal progress
+// are shown at the top of the screen.
+
+// How quickly the camera glides to a step's focus, per second
+const FOCUS_SMOOTHING: f32 = 4.0;
+
+#[derive(Deserialize, Clone, Debug)]
+enum Goal {
+    // Food delivered to our store since the step began
+    GatherFood(u32),
+    // One of our ants standing on the hex
+    Reach(Hex),
+    // At least this many of our ants selected
+    Select(u32),
+    // At least this many of our workers alive
+    Workers(u32),
+    // Seconds of game time
+    Wait(f32),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+enum Spawn {
+    // Units of `player`'s colony on the free hexes closest to `hex`
+    Units { caste: Caste, player: u32, hex: Hex, count: usize },
+    Food { hex: Hex, amount: u32 },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct Step {
+    message: String,
+    goal: Goal,
+    #[serde(default)]
+    spawn: Vec<Spawn>,
+    // Hex the camera moves to
+    #[serde(default)]
+    focus: Option<Hex>,
+    // Hex outlined on the map until the step ends
+    #[serde(default)]
+    mark: Option<Hex>,
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+struct ScenarioFile {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug)]
+enum ScenarioFileError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl std::fmt::Display for ScenarioFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioFileError::Io(err) => write!(f, "could not read scenario: {err}"),
+            ScenarioFileError::Ron(err) => write!(f, "could not parse scenario: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioFileError {}
+
+impl From<std::io::Error> for ScenarioFileError {
+    fn from(err: std::io::Error) -> Self {
+        ScenarioFileError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for ScenarioFileError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        ScenarioFileError::Ron(err)
+    }
+}
+
+#[derive(Default)]
+struct ScenarioFileLoader;
+
+impl AssetLoader for ScenarioFileLoader {
+    type Asset = ScenarioFile;
+    type Settings = ();
+    type Error = ScenarioFileError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<ScenarioFile, ScenarioFileError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["scenario.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct ActiveScenario {
+    handle: Handle<ScenarioFile>,
+    // Index of the current step; `None` until the first one begins
+    step: Option<usize>,
+    finished: bool,
+    // Game time spent in the current step
+    elapsed_secs: f32,
+    // Our food delivered when the current step began
+    food_at_start: u32,
+    focus: Option<Vec2>,
+}
+
+fn scenario_from_args() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2).find(|pair| pair[0] == "--scenario").map(|pair| pair[1].clone())
+}
+
+fn load_scenario(mut commands: Commands, asset_server: Res<AssetServer>, mut loading: ResMut<LoadingAssets>) {
+    let Some(name) = scenario_from_args() else {
+        return;
+    };
+    let handle = asset_server.load::<ScenarioFile>(format!("scenarios/{name}.scenario.ron"));
+    loading.queue(handle.clone());
+    commands.insert_resource(ActiveScenario {
+        handle,
+        step: None,
+        finished: false,
+        elapsed_secs: 0.0,
+        food_at_start: 0,
+        focus: None,
+    });
+}
+
+type AntQuery<'w, 's> = Query<'w, 's, (&'static Transform, &'static Faction, &'static Caste, Has<Selected>), With<Ant>>;
+
+// Hex, whether selected and whether a worker, for each of our ants
+fn our_ants(ant_q: &AntQuery, layout: &MapLayout, player: PlayerId) -> Vec<(Hex, bool, bool)> {
+    ant_q
+        .iter()
+        .filter(|(_, faction, _, _)| faction.0 == player)
+        .map(|(transform, _, caste, selected)| {
+            (layout.0.world_to_hex(transform.translation.truncate()), selected, *caste == Caste::Worker)
+        })
+        .collect()
+}
+
+// Whether the step's goal is met, and how far along it is for the display
+fn goal_progress(
+    goal: &Goal,
+    scenario: &ActiveScenario,
+    food: u32,
+    ours: &[(Hex, bool, bool)],
+) -> (bool, Option<(u32, u32)>) {
+    match *goal {
+        Goal::GatherFood(target) => {
+            let gathered = food.saturating_sub(scenario.food_at_start).min(target);
+            (gathered >= target, Some((gathered, target)))
+        }
+        Goal::Reach(hex) => (ours.iter().any(|&(at, _, _)| at == hex), None),
+        Goal::Select(target) => {
+            let selected = (ours.iter().filter(|&&(_, selected, _)| selected).count() as u32).min(target);
+            (selected >= target, Some((selected, target)))
+        }
+        Goal::Workers(target) => {
+            let workers = (ours.iter().filter(|&&(_, _, worker)| worker).count() as u32).min(target);
+            (workers >= target, Some((workers, target)))
+        }
+        Goal::Wait(secs) => (scenario.elapsed_secs >= secs, None),
+    }
+}
+
+fn run_scenario(
+    mut commands: Commands,
+    time: Res<Time>,
+    scenario: Option<ResMut<ActiveScenario>>,
+    files: Res<Assets<ScenarioFile>>,
+    layout: Res<MapLayout>,
+    scores: Res<Scores>,
+    local_player: Res<LocalPlayer>,
+    spawner: UnitSpawner,
+    placer: SpawnPlacer,
+    ant_q: AntQuery,
+) {
+    let Some(mut scenario) = scenario else {
+        return;
+    };
+    if scenario.finished {
+        return;
+    }
+    let Some(file) = files.get(&scenario.handle) else {
+        error!("The scenario failed to load");
+        scenario.finished = true;
+        return;
+    };
+    let food = scores.get(local_player.0).food_gathered;
+    scenario.elapsed_secs += time.delta_seconds();
+
+    if let Some(step) = scenario.step.and_then(|index| file.steps.get(index)) {
+        let ours = our_ants(&ant_q, &layout, local_player.0);
+        if !goal_progress(&step.goal, &scenario, food, &ours).0 {
+            return;
+        }
+    }
+
+    // Begin the next step
+    let next = scenario.step.map_or(0, |index| index + 1);
+    scenario.step = Some(next);
+    scenario.elapsed_secs = 0.0;
+    scenario.food_at_start = food;
+    let Some(step) = file.steps.get(next) else {
+        scenario.finished = true;
+        return;
+    };
+    scenario.focus = step.focus.map(|hex| layout.0.hex_to_world(hex));
+    for spawn in &step.spawn {
+        match *spawn {
+            Spawn::Units { caste, player, hex, count } => {
+                for (_, pos) in placer.around(hex).take(count) {
+                    spawner.spawn(&mut commands, caste, Faction(PlayerId(player)), pos);
+                }
+            }
+            Spawn::Food { hex, amount } => spawn_food_source(&mut commands, layout.0.hex_to_world(hex), amount),
+        }
+    }
+}
+
+// Glides the camera to the current step's focus, once
+fn focus_camera(
+    time: Res<Time<Real>>,
+    scenario: Option<ResMut<ActiveScenario>>,
+    mut camera_q: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Some(mut scenario) = scenario else {
+        return;
+    };
+    let Some(focus) = scenario.focus else {
+        return;
+    };
+    let Ok(mut transform) = camera_q.get_single_mut() else {
+        return;
+    };
+    let t = 1.0 - (-FOCUS_SMOOTHING * time.delta_seconds()).exp();
+    let next = transform.translation.truncate().lerp(focus, t);
+    transform.translation.x = next.x;
+    transform.translation.y = next.y;
+    if next.distance(focus) < 1.0 {
+        scenario.focus = None;
+    }
+}
+
+fn draw_marked_hex(
+    mut gizmos: Gizmos,
+    time: Res<Time<Real>>,
+    scenario: Option<Res<ActiveScenario>>,
+    files: Res<Assets<ScenarioFile>>,
+    layout: Res<MapLayout>,
+) {
+    let Some(scenario) = scenario else {
+        return;
+    };
+    let Some(hex) = files
+        .get(&scenario.handle)
+        .zip(scenario.step)
+        .filter(|_| !scenario.finished)
+        .and_then(|(file, index)| file.steps.get(index))
+        .and_then(|step| step.mark)
+    else {
+        return;
+    };
+    // Pulses so it stands out from the grid
+    let alpha = 0.6 + 0.4 * (time.elapsed_seconds() * 4.0).sin();
+    let color = Color::from(Srgba::hex("FFD54F").unwrap()).with_alpha(alpha);
+    let corners = layout.0.world_corners(hex);
+    for i in 0..6 {
+        gizmos.line_2d(corners[i], corners[(i + 1) % 6], color);
+    }
+}
+
+#[derive(Component)]
+struct ScenarioText;
+
+fn setup_scenario_text(mut commands: Commands) {
+    let mut text = TextBundle::from_section("", TextStyle { font_size: 16.0, color: Color::WHITE, ..default() })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            left: Val::Percent(30.0),
+            width: Val::Percent(40.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        })
+        .with_background_color(Color::srgba(0.1, 0.1, 0.1, 0.75));
+    // Shown while a scenario step is running
+    text.visibility = Visibility::Hidden;
+    commands.spawn((text, ScenarioText));
+}
+
+fn update_scenario_text(
+    scenario: Option<Res<ActiveScenario>>,
+    files: Res<Assets<ScenarioFile>>,
+    scores: Res<Scores>,
+    local_player: Res<LocalPlayer>,
+    locale: Res<Locale>,
+    layout: Res<MapLayout>,
+    ant_q: AntQuery,
+    photo: Res<PhotoMode>,
+    mut text_q: Query<(&mut Text, &mut Visibility), With<ScenarioText>>,
+) {
+    let step = scenario.as_ref().filter(|scenario| !scenario.finished).and_then(|scenario| {
+        let index = scenario.step?;
+        files.get(&scenario.handle)?.steps.get(index).map(|step| (scenario, step))
+    });
+    let Some((scenario, step)) = step else {
+        for (_, mut visibility) in text_q.iter_mut() {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    };
+
+    let ours = our_ants(&ant_q, &layout, local_player.0);
+    let food = scores.get(local_player.0).food_gathered;
+    let mut text = locale.get(&step.message).to_string();
+    if let (_, Some((done, target))) = goal_progress(&step.goal, scenario, food, &ours) {
+        text.push('\n');
+        text.push_str(&locale.format("scenario.progress", &[("done", &done), ("target", &target)]));
+    }
+    for (mut label, mut visibility) in text_q.iter_mut() {
+        label.sections[0].value = text.clone();
+        *visibility = if photo.is_active() { Visibility::Hidden } else { Visibility::Inherited };
+    }
+}
+
+pub struct ScenarioPlugin;
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ScenarioFile>()
+            .init_asset_loader::<ScenarioFileLoader>()
+            .add_systems(Startup, (load_scenario, setup_scenario_text))
+            .add_systems(
+                Update,
+                (run_scenario, focus_camera, draw_marked_hex, update_scenario_text)
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::locale::{Locale, Localized};
+use crate::net::{LocalPlayer, PlayerId};
+use crate::territory::Territory;
+
+// Colony scores, kept by whoever runs the simulation: food delivered to the
+// colony, hexes of territory held right now and enemy ants defeated, each
+// worth a fixed number of points. Gameplay reports food and kills through
+// `Scores`; territory is read from `Territory`. The HUD shows our score, Tab
+// the scoreboard. When a match ends (`MatchEnded`), every colony's result goes
+// into the leaderboard, which outlives the session in the world save (see
+// `save`), and the scoreboard opens as the match summary.
+
+const LEADERBOARD_SIZE: usize = 10;
+const POINTS_PER_FOOD: u32 = 1;
+const POINTS_PER_HEX: u32 = 2;
+const POINTS_PER_KILL: u32 = 10;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColonyScore {
+    pub food_gathered: u32,
+    pub territory: u32,
+    pub enemies_defeated: u32,
+}
+
+impl ColonyScore {
+    pub fn total(&self) -> u32 {
+        self.food_gathered * POINTS_PER_FOOD + self.territory * POINTS_PER_HEX + self.enemies_defeated * POINTS_PER_KILL
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Scores(BTreeMap<PlayerId, ColonyScore>);
+
+impl Scores {
+    pub fn get(&self, player: PlayerId) -> ColonyScore {
+        self.0.get(&player).copied().unwrap_or_default()
+    }
+
+    pub fn add_food(&mut self, player: PlayerId, amount: u32) {
+        self.0.entry(player).or_default().food_gathered += amount;
+    }
+
+    pub fn add_kill(&mut self, player: PlayerId) {
+        self.0.entry(player).or_default().enemies_defeated += 1;
+    }
+
+    // Highest total first
+    fn ranked(&self) -> Vec<(PlayerId, ColonyScore)> {
+        let mut ranked: Vec<_> = self.0.iter().map(|(&player, &score)| (player, score)).collect();
+        ranked.sort_by_key(|(_, score)| std::cmp::Reverse(score.total()));
+        ranked
+    }
+}
+
+// Sent by whatever decides the match is over
+#[derive(Event, Clone, Copy)]
+pub struct MatchEnded;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct LeaderboardEntry {
+    player: u32,
+    score: ColonyScore,
+}
+
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct Leaderboard(Vec<LeaderboardEntry>);
+
+impl Leaderboard {
+    fn record(&mut self, results: &[(PlayerId, ColonyScore)]) {
+        self.0.extend(results.iter().map(|&(player, score)| LeaderboardEntry { player: player.0, score }));
+        self.0.sort_by_key(|entry| std::cmp::Reverse(entry.score.total()));
+        self.0.truncate(LEADERBOARD_SIZE);
+    }
+}
+
+pub fn player_name(locale: &Locale, local_player: PlayerId, player: PlayerId) -> String {
+    if player == local_player {
+        locale.get("score.you").to_string()
+    } else {
+        locale.format("score.colony", &[("player", &player.0)])
+    }
+}
+
+fn sync_territory_scores(territory: Res<Territory>, mut scores: ResMut<Scores>) {
+    if !territory.is_changed() {
+        return;
+    }
+    for score in scores.0.values_mut() {
+        score.territory = 0;
+    }
+    for (player, held) in territory.held() {
+        scores.0.entry(player).or_default().territory = held as u32;
+    }
+}
+
+#[derive(Component)]
+struct ScoreHud;
+
+#[derive(Component)]
+struct ScorePanel;
+
+#[derive(Component)]
+struct ScorePanelTitle;
+
+#[derive(Component)]
+struct ScorePanelRows;
+
+#[derive(Component)]
+struct CloseScorePanel;
+
+// Whether the scoreboard shows a finished match rather than the one in play
+#[derive(Resource, Default)]
+struct MatchSummary(bool);
+
+fn setup_score_ui(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 16.0, color: Color::WHITE, ..default() }).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        }),
+        ScoreHud,
+    ));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(20.0),
+                    left: Val::Percent(35.0),
+                    width: Val::Percent(30.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.9).into(),
+                ..default()
+            },
+            Interaction::default(),
+            ScorePanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 20.0, color: Color::WHITE, ..default() }),
+                ScorePanelTitle,
+            ));
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }),
+                ScorePanelRows,
+            ));
+            panel
+                .spawn((
+                    ButtonBundle {
+                        style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+                        background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                        ..default()
+                    },
+                    CloseScorePanel,
+                ))
+                .with_children(|button| {
+                    button.spawn((
+                        TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }),
+                        Localized("score.close"),
+                    ));
+                });
+        });
+}
+
+fn toggle_score_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    close_q: Query<&Interaction, (With<CloseScorePanel>, Changed<Interaction>)>,
+    mut summary: ResMut<MatchSummary>,
+    mut style_q: Query<&mut Style, With<ScorePanel>>,
+) {
+    let toggled = bindings.just_pressed(&keyboard_input, Action::ToggleScores);
+    let closed = close_q.iter().any(|interaction| *interaction == Interaction::Pressed);
+    if !toggled && !closed {
+        return;
+    }
+    for mut style in style_q.iter_mut() {
+        style.display = match style.display {
+            Display::None if !closed => Display::Flex,
+            _ => Display::None,
+        };
+    }
+    if summary.0 {
+        summary.0 = false;
+    }
+}
+
+// Records the results and opens the scoreboard as the match summary
+fn end_match(
+    mut ended: EventReader<MatchEnded>,
+    scores: Res<Scores>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut summary: ResMut<MatchSummary>,
+    mut style_q: Query<&mut Style, With<ScorePanel>>,
+) {
+    if ended.read().count() == 0 {
+        return;
+    }
+    leaderboard.record(&scores.ranked());
+    summary.0 = true;
+    for mut style in style_q.iter_mut() {
+        style.display = Display::Flex;
+    }
+}
+
+fn update_score_text(
+    scores: Res<Scores>,
+    leaderboard: Res<Leaderboard>,
+    summary: Res<MatchSummary>,
+    local_player: Res<LocalPlayer>,
+    locale: Res<Locale>,
+    mut hud_q: Query<&mut Text, (With<ScoreHud>, Without<ScorePanelTitle>, Without<ScorePanelRows>)>,
+    mut title_q: Query<&mut Text, (With<ScorePanelTitle>, Without<ScorePanelRows>)>,
+    mut rows_q: Query<&mut Text, With<ScorePanelRows>>,
+) {
+    if !scores.is_changed() && !leaderboard.is_changed() && !summary.is_changed() && !locale.is_changed() {
+        return;
+    }
+    let ours = scores.get(local_player.0).total();
+    for mut text in hud_q.iter_mut() {
+        text.sections[0].value = locale.format("score.hud", &[("score", &ours)]);
+    }
+    for mut text in title_q.iter_mut() {
+        text.sections[0].value = locale.get(if summary.0 { "score.summary-title" } else { "score.title" }).to_string();
+    }
+
+    let mut lines = Vec::new();
+    for (player, score) in scores.ranked() {
+        lines.push(locale.format(
+            "score.row",
+            &[
+                ("player", &player_name(&locale, local_player.0, player)),
+                ("score", &score.total()),
+                ("food", &score.food_gathered),
+                ("territory", &score.territory),
+                ("kills", &score.enemies_defeated),
+            ],
+        ));
+    }
+    if !leaderboard.0.is_empty() {
+        lines.push(String::new());
+        lines.push(locale.get("score.leaderboard").to_string());
+        for (rank, entry) in leaderboard.0.iter().enumerate() {
+            let player = player_name(&locale, local_player.0, PlayerId(entry.player));
+            let (rank, score) = (rank + 1, entry.score.total());
+            lines.push(locale.format("score.leaderboard-row", &[("rank", &rank), ("player", &player), ("score", &score)]));
+        }
+    }
+    for mut text in rows_q.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn end_match_command(In(_args): In<Vec<String>>, mut ended: EventWriter<MatchEnded>) -> CommandResult {
+    ended.send(MatchEnded);
+    Ok("match ended".to_string())
+}
+
+pub struct ScoringPlugin;
+
+impl Plugin for ScoringPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Scores>()
+            .init_resource::<MatchSummary>()
+            .init_resource::<Leaderboard>()
+            .add_event::<MatchEnded>()
+            .register_action(Action::ToggleScores, &[KeyCode::Tab])
+            .register_console_command("endmatch", "endmatch: end the match and record the scores", end_match_command)
+            .add_systems(Startup, setup_score_ui)
+            .add_systems(
+                Update,
+                (sync_territory_scores, toggle_score_panel, end_match, update_score_text).chain(),
+            );
+    }
+}
@@ -0,0 +1,280 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use mlua::{Function, HookTriggers, Lua, LuaOptions, StdLib, Table};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::coords::HexLayoutExt;
+use crate::game_commands::{GameCommand, MoveMode};
+use crate::health::Health;
+use crate::history::ColonyEvent;
+use crate::interactions::{Carrying, FoodSource};
+use crate::net::LocalPlayer;
+use crate::pathfinding::PathQueue;
+use crate::{Ant, Caste, Faction, HoldPosition, MapLayout, MapSize, Path, Queen};
+
+// Modder-written unit behaviour in Lua (built with `--features scripting`).
+// Every `*.lua` file in `scripts/` is run once at startup into one shared,
+// sandboxed interpreter: only the table, string and math libraries, a memory
+// cap and an instruction budget per tick. Scripts define any of
+//   on_idle(unit)         called for each of our units with nothing to do
+//   on_event(name, value) called for colony events (see `ColonyEvent`)
+// and talk to the game through the `game` table:
+//   game.units()                 our units, as { id, caste, q, r, health, carrying }
+//   game.unit(id)                one of our units, or nil
+//   game.queen()                 our queen's hex, as { q, r }, or nil
+//   game.food_near(q, r, radius) food piles, as { q, r, amount, distance }
+//   game.on_map(q, r)            whether a hex is on the map
+//   game.move(id, q, r)          order one of our units to a hex
+//   game.log(message)
+// Scripts only ever see a snapshot, and orders for units we don't own are
+// dropped, so a script can't reach past its own colony.
+
+const SCRIPT_DIR: &str = "scripts";
+const SCRIPT_TICK_SECS: f32 = 0.5;
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+const INSTRUCTION_BUDGET: u32 = 1_000_000;
+
+struct ScriptHost {
+    lua: Lua,
+}
+
+#[derive(Resource)]
+struct ScriptTimer(Timer);
+
+// What scripts get to know about one of our units
+struct UnitView {
+    entity: Entity,
+    caste: Caste,
+    hex: Hex,
+    health: f32,
+    carrying: u32,
+    idle: bool,
+}
+
+fn unit_table<'lua>(lua: &'lua Lua, id: u64, unit: &UnitView) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set("id", id)?;
+    table.set("caste", format!("{:?}", unit.caste))?;
+    table.set("q", unit.hex.x)?;
+    table.set("r", unit.hex.y)?;
+    table.set("health", unit.health)?;
+    table.set("carrying", unit.carrying)?;
+    Ok(table)
+}
+
+fn hex_table(lua: &Lua, hex: Hex) -> mlua::Result<Table<'_>> {
+    let table = lua.create_table()?;
+    table.set("q", hex.x)?;
+    table.set("r", hex.y)?;
+    Ok(table)
+}
+
+fn event_args(event: &ColonyEvent) -> (&'static str, Option<String>) {
+    match event {
+        ColonyEvent::Founded => ("founded", None),
+        ColonyEvent::FirstOfCaste(caste) => ("first_of_caste", Some(format!("{caste:?}"))),
+        ColonyEvent::Expanded { entrances } => ("expanded", Some(entrances.to_string())),
+        ColonyEvent::FoodMilestone(amount) => ("food_milestone", Some(amount.to_string())),
+        ColonyEvent::RaidSurvived => ("raid_survived", None),
+    }
+}
+
+// Re-arming the hook restarts its count, so each call gives a fresh budget
+fn arm_instruction_budget(lua: &Lua) {
+    lua.set_hook(HookTriggers::new().every_nth_instruction(INSTRUCTION_BUDGET), |_, _| {
+        Err(mlua::Error::RuntimeError("script exceeded its instruction budget".to_string()))
+    });
+}
+
+fn setup_script_host(world: &mut World) {
+    let lua = match Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH, LuaOptions::default()) {
+        Ok(lua) => lua,
+        Err(err) => {
+            error!("Could not start the script interpreter: {err}");
+            return;
+        }
+    };
+    if let Err(err) = lua.set_memory_limit(MEMORY_LIMIT) {
+        warn!("Scripts run without a memory limit: {err}");
+    }
+
+    let mut paths: Vec<_> = fs::read_dir(SCRIPT_DIR)
+        .map(|dir| dir.filter_map(Result::ok).map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "lua"));
+    // Load order is file name order, so later files can override earlier hooks
+    paths.sort();
+    for path in paths {
+        // A file's top level runs under the budget too, so a runaway loop
+        // there can't hang startup
+        arm_instruction_budget(&lua);
+        let result = fs::read_to_string(&path)
+            .map_err(mlua::Error::external)
+            .and_then(|source| lua.load(source).set_name(path.display().to_string()).exec());
+        match result {
+            Ok(()) => info!("Loaded script {}", path.display()),
+            Err(err) => error!("Script {} failed to load: {err}", path.display()),
+        }
+    }
+
+    world.insert_non_send_resource(ScriptHost { lua });
+}
+
+fn run_scripts(
+    host: Option<NonSend<ScriptHost>>,
+    time: Res<Time>,
+    mut timer: ResMut<ScriptTimer>,
+    mut colony_events: EventReader<ColonyEvent>,
+    // Events arrive every frame but scripts only run on ticks
+    mut pending_events: Local<Vec<(&'static str, Option<String>)>>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    local_player: Res<LocalPlayer>,
+    unit_q: Query<(Entity, &Transform, &Caste, &Faction, &Path, Option<&Carrying>, &Health, Has<Queen>, Has<HoldPosition>), With<Ant>>,
+    food_q: Query<(&Transform, &FoodSource)>,
+    path_queue: Res<PathQueue>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    let Some(host) = host else {
+        return;
+    };
+    pending_events.extend(colony_events.read().map(event_args));
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let lua = &host.lua;
+
+    // Snapshot of everything scripts may look at. Ordered, so hooks run in
+    // the same order every time.
+    let mut units: BTreeMap<u64, UnitView> = BTreeMap::new();
+    let mut queen_hex = None;
+    for (entity, transform, caste, faction, path, carrying, health, is_queen, holding) in unit_q.iter() {
+        if faction.0 != local_player.0 {
+            continue;
+        }
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        if is_queen {
+            queen_hex = Some(hex);
+        }
+        // Units told to hold position aren't waiting for something to do
+        let idle = !is_queen && !holding && path.waypoints.is_empty() && !path_queue.is_pending(entity);
+        let view = UnitView {
+            entity,
+            caste: *caste,
+            hex,
+            health: health.current,
+            carrying: carrying.map_or(0, |carrying| carrying.food),
+            idle,
+        };
+        units.insert(entity.to_bits(), view);
+    }
+    let food: Vec<(Hex, u32)> = food_q
+        .iter()
+        .map(|(transform, source)| (layout.0.world_to_hex(transform.translation.truncate()), source.amount))
+        .collect();
+    let events = std::mem::take(&mut *pending_events);
+    let orders: RefCell<Vec<(u64, Hex)>> = RefCell::new(Vec::new());
+
+    // The budget is per tick
+    arm_instruction_budget(lua);
+
+    let result = lua.scope(|scope| {
+        let game = lua.create_table()?;
+        game.set(
+            "units",
+            scope.create_function(|lua, ()| {
+                let list = lua.create_table()?;
+                for (&id, unit) in units.iter() {
+                    list.push(unit_table(lua, id, unit)?)?;
+                }
+                Ok(list)
+            })?,
+        )?;
+        game.set(
+            "unit",
+            scope.create_function(|lua, id: u64| units.get(&id).map(|unit| unit_table(lua, id, unit)).transpose())?,
+        )?;
+        game.set(
+            "queen",
+            scope.create_function(|lua, ()| queen_hex.map(|hex| hex_table(lua, hex)).transpose())?,
+        )?;
+        game.set(
+            "food_near",
+            scope.create_function(|lua, (q, r, radius): (i32, i32, u32)| {
+                let center = Hex::new(q, r);
+                let list = lua.create_table()?;
+                for &(hex, amount) in food.iter() {
+                    let distance = hex.unsigned_distance_to(center);
+                    if distance <= radius {
+                        let pile = hex_table(lua, hex)?;
+                        pile.set("amount", amount)?;
+                        pile.set("distance", distance)?;
+                        list.push(pile)?;
+                    }
+                }
+                Ok(list)
+            })?,
+        )?;
+        game.set(
+            "on_map",
+            scope.create_function(|_, (q, r): (i32, i32)| Ok(map_size.contains(Hex::new(q, r))))?,
+        )?;
+        game.set(
+            "move",
+            scope.create_function(|_, (id, q, r): (u64, i32, i32)| {
+                orders.borrow_mut().push((id, Hex::new(q, r)));
+                Ok(())
+            })?,
+        )?;
+        game.set(
+            "log",
+            scope.create_function(|_, message: String| {
+                info!("[script] {message}");
+                Ok(())
+            })?,
+        )?;
+        lua.globals().set("game", game)?;
+
+        if let Ok(on_event) = lua.globals().get::<_, Function>("on_event") {
+            for (name, value) in events.iter() {
+                on_event.call::<_, ()>((*name, value.clone()))?;
+            }
+        }
+        if let Ok(on_idle) = lua.globals().get::<_, Function>("on_idle") {
+            for (&id, unit) in units.iter().filter(|(_, unit)| unit.idle) {
+                on_idle.call::<_, ()>(unit_table(lua, id, unit)?)?;
+            }
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        warn!("Script error: {err}");
+    }
+
+    // Only orders for our own units, to hexes on the map, get through
+    for (id, destination) in orders.into_inner() {
+        let Some(unit) = units.get(&id) else {
+            continue;
+        };
+        if map_size.contains(destination) {
+            game_commands.send(GameCommand::Move {
+                player: local_player.0,
+                units: vec![unit.entity],
+                destination: layout.0.hex_to_world(destination),
+                mode: MoveMode::Direct,
+            });
+        }
+    }
+}
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ScriptTimer(Timer::from_seconds(SCRIPT_TICK_SECS, TimerMode::Repeating)))
+            .add_systems(Startup, setup_script_host)
+            .add_systems(Update, run_scripts.run_if(in_state(crate::loading::AppState::InGame)));
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use std::collections::HashMap;
+
+use crate::factions::FactionColors;
+use crate::net::PlayerId;
+use crate::photo_mode::HudOnly;
+use crate::{Faction, HoldPosition, MapLayout, Selected};
+
+// Selection ring as a child mesh of each selected unit, spawned when
+// `Selected` is added and despawned when it's removed, so nothing is redrawn
+// per frame. A ring rather than a hex, since units rotate to face where
+// they walk. All rings share one mesh, and a faction's rings share a material
+// in its color. Units holding position get a thicker ring.
+
+const RING_THICKNESS: f32 = 2.0;
+const HOLD_RING_THICKNESS: f32 = 4.0;
+
+#[derive(Resource)]
+struct SelectionRingAssets {
+    mesh: Mesh2dHandle,
+    hold_mesh: Mesh2dHandle,
+    materials: HashMap<PlayerId, Handle<ColorMaterial>>,
+}
+
+#[derive(Component)]
+struct SelectionRing;
+
+fn setup_selection_ring(
+    mut commands: Commands,
+    layout: Res<MapLayout>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    // Same size as the hex outline it replaces
+    let outer = layout.0.scale.min_element();
+    commands.insert_resource(SelectionRingAssets {
+        mesh: Mesh2dHandle(meshes.add(Annulus::new(outer - RING_THICKNESS, outer))),
+        hold_mesh: Mesh2dHandle(meshes.add(Annulus::new(outer - HOLD_RING_THICKNESS, outer))),
+        materials: HashMap::new(),
+    });
+}
+
+fn attach_selection_rings(
+    mut commands: Commands,
+    mut assets: ResMut<SelectionRingAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    colors: Res<FactionColors>,
+    newly_selected: Query<(Entity, &Faction, Has<HoldPosition>), Added<Selected>>,
+) {
+    for (entity, faction, holding) in newly_selected.iter() {
+        let material = assets
+            .materials
+            .entry(faction.0)
+            .or_insert_with(|| materials.add(ColorMaterial::from(colors.get(faction.0))))
+            .clone();
+        let ring = commands
+            .spawn((
+                MaterialMesh2dBundle {
+                    mesh: if holding { assets.hold_mesh.clone() } else { assets.mesh.clone() },
+                    material,
+                    // Under the unit's sprite
+                    transform: Transform::from_xyz(0.0, 0.0, -0.2),
+                    ..default()
+                },
+                SelectionRing,
+                HudOnly,
+            ))
+            .id();
+        commands.entity(entity).add_child(ring);
+    }
+}
+
+fn detach_selection_rings(
+    mut commands: Commands,
+    mut deselected: RemovedComponents<Selected>,
+    children_q: Query<&Children>,
+    ring_q: Query<(), With<SelectionRing>>,
+) {
+    for entity in deselected.read() {
+        // Entity may have been despawned outright, taking its ring with it
+        let Ok(children) = children_q.get(entity) else {
+            continue;
+        };
+        for &child in children.iter() {
+            if ring_q.contains(child) {
+                commands.entity(entity).remove_children(&[child]);
+                commands.entity(child).despawn();
+            }
+        }
+    }
+}
+
+// Selected units that start or stop holding position swap ring meshes
+fn sync_hold_rings(
+    assets: Res<SelectionRingAssets>,
+    unit_q: Query<Has<HoldPosition>, With<Selected>>,
+    mut ring_q: Query<(&Parent, &mut Mesh2dHandle), With<SelectionRing>>,
+) {
+    for (parent, mut mesh) in ring_q.iter_mut() {
+        let Ok(holding) = unit_q.get(parent.get()) else {
+            continue;
+        };
+        let wanted = if holding { &assets.hold_mesh } else { &assets.mesh };
+        if mesh.0 != wanted.0 {
+            *mesh = wanted.clone();
+        }
+    }
+}
+
+pub struct SelectionRingPlugin;
+
+impl Plugin for SelectionRingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_selection_ring.after(crate::setup_hex_grid))
+            .add_systems(Update, (detach_selection_rings, attach_selection_rings, sync_hold_rings).chain());
+    }
+}
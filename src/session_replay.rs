@@ -0,0 +1,395 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::bots::Bots;
+use crate::determinism::{record_checksum, ChecksumLog, Determinism, SimRng};
+use crate::diplomacy::DiplomacyAction;
+use crate::game_commands::GameCommand;
+use crate::loading::AppState;
+use crate::lobby::MatchSettings;
+use crate::net::{IncomingMessage, LocalPlayer, NetMessage, PlayerId};
+use crate::scoring::MatchEnded;
+use crate::storage::storage;
+
+// Offline replay of suspicious sessions (built with `--features admin`). A
+// server run with `--deterministic` keeps every input that drives the
+// simulation with the tick it arrived on: group orders and diplomacy from
+// players and bots over the network, and the commands and diplomacy the
+// host's own player gives (see `game_commands`). Every `CHECKPOINT_TICKS` it
+// keeps the world checksum too. `POST /flag/<player>` on the admin endpoint
+// marks a player as suspect and writes the match so far to
+// `saves/sessions/`; it's written again when the match ends or the server
+// stops.
+//
+// `--deterministic --replay-session <file>` plays such a file back: same
+// seed, same match settings, no lobby, bots silenced, and every recorded
+// input, theirs included, fed back in on its tick. At each checkpoint the
+// replayed checksum is compared with the recorded one. Once the last
+// checkpoint is reached a divergence report goes next to the session file
+// (`.report.txt`) and the game quits. A replay that matches clears the
+// session of a desync on the server's side; the first divergent tick, and
+// what the suspects sent just before it, is where to look otherwise. The
+// replay starts from the world save as it is now, so replay a session before
+// the world it was played in moves on.
+
+const SESSIONS_DIR: &str = "saves/sessions";
+const CHECKPOINT_TICKS: u64 = 60;
+// Suspects' inputs listed before the first divergence
+const REPORTED_INPUTS: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum Input {
+    // From another player or a bot
+    Message(NetMessage),
+    // Given by the host's own player
+    Command(GameCommand),
+    Diplomacy(DiplomacyAction),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct RecordedInput {
+    tick: u64,
+    from: PlayerId,
+    input: Input,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct SessionRecord {
+    seed: u64,
+    settings: MatchSettings,
+    suspects: BTreeSet<PlayerId>,
+    inputs: Vec<RecordedInput>,
+    // (tick, checksum)
+    checkpoints: Vec<(u64, u64)>,
+}
+
+// Server side: the current match, written out once a player is flagged
+#[derive(Resource, Default)]
+pub struct SessionRecorder {
+    record: SessionRecord,
+    // Only deterministic servers record
+    recording: bool,
+    path: Option<String>,
+}
+
+impl SessionRecorder {
+    // Marks the player as a suspect and writes the session now
+    pub fn flag(&mut self, player: PlayerId) -> Result<String, String> {
+        if !self.recording {
+            return Err("sessions are only recorded with --deterministic\n".to_string());
+        }
+        self.record.suspects.insert(player);
+        let path = self.persist()?;
+        Ok(format!("flagged {}, session in {path}\n", player.0))
+    }
+
+    fn persist(&mut self) -> Result<String, String> {
+        let path = self
+            .path
+            .get_or_insert_with(|| format!("{SESSIONS_DIR}/session_{}.ron", storage().list(SESSIONS_DIR).len()))
+            .clone();
+        ron::ser::to_string_pretty(&self.record, PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage().write(&path, &contents))
+            .map(|()| path)
+    }
+}
+
+// Every session file and report, for `GET /sessions`
+pub fn list_sessions() -> String {
+    storage().list(SESSIONS_DIR).into_iter().map(|path| format!("{path}\n")).collect()
+}
+
+// Replay side: the session being played back and how it's going
+#[derive(Resource)]
+struct ReplaySession {
+    path: String,
+    record: SessionRecord,
+    next_input: usize,
+    next_checkpoint: usize,
+    // (tick, recorded, replayed)
+    divergences: Vec<(u64, u64, u64)>,
+}
+
+fn divergence_report(path: &str, record: &SessionRecord, divergences: &[(u64, u64, u64)]) -> String {
+    let suspects: Vec<String> = record.suspects.iter().map(|player| player.0.to_string()).collect();
+    let mut report = format!(
+        "session {path}, seed {}, suspects [{}]\n{} checkpoints, {} diverged\n",
+        record.seed,
+        suspects.join(", "),
+        record.checkpoints.len(),
+        divergences.len()
+    );
+    let Some(&(tick, recorded, replayed)) = divergences.first() else {
+        report.push_str("the replay matches the server at every checkpoint\n");
+        return report;
+    };
+    report.push_str(&format!("first divergence at tick {tick}: recorded {recorded:016x}, replayed {replayed:016x}\n"));
+    let last_match = record.checkpoints.iter().map(|&(at, _)| at).rfind(|&at| at < tick).unwrap_or(0);
+    report.push_str(&format!("last matching checkpoint: tick {last_match}\n"));
+
+    let before: Vec<&RecordedInput> = record
+        .inputs
+        .iter()
+        .filter(|recorded| recorded.tick <= tick && record.suspects.contains(&recorded.from))
+        .collect();
+    report.push_str("suspects' inputs before it:\n");
+    for recorded in &before[before.len().saturating_sub(REPORTED_INPUTS)..] {
+        report.push_str(&format!("  tick {} player {}: {:?}\n", recorded.tick, recorded.from.0, recorded.input));
+    }
+    report
+}
+
+fn start_recording(
+    determinism: Res<Determinism>,
+    settings: Res<MatchSettings>,
+    log: Option<Res<ChecksumLog>>,
+    mut recorder: ResMut<SessionRecorder>,
+) {
+    *recorder = SessionRecorder {
+        record: SessionRecord { seed: determinism.seed, settings: *settings, ..default() },
+        recording: log.is_some(),
+        path: None,
+    };
+}
+
+// What the authority acts on; everything else (cameras, pings, lobby chatter)
+// leaves the simulation alone
+fn drives_simulation(message: &NetMessage) -> bool {
+    matches!(message, NetMessage::GroupOrder { .. } | NetMessage::Propose { .. } | NetMessage::Answer { .. })
+}
+
+// Orders given for the player, and sandbox tools, which only the player at
+// the keyboard can use
+fn given_by(command: &GameCommand, player: PlayerId) -> bool {
+    match command {
+        #[cfg(feature = "sandbox")]
+        GameCommand::Sandbox(_) => true,
+        _ => command.player() == Some(player),
+    }
+}
+
+// Stamped with the tick before `record_checksum` counts this frame. Commands
+// for other players came in as messages and are recorded as those.
+fn record_inputs(
+    log: Res<ChecksumLog>,
+    local_player: Res<LocalPlayer>,
+    mut recorder: ResMut<SessionRecorder>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut game_commands: EventReader<GameCommand>,
+    mut diplomacy: EventReader<DiplomacyAction>,
+) {
+    let tick = log.tick;
+    for message in incoming.read().filter(|message| drives_simulation(&message.message)) {
+        let input = Input::Message(message.message.clone());
+        recorder.record.inputs.push(RecordedInput { tick, from: message.from, input });
+    }
+    for command in game_commands.read().filter(|command| given_by(command, local_player.0)) {
+        let input = Input::Command(command.clone());
+        recorder.record.inputs.push(RecordedInput { tick, from: local_player.0, input });
+    }
+    for &action in diplomacy.read() {
+        recorder.record.inputs.push(RecordedInput { tick, from: local_player.0, input: Input::Diplomacy(action) });
+    }
+}
+
+fn record_checkpoints(log: Res<ChecksumLog>, mut recorder: ResMut<SessionRecorder>) {
+    if log.tick.is_multiple_of(CHECKPOINT_TICKS) {
+        recorder.record.checkpoints.push((log.tick, log.last));
+    }
+}
+
+fn persist_flagged_sessions(
+    mut ended: EventReader<MatchEnded>,
+    mut exit_events: EventReader<AppExit>,
+    mut recorder: ResMut<SessionRecorder>,
+) {
+    let ended = ended.read().count() > 0;
+    if (exit_events.read().next().is_some() || ended) && !recorder.record.suspects.is_empty() {
+        if let Err(err) = recorder.persist() {
+            warn!("Failed to save the flagged session: {err}");
+        }
+    }
+}
+
+fn begin_replay(
+    replay: Res<ReplaySession>,
+    mut determinism: ResMut<Determinism>,
+    mut sim_rng: ResMut<SimRng>,
+    mut settings: ResMut<MatchSettings>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !determinism.enabled {
+        error!("--replay-session needs --deterministic");
+        exit.send(AppExit::error());
+        return;
+    }
+    info!("Replaying {}: seed {}, {} inputs", replay.path, replay.record.seed, replay.record.inputs.len());
+    determinism.seed = replay.record.seed;
+    *sim_rng = SimRng::seeded(replay.record.seed);
+    *settings = replay.record.settings;
+}
+
+// Bots' orders come from the recording
+fn silence_bots(mut bots: ResMut<Bots>) {
+    bots.silence();
+}
+
+fn feed_recorded_inputs(
+    log: Res<ChecksumLog>,
+    mut replay: ResMut<ReplaySession>,
+    mut incoming: EventWriter<IncomingMessage>,
+    mut game_commands: EventWriter<GameCommand>,
+    mut diplomacy: EventWriter<DiplomacyAction>,
+) {
+    let replay = &mut *replay;
+    while let Some(recorded) = replay.record.inputs.get(replay.next_input).filter(|recorded| recorded.tick <= log.tick) {
+        match &recorded.input {
+            Input::Message(message) => {
+                incoming.send(IncomingMessage { from: recorded.from, message: message.clone() });
+            }
+            Input::Command(command) => {
+                game_commands.send(command.clone());
+            }
+            Input::Diplomacy(action) => {
+                diplomacy.send(*action);
+            }
+        }
+        replay.next_input += 1;
+    }
+}
+
+fn compare_checkpoints(log: Res<ChecksumLog>, mut replay: ResMut<ReplaySession>, mut exit: EventWriter<AppExit>) {
+    let replay = &mut *replay;
+    let Some(&(tick, recorded)) = replay.record.checkpoints.get(replay.next_checkpoint) else {
+        return;
+    };
+    if log.tick < tick {
+        return;
+    }
+    if recorded != log.last {
+        replay.divergences.push((tick, recorded, log.last));
+    }
+    replay.next_checkpoint += 1;
+    if replay.next_checkpoint < replay.record.checkpoints.len() {
+        return;
+    }
+
+    let report = divergence_report(&replay.path, &replay.record, &replay.divergences);
+    let report_path = format!("{}.report.txt", replay.path.trim_end_matches(".ron"));
+    match storage().write(&report_path, &report) {
+        Ok(()) => info!("Replay done, report in {report_path}:\n{report}"),
+        Err(err) => warn!("Failed to write {report_path}: {err}\n{report}"),
+    }
+    exit.send(AppExit::Success);
+}
+
+fn replay_from_args() -> Option<ReplaySession> {
+    let args: Vec<String> = std::env::args().collect();
+    let path = args.windows(2).find(|pair| pair[0] == "--replay-session")?[1].clone();
+    let record = storage()
+        .read(&path)
+        .ok_or_else(|| "could not read it".to_string())
+        .and_then(|contents| ron::from_str::<SessionRecord>(&contents).map_err(|err| err.to_string()));
+    match record {
+        Ok(record) => Some(ReplaySession { path, record, next_input: 0, next_checkpoint: 0, divergences: Vec::new() }),
+        Err(err) => {
+            error!("Can't replay {path}: {err}");
+            None
+        }
+    }
+}
+
+pub struct SessionReplayPlugin;
+
+impl Plugin for SessionReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let in_game = in_state(AppState::InGame);
+        let Some(replay) = replay_from_args() else {
+            app.init_resource::<SessionRecorder>()
+                .add_systems(OnEnter(AppState::InGame), start_recording)
+                .add_systems(
+                    Last,
+                    (
+                        (record_inputs.before(record_checksum), record_checkpoints.after(record_checksum))
+                            .run_if(in_game.and_then(resource_exists::<ChecksumLog>)),
+                        persist_flagged_sessions,
+                    ),
+                );
+            return;
+        };
+        app.init_resource::<SessionRecorder>()
+            .insert_resource(replay)
+            .add_systems(Startup, begin_replay.after(crate::lobby::init_match_settings))
+            .add_systems(OnEnter(AppState::InGame), silence_bots.after(crate::lobby::apply_match_settings))
+            .add_systems(PreUpdate, feed_recorded_inputs.run_if(in_game.clone().and_then(resource_exists::<ChecksumLog>)))
+            .add_systems(Last, compare_checkpoints.after(record_checksum).run_if(in_game.and_then(resource_exists::<ChecksumLog>)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::StructureKind;
+    use hexx::Hex;
+
+    fn record() -> SessionRecord {
+        let order = |tick, player| RecordedInput {
+            tick,
+            from: PlayerId(player),
+            input: Input::Message(NetMessage::GroupOrder { order: 0, units: vec![1], destination: Vec2::ZERO }),
+        };
+        // The host's own player builds something
+        let build = RecordedInput {
+            tick: 15,
+            from: PlayerId(1),
+            input: Input::Command(GameCommand::Build {
+                player: PlayerId(1),
+                kind: StructureKind::StorageChamber,
+                hex: Hex::new(2, -1),
+            }),
+        };
+        SessionRecord {
+            seed: 7,
+            settings: MatchSettings::default(),
+            suspects: BTreeSet::from([PlayerId(3)]),
+            inputs: vec![order(10, 3), build, order(20, 1000), order(130, 3)],
+            checkpoints: vec![(60, 1), (120, 2), (180, 3)],
+        }
+    }
+
+    #[test]
+    fn a_matching_replay_says_so() {
+        let report = divergence_report("session_0.ron", &record(), &[]);
+        assert!(report.contains("3 checkpoints, 0 diverged"));
+        assert!(report.contains("matches the server at every checkpoint"));
+    }
+
+    #[test]
+    fn report_points_at_the_first_divergence_and_the_suspects_inputs() {
+        let report = divergence_report("session_0.ron", &record(), &[(180, 3, 4), (240, 5, 6)]);
+        assert!(report.contains("first divergence at tick 180"));
+        assert!(report.contains("last matching checkpoint: tick 120"));
+        assert!(report.contains("tick 10 player 3"));
+        assert!(report.contains("tick 130 player 3"));
+        // Neither the host nor bots are suspects
+        assert!(!report.contains("player 1:"));
+        assert!(!report.contains("player 1000"));
+    }
+
+    #[test]
+    fn sessions_round_trip_through_ron() {
+        let contents = ron::ser::to_string_pretty(&record(), PrettyConfig::default()).unwrap();
+        let loaded: SessionRecord = ron::from_str(&contents).unwrap();
+        assert_eq!(loaded.inputs.len(), 4);
+        assert!(matches!(
+            loaded.inputs[1].input,
+            Input::Command(GameCommand::Build { player: PlayerId(1), kind: StructureKind::StorageChamber, .. })
+        ));
+        assert_eq!(loaded.checkpoints, record().checkpoints);
+        assert_eq!(loaded.suspects, record().suspects);
+    }
+}
@@ -0,0 +1,358 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::input::InputScheme;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt, SETTINGS_PATH};
+use crate::locale::{Locale, Localized, FALLBACK_LANGUAGE, LANGUAGES};
+use crate::storage::storage;
+
+// Player settings: audio, camera feel, input scheme and grid, next to the key
+// bindings in `settings.ron`. Read once at startup; changes from the settings
+// screen (F10) or the console are written back shortly after the last one, so
+// dragging through values doesn't rewrite the file every frame. On the web
+// the "file" is a localStorage entry (see `storage`). Systems read their
+// tuning from here instead of from constants of their own.
+
+// Quiet time after a change before the file is written
+const SAVE_DELAY_SECS: f32 = 0.5;
+const FILE_HEADER: &str = "// Key bindings and player settings (volumes 0-1). The settings screen (F10)
+// writes changes back here. Any action or setting left out keeps its default.
+";
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self { music_volume: 0.5, sfx_volume: 0.8 }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    // Keyboard panning, world units per second at zoom 1
+    pub pan_speed: f32,
+    // Keyboard zoom, scale change per second
+    pub zoom_speed: f32,
+    // Scroll wheel zoom per notch (exponential, so in and out feel symmetric)
+    pub scroll_zoom_step: f32,
+    pub edge_pan: bool,
+    // Distance from the window edge (logical pixels) where edge panning starts
+    pub edge_pan_margin: f32,
+    // Edge pan speed at the very edge, in screen pixels per second
+    pub edge_pan_speed: f32,
+    // Fully zoomed in, this many hexes still fit across the short side of the window
+    pub min_visible_hexes: f32,
+    // Fully zoomed out, the view is this many times the size that just fits the map
+    pub max_zoom_out: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            pan_speed: 500.0,
+            zoom_speed: 1.0,
+            scroll_zoom_step: 0.15,
+            edge_pan: true,
+            edge_pan_margin: 20.0,
+            edge_pan_speed: 600.0,
+            min_visible_hexes: 4.0,
+            max_zoom_out: 1.0,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Locale file the interface text comes from; see `locale`
+    pub language: String,
+    pub audio: AudioSettings,
+    pub camera: CameraSettings,
+    pub input_scheme: InputScheme,
+    pub show_grid: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            language: FALLBACK_LANGUAGE.to_string(),
+            audio: AudioSettings::default(),
+            camera: CameraSettings::default(),
+            input_scheme: InputScheme::default(),
+            show_grid: true,
+        }
+    }
+}
+
+impl Settings {
+    fn load() -> Self {
+        let Some(contents) = storage().read(SETTINGS_PATH) else {
+            return Self::default();
+        };
+        match ron::from_str(&contents) {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!("Ignoring settings in {SETTINGS_PATH}: {err}");
+                Self::default()
+            }
+        }
+    }
+}
+
+// The whole file as written back: bindings first, sorted so saves diff cleanly
+#[derive(Serialize)]
+struct SettingsFile<'a> {
+    bindings: BTreeMap<Action, &'a [KeyCode]>,
+    language: &'a str,
+    audio: AudioSettings,
+    camera: CameraSettings,
+    input_scheme: InputScheme,
+    show_grid: bool,
+}
+
+fn write_settings(settings: &Settings, bindings: &KeyBindings) -> Result<(), String> {
+    let file = SettingsFile {
+        bindings: bindings.all().collect(),
+        language: &settings.language,
+        audio: settings.audio,
+        camera: settings.camera,
+        input_scheme: settings.input_scheme,
+        show_grid: settings.show_grid,
+    };
+    let body = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).map_err(|err| err.to_string())?;
+    storage().write(SETTINGS_PATH, &format!("{FILE_HEADER}{body}\n"))
+}
+
+fn save_settings(
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    bindings: Res<KeyBindings>,
+    mut countdown: Local<Option<f32>>,
+) {
+    if settings.is_changed() && !settings.is_added() {
+        *countdown = Some(SAVE_DELAY_SECS);
+    }
+    let Some(remaining) = countdown.as_mut() else {
+        return;
+    };
+    *remaining -= time.delta_seconds();
+    if *remaining > 0.0 {
+        return;
+    }
+    *countdown = None;
+    if let Err(err) = write_settings(&settings, &bindings) {
+        warn!("Could not save {SETTINGS_PATH}: {err}");
+    }
+}
+
+// One adjustable line on the settings screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingRow {
+    Language,
+    MusicVolume,
+    SfxVolume,
+    PanSpeed,
+    EdgePan,
+    ZoomIn,
+    ZoomOut,
+    InputScheme,
+    ShowGrid,
+}
+
+impl SettingRow {
+    const ALL: [SettingRow; 9] = [
+        SettingRow::Language,
+        SettingRow::MusicVolume,
+        SettingRow::SfxVolume,
+        SettingRow::PanSpeed,
+        SettingRow::EdgePan,
+        SettingRow::ZoomIn,
+        SettingRow::ZoomOut,
+        SettingRow::InputScheme,
+        SettingRow::ShowGrid,
+    ];
+
+    fn label_key(self) -> &'static str {
+        match self {
+            SettingRow::Language => "settings.language",
+            SettingRow::MusicVolume => "settings.music-volume",
+            SettingRow::SfxVolume => "settings.sfx-volume",
+            SettingRow::PanSpeed => "settings.camera-speed",
+            SettingRow::EdgePan => "settings.edge-pan",
+            SettingRow::ZoomIn => "settings.closest-zoom",
+            SettingRow::ZoomOut => "settings.furthest-zoom",
+            SettingRow::InputScheme => "settings.input-scheme",
+            SettingRow::ShowGrid => "settings.hex-grid",
+        }
+    }
+
+    fn value(self, settings: &Settings, locale: &Locale) -> String {
+        let on_off = |on: bool| locale.get(if on { "settings.on" } else { "settings.off" }).to_string();
+        match self {
+            SettingRow::Language => locale.get("language.name").to_string(),
+            SettingRow::MusicVolume => format!("{:.0}%", settings.audio.music_volume * 100.0),
+            SettingRow::SfxVolume => format!("{:.0}%", settings.audio.sfx_volume * 100.0),
+            SettingRow::PanSpeed => format!("{:.0}", settings.camera.pan_speed),
+            SettingRow::EdgePan => on_off(settings.camera.edge_pan),
+            SettingRow::ZoomIn => format!("{:.0}", settings.camera.min_visible_hexes),
+            SettingRow::ZoomOut => format!("{:.2}", settings.camera.max_zoom_out),
+            SettingRow::InputScheme => locale.get(match settings.input_scheme {
+                InputScheme::Touch => "settings.scheme-touch",
+                InputScheme::ClassicRts => "settings.scheme-classic",
+            })
+            .to_string(),
+            SettingRow::ShowGrid => on_off(settings.show_grid),
+        }
+    }
+
+    // One step up or down; on/off and either/or settings flip either way
+    fn step(self, settings: &mut Settings, up: bool) {
+        let sign = if up { 1.0 } else { -1.0 };
+        match self {
+            SettingRow::Language => {
+                let current = LANGUAGES.iter().position(|&language| language == settings.language).unwrap_or(0);
+                let step = if up { 1 } else { LANGUAGES.len() - 1 };
+                settings.language = LANGUAGES[(current + step) % LANGUAGES.len()].to_string();
+            }
+            SettingRow::MusicVolume => {
+                settings.audio.music_volume = (settings.audio.music_volume + 0.1 * sign).clamp(0.0, 1.0);
+            }
+            SettingRow::SfxVolume => {
+                settings.audio.sfx_volume = (settings.audio.sfx_volume + 0.1 * sign).clamp(0.0, 1.0);
+            }
+            SettingRow::PanSpeed => settings.camera.pan_speed = (settings.camera.pan_speed + 50.0 * sign).clamp(100.0, 2000.0),
+            SettingRow::EdgePan => settings.camera.edge_pan = !settings.camera.edge_pan,
+            SettingRow::ZoomIn => {
+                settings.camera.min_visible_hexes = (settings.camera.min_visible_hexes + sign).clamp(2.0, 20.0);
+            }
+            SettingRow::ZoomOut => {
+                settings.camera.max_zoom_out = (settings.camera.max_zoom_out + 0.25 * sign).clamp(0.5, 3.0);
+            }
+            SettingRow::InputScheme => settings.input_scheme = settings.input_scheme.other(),
+            SettingRow::ShowGrid => settings.show_grid = !settings.show_grid,
+        }
+    }
+}
+
+#[derive(Component)]
+struct SettingsPanel;
+
+#[derive(Component)]
+struct SettingValue(SettingRow);
+
+#[derive(Component)]
+struct SettingButton {
+    row: SettingRow,
+    up: bool,
+}
+
+fn setup_settings_panel(mut commands: Commands) {
+    let text = |value: &str| TextBundle::from_section(value, TextStyle { font_size: 14.0, color: Color::WHITE, ..default() });
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(20.0),
+                    left: Val::Percent(35.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(12.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.9).into(),
+                ..default()
+            },
+            Interaction::default(),
+            SettingsPanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 18.0, color: Color::WHITE, ..default() }),
+                Localized("settings.title"),
+            ));
+            for row in SettingRow::ALL {
+                panel
+                    .spawn(NodeBundle {
+                        style: Style { column_gap: Val::Px(6.0), align_items: AlignItems::Center, ..default() },
+                        ..default()
+                    })
+                    .with_children(|line| {
+                        line.spawn(NodeBundle { style: Style { width: Val::Px(170.0), ..default() }, ..default() })
+                            .with_children(|label| {
+                                label.spawn((text(""), Localized(row.label_key())));
+                            });
+                        for (up, symbol) in [(false, "-"), (true, "+")] {
+                            line.spawn((
+                                ButtonBundle {
+                                    style: Style { padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)), ..default() },
+                                    background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                                    ..default()
+                                },
+                                SettingButton { row, up },
+                            ))
+                            .with_children(|button| {
+                                button.spawn(text(symbol));
+                            });
+                        }
+                        line.spawn((text(""), SettingValue(row)));
+                    });
+            }
+        });
+}
+
+fn toggle_settings_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut style_q: Query<&mut Style, With<SettingsPanel>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleSettings) {
+        return;
+    }
+    for mut style in style_q.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn press_setting_buttons(
+    button_q: Query<(&Interaction, &SettingButton), Changed<Interaction>>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction == Interaction::Pressed {
+            button.row.step(&mut settings, button.up);
+        }
+    }
+}
+
+fn update_setting_values(settings: Res<Settings>, locale: Res<Locale>, mut value_q: Query<(&SettingValue, &mut Text)>) {
+    if !settings.is_changed() && !locale.is_changed() {
+        return;
+    }
+    for (value, mut text) in value_q.iter_mut() {
+        text.sections[0].value = value.0.value(&settings, &locale);
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load())
+            .register_action(Action::ToggleSettings, &[KeyCode::F10])
+            .add_systems(Startup, setup_settings_panel)
+            .add_systems(Update, (toggle_settings_panel, press_setting_buttons, update_setting_values).chain())
+            .add_systems(Last, save_settings);
+    }
+}
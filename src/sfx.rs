@@ -0,0 +1,175 @@
+use bevy::audio::{AudioSource, PlaybackMode, Volume};
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap};
+use bevy::window::PrimaryWindow;
+
+use crate::settings::Settings;
+use crate::synth::{note, synthesize, wav_bytes, Note};
+use crate::{MainCamera, Selected};
+
+// Sound effects. Gameplay systems send a `PlaySound` event and never touch
+// audio themselves; this plugin turns events into sounds. The sounds are
+// synthesized into WAV clips at startup (see `synth`), like the ant atlas is
+// painted, so there are no audio files to ship; dropping recorded clips in
+// later only means loading handles instead.
+//
+// Sounds from the world (fights, deaths, tunnelling) carry a position and are
+// heard from the middle of the camera view: full volume on screen, fading out
+// past its edge and silent a little further on. The hearing range grows with
+// zoom, so a zoomed-out view hears the whole battle, just more quietly.
+
+// The same effect won't restart more often than this, so a brawl of fifty
+// ants doesn't play fifty overlapping hits; of the ones sent in a frame, the
+// loudest is played
+const MIN_REPEAT: Duration = Duration::from_millis(80);
+const VOLUME: f32 = 0.4;
+// Hearing range as a multiple of the distance from the view center to a corner
+const HEARING_RANGE: f32 = 1.5;
+// Zoomed out this far, world sounds are at their quietest
+const FAR_ZOOM: f32 = 4.0;
+const FAR_ZOOM_VOLUME: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    Select,
+    OrderConfirmed,
+    Hit,
+    Death,
+    ProductionComplete,
+    Dig,
+}
+
+#[derive(Event, Clone, Copy)]
+pub struct PlaySound {
+    pub effect: SoundEffect,
+    // Where in the world it happened; interface sounds have no position
+    pub at: Option<Vec2>,
+}
+
+impl PlaySound {
+    pub fn ui(effect: SoundEffect) -> Self {
+        Self { effect, at: None }
+    }
+
+    pub fn at(effect: SoundEffect, pos: Vec2) -> Self {
+        Self { effect, at: Some(pos) }
+    }
+}
+
+#[derive(Resource)]
+struct SoundClips(HashMap<SoundEffect, Handle<AudioSource>>);
+
+fn notes(effect: SoundEffect) -> Vec<Note> {
+    match effect {
+        SoundEffect::Select => vec![note(880.0, 880.0, 0.05)],
+        SoundEffect::OrderConfirmed => vec![note(660.0, 660.0, 0.06), note(990.0, 990.0, 0.08)],
+        SoundEffect::Hit => vec![note(0.0, 0.0, 0.08)],
+        SoundEffect::Death => vec![note(400.0, 110.0, 0.3)],
+        SoundEffect::ProductionComplete => {
+            vec![note(523.0, 523.0, 0.08), note(659.0, 659.0, 0.08), note(784.0, 784.0, 0.14)]
+        }
+        SoundEffect::Dig => vec![note(0.0, 0.0, 0.04), note(150.0, 90.0, 0.1)],
+    }
+}
+
+fn setup_sound_clips(mut commands: Commands, mut sources: ResMut<Assets<AudioSource>>) {
+    let effects = [
+        SoundEffect::Select,
+        SoundEffect::OrderConfirmed,
+        SoundEffect::Hit,
+        SoundEffect::Death,
+        SoundEffect::ProductionComplete,
+        SoundEffect::Dig,
+    ];
+    let clips = effects
+        .into_iter()
+        .map(|effect| {
+            let bytes = wav_bytes(&synthesize(&notes(effect)));
+            (effect, sources.add(AudioSource { bytes: bytes.into() }))
+        })
+        .collect();
+    commands.insert_resource(SoundClips(clips));
+}
+
+// Selection happens in many places (clicks, boxes, hotkeys, panels), so it is
+// heard here rather than announced by each of them
+fn announce_selection(selected_q: Query<(), Added<Selected>>, mut sounds: EventWriter<PlaySound>) {
+    if !selected_q.is_empty() {
+        sounds.send(PlaySound::ui(SoundEffect::Select));
+    }
+}
+
+// Where world sounds are heard from, derived from the main camera
+struct Listener {
+    position: Vec2,
+    range: f32,
+    zoom: f32,
+}
+
+impl Listener {
+    // Linear falloff from the view center to the edge of hearing, squared so
+    // sounds near the edge drop away quickly
+    fn gain(&self, at: Option<Vec2>) -> f32 {
+        let Some(pos) = at else {
+            return 1.0;
+        };
+        let falloff = (1.0 - pos.distance(self.position) / self.range).clamp(0.0, 1.0);
+        let zoom_t = ((self.zoom - 1.0) / (FAR_ZOOM - 1.0)).clamp(0.0, 1.0);
+        falloff * falloff * (1.0 - zoom_t * (1.0 - FAR_ZOOM_VOLUME))
+    }
+}
+
+fn play_sound_effects(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    clips: Res<SoundClips>,
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    mut events: EventReader<PlaySound>,
+    mut last_played: Local<HashMap<SoundEffect, Duration>>,
+) {
+    let (Ok(window), Ok((camera_transform, projection))) = (windows.get_single(), camera_q.get_single()) else {
+        events.clear();
+        return;
+    };
+    let listener = Listener {
+        position: camera_transform.translation().truncate(),
+        range: Vec2::new(window.width(), window.height()).length() / 2.0 * projection.scale * HEARING_RANGE,
+        zoom: projection.scale,
+    };
+
+    let mut loudest: HashMap<SoundEffect, f32> = HashMap::new();
+    for sound in events.read() {
+        let gain = listener.gain(sound.at);
+        let entry = loudest.entry(sound.effect).or_default();
+        *entry = entry.max(gain);
+    }
+
+    let now = time.elapsed();
+    for (effect, gain) in loudest {
+        if gain <= 0.0 || last_played.get(&effect).is_some_and(|&last| now - last < MIN_REPEAT) {
+            continue;
+        }
+        last_played.insert(effect, now);
+        commands.spawn(AudioBundle {
+            source: clips.0[&effect].clone(),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                volume: Volume::new(VOLUME * settings.audio.sfx_volume * gain),
+                ..default()
+            },
+        });
+    }
+}
+
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySound>()
+            .add_systems(Startup, setup_sound_clips)
+            .add_systems(Update, announce_selection)
+            .add_systems(PostUpdate, play_sound_effects);
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+
+use crate::coords::HexLayoutExt;
+use crate::{Ant, MapLayout};
+
+mod index;
+pub use index::SpatialIndex;
+
+// Which units stand in which hex, kept up to date as units move so picking
+// and proximity queries don't have to scan every ant.
+
+pub fn update_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    layout: Res<MapLayout>,
+    moved_q: Query<(Entity, &Transform), (With<Ant>, Changed<Transform>)>,
+    mut removed: RemovedComponents<Ant>,
+) {
+    for entity in removed.read() {
+        index.remove(entity);
+    }
+    for (entity, transform) in moved_q.iter() {
+        let pos = transform.translation.truncate();
+        index.insert(entity, layout.0.world_to_hex(pos));
+    }
+}
+
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpatialIndex>()
+            .add_systems(Update, update_spatial_index.before(crate::ant_input).before(crate::move_ants));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hexx::Hex;
+
+    #[test]
+    fn insert_places_units_in_arrival_order() {
+        let mut index = SpatialIndex::default();
+        let (a, b) = (Entity::from_raw(1), Entity::from_raw(2));
+        index.insert(a, Hex::ZERO);
+        index.insert(b, Hex::ZERO);
+        assert_eq!(index.at(Hex::ZERO), &[a, b]);
+        assert!(index.contains(a) && index.contains(b));
+    }
+
+    #[test]
+    fn moving_a_unit_leaves_its_old_hex() {
+        let mut index = SpatialIndex::default();
+        let unit = Entity::from_raw(1);
+        index.insert(unit, Hex::ZERO);
+        index.insert(unit, Hex::new(2, -1));
+        assert!(index.at(Hex::ZERO).is_empty());
+        assert_eq!(index.at(Hex::new(2, -1)), &[unit]);
+
+        // Staying put doesn't add it twice
+        index.insert(unit, Hex::new(2, -1));
+        assert_eq!(index.at(Hex::new(2, -1)), &[unit]);
+    }
+
+    #[test]
+    fn removed_units_are_gone() {
+        let mut index = SpatialIndex::default();
+        let unit = Entity::from_raw(1);
+        index.insert(unit, Hex::ZERO);
+        index.remove(unit);
+        assert!(!index.contains(unit));
+        assert!(index.at(Hex::ZERO).is_empty());
+
+        // Removing twice is harmless
+        index.remove(unit);
+    }
+
+    #[test]
+    fn within_finds_nearest_rings_first_and_stops_at_the_radius() {
+        let mut index = SpatialIndex::default();
+        let (near, far, outside) = (Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3));
+        index.insert(far, Hex::new(2, 0));
+        index.insert(near, Hex::new(0, 1));
+        index.insert(outside, Hex::new(3, 0));
+        let found: Vec<Entity> = index.within(Hex::ZERO, 2).collect();
+        assert_eq!(found, vec![near, far]);
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::HashMap;
+
+// The index itself, free of ECS systems so the benchmarks in `benches/` can
+// build it on their own.
+
+#[derive(Resource, Default)]
+pub struct SpatialIndex {
+    cells: HashMap<Hex, Vec<Entity>>,
+    positions: HashMap<Entity, Hex>,
+}
+
+impl SpatialIndex {
+    // Place (or move) an entity in a hex
+    pub fn insert(&mut self, entity: Entity, hex: Hex) {
+        match self.positions.insert(entity, hex) {
+            Some(old) if old == hex => return,
+            Some(old) => self.remove_from_cell(entity, old),
+            None => {}
+        }
+        self.cells.entry(hex).or_default().push(entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(old) = self.positions.remove(&entity) {
+            self.remove_from_cell(entity, old);
+        }
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, hex: Hex) {
+        if let Some(cell) = self.cells.get_mut(&hex) {
+            cell.retain(|&e| e != entity);
+            if cell.is_empty() {
+                self.cells.remove(&hex);
+            }
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.positions.contains_key(&entity)
+    }
+
+    // Units in one hex, in the order they arrived
+    pub fn at(&self, hex: Hex) -> &[Entity] {
+        self.cells.get(&hex).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // Units within `radius` hexes of `center` (inclusive), nearest rings first
+    pub fn within(&self, center: Hex, radius: u32) -> impl Iterator<Item = Entity> + '_ {
+        center.spiral_range(0..=radius).flat_map(move |hex| self.at(hex).iter().copied())
+    }
+}
@@ -0,0 +1,94 @@
+// Where saved data lives. Saves and settings are addressed by relative paths
+// like `saves/world/chunk_0_0.ron` and read and written through `Storage`,
+// so the code that saves doesn't care about the platform: native builds use
+// files under the working directory, the web build the browser's
+// localStorage (one entry per path), so settings and the colony survive a page
+// reload. localStorage is synchronous and small saves fit its quota easily,
+// which keeps saving as simple as on disk.
+
+pub trait Storage: Sync {
+    fn read(&self, path: &str) -> Option<String>;
+    fn write(&self, path: &str, contents: &str) -> Result<(), String>;
+    // Paths of everything saved directly inside `dir`
+    fn list(&self, dir: &str) -> Vec<String>;
+}
+
+// The storage of the platform we're running on
+pub fn storage() -> &'static dyn Storage {
+    #[cfg(target_arch = "wasm32")]
+    {
+        &BrowserStorage
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        &FileStorage
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct FileStorage;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn read(&self, path: &str) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn write(&self, path: &str, contents: &str) -> Result<(), String> {
+        if let Some(dir) = std::path::Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(path, contents).map_err(|err| err.to_string())
+    }
+
+    fn list(&self, dir: &str) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().to_str().map(|name| format!("{dir}/{name}")))
+            .collect()
+    }
+}
+
+// Keys are prefixed so other apps on the same origin don't collide with us
+#[cfg(target_arch = "wasm32")]
+const KEY_PREFIX: &str = "ant-colony/";
+
+#[cfg(target_arch = "wasm32")]
+struct BrowserStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl BrowserStorage {
+    // None in private modes or sandboxed frames that block storage
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for BrowserStorage {
+    fn read(&self, path: &str) -> Option<String> {
+        Self::local_storage()?.get_item(&format!("{KEY_PREFIX}{path}")).ok()?
+    }
+
+    fn write(&self, path: &str, contents: &str) -> Result<(), String> {
+        let storage = Self::local_storage().ok_or("localStorage is not available")?;
+        // Fails when the origin's quota is used up
+        storage.set_item(&format!("{KEY_PREFIX}{path}"), contents).map_err(|err| format!("{err:?}"))
+    }
+
+    fn list(&self, dir: &str) -> Vec<String> {
+        let Some(storage) = Self::local_storage() else {
+            return Vec::new();
+        };
+        let prefix = format!("{dir}/");
+        (0..storage.length().unwrap_or(0))
+            .filter_map(|index| storage.key(index).ok().flatten())
+            .filter_map(|key| key.strip_prefix(KEY_PREFIX).map(str::to_string))
+            .filter(|path| path.strip_prefix(&prefix).is_some_and(|name| !name.contains('/')))
+            .collect()
+    }
+}
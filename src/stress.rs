@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::RigidBody;
+use bevy::utils::{Duration, Instant};
+use hexx::Hex;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::coords::HexLayoutExt;
+use crate::determinism::SimRng;
+use crate::net::LocalPlayer;
+use crate::pathfinding::{PathPriority, PathQueue};
+use crate::units::UnitSpawner;
+use crate::{Caste, Faction, MapLayout, MapSize};
+
+// `--stress N`: N thousand extra workers on a map grown to fit them, a share
+// of them sent somewhere random every second, and a report every few seconds
+// of frame time, time spent in `move_ants` and pathfinding throughput. For
+// catching performance regressions with the whole game running; the
+// criterion benches in `benches/` time the pathfinder and spatial index alone.
+
+const ORDER_SECS: f32 = 1.0;
+// Share of stress ants given a new destination each order round
+const ORDER_SHARE: f64 = 0.05;
+const REPORT_SECS: f32 = 5.0;
+// Room to spare so ants can still move around
+const HEXES_PER_ANT: usize = 2;
+
+#[derive(Resource)]
+struct StressTest {
+    ants: usize,
+    order_timer: Timer,
+    report_timer: Timer,
+    frames: u32,
+    frame_time: Duration,
+    movement_time: Duration,
+    movement_started: Option<Instant>,
+    solved_at_last_report: u64,
+}
+
+#[derive(Component)]
+struct StressAnt;
+
+fn stress_count_from_args() -> usize {
+    let args: Vec<String> = std::env::args().collect();
+    args.windows(2)
+        .find(|pair| pair[0] == "--stress")
+        .and_then(|pair| pair[1].parse::<usize>().ok())
+        .map_or(0, |thousands| thousands * 1000)
+}
+
+// Smallest map radius with room for every ant
+fn enlarge_map(stress: Res<StressTest>, mut map_size: ResMut<MapSize>) {
+    let needed = stress.ants * HEXES_PER_ANT;
+    // A map of radius r holds 3r² - 3r + 1 hexes
+    let mut radius = map_size.radius;
+    while ((3 * radius * radius - 3 * radius + 1) as usize) < needed {
+        radius += 1;
+    }
+    map_size.radius = radius;
+}
+
+fn spawn_stress_ants(
+    mut commands: Commands,
+    stress: Res<StressTest>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    local_player: Res<LocalPlayer>,
+    spawner: UnitSpawner,
+    mut rng: ResMut<SimRng>,
+) {
+    // Keep clear of the starting colony in the middle
+    let mut hexes: Vec<Hex> = map_size.hexes().filter(|hex| hex.unsigned_distance_to(Hex::ZERO) > 2).collect();
+    hexes.shuffle(&mut rng.0);
+    for &hex in hexes.iter().take(stress.ants) {
+        let entity = spawner.spawn(&mut commands, Caste::Worker, Faction(local_player.0), layout.0.hex_to_world(hex));
+        commands.entity(entity).insert(StressAnt);
+    }
+    info!("Stress test: spawned {} ants on a map of radius {}", stress.ants.min(hexes.len()), map_size.radius);
+}
+
+fn order_stress_ants(
+    time: Res<Time>,
+    mut stress: ResMut<StressTest>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    mut rng: ResMut<SimRng>,
+    mut path_queue: ResMut<PathQueue>,
+    ant_q: Query<(Entity, &Transform), With<StressAnt>>,
+) {
+    if !stress.order_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let radius = map_size.radius as i32 - 1;
+    for (entity, transform) in ant_q.iter() {
+        if !rng.0.gen_bool(ORDER_SHARE) {
+            continue;
+        }
+        let to = Hex::new(rng.0.gen_range(-radius..=radius), rng.0.gen_range(-radius..=radius));
+        if !map_size.contains(to) {
+            continue;
+        }
+        let from = layout.0.world_to_hex(transform.translation.truncate());
+        path_queue.request(entity, from, to, PathPriority::Ai);
+    }
+}
+
+fn start_movement_timer(mut stress: ResMut<StressTest>) {
+    stress.movement_started = Some(Instant::now());
+}
+
+fn stop_movement_timer(mut stress: ResMut<StressTest>) {
+    if let Some(started) = stress.movement_started.take() {
+        stress.movement_time += started.elapsed();
+    }
+}
+
+fn report_stress(
+    time: Res<Time<Real>>,
+    mut stress: ResMut<StressTest>,
+    path_queue: Res<PathQueue>,
+    body_q: Query<&RigidBody, With<StressAnt>>,
+) {
+    stress.frames += 1;
+    stress.frame_time += time.delta();
+    if !stress.report_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let frames = stress.frames.max(1);
+    let solved = path_queue.solved_total() - stress.solved_at_last_report;
+    // See `movement`; compare runs with `--movement dynamic` and `--movement kinematic`
+    let kinematic = body_q.iter().filter(|body| **body == RigidBody::KinematicVelocityBased).count();
+    info!(
+        "Stress test ({} ants, {} kinematic): frame {:.2} ms, move_ants {:.3} ms, {:.0} paths/s ({} queued)",
+        stress.ants,
+        kinematic,
+        stress.frame_time.as_secs_f64() * 1000.0 / f64::from(frames),
+        stress.movement_time.as_secs_f64() * 1000.0 / f64::from(frames),
+        solved as f64 / f64::from(REPORT_SECS),
+        path_queue.pending(),
+    );
+    stress.frames = 0;
+    stress.frame_time = Duration::ZERO;
+    stress.movement_time = Duration::ZERO;
+    stress.solved_at_last_report = path_queue.solved_total();
+}
+
+pub struct StressPlugin;
+
+impl Plugin for StressPlugin {
+    fn build(&self, app: &mut App) {
+        let ants = stress_count_from_args();
+        if ants == 0 {
+            return;
+        }
+        app.insert_resource(StressTest {
+            ants,
+            order_timer: Timer::from_seconds(ORDER_SECS, TimerMode::Repeating),
+            report_timer: Timer::from_seconds(REPORT_SECS, TimerMode::Repeating),
+            frames: 0,
+            frame_time: Duration::ZERO,
+            movement_time: Duration::ZERO,
+            movement_started: None,
+            solved_at_last_report: 0,
+        })
+        .add_systems(
+            OnEnter(crate::loading::AppState::InGame),
+            (enlarge_map, spawn_stress_ants.after(crate::units::apply_unit_definitions))
+                .chain()
+                .after(crate::lobby::apply_match_settings)
+                .before(crate::spawn_units),
+        )
+        .add_systems(
+            Update,
+            (
+                order_stress_ants,
+                start_movement_timer.before(crate::move_ants),
+                stop_movement_timer.after(crate::move_ants),
+                report_stress,
+            )
+                .run_if(in_state(crate::loading::AppState::InGame)),
+        );
+    }
+}
@@ -0,0 +1,649 @@
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+use bevy_rapier2d::prelude::*;
+use hexx::Hex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::burrow::{BurrowNetwork, FastTravel};
+use crate::collision;
+use crate::coords::HexLayoutExt;
+use crate::game_commands::{AttackMove, AttackTarget, GameCommand};
+use crate::input::{pointer_over_ui, PointerAction};
+use crate::interactions::{FoodDropOff, FoodStores};
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::locale::{Locale, Localized};
+use crate::net::{LocalPlayer, PlayerId};
+use crate::pathfinding::{PathPriority, PathQueue};
+use crate::rocks::Rocks;
+use crate::sfx::{PlaySound, SoundEffect};
+use crate::spatial::SpatialIndex;
+use crate::weather::Flooded;
+use crate::zones::{self, Zone, ZoneEffect, ZoneFilter};
+use crate::{chunk_of, Ant, Caste, Faction, MainCamera, MapLayout, MapSize, Path, Patrol, Queen};
+
+// Nest structures. A structure is ordered as a foundation on a hex (paid for
+// up front) and is raised by workers standing next to it; more workers build
+// faster. The hex is blocked for pathing and spawning from the moment the
+// foundation is laid. Once finished it gives its colony a bonus:
+// - storage chamber: a second place to drop off carried food,
+// - nursery: extra brood slots,
+// - guard post: slowly heals the colony's ants around it (a healing zone, see
+//   `zones`).
+// N opens the build menu. Picking a structure there enters placement: the
+// hovered hex shows green where it can go and red where it can't, a click
+// orders it built (`GameCommand::Build`), which lays the foundation and sends
+// the nearest idle workers over to build it; right click or Escape cancels.
+// Clicking a structure shows its info panel. Structures are part of the
+// world save: a chunk is saved again whenever one in it is laid or worked on.
+
+// Worker-seconds of building to finish a structure
+const BUILD_WORK: f32 = 30.0;
+const NURSERY_BROOD_SLOTS: usize = 2;
+const GUARD_RADIUS: u32 = 2;
+// Zones tick once a second
+const GUARD_HEAL_PER_TICK: f32 = 2.0;
+const DROP_OFF_RADIUS: f32 = 12.0;
+// Farthest a structure may be from its colony's queen, in hexes
+const COLONY_RANGE: u32 = 5;
+// Idle workers sent to a new foundation
+const BUILDERS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StructureKind {
+    StorageChamber,
+    Nursery,
+    GuardPost,
+}
+
+impl StructureKind {
+    pub const ALL: [StructureKind; 3] = [StructureKind::StorageChamber, StructureKind::Nursery, StructureKind::GuardPost];
+
+    pub fn name_key(self) -> &'static str {
+        match self {
+            StructureKind::StorageChamber => "structures.storage-chamber",
+            StructureKind::Nursery => "structures.nursery",
+            StructureKind::GuardPost => "structures.guard-post",
+        }
+    }
+
+    fn bonus_key(self) -> &'static str {
+        match self {
+            StructureKind::StorageChamber => "structures.storage-chamber-bonus",
+            StructureKind::Nursery => "structures.nursery-bonus",
+            StructureKind::GuardPost => "structures.guard-post-bonus",
+        }
+    }
+
+    fn cost(self) -> u32 {
+        match self {
+            StructureKind::StorageChamber => 10,
+            StructureKind::Nursery => 15,
+            StructureKind::GuardPost => 12,
+        }
+    }
+
+    fn color(self) -> Color {
+        let hex = match self {
+            StructureKind::StorageChamber => "A1887F",
+            StructureKind::Nursery => "F8BBD0",
+            StructureKind::GuardPost => "78909C",
+        };
+        Color::from(Srgba::hex(hex).unwrap())
+    }
+}
+
+#[derive(Component)]
+pub struct Structure {
+    pub kind: StructureKind,
+    hex: Hex,
+    // 0..1; finished at 1
+    progress: f32,
+}
+
+impl Structure {
+    pub fn is_built(&self) -> bool {
+        self.progress >= 1.0
+    }
+
+    pub fn hex(&self) -> Hex {
+        self.hex
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+}
+
+// Which hexes hold a structure, and how many finished ones each colony has
+#[derive(Resource, Default)]
+pub struct Structures {
+    by_hex: HashMap<Hex, Entity>,
+    built: HashMap<(PlayerId, StructureKind), usize>,
+    // Chunks changed since the last save
+    dirty_chunks: HashSet<Hex>,
+}
+
+impl Structures {
+    pub fn occupies(&self, hex: Hex) -> bool {
+        self.by_hex.contains_key(&hex)
+    }
+
+    pub fn occupied(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.by_hex.keys().copied()
+    }
+
+    pub fn at(&self, hex: Hex) -> Option<Entity> {
+        self.by_hex.get(&hex).copied()
+    }
+
+    fn built_count(&self, player: PlayerId, kind: StructureKind) -> usize {
+        self.built.get(&(player, kind)).copied().unwrap_or(0)
+    }
+
+    pub fn brood_slot_bonus(&self, player: PlayerId) -> usize {
+        NURSERY_BROOD_SLOTS * self.built_count(player, StructureKind::Nursery)
+    }
+
+    pub fn take_dirty_chunks(&mut self) -> HashSet<Hex> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
+}
+
+#[derive(Resource, Default)]
+struct SelectedStructure(Option<Entity>);
+
+// The structure being placed, if any. While set, clicks place it instead of
+// selecting or ordering units.
+#[derive(Resource, Default)]
+pub struct BuildPlacement {
+    kind: Option<StructureKind>,
+}
+
+impl BuildPlacement {
+    pub fn is_active(&self) -> bool {
+        self.kind.is_some()
+    }
+}
+
+#[derive(Component)]
+struct BuildMenu;
+
+#[derive(Component)]
+struct BuildButton(StructureKind);
+
+#[derive(Component)]
+struct StructureInfoPanel;
+
+// Lay a foundation, paid for from the player's store; false if they can't afford it
+pub fn order_structure(
+    commands: &mut Commands,
+    structures: &mut Structures,
+    stores: &mut FoodStores,
+    layout: &MapLayout,
+    kind: StructureKind,
+    player: PlayerId,
+    hex: Hex,
+) -> bool {
+    let food = stores.0.entry(player).or_default();
+    if *food < kind.cost() || structures.occupies(hex) {
+        return false;
+    }
+    *food -= kind.cost();
+    spawn_structure(commands, structures, layout, kind, player, hex, 0.0);
+    true
+}
+
+// A structure as far along as `progress`; finished ones already give their
+// bonus. Used for new foundations, the sandbox and structures loaded from the
+// save.
+pub fn spawn_structure(
+    commands: &mut Commands,
+    structures: &mut Structures,
+    layout: &MapLayout,
+    kind: StructureKind,
+    player: PlayerId,
+    hex: Hex,
+    progress: f32,
+) -> Entity {
+    let structure = Structure { kind, hex, progress: progress.clamp(0.0, 1.0) };
+    let built = structure.is_built();
+    let entity = commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    // Foundations are see-through until finished
+                    color: if built { kind.color() } else { kind.color().with_alpha(0.35) },
+                    custom_size: Some(Vec2::splat(16.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(layout.0.hex_to_world(hex).extend(0.3)),
+                ..default()
+            },
+            // Solid like terrain: units walk around it and nothing spawns on it
+            RigidBody::Fixed,
+            Collider::ball(8.0),
+            collision::terrain_collision_groups(),
+            structure,
+            Faction(player),
+        ))
+        .id();
+    structures.by_hex.insert(hex, entity);
+    structures.dirty_chunks.insert(chunk_of(hex));
+    if built {
+        finish_structure(commands, structures, entity, kind, player);
+    }
+    entity
+}
+
+// Puts a finished structure's bonus into effect
+fn finish_structure(commands: &mut Commands, structures: &mut Structures, entity: Entity, kind: StructureKind, player: PlayerId) {
+    *structures.built.entry((player, kind)).or_default() += 1;
+    if kind == StructureKind::GuardPost {
+        let heal = Zone::new(GUARD_RADIUS, Some(player), ZoneFilter::Friendly, ZoneEffect::Heal(GUARD_HEAL_PER_TICK));
+        zones::attach_zone(commands, entity, heal);
+    }
+    if kind == StructureKind::StorageChamber {
+        let drop_off = commands
+            .spawn((
+                Collider::ball(DROP_OFF_RADIUS),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+                collision::sensor_collision_groups(),
+                TransformBundle::default(),
+                FoodDropOff(player),
+            ))
+            .id();
+        commands.entity(entity).add_child(drop_off);
+    }
+}
+
+fn setup_structure_ui(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(8.0),
+                    left: Val::Px(8.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.85).into(),
+                ..default()
+            },
+            Interaction::default(),
+            BuildMenu,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 18.0, color: Color::WHITE, ..default() }),
+                Localized("structures.build-title"),
+            ));
+            for kind in StructureKind::ALL {
+                panel
+                    .spawn((
+                        ButtonBundle {
+                            style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+                            background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                            ..default()
+                        },
+                        BuildButton(kind),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            "",
+                            TextStyle { font_size: 14.0, color: Color::WHITE, ..default() },
+                        ));
+                    });
+            }
+        });
+
+    commands.spawn((
+        TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(8.0),
+                left: Val::Px(220.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                display: Display::None,
+                ..default()
+            },
+            background_color: Color::srgba(0.1, 0.1, 0.1, 0.85).into(),
+            ..TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() })
+        },
+        StructureInfoPanel,
+    ));
+}
+
+fn toggle_build_menu(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut style_q: Query<&mut Style, With<BuildMenu>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleBuildMenu) {
+        return;
+    }
+    for mut style in style_q.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn update_build_labels(locale: Res<Locale>, button_q: Query<(&BuildButton, &Children)>, mut text_q: Query<&mut Text>) {
+    if !locale.is_changed() {
+        return;
+    }
+    for (button, children) in button_q.iter() {
+        let name = locale.get(button.0.name_key());
+        let label = locale.format("structures.build-button", &[("name", &name), ("cost", &button.0.cost())]);
+        for &child in children.iter() {
+            if let Ok(mut text) = text_q.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+// Picking a structure in the build menu starts placing it
+fn build_buttons(button_q: Query<(&Interaction, &BuildButton), Changed<Interaction>>, mut placement: ResMut<BuildPlacement>) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction == Interaction::Pressed {
+            placement.kind = Some(button.0);
+        }
+    }
+}
+
+// Where a colony may build: on the map, on open dry ground (not flooded, not
+// rock, not a tunnel entrance), on a hex nobody stands in or builds on, and within reach
+// of its queen but outside the nursery ring right next to her
+#[derive(SystemParam)]
+struct SiteCheck<'w, 's> {
+    layout: Res<'w, MapLayout>,
+    map_size: Res<'w, MapSize>,
+    flooded: Res<'w, Flooded>,
+    network: Res<'w, BurrowNetwork>,
+    rocks: Res<'w, Rocks>,
+    spatial_index: Res<'w, SpatialIndex>,
+    queen_q: Query<'w, 's, (&'static Transform, &'static Faction), With<Queen>>,
+}
+
+impl SiteCheck<'_, '_> {
+    fn is_valid(&self, hex: Hex, player: PlayerId, structures: &Structures) -> bool {
+        let near_queen = self.queen_q.iter().any(|(transform, faction)| {
+            let distance = self.layout.0.world_to_hex(transform.translation.truncate()).unsigned_distance_to(hex);
+            faction.0 == player && (2..=COLONY_RANGE).contains(&distance)
+        });
+        near_queen
+            && self.map_size.contains(hex)
+            && !self.flooded.contains(hex)
+            && !self.network.is_entrance(hex)
+            && !self.rocks.contains(hex)
+            && self.spatial_index.at(hex).is_empty()
+            && !structures.occupies(hex)
+    }
+}
+
+fn cursor_world_pos(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera_q: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = camera_q.get_single().ok()?;
+    windows
+        .get_single()
+        .ok()?
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+}
+
+fn draw_placement(
+    mut gizmos: Gizmos,
+    placement: Res<BuildPlacement>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    check: SiteCheck,
+    structures: Res<Structures>,
+    local_player: Res<LocalPlayer>,
+) {
+    if placement.kind.is_none() {
+        return;
+    }
+    let Some(world_pos) = cursor_world_pos(&windows, &camera_q) else {
+        return;
+    };
+    let hex = check.layout.0.world_to_hex(world_pos);
+    let color = if check.is_valid(hex, local_player.0, &structures) { "4CAF50" } else { "F44336" };
+    let color = Color::from(Srgba::hex(color).unwrap());
+    let corners = check.layout.0.world_corners(hex);
+    for i in 0..6 {
+        gizmos.line_2d(corners[i], corners[(i + 1) % 6], color);
+    }
+}
+
+fn cancel_placement(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut placement: ResMut<BuildPlacement>,
+) {
+    if placement.kind.is_some()
+        && (mouse_input.just_pressed(MouseButton::Right) || bindings.just_pressed(&keyboard_input, Action::CancelPlacement))
+    {
+        placement.kind = None;
+    }
+}
+
+// A click or tap on a valid hex orders the structure built there
+fn confirm_placement(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    ui_q: Query<&Interaction, With<Node>>,
+    placement: Res<BuildPlacement>,
+    check: SiteCheck,
+    structures: Res<Structures>,
+    local_player: Res<LocalPlayer>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    let Some(kind) = placement.kind else {
+        return;
+    };
+    if pointer_over_ui(&ui_q) {
+        return;
+    }
+    let world_pos = if mouse_input.just_pressed(MouseButton::Left) {
+        cursor_world_pos(&windows, &camera_q)
+    } else {
+        let Ok((camera, camera_transform)) = camera_q.get_single() else {
+            return;
+        };
+        touches
+            .iter_just_pressed()
+            .next()
+            .and_then(|touch| camera.viewport_to_world_2d(camera_transform, touch.position()))
+    };
+    let Some(world_pos) = world_pos else {
+        return;
+    };
+    let site = check.layout.0.world_to_hex(world_pos);
+    if check.is_valid(site, local_player.0, &structures) {
+        game_commands.send(GameCommand::Build { player: local_player.0, kind, hex: site });
+    }
+}
+
+// `GameCommand::Build`: lays the foundation and sends the player's nearest idle
+// workers to stand around it. Placement ends once ours is laid.
+fn execute_build_commands(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    mut placement: ResMut<BuildPlacement>,
+    check: SiteCheck,
+    mut structures: ResMut<Structures>,
+    mut stores: ResMut<FoodStores>,
+    local_player: Res<LocalPlayer>,
+    worker_q: Query<(Entity, &Transform, &Faction, &Caste, &Path), With<Ant>>,
+    mut path_queue: ResMut<PathQueue>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Build { player, kind, hex: site } = *command else {
+            continue;
+        };
+        if !check.is_valid(site, player, &structures) {
+            continue;
+        }
+        if !order_structure(&mut commands, &mut structures, &mut stores, &check.layout, kind, player, site) {
+            continue;
+        }
+        if player == local_player.0 {
+            placement.kind = None;
+        }
+
+        let mut idle_workers: Vec<_> = worker_q
+            .iter()
+            .filter(|(entity, _, faction, caste, path)| {
+                faction.0 == player && **caste == Caste::Worker && path.waypoints.is_empty() && !path_queue.is_pending(*entity)
+            })
+            .map(|(entity, transform, ..)| (entity, check.layout.0.world_to_hex(transform.translation.truncate())))
+            .collect();
+        idle_workers.sort_by_key(|(_, hex)| hex.unsigned_distance_to(site));
+
+        let spots = site
+            .all_neighbors()
+            .into_iter()
+            .filter(|&hex| {
+                check.map_size.contains(hex)
+                    && !check.flooded.contains(hex)
+                    && !check.rocks.contains(hex)
+                    && !structures.occupies(hex)
+            });
+        for ((entity, from), spot) in idle_workers.into_iter().take(BUILDERS).zip(spots) {
+            path_queue.request(entity, from, spot, PathPriority::Ai);
+            commands.entity(entity).remove::<(Patrol, FastTravel, AttackTarget, AttackMove)>();
+        }
+    }
+}
+
+// Workers next to an unfinished structure of their colony raise it
+fn construct_structures(
+    mut commands: Commands,
+    time: Res<Time>,
+    spatial_index: Res<SpatialIndex>,
+    mut structures: ResMut<Structures>,
+    mut structure_q: Query<(Entity, &mut Structure, &Faction, &mut Sprite)>,
+    worker_q: Query<(&Faction, &Caste), With<Ant>>,
+    local_player: Res<LocalPlayer>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for (entity, mut structure, faction, mut sprite) in structure_q.iter_mut() {
+        if structure.is_built() {
+            continue;
+        }
+        let builders = spatial_index
+            .within(structure.hex, 1)
+            .filter(|&unit| {
+                worker_q
+                    .get(unit)
+                    .is_ok_and(|(worker_faction, caste)| worker_faction == faction && *caste == Caste::Worker)
+            })
+            .count();
+        if builders == 0 {
+            continue;
+        }
+
+        structure.progress = (structure.progress + builders as f32 * time.delta_seconds() / BUILD_WORK).min(1.0);
+        structures.dirty_chunks.insert(chunk_of(structure.hex));
+        if !structure.is_built() {
+            continue;
+        }
+        sprite.color = structure.kind.color();
+        finish_structure(&mut commands, &mut structures, entity, structure.kind, faction.0);
+        if faction.0 == local_player.0 {
+            sounds.send(PlaySound::ui(SoundEffect::ProductionComplete));
+        }
+    }
+}
+
+fn select_structures(
+    mut actions: EventReader<PointerAction>,
+    layout: Res<MapLayout>,
+    structures: Res<Structures>,
+    mut selected: ResMut<SelectedStructure>,
+) {
+    for action in actions.read() {
+        if let PointerAction::Select { world_pos, .. } = *action {
+            selected.0 = structures.by_hex.get(&layout.0.world_to_hex(world_pos)).copied();
+        }
+    }
+}
+
+fn update_structure_info(
+    selected: Res<SelectedStructure>,
+    locale: Res<Locale>,
+    structure_q: Query<&Structure>,
+    mut panel_q: Query<(&mut Text, &mut Style), With<StructureInfoPanel>>,
+) {
+    let Ok((mut text, mut style)) = panel_q.get_single_mut() else {
+        return;
+    };
+    let Some(structure) = selected.0.and_then(|entity| structure_q.get(entity).ok()) else {
+        style.display = Display::None;
+        return;
+    };
+    style.display = Display::Flex;
+    text.sections[0].value = if structure.is_built() {
+        format!("{}\n{}", locale.get(structure.kind.name_key()), locale.get(structure.kind.bonus_key()))
+    } else {
+        let progress = format!("{:.0}", structure.progress * 100.0);
+        let status = locale.format("structures.building", &[("progress", &progress)]);
+        format!("{}\n{status}", locale.get(structure.kind.name_key()))
+    };
+}
+
+fn draw_selected_structure(
+    mut gizmos: Gizmos,
+    selected: Res<SelectedStructure>,
+    layout: Res<MapLayout>,
+    structure_q: Query<&Structure>,
+) {
+    let Some(structure) = selected.0.and_then(|entity| structure_q.get(entity).ok()) else {
+        return;
+    };
+    let color = Color::from(Srgba::hex("FFD54F").unwrap());
+    let corners = layout.0.world_corners(structure.hex);
+    for i in 0..6 {
+        gizmos.line_2d(corners[i], corners[(i + 1) % 6], color);
+    }
+}
+
+pub struct StructuresPlugin;
+
+impl Plugin for StructuresPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Structures>()
+            .init_resource::<SelectedStructure>()
+            .init_resource::<BuildPlacement>()
+            .register_action(Action::ToggleBuildMenu, &[KeyCode::KeyN])
+            .register_action(Action::CancelPlacement, &[KeyCode::Escape])
+            .add_systems(Startup, setup_structure_ui)
+            .add_systems(Update, update_build_labels)
+            .add_systems(
+                Update,
+                (
+                    toggle_build_menu,
+                    (build_buttons, cancel_placement, confirm_placement, execute_build_commands, draw_placement)
+                        .chain()
+                        .after(crate::ant_input),
+                    construct_structures.after(crate::spatial::update_spatial_index),
+                    select_structures,
+                    update_structure_info,
+                    draw_selected_structure,
+                )
+                    .run_if(in_state(crate::loading::AppState::InGame)),
+            );
+    }
+}
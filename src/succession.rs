@@ -0,0 +1,254 @@
+use bevy::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::game_commands::GameCommand;
+use crate::health::Health;
+use crate::interactions::FoodStores;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::loading::AppState;
+use crate::locale::Locale;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::units::{UnitDefinitions, UnitSpawner};
+use crate::upgrades::ColonyUpgrades;
+use crate::{Caste, Faction, Queen, Selected};
+
+// Queen succession. A colony that has bought the princess research raises a
+// princess: the next larva to pupate after it has none comes out as one (see
+// `brood`), a worker a size bigger than the rest. When the queen dies while a
+// princess lives, the colony isn't beaten yet: it has `CROWN_SECS` to crown
+// her (F11 crowns the selected princess, or any of ours if none is), and
+// only loses if the time runs out or every princess dies first. Crowning has
+// its price: the new queen starts at half health, half the food store is
+// lost in the upheaval and the research is used up, so the next succession
+// needs it bought again.
+
+const CROWN_SECS: f32 = 60.0;
+const CROWNED_HEALTH: f32 = 0.5;
+const PRINCESS_SCALE: f32 = 1.3;
+
+#[derive(Component)]
+pub struct Princess;
+
+#[derive(Resource, Default)]
+pub struct Succession {
+    // Colonies whose queen was alive last frame
+    reigning: BTreeSet<PlayerId>,
+    // Colonies without a queen, and how long they have left to crown one
+    interregnums: BTreeMap<PlayerId, Timer>,
+}
+
+impl Succession {
+    // Whether the colony lost its queen but can still crown a princess
+    pub fn pending(&self, player: PlayerId) -> bool {
+        self.interregnums.contains_key(&player)
+    }
+
+    fn secs_left(&self, player: PlayerId) -> Option<f32> {
+        self.interregnums.get(&player).map(Timer::remaining_secs)
+    }
+}
+
+#[derive(Component)]
+struct SuccessionBanner;
+
+fn setup_succession_banner(mut commands: Commands) {
+    let style = TextStyle { font_size: 20.0, color: Color::from(Srgba::hex("FFD54F").unwrap()), ..default() };
+    commands.spawn((
+        TextBundle::from_section("", style).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            left: Val::Percent(30.0),
+            display: Display::None,
+            ..default()
+        }),
+        SuccessionBanner,
+    ));
+}
+
+fn dress_princesses(mut princess_q: Query<&mut Transform, Added<Princess>>) {
+    for mut transform in princess_q.iter_mut() {
+        transform.scale = Vec3::splat(PRINCESS_SCALE);
+    }
+}
+
+// Opens an interregnum for every colony that just lost its queen with a
+// princess to succeed her, and closes those that ran out of time or princesses
+fn watch_queens(
+    time: Res<Time>,
+    mut succession: ResMut<Succession>,
+    queen_q: Query<&Faction, With<Queen>>,
+    princess_q: Query<&Faction, With<Princess>>,
+) {
+    let alive: BTreeSet<PlayerId> = queen_q.iter().map(|faction| faction.0).collect();
+    let has_princess = |player: PlayerId| princess_q.iter().any(|faction| faction.0 == player);
+
+    let fallen: Vec<PlayerId> = succession.reigning.difference(&alive).copied().collect();
+    for player in fallen {
+        if has_princess(player) {
+            info!("Queen of player {} died; {CROWN_SECS} s to crown a princess", player.0);
+            succession.interregnums.insert(player, Timer::from_seconds(CROWN_SECS, TimerMode::Once));
+        }
+    }
+    succession.reigning = alive;
+
+    let mut ended = Vec::new();
+    for (&player, timer) in succession.interregnums.iter_mut() {
+        if timer.tick(time.delta()).finished() || !has_princess(player) {
+            ended.push(player);
+        }
+    }
+    for player in ended {
+        info!("Succession failed for player {}", player.0);
+        succession.interregnums.remove(&player);
+    }
+}
+
+fn crown_key(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    local_player: Res<LocalPlayer>,
+    succession: Res<Succession>,
+    princess_q: Query<(Entity, &Faction, Has<Selected>), With<Princess>>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    let player = local_player.0;
+    if !bindings.just_pressed(&keyboard_input, Action::CrownPrincess) || !succession.pending(player) {
+        return;
+    }
+    let mut ours: Vec<_> = princess_q.iter().filter(|(_, faction, _)| faction.0 == player).collect();
+    // A selected princess first
+    ours.sort_by_key(|&(entity, _, selected)| (!selected, entity));
+    if let Some(&(unit, ..)) = ours.first() {
+        game_commands.send(GameCommand::Crown { player, unit });
+    }
+}
+
+// `GameCommand::Crown`: the princess becomes the colony's new queen
+fn execute_crowning(
+    mut commands: Commands,
+    mut game_commands: EventReader<GameCommand>,
+    mut succession: ResMut<Succession>,
+    mut upgrades: ResMut<ColonyUpgrades>,
+    mut stores: ResMut<FoodStores>,
+    spawner: UnitSpawner,
+    definitions: Res<UnitDefinitions>,
+    princess_q: Query<(&Faction, &Transform), With<Princess>>,
+) {
+    for command in game_commands.read() {
+        let GameCommand::Crown { player, unit } = *command else {
+            continue;
+        };
+        if !succession.pending(player) {
+            continue;
+        }
+        let Ok((faction, transform)) = princess_q.get(unit) else {
+            continue;
+        };
+        if faction.0 != player {
+            continue;
+        }
+
+        commands.entity(unit).despawn_recursive();
+        let queen = spawner.spawn(&mut commands, Caste::Queen, *faction, transform.translation.truncate());
+        let max = definitions.get(Caste::Queen).health;
+        commands.entity(queen).insert(Health { current: max * CROWNED_HEALTH, ..Health::new(max) });
+        if let Some(food) = stores.0.get_mut(&player) {
+            *food /= 2;
+        }
+        upgrades.use_princess_research(player);
+        succession.interregnums.remove(&player);
+        succession.reigning.insert(player);
+        info!("Player {} crowned a new queen", player.0);
+    }
+}
+
+fn update_succession_banner(
+    succession: Res<Succession>,
+    local_player: Res<LocalPlayer>,
+    locale: Res<Locale>,
+    mut banner_q: Query<(&mut Text, &mut Style), With<SuccessionBanner>>,
+) {
+    let left = succession.secs_left(local_player.0);
+    for (mut text, mut style) in banner_q.iter_mut() {
+        let display = if left.is_some() { Display::Flex } else { Display::None };
+        if style.display != display {
+            style.display = display;
+        }
+        if let Some(left) = left {
+            text.sections[0].value = locale.format("succession.crown-now", &[("secs", &left.ceil())]);
+        }
+    }
+}
+
+pub struct SuccessionPlugin;
+
+impl Plugin for SuccessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Succession>()
+            .register_action(Action::CrownPrincess, &[KeyCode::F11])
+            .add_systems(Startup, setup_succession_banner)
+            .add_systems(
+                Update,
+                (
+                    dress_princesses,
+                    crown_key.before(crate::dispatch_pointer_actions),
+                    (execute_crowning.after(crate::dispatch_pointer_actions), watch_queens)
+                        .chain()
+                        .before(crate::victory::evaluate_conditions),
+                    update_succession_banner,
+                )
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).init_resource::<Succession>().add_systems(Update, watch_queens);
+        app
+    }
+
+    #[test]
+    fn a_colony_with_a_princess_gets_an_interregnum() {
+        let mut app = app();
+        let queen = app.world_mut().spawn((Queen, Faction(PlayerId(1)))).id();
+        app.world_mut().spawn((Princess, Faction(PlayerId(1))));
+        app.world_mut().spawn((Queen, Faction(PlayerId(2))));
+        app.update();
+        assert!(!app.world().resource::<Succession>().pending(PlayerId(1)));
+
+        app.world_mut().despawn(queen);
+        app.update();
+        assert!(app.world().resource::<Succession>().pending(PlayerId(1)));
+        assert!(!app.world().resource::<Succession>().pending(PlayerId(2)));
+    }
+
+    #[test]
+    fn a_colony_without_a_princess_falls_with_its_queen() {
+        let mut app = app();
+        let queen = app.world_mut().spawn((Queen, Faction(PlayerId(1)))).id();
+        // Someone else's princess doesn't help
+        app.world_mut().spawn((Princess, Faction(PlayerId(2))));
+        app.update();
+        app.world_mut().despawn(queen);
+        app.update();
+        assert!(!app.world().resource::<Succession>().pending(PlayerId(1)));
+    }
+
+    #[test]
+    fn the_interregnum_ends_when_the_last_princess_dies() {
+        let mut app = app();
+        let queen = app.world_mut().spawn((Queen, Faction(PlayerId(1)))).id();
+        let princess = app.world_mut().spawn((Princess, Faction(PlayerId(1)))).id();
+        app.update();
+        app.world_mut().despawn(queen);
+        app.update();
+        app.world_mut().despawn(princess);
+        app.update();
+        assert!(!app.world().resource::<Succession>().pending(PlayerId(1)));
+    }
+}
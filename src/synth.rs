@@ -0,0 +1,64 @@
+// Tiny software synthesizer for the game's sounds and music: notes rendered
+// to 16-bit samples and wrapped as in-memory WAV clips Bevy can play.
+
+pub const SAMPLE_RATE: u32 = 22_050;
+
+// One note: frequency sweep from start to end (Hz) over `secs`; 0 Hz is noise
+pub struct Note {
+    pub start_hz: f32,
+    pub end_hz: f32,
+    pub secs: f32,
+}
+
+pub const fn note(start_hz: f32, end_hz: f32, secs: f32) -> Note {
+    Note { start_hz, end_hz, secs }
+}
+
+// Square-ish waves with a fast attack and linear decay per note
+pub fn synthesize(notes: &[Note]) -> Vec<i16> {
+    let mut samples = Vec::new();
+    let mut noise: u32 = 0x1234_5678;
+    for note in notes {
+        let count = (note.secs * SAMPLE_RATE as f32) as usize;
+        let mut phase = 0.0_f32;
+        for i in 0..count {
+            let t = i as f32 / count as f32;
+            let value = if note.start_hz == 0.0 {
+                // xorshift white noise
+                noise ^= noise << 13;
+                noise ^= noise >> 17;
+                noise ^= noise << 5;
+                noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+            } else {
+                phase = (phase + (note.start_hz + (note.end_hz - note.start_hz) * t) / SAMPLE_RATE as f32).fract();
+                // Soft square: a sine pushed towards clipping
+                ((phase * std::f32::consts::TAU).sin() * 3.0).clamp(-1.0, 1.0)
+            };
+            let envelope = (t * 50.0).min(1.0) * (1.0 - t);
+            samples.push((value * envelope * 0.5 * i16::MAX as f32) as i16);
+        }
+    }
+    samples
+}
+
+// Mono 16-bit PCM WAV
+pub fn wav_bytes(samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // byte rate
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // block align
+    bytes.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
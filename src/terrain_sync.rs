@@ -0,0 +1,194 @@
+use bevy::prelude::*;
+use hexx::Hex;
+
+use crate::burrow::BurrowNetwork;
+use crate::net::{IncomingMessage, NetConnection, NetMessage, OutgoingMessage, PlayerId};
+use crate::CHUNK_RADIUS;
+
+// Terrain replication. Changes are sent per chunk, never per hex: each
+// message carries the whole chunk as two bitmasks (dug, entrance) over the
+// chunk's hexes in a fixed order, followed by the owner of each entrance, all
+// run-length encoded. A mostly-solid chunk packs into a handful of bytes no
+// matter how much digging happened in it.
+
+// Batching window for terrain changes, so heavy excavation sends each touched
+// chunk at most this often
+const SYNC_INTERVAL_SECS: f32 = 0.25;
+
+#[derive(Resource)]
+struct TerrainSyncTimer(Timer);
+
+// Hexes of a chunk in the order used for the bitmasks
+fn chunk_members(chunk: Hex) -> Vec<Hex> {
+    chunk.to_higher_res(CHUNK_RADIUS).range(CHUNK_RADIUS).collect()
+}
+
+fn pack_bits(bits: impl Iterator<Item = bool>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (i, bit) in bits.enumerate() {
+        if i % 8 == 0 {
+            bytes.push(0);
+        }
+        if bit {
+            *bytes.last_mut().unwrap() |= 1 << (i % 8);
+        }
+    }
+    bytes
+}
+
+// (run length, byte) pairs
+fn rle_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut iter = bytes.iter().peekable();
+    while let Some(&byte) = iter.next() {
+        let mut run: u8 = 1;
+        while run < u8::MAX && iter.peek() == Some(&&byte) {
+            iter.next();
+            run += 1;
+        }
+        out.extend([run, byte]);
+    }
+    out
+}
+
+fn rle_decode(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut out = Vec::new();
+    for pair in data.chunks_exact(2) {
+        out.extend(std::iter::repeat_n(pair[1], pair[0] as usize));
+    }
+    Some(out)
+}
+
+pub fn encode_chunk(network: &BurrowNetwork, chunk: Hex) -> Vec<u8> {
+    let members = chunk_members(chunk);
+    let mut bytes = pack_bits(members.iter().map(|&hex| network.is_dug(hex)));
+    bytes.extend(pack_bits(members.iter().map(|&hex| network.is_entrance(hex))));
+    for owner in members.iter().filter_map(|&hex| network.entrance_owner(hex)) {
+        bytes.extend(owner.0.to_le_bytes());
+    }
+    rle_encode(&bytes)
+}
+
+// Returns the chunk's dug hexes and entrances (with their owners), or `None`
+// for malformed data
+pub fn decode_chunk(chunk: Hex, data: &[u8]) -> Option<(Vec<Hex>, Vec<(Hex, PlayerId)>)> {
+    let members = chunk_members(chunk);
+    let mask_len = members.len().div_ceil(8);
+    let bytes = rle_decode(data)?;
+    if bytes.len() < mask_len * 2 {
+        return None;
+    }
+
+    let bit = |mask: &[u8], i: usize| mask[i / 8] & (1 << (i % 8)) != 0;
+    let (masks, owners) = bytes.split_at(mask_len * 2);
+    let (dug_mask, entrance_mask) = masks.split_at(mask_len);
+    let dug = members.iter().enumerate().filter(|&(i, _)| bit(dug_mask, i)).map(|(_, &h)| h).collect();
+    let entrances: Vec<Hex> =
+        members.iter().enumerate().filter(|&(i, _)| bit(entrance_mask, i)).map(|(_, &h)| h).collect();
+    if owners.len() != entrances.len() * 4 {
+        return None;
+    }
+    let owners = owners.chunks_exact(4).map(|bytes| PlayerId(u32::from_le_bytes(bytes.try_into().unwrap())));
+    Some((dug, entrances.into_iter().zip(owners).collect()))
+}
+
+fn broadcast_terrain_changes(
+    time: Res<Time<Real>>,
+    mut timer: ResMut<TerrainSyncTimer>,
+    mut network: ResMut<BurrowNetwork>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    for chunk in network.take_unsynced_chunks() {
+        let data = encode_chunk(&network, chunk);
+        outgoing.send(OutgoingMessage::broadcast(NetMessage::TerrainChunk { chunk, data }));
+    }
+}
+
+fn handle_terrain_messages(
+    connection: Res<NetConnection>,
+    mut incoming: EventReader<IncomingMessage>,
+    mut network: ResMut<BurrowNetwork>,
+    mut outgoing: EventWriter<OutgoingMessage>,
+) {
+    for message in incoming.read() {
+        match &message.message {
+            // Newly subscribed player: send a snapshot of every chunk they asked for
+            NetMessage::SubscribeChunks { chunks } => {
+                for &chunk in chunks {
+                    let data = encode_chunk(&network, chunk);
+                    outgoing.send(OutgoingMessage::to(message.from, NetMessage::TerrainChunk { chunk, data }));
+                }
+            }
+            // Terrain only comes from the server; as the server, ours is the truth
+            NetMessage::TerrainChunk { .. } if !connection.online => {}
+            NetMessage::TerrainChunk { chunk, data } => match decode_chunk(*chunk, data) {
+                Some((dug, entrances)) => network.replace_chunk(*chunk, &dug, &entrances),
+                None => warn!("Dropping malformed terrain chunk ({}, {})", chunk.x, chunk.y),
+            },
+            _ => {}
+        }
+    }
+}
+
+pub struct TerrainSyncPlugin;
+
+impl Plugin for TerrainSyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TerrainSyncTimer(Timer::from_seconds(SYNC_INTERVAL_SECS, TimerMode::Repeating)))
+            .add_systems(Update, (broadcast_terrain_changes, handle_terrain_messages));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn rle_round_trips_runs_longer_than_a_byte() {
+        let bytes: Vec<u8> = [vec![0; 300], vec![7, 7, 1], vec![0xff; 2]].concat();
+        let encoded = rle_encode(&bytes);
+        assert_eq!(encoded, vec![255, 0, 45, 0, 2, 7, 1, 1, 2, 0xff]);
+        assert_eq!(rle_decode(&encoded), Some(bytes));
+        assert_eq!(rle_decode(&[]), Some(Vec::new()));
+    }
+
+    #[test]
+    fn rle_refuses_a_dangling_run_length() {
+        assert_eq!(rle_decode(&[3, 1, 4]), None);
+    }
+
+    #[test]
+    fn chunk_round_trips_dug_hexes_and_entrances() {
+        let mut network = BurrowNetwork::default();
+        network.dig(Hex::new(1, 0));
+        network.dig(Hex::new(-2, 1));
+        network.add_entrance(Hex::new(0, 1), PlayerId(3));
+        // Outside the chunk, so not part of it
+        network.dig(Hex::new(40, 0));
+
+        let (dug, entrances) = decode_chunk(Hex::ZERO, &encode_chunk(&network, Hex::ZERO)).unwrap();
+        let dug: HashSet<Hex> = dug.into_iter().collect();
+        assert_eq!(dug, HashSet::from([Hex::new(1, 0), Hex::new(-2, 1), Hex::new(0, 1)]));
+        assert_eq!(entrances, vec![(Hex::new(0, 1), PlayerId(3))]);
+    }
+
+    #[test]
+    fn chunk_refuses_truncated_or_oversized_data() {
+        let mut network = BurrowNetwork::default();
+        network.dig(Hex::new(1, 0));
+        network.add_entrance(Hex::new(0, 1), PlayerId(300));
+        let data = encode_chunk(&network, Hex::ZERO);
+
+        assert!(decode_chunk(Hex::ZERO, &data[..data.len() - 1]).is_none());
+        assert!(decode_chunk(Hex::ZERO, &data[..data.len() - 2]).is_none());
+        assert!(decode_chunk(Hex::ZERO, &[data.clone(), vec![1, 0]].concat()).is_none());
+        assert!(decode_chunk(Hex::ZERO, &[]).is_none());
+    }
+}
@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::coords::HexLayoutExt;
+use crate::culling::CameraView;
+use crate::factions::FactionColors;
+use crate::gpu_fields::{FieldReadbacks, GpuFields};
+use crate::net::PlayerId;
+use crate::{Ant, Faction, MapLayout, MapSize, Queen};
+
+// Territory. Ants leave pheromone on the hex they stand on (and a little on
+// the hexes around it; queens mark their whole nest), and every trace fades
+// over time. A hex belongs to the colony whose pheromone on it is strongest,
+// once that is enough to show more than passing through; a rival has to
+// outweigh the owner by a clear margin to take it over, so a contested border
+// doesn't flip back and forth every tick. Borders are drawn in the owner's
+// faction color. Held territory counts towards scoring, and a colony and its
+// allies see everything inside it (see `vision`). Where the GPU can take it,
+// the fading and depositing run there instead (see `gpu_fields`): each tick
+// only uploads where the ants are, and ownership is worked out from the
+// pheromone whenever it's read back.
+
+const TICK_SECS: f32 = 0.5;
+// Pheromone left per tick by an ant on its own hex, and on each neighbor
+pub const DEPOSIT: f32 = 1.0;
+pub const NEIGHBOR_DEPOSIT: f32 = 0.5;
+// Queens mark every hex this close to them
+pub const NEST_RADIUS: u32 = 3;
+// Share of the pheromone on a hex that is still there one tick later
+pub const DECAY: f32 = 0.99;
+// Traces weaker than this are forgotten
+pub const FORGET_BELOW: f32 = 0.05;
+// Pheromone needed to claim an unowned hex: about ten seconds of one ant
+pub const CLAIM_STRENGTH: f32 = 20.0;
+// An abandoned hex is lost once its owner's pheromone fades below this
+const HOLD_STRENGTH: f32 = 5.0;
+// How many times the owner's pheromone a rival needs to take a hex over
+const TAKEOVER_MARGIN: f32 = 1.25;
+// Borders are drawn this far in from the edge, towards the owner's side, so
+// both colors show where two territories meet
+const BORDER_INSET: f32 = 0.12;
+
+#[derive(Resource, Default)]
+pub struct Territory {
+    pheromone: HashMap<Hex, BTreeMap<PlayerId, f32>>,
+    owners: HashMap<Hex, PlayerId>,
+    held: BTreeMap<PlayerId, usize>,
+    borders: Vec<(Vec2, Vec2, PlayerId)>,
+}
+
+impl Territory {
+    pub fn owner(&self, hex: Hex) -> Option<PlayerId> {
+        self.owners.get(&hex).copied()
+    }
+
+    // Hexes held per colony, for every colony holding any
+    pub fn held(&self) -> impl Iterator<Item = (PlayerId, usize)> + '_ {
+        self.held.iter().map(|(&player, &count)| (player, count))
+    }
+
+    pub fn hexes(&self) -> impl Iterator<Item = (Hex, PlayerId)> + '_ {
+        self.owners.iter().map(|(&hex, &player)| (hex, player))
+    }
+
+    fn deposit(&mut self, hex: Hex, player: PlayerId, amount: f32) {
+        *self.pheromone.entry(hex).or_default().entry(player).or_default() += amount;
+    }
+
+    fn fade(&mut self) {
+        for traces in self.pheromone.values_mut() {
+            for strength in traces.values_mut() {
+                *strength *= DECAY;
+            }
+            traces.retain(|_, strength| *strength >= FORGET_BELOW);
+        }
+        self.pheromone.retain(|_, traces| !traces.is_empty());
+    }
+
+    // What an ant leaves on a tick: its own hex and a little around it, or
+    // the whole nest for a queen
+    fn mark(&mut self, hex: Hex, player: PlayerId, queen: bool) {
+        if queen {
+            for nest_hex in hex.range(NEST_RADIUS) {
+                self.deposit(nest_hex, player, DEPOSIT);
+            }
+            return;
+        }
+        self.deposit(hex, player, DEPOSIT);
+        for neighbor in hex.all_neighbors() {
+            self.deposit(neighbor, player, NEIGHBOR_DEPOSIT);
+        }
+    }
+
+    // The hex's owner after this tick's pheromone
+    fn claim(&self, hex: Hex, traces: &BTreeMap<PlayerId, f32>) -> Option<PlayerId> {
+        let (&strongest, &strength) = traces.iter().max_by(|a, b| a.1.total_cmp(b.1))?;
+        let Some(owner) = self.owner(hex) else {
+            return (strength >= CLAIM_STRENGTH).then_some(strongest);
+        };
+        let held = traces.get(&owner).copied().unwrap_or(0.0);
+        if strongest != owner && strength >= CLAIM_STRENGTH && strength >= held * TAKEOVER_MARGIN {
+            Some(strongest)
+        } else {
+            (held >= HOLD_STRENGTH).then_some(owner)
+        }
+    }
+}
+
+#[derive(Resource)]
+struct TerritoryTick(Timer);
+
+fn update_territory(
+    time: Res<Time>,
+    mut tick: ResMut<TerritoryTick>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    ant_q: Query<(&Transform, &Faction, Has<Queen>), With<Ant>>,
+    mut territory: ResMut<Territory>,
+    mut fields: ResMut<GpuFields>,
+    mut readbacks: ResMut<FieldReadbacks>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let mut updated = false;
+    if tick.0.tick(time.delta()).just_finished() {
+        let marks: Vec<(Hex, PlayerId, bool)> = ant_q
+            .iter()
+            .map(|(transform, faction, queen)| (layout.0.world_to_hex(transform.translation.truncate()), faction.0, queen))
+            .collect();
+        if !fields.deposit(&mut images, &marks) {
+            territory.fade();
+            for (hex, player, queen) in marks {
+                territory.mark(hex, player, queen);
+            }
+            updated = true;
+        }
+    }
+    if let Some(pheromone) = readbacks.take_pheromone() {
+        territory.pheromone = pheromone;
+        updated = true;
+    }
+    if !updated {
+        return;
+    }
+
+    let owners: HashMap<Hex, PlayerId> = territory
+        .pheromone
+        .iter()
+        .filter(|(hex, _)| map_size.contains(**hex))
+        .filter_map(|(&hex, traces)| territory.claim(hex, traces).map(|owner| (hex, owner)))
+        .collect();
+    if owners == territory.owners {
+        return;
+    }
+    territory.held.clear();
+    for &owner in owners.values() {
+        *territory.held.entry(owner).or_default() += 1;
+    }
+    territory.borders = border_edges(&owners, &layout);
+    territory.owners = owners;
+}
+
+// Edges of owned hexes whose neighbor across the edge isn't held by the same colony
+fn border_edges(owners: &HashMap<Hex, PlayerId>, layout: &MapLayout) -> Vec<(Vec2, Vec2, PlayerId)> {
+    let reach = layout.0.scale.max_element() * 1.01;
+    let mut edges = Vec::new();
+    for (&hex, &owner) in owners {
+        let center = layout.0.hex_to_world(hex);
+        for neighbor in hex.all_neighbors() {
+            if owners.get(&neighbor) == Some(&owner) {
+                continue;
+            }
+            let across = layout.0.hex_to_world(neighbor);
+            let shared: Vec<Vec2> =
+                layout.0.world_corners(hex).into_iter().filter(|corner| corner.distance(across) <= reach).collect();
+            if let [a, b] = shared[..] {
+                edges.push((a.lerp(center, BORDER_INSET), b.lerp(center, BORDER_INSET), owner));
+            }
+        }
+    }
+    edges
+}
+
+fn draw_territory_borders(
+    mut gizmos: Gizmos,
+    territory: Res<Territory>,
+    colors: Res<FactionColors>,
+    view: Res<CameraView>,
+) {
+    for &(a, b, owner) in territory.borders.iter().filter(|&&(a, b, _)| view.overlaps_segment(a, b)) {
+        gizmos.line_2d(a, b, colors.get(owner).with_alpha(0.7));
+    }
+}
+
+pub struct TerritoryPlugin;
+
+impl Plugin for TerritoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Territory>()
+            .insert_resource(TerritoryTick(Timer::from_seconds(TICK_SECS, TimerMode::Repeating)))
+            .add_systems(Update, (update_territory, draw_territory_borders).chain());
+    }
+}
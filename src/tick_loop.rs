@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+use bevy::utils::{Duration, Instant};
+use serde::Deserialize;
+
+use crate::determinism::Determinism;
+use crate::pathfinding::PathBudget;
+use crate::storage::storage;
+
+// The tick loop's pace and budget, from `server.ron`. `tick_rate` caps how
+// many ticks run per second (0 leaves it to the display, as a client wants);
+// a dedicated server sets it so the simulation doesn't spin a core. Each tick
+// is timed from First to Last. One that takes longer than `tick_budget_ms` is
+// an overrun: the pathfinding budget is halved, so searches wait in the queue
+// instead of the next tick running late too, and grows back while ticks fit.
+// Deterministic runs keep theirs, since they serve a fixed number of searches.
+// Virtual time never advances by more than a few ticks at once, so after a
+// stall the world slows down rather than trying to catch up in one step and
+// stalling again. Overruns and the rate actually reached end up in `Metrics`.
+
+const SERVER_CONFIG_PATH: &str = "server.ron";
+// Game time one tick may cover, in ticks of the configured rate
+const MAX_CATCH_UP_TICKS: f32 = 4.0;
+// The pathfinding budget never drops below this share of the configured one,
+// so queued paths still trickle through under sustained load
+const MIN_PATH_BUDGET_SHARE: f32 = 0.1;
+// Share of the configured pathfinding budget won back per tick within budget
+const PATH_BUDGET_RECOVERY: f32 = 0.05;
+// Weight of the newest tick in the effective rate
+const SMOOTHING: f32 = 0.05;
+
+#[derive(Resource, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct TickConfig {
+    pub tick_rate: f32,
+    pub tick_budget_ms: f32,
+    pub pathfinding_budget_ms: f32,
+}
+
+impl Default for TickConfig {
+    fn default() -> Self {
+        Self { tick_rate: 0.0, tick_budget_ms: 1000.0 / 60.0, pathfinding_budget_ms: 2.0 }
+    }
+}
+
+impl TickConfig {
+    fn load() -> Self {
+        let Some(contents) = storage().read(SERVER_CONFIG_PATH) else {
+            return Self::default();
+        };
+        ron::from_str(&contents).unwrap_or_else(|err| {
+            warn!("Ignoring {SERVER_CONFIG_PATH}: {err}");
+            Self::default()
+        })
+    }
+
+    fn tick_length(&self) -> Option<Duration> {
+        (self.tick_rate > 0.0).then(|| Duration::from_secs_f32(1.0 / self.tick_rate))
+    }
+
+    fn path_budget_micros(&self) -> f32 {
+        self.pathfinding_budget_ms * 1000.0
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TickStats {
+    // Ticks per second actually reached, smoothed
+    pub effective_rate: f32,
+    pub last_tick_secs: f32,
+    pub overruns: u64,
+    started: Option<Instant>,
+    previous_start: Option<Instant>,
+}
+
+fn apply_tick_config(config: Res<TickConfig>, mut time: ResMut<Time<Virtual>>) {
+    let tick = config.tick_length().map_or(1.0 / 60.0, |tick| tick.as_secs_f32());
+    time.set_max_delta(Duration::from_secs_f32(tick * MAX_CATCH_UP_TICKS));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn wait_until(deadline: Instant) {
+    if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+        std::thread::sleep(wait);
+    }
+}
+
+// There's no blocking on the web; the browser paces frames itself
+#[cfg(target_arch = "wasm32")]
+fn wait_until(_deadline: Instant) {}
+
+// Before anything else this tick, waits out the rest of the previous tick's
+// time slot, then starts the clock
+fn start_tick(config: Res<TickConfig>, mut stats: ResMut<TickStats>) {
+    if let (Some(tick), Some(previous)) = (config.tick_length(), stats.previous_start) {
+        wait_until(previous + tick);
+    }
+    let now = Instant::now();
+    if let Some(previous) = stats.previous_start {
+        let rate = 1.0 / (now - previous).as_secs_f32().max(f32::EPSILON);
+        stats.effective_rate = if stats.effective_rate == 0.0 {
+            rate
+        } else {
+            stats.effective_rate + (rate - stats.effective_rate) * SMOOTHING
+        };
+    }
+    stats.previous_start = Some(now);
+    stats.started = Some(now);
+}
+
+fn finish_tick(
+    config: Res<TickConfig>,
+    determinism: Res<Determinism>,
+    mut stats: ResMut<TickStats>,
+    mut budget: ResMut<PathBudget>,
+) {
+    let Some(started) = stats.started.take() else {
+        return;
+    };
+    let work = started.elapsed().as_secs_f32();
+    stats.last_tick_secs = work;
+
+    let overrun = work * 1000.0 > config.tick_budget_ms;
+    if overrun {
+        stats.overruns += 1;
+    }
+    if determinism.enabled {
+        return;
+    }
+    let full = config.path_budget_micros();
+    let current = budget.micros_per_tick as f32;
+    let next = if overrun {
+        (current / 2.0).max(full * MIN_PATH_BUDGET_SHARE)
+    } else {
+        (current + full * PATH_BUDGET_RECOVERY).min(full)
+    };
+    budget.micros_per_tick = next as u64;
+}
+
+pub struct TickLoopPlugin;
+
+impl Plugin for TickLoopPlugin {
+    fn build(&self, app: &mut App) {
+        let config = TickConfig::load();
+        app.insert_resource(config)
+            .insert_resource(PathBudget { micros_per_tick: config.path_budget_micros() as u64 })
+            .init_resource::<TickStats>()
+            .add_systems(Startup, apply_tick_config)
+            .add_systems(First, start_tick)
+            .add_systems(Last, finish_tick);
+    }
+}
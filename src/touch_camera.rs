@@ -0,0 +1,70 @@
+use bevy::input::touch::Touch;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::camera_controls::{CameraInput, ManualCameraMove, ZoomLimits};
+use crate::{MainCamera, SelectionState, TouchDrag};
+
+// Camera control for touch devices: dragging with one or two fingers pans,
+// pinching zooms around the point between the fingers. `ant_input` ignores
+// multi-touch so these gestures never turn into a selection box, and decides
+// whether a one-finger drag pans or, after a long press, selects.
+
+fn touch_camera(
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    limits: Res<ZoomLimits>,
+    selection_state: Res<SelectionState>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    mut moved: EventWriter<ManualCameraMove>,
+) {
+    let pressed: Vec<&Touch> = touches.iter().collect();
+    if let [touch] = pressed.as_slice() {
+        if selection_state.touch_drag == TouchDrag::Pan {
+            let (mut transform, projection) = camera_q.single_mut();
+            let delta = touch.delta();
+            transform.translation.x -= delta.x * projection.scale;
+            transform.translation.y += delta.y * projection.scale;
+            moved.send(ManualCameraMove);
+        }
+        return;
+    }
+    let [a, b] = pressed.as_slice() else {
+        return;
+    };
+    moved.send(ManualCameraMove);
+
+    let window = windows.single();
+    let (mut transform, mut projection) = camera_q.single_mut();
+
+    let mid = (a.position() + b.position()) / 2.0;
+    let prev_mid = (a.previous_position() + b.previous_position()) / 2.0;
+
+    // Pan: the world should follow the fingers (viewport y grows downward)
+    let delta = mid - prev_mid;
+    transform.translation.x -= delta.x * projection.scale;
+    transform.translation.y += delta.y * projection.scale;
+
+    // Pinch: scale by the change in finger spread
+    let dist = a.position().distance(b.position());
+    let prev_dist = a.previous_position().distance(b.previous_position());
+    if dist < 1.0 || prev_dist < 1.0 {
+        return;
+    }
+    let new_scale = limits.clamp(projection.scale * prev_dist / dist);
+
+    // Keep the world point under the gesture midpoint fixed while zooming
+    let offset = Vec2::new(mid.x - window.width() / 2.0, window.height() / 2.0 - mid.y);
+    let shift = offset * (projection.scale - new_scale);
+    transform.translation.x += shift.x;
+    transform.translation.y += shift.y;
+    projection.scale = new_scale;
+}
+
+pub struct TouchCameraPlugin;
+
+impl Plugin for TouchCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, touch_camera.after(crate::ant_input).in_set(CameraInput));
+    }
+}
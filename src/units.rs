@@ -0,0 +1,410 @@
+use bevy::asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use hexx::Hex;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::ant_sprites::{AntAnimation, AntSprites};
+use crate::collision;
+use crate::console::{CommandResult, ConsoleAppExt};
+use crate::coords::HexLayoutExt;
+use crate::health::Health;
+use crate::loading::{AppState, LoadingAssets};
+use crate::lobby::MatchSettings;
+use crate::movement::{Movement, MovementMode};
+use crate::mutators;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::placement::SpawnPlacer;
+use crate::upgrades::ColonyUpgrades;
+use crate::veterancy::Veterancy;
+use crate::{Ant, Caste, Faction, MapLayout, Path, Queen, TargetPosition};
+
+// Unit stats live in `assets/units/ants.units.ron`, loaded through the asset
+// server like any other asset, so balancing is an edit to that file. Its
+// contents are copied into `UnitDefinitions` when the game starts and again
+// whenever the file is reloaded, with the match's mutators laid on top (see
+// `mutators`). `UnitSpawner` builds any unit from its definition. The file as
+// shipped is also built in: until it loads, and for any caste it leaves out,
+// units use the built-in definitions. A reload that leaves a caste out or has
+// impossible stats is refused and the current definitions stay.
+
+const UNITS_FILE: &str = "units/ants.units.ron";
+const BUILT_IN_UNITS: &str = include_str!("../assets/units/ants.units.ron");
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UnitDefinition {
+    pub caste: Caste,
+    pub size: f32,
+    // Sprite tint, as an sRGB hex string
+    pub color: String,
+    // Movement speed in px/s
+    pub speed: f32,
+    pub collider_radius: f32,
+    pub damping: f32,
+    pub health: f32,
+    // Damage per second dealt to the enemy it's fighting
+    pub bite: f32,
+    // Immobile units get a fixed body and never move
+    pub immobile: bool,
+    // Physics body of mobile units (see `movement`)
+    #[serde(default)]
+    pub movement: MovementMode,
+    // Seconds a unit lives before dying of old age; castes without one live
+    // until killed (see `lifespan`)
+    #[serde(default)]
+    pub lifespan: Option<f32>,
+    // Whether the caste can dig in and lie in ambush (see `dig_in`)
+    #[serde(default)]
+    pub can_dig_in: bool,
+}
+
+#[derive(Asset, TypePath, Deserialize)]
+pub struct UnitsFile {
+    units: Vec<UnitDefinition>,
+}
+
+#[derive(Debug)]
+enum UnitsFileError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl std::fmt::Display for UnitsFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnitsFileError::Io(err) => write!(f, "could not read unit definitions: {err}"),
+            UnitsFileError::Ron(err) => write!(f, "could not parse unit definitions: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for UnitsFileError {}
+
+impl From<std::io::Error> for UnitsFileError {
+    fn from(err: std::io::Error) -> Self {
+        UnitsFileError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for UnitsFileError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        UnitsFileError::Ron(err)
+    }
+}
+
+#[derive(Default)]
+struct UnitsFileLoader;
+
+impl AssetLoader for UnitsFileLoader {
+    type Asset = UnitsFile;
+    type Settings = ();
+    type Error = UnitsFileError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<UnitsFile, UnitsFileError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["units.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct UnitsFileHandle(Handle<UnitsFile>);
+
+#[derive(Resource)]
+pub struct UnitDefinitions {
+    by_caste: HashMap<Caste, UnitDefinition>,
+    // The definitions taken from the file, before mutators
+    accepted: Vec<UnitDefinition>,
+    // Whether `accepted` is a complete file rather than (partly) the built-in
+    // copy
+    loaded: bool,
+    // Fingerprint of the file and the mutators, checked when joining a lobby
+    rules_checksum: u64,
+}
+
+impl Default for UnitDefinitions {
+    fn default() -> Self {
+        let accepted = built_in_units();
+        let by_caste = accepted.iter().map(|unit| (unit.caste, unit.clone())).collect();
+        Self { by_caste, rules_checksum: mutators::rules_checksum(&accepted), accepted, loaded: false }
+    }
+}
+
+impl UnitDefinitions {
+    pub fn get(&self, caste: Caste) -> &UnitDefinition {
+        &self.by_caste[&caste]
+    }
+
+    pub fn rules_checksum(&self) -> u64 {
+        self.rules_checksum
+    }
+
+    // Takes the file's definitions, falling back to the built-in ones for the
+    // castes it leaves out. Once a complete file has been taken, a later one
+    // has to be complete and sane too or it is refused.
+    fn accept(&mut self, units: &[UnitDefinition]) -> Result<(), String> {
+        let problem = Caste::ALL
+            .into_iter()
+            .find(|&caste| !units.iter().any(|unit| unit.caste == caste))
+            .map(|caste| format!("has no definition for {caste:?}"))
+            .or_else(|| units.iter().find_map(unit_problem));
+        match &problem {
+            Some(problem) if self.loaded => return Err(problem.clone()),
+            Some(problem) => warn!("{UNITS_FILE} {problem}; using the built-in definitions there"),
+            None => {}
+        }
+        let built_in = built_in_units();
+        self.accepted = Caste::ALL
+            .into_iter()
+            .filter_map(|caste| {
+                units
+                    .iter()
+                    .find(|unit| unit.caste == caste && unit_problem(unit).is_none())
+                    .or_else(|| built_in.iter().find(|unit| unit.caste == caste))
+                    .cloned()
+            })
+            .collect();
+        self.loaded = problem.is_none();
+        Ok(())
+    }
+
+    // The accepted definitions with the match's mutators laid on top
+    fn apply_mutators(&mut self, settings: &MatchSettings) {
+        self.rules_checksum = mutators::rules_checksum(&self.accepted);
+        self.by_caste = self
+            .accepted
+            .iter()
+            .map(|unit| {
+                let mut unit = unit.clone();
+                settings.mutators.apply(&mut unit);
+                (unit.caste, unit)
+            })
+            .collect();
+    }
+}
+
+fn built_in_units() -> Vec<UnitDefinition> {
+    ron::from_str::<UnitsFile>(BUILT_IN_UNITS).expect("the built-in unit definitions parse").units
+}
+
+// Stats no unit can play with
+fn unit_problem(unit: &UnitDefinition) -> Option<String> {
+    let sizes = [unit.size, unit.collider_radius, unit.health];
+    let rates = [unit.speed, unit.damping, unit.bite];
+    let sane = sizes.iter().all(|&value| value > 0.0) && rates.iter().all(|&value| value >= 0.0);
+    (!sane).then(|| format!("has impossible stats for {:?}", unit.caste))
+}
+
+fn load_units_file(mut commands: Commands, asset_server: Res<AssetServer>, mut loading: ResMut<LoadingAssets>) {
+    let handle = asset_server.load::<UnitsFile>(UNITS_FILE);
+    loading.queue(handle.clone());
+    commands.insert_resource(UnitsFileHandle(handle));
+}
+
+pub fn apply_unit_definitions(
+    handle: Res<UnitsFileHandle>,
+    files: Res<Assets<UnitsFile>>,
+    settings: Res<MatchSettings>,
+    mut definitions: ResMut<UnitDefinitions>,
+) {
+    match files.get(&handle.0) {
+        Some(file) => {
+            if let Err(problem) = definitions.accept(&file.units) {
+                error!("Keeping the current unit definitions: {UNITS_FILE} {problem}");
+            }
+        }
+        None => error!("{UNITS_FILE} failed to load; using the built-in definitions"),
+    }
+    definitions.apply_mutators(&settings);
+}
+
+fn units_file_changed(handle: Res<UnitsFileHandle>, mut events: EventReader<AssetEvent<UnitsFile>>) -> bool {
+    events
+        .read()
+        .any(|event| matches!(event, AssetEvent::Modified { id } if *id == handle.0.id()))
+}
+
+#[derive(SystemParam)]
+pub struct UnitSpawner<'w> {
+    sprites: Res<'w, AntSprites>,
+    definitions: Res<'w, UnitDefinitions>,
+}
+
+impl UnitSpawner<'_> {
+    // One unit of the given caste, standing at `pos`
+    pub fn spawn(&self, commands: &mut Commands, caste: Caste, faction: Faction, pos: Vec2) -> Entity {
+        let definition = self.definitions.get(caste);
+        let color = Srgba::hex(&definition.color).map_or(Color::WHITE, Color::from);
+        let (texture, atlas) = self.sprites.for_caste(caste);
+
+        let mut unit = commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(definition.size)),
+                    ..default()
+                },
+                texture,
+                transform: Transform::from_translation(pos.extend(1.0)),
+                ..default()
+            },
+            atlas,
+            AntAnimation::default(),
+            Collider::ball(definition.collider_radius),
+            // Units pass through each other; see `collision`
+            collision::unit_collision_groups(),
+            Ant,
+            caste,
+            faction,
+            Health::new(definition.health),
+            TargetPosition(pos),
+            Path::default(),
+        ));
+        if definition.immobile {
+            unit.insert(RigidBody::Fixed);
+        } else {
+            unit.insert((
+                RigidBody::Dynamic,
+                Velocity::zero(),
+                Damping { linear_damping: definition.damping, angular_damping: 1.0 },
+                Movement(definition.movement),
+            ));
+        }
+        if caste == Caste::Queen {
+            unit.insert(Queen);
+        }
+        unit.id()
+    }
+}
+
+// A unit's working stats: its caste's base stats, scaled by its colony's
+// upgrades and its own rank (see `veterancy`)
+#[derive(SystemParam)]
+pub struct UnitStats<'w> {
+    definitions: Res<'w, UnitDefinitions>,
+    upgrades: Res<'w, ColonyUpgrades>,
+}
+
+impl UnitStats<'_> {
+    // Movement speed in px/s, before terrain, hunger and genes
+    pub fn speed(&self, caste: Caste, player: PlayerId, veterancy: Option<&Veterancy>) -> f32 {
+        let mut speed = self.definitions.get(caste).speed * veterancy.map_or(1.0, Veterancy::speed_multiplier);
+        if caste == Caste::Worker {
+            speed *= self.upgrades.worker_speed_multiplier(player);
+        }
+        speed
+    }
+
+    // Damage per second in a fight
+    pub fn bite(&self, caste: Caste, veterancy: Option<&Veterancy>) -> f32 {
+        self.definitions.get(caste).bite * veterancy.map_or(1.0, Veterancy::damage_multiplier)
+    }
+}
+
+// `spawn worker 5`: new units on the free hexes closest to our queen
+fn spawn_command(
+    In(args): In<Vec<String>>,
+    mut commands: Commands,
+    spawner: UnitSpawner,
+    placer: SpawnPlacer,
+    layout: Res<MapLayout>,
+    local_player: Res<LocalPlayer>,
+    queen_q: Query<(&Transform, &Faction), With<Queen>>,
+) -> CommandResult {
+    let usage = || "usage: spawn <queen|worker|soldier> [count]".to_string();
+    let caste = args.first().and_then(|name| Caste::from_name(name)).ok_or_else(usage)?;
+    let count: usize = match args.get(1) {
+        Some(count) => count.parse().map_err(|_| usage())?,
+        None => 1,
+    };
+
+    let center = queen_q
+        .iter()
+        .find(|(_, faction)| faction.0 == local_player.0)
+        .map_or(Hex::ZERO, |(transform, _)| layout.0.world_to_hex(transform.translation.truncate()));
+    let mut spots = placer.around(center);
+    spots.reserve(center);
+    let mut spawned = 0;
+    for (_, pos) in spots.take(count) {
+        spawner.spawn(&mut commands, caste, Faction(local_player.0), pos);
+        spawned += 1;
+    }
+    Ok(format!("spawned {spawned} {caste:?}"))
+}
+
+pub struct UnitsPlugin;
+
+impl Plugin for UnitsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<UnitsFile>()
+            .init_asset_loader::<UnitsFileLoader>()
+            .init_resource::<UnitDefinitions>()
+            .register_console_command(
+                "spawn",
+                "spawn <queen|worker|soldier> [count]: units next to your queen",
+                spawn_command,
+            )
+            .add_systems(Startup, load_units_file)
+            // The lobby checks the rules; the match plays them with its mutators
+            .add_systems(OnEnter(AppState::Lobby), apply_unit_definitions)
+            .add_systems(OnEnter(AppState::InGame), apply_unit_definitions)
+            .add_systems(Update, apply_unit_definitions.run_if(units_file_changed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units_without(caste: Caste) -> Vec<UnitDefinition> {
+        built_in_units().into_iter().filter(|unit| unit.caste != caste).collect()
+    }
+
+    #[test]
+    fn built_in_definitions_cover_every_caste() {
+        let definitions = UnitDefinitions::default();
+        for caste in Caste::ALL {
+            assert_eq!(definitions.get(caste).caste, caste);
+        }
+    }
+
+    #[test]
+    fn a_first_file_missing_a_caste_falls_back_to_the_built_in_one() {
+        let mut definitions = UnitDefinitions::default();
+        let mut units = units_without(Caste::Soldier);
+        units[0].speed = 99.0;
+        definitions.accept(&units).unwrap();
+        definitions.apply_mutators(&MatchSettings::default());
+        assert_eq!(definitions.get(units[0].caste).speed, 99.0);
+        assert_eq!(definitions.get(Caste::Soldier).caste, Caste::Soldier);
+    }
+
+    #[test]
+    fn a_bad_reload_keeps_the_current_definitions() {
+        let mut definitions = UnitDefinitions::default();
+        let mut units = built_in_units();
+        units[0].speed = 99.0;
+        definitions.accept(&units).unwrap();
+
+        let mut missing = units_without(Caste::Worker);
+        missing[0].speed = 1.0;
+        assert!(definitions.accept(&missing).is_err());
+        let mut broken = built_in_units();
+        broken[0].health = 0.0;
+        assert!(definitions.accept(&broken).is_err());
+
+        definitions.apply_mutators(&MatchSettings::default());
+        assert_eq!(definitions.get(units[0].caste).speed, 99.0);
+    }
+}
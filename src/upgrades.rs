@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::health::Health;
+use crate::interactions::FoodStores;
+use crate::keybindings::{Action, KeyBindings, KeyBindingsAppExt};
+use crate::locale::{Locale, Localized};
+use crate::net::{LocalPlayer, PlayerId};
+use crate::{Ant, Faction};
+
+// Colony upgrades bought with stored food. Levels live in `ColonyUpgrades`,
+// per player, and gameplay asks it for stats (worker speed, carry capacity,
+// toughness, brood slots) instead of using fixed numbers. The princess is
+// research rather than a stat: a single level that lets the colony raise a
+// princess who can succeed a fallen queen (see `succession`).
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Upgrade {
+    WorkerSpeed,
+    Toughness,
+    CarryCapacity,
+    BroodSlots,
+    Princess,
+}
+
+impl Upgrade {
+    const ALL: [Upgrade; 5] =
+        [Upgrade::WorkerSpeed, Upgrade::Toughness, Upgrade::CarryCapacity, Upgrade::BroodSlots, Upgrade::Princess];
+
+    fn name_key(self) -> &'static str {
+        match self {
+            Upgrade::WorkerSpeed => "upgrades.worker-speed",
+            Upgrade::Toughness => "upgrades.toughness",
+            Upgrade::CarryCapacity => "upgrades.carry-capacity",
+            Upgrade::BroodSlots => "upgrades.brood-slots",
+            Upgrade::Princess => "upgrades.princess",
+        }
+    }
+
+    fn base_cost(self) -> u32 {
+        match self {
+            Upgrade::WorkerSpeed => 10,
+            Upgrade::Toughness => 15,
+            Upgrade::CarryCapacity => 10,
+            Upgrade::BroodSlots => 25,
+            Upgrade::Princess => 40,
+        }
+    }
+
+    fn max_level(self) -> u32 {
+        match self {
+            Upgrade::Princess => 1,
+            _ => MAX_LEVEL,
+        }
+    }
+
+    // Each level costs one more base cost than the last
+    fn cost(self, current_level: u32) -> u32 {
+        self.base_cost() * (current_level + 1)
+    }
+}
+
+const MAX_LEVEL: u32 = 5;
+
+#[derive(Resource, Default)]
+pub struct ColonyUpgrades(HashMap<(PlayerId, Upgrade), u32>);
+
+impl ColonyUpgrades {
+    pub fn level(&self, player: PlayerId, upgrade: Upgrade) -> u32 {
+        self.0.get(&(player, upgrade)).copied().unwrap_or(0)
+    }
+
+    pub fn worker_speed_multiplier(&self, player: PlayerId) -> f32 {
+        1.0 + 0.1 * self.level(player, Upgrade::WorkerSpeed) as f32
+    }
+
+    pub fn health_multiplier(&self, player: PlayerId) -> f32 {
+        1.0 + 0.2 * self.level(player, Upgrade::Toughness) as f32
+    }
+
+    pub fn carry_capacity(&self, player: PlayerId) -> u32 {
+        1 + self.level(player, Upgrade::CarryCapacity)
+    }
+
+    // Eggs and larvae a colony can raise at once
+    pub fn brood_slots(&self, player: PlayerId) -> usize {
+        2 + self.level(player, Upgrade::BroodSlots) as usize
+    }
+
+    // Whether the colony may raise a princess
+    pub fn has_princess_research(&self, player: PlayerId) -> bool {
+        self.level(player, Upgrade::Princess) > 0
+    }
+
+    // Crowning a princess uses the research up; another needs it bought again
+    pub fn use_princess_research(&mut self, player: PlayerId) {
+        self.0.remove(&(player, Upgrade::Princess));
+    }
+
+    // Spend food from the player's store on the next level; false if unaffordable or maxed
+    fn buy(&mut self, player: PlayerId, upgrade: Upgrade, stores: &mut FoodStores) -> bool {
+        let level = self.level(player, upgrade);
+        let food = stores.0.entry(player).or_default();
+        if level >= upgrade.max_level() || *food < upgrade.cost(level) {
+            return false;
+        }
+        *food -= upgrade.cost(level);
+        self.0.insert((player, upgrade), level + 1);
+        true
+    }
+}
+
+#[derive(Component)]
+struct UpgradePanel;
+
+#[derive(Component)]
+struct UpgradeButton(Upgrade);
+
+fn setup_upgrade_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(8.0),
+                    right: Val::Px(8.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.85).into(),
+                ..default()
+            },
+            Interaction::default(),
+            UpgradePanel,
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 18.0, color: Color::WHITE, ..default() }),
+                Localized("upgrades.title"),
+            ));
+            for upgrade in Upgrade::ALL {
+                panel
+                    .spawn((
+                        ButtonBundle {
+                            style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+                            background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                            ..default()
+                        },
+                        UpgradeButton(upgrade),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(TextBundle::from_section(
+                            "",
+                            TextStyle { font_size: 14.0, color: Color::WHITE, ..default() },
+                        ));
+                    });
+            }
+        });
+}
+
+fn toggle_upgrade_panel(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut style_q: Query<&mut Style, With<UpgradePanel>>,
+) {
+    if !bindings.just_pressed(&keyboard_input, Action::ToggleUpgrades) {
+        return;
+    }
+    for mut style in style_q.iter_mut() {
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn buy_upgrades(
+    button_q: Query<(&Interaction, &UpgradeButton), Changed<Interaction>>,
+    local_player: Res<LocalPlayer>,
+    mut upgrades: ResMut<ColonyUpgrades>,
+    mut stores: ResMut<FoodStores>,
+    mut ant_q: Query<(&Faction, &mut Health), With<Ant>>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let player = local_player.0;
+        let before = upgrades.health_multiplier(player);
+        if !upgrades.buy(player, button.0, &mut stores) {
+            continue;
+        }
+
+        // Toughness applies to the ants we already have, keeping their health fraction
+        let ratio = upgrades.health_multiplier(player) / before;
+        if ratio != 1.0 {
+            for (faction, mut health) in ant_q.iter_mut() {
+                if faction.0 == player {
+                    health.max *= ratio;
+                    health.current *= ratio;
+                }
+            }
+        }
+    }
+}
+
+// New ants start with the colony's current toughness
+fn apply_toughness_to_new_ants(
+    upgrades: Res<ColonyUpgrades>,
+    mut new_ants: Query<(&Faction, &mut Health), Added<Ant>>,
+) {
+    for (faction, mut health) in new_ants.iter_mut() {
+        let multiplier = upgrades.health_multiplier(faction.0);
+        if multiplier == 1.0 {
+            continue;
+        }
+        health.max *= multiplier;
+        health.current *= multiplier;
+    }
+}
+
+fn update_upgrade_labels(
+    upgrades: Res<ColonyUpgrades>,
+    stores: Res<FoodStores>,
+    local_player: Res<LocalPlayer>,
+    locale: Res<Locale>,
+    button_q: Query<(&UpgradeButton, &Children)>,
+    mut text_q: Query<&mut Text>,
+) {
+    if !upgrades.is_changed() && !stores.is_changed() && !locale.is_changed() {
+        return;
+    }
+    for (button, children) in button_q.iter() {
+        let level = upgrades.level(local_player.0, button.0);
+        let name = locale.get(button.0.name_key());
+        let label = if level >= button.0.max_level() {
+            locale.format("upgrades.button-max", &[("name", &name), ("level", &level)])
+        } else {
+            locale.format("upgrades.button", &[("name", &name), ("level", &level), ("cost", &button.0.cost(level))])
+        };
+        for &child in children.iter() {
+            if let Ok(mut text) = text_q.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+pub struct UpgradesPlugin;
+
+impl Plugin for UpgradesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ColonyUpgrades>()
+            .register_action(Action::ToggleUpgrades, &[KeyCode::KeyU])
+            .add_systems(Startup, setup_upgrade_panel)
+            .add_systems(
+                Update,
+                (toggle_upgrade_panel, buy_upgrades, apply_toughness_to_new_ants, update_upgrade_labels).chain(),
+            );
+    }
+}
@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::health::{Health, BAR_GAP};
+use crate::net::PlayerId;
+use crate::{Ant, Caste, Faction, MainCamera};
+
+// Veterancy. Ants earn experience by bringing food home and by landing the
+// killing bite, and rank up at the thresholds in `RANK_XP`. Each rank adds a
+// little speed, health and bite on top of the caste's base stats and the
+// colony's upgrades; `units::UnitStats` combines the three. Ranked ants wear
+// one gold chevron per rank under their sprite.
+//
+// A colony's veterans are kept in the world save with the rest of its data
+// (see `save`). Units themselves aren't saved, so on load each veteran's
+// experience goes to the first ant of its caste the colony fields.
+
+const XP_PER_DELIVERY: u32 = 1;
+const XP_PER_KILL: u32 = 5;
+// Experience needed for each rank after the first
+const RANK_XP: [u32; 3] = [5, 15, 30];
+const SPEED_PER_RANK: f32 = 0.05;
+const HEALTH_PER_RANK: f32 = 0.1;
+const DAMAGE_PER_RANK: f32 = 0.1;
+// Chevron size and spacing, in screen pixels
+const CHEVRON_WIDTH: f32 = 4.0;
+const CHEVRON_HEIGHT: f32 = 2.0;
+const CHEVRON_SPACING: f32 = 6.0;
+
+// Something an ant did that counts towards its rank
+#[derive(Event, Clone, Copy, Debug)]
+pub enum Experience {
+    // Carried food home
+    Delivery(Entity),
+    // Finished off an enemy
+    Kill(Entity),
+}
+
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq)]
+pub struct Veterancy {
+    pub xp: u32,
+    pub rank: u32,
+}
+
+impl Veterancy {
+    fn from_xp(xp: u32) -> Self {
+        Self { xp, rank: Self::rank_for(xp) }
+    }
+
+    fn rank_for(xp: u32) -> u32 {
+        RANK_XP.iter().filter(|&&needed| xp >= needed).count() as u32
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        1.0 + SPEED_PER_RANK * self.rank as f32
+    }
+
+    pub fn health_multiplier(&self) -> f32 {
+        1.0 + HEALTH_PER_RANK * self.rank as f32
+    }
+
+    pub fn damage_multiplier(&self) -> f32 {
+        1.0 + DAMAGE_PER_RANK * self.rank as f32
+    }
+}
+
+// An ant with experience as the world save keeps it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SavedVeteran {
+    caste: Caste,
+    xp: u32,
+}
+
+// Saved veterans of each colony still waiting for an ant to come back on
+#[derive(Resource, Default)]
+pub struct ReturningVeterans(BTreeMap<PlayerId, Vec<SavedVeteran>>);
+
+impl ReturningVeterans {
+    pub fn from_saved(saved: BTreeMap<u32, Vec<SavedVeteran>>) -> Self {
+        Self(saved.into_iter().map(|(player, veterans)| (PlayerId(player), veterans)).collect())
+    }
+
+    // Every colony's veterans for the save: the living ones and those still
+    // waiting to return
+    pub fn to_saved<'a>(
+        &self,
+        living: impl Iterator<Item = (&'a Faction, &'a Caste, &'a Veterancy)>,
+    ) -> BTreeMap<u32, Vec<SavedVeteran>> {
+        let mut saved: BTreeMap<u32, Vec<SavedVeteran>> =
+            self.0.iter().map(|(player, veterans)| (player.0, veterans.clone())).collect();
+        for (faction, &caste, veterancy) in living.filter(|(_, _, veterancy)| veterancy.xp > 0) {
+            saved.entry(faction.0 .0).or_default().push(SavedVeteran { caste, xp: veterancy.xp });
+        }
+        // Most experienced first, so they return first
+        for veterans in saved.values_mut() {
+            veterans.sort_by_key(|veteran| std::cmp::Reverse(veteran.xp));
+        }
+        saved
+    }
+
+    fn take(&mut self, player: PlayerId, caste: Caste) -> Option<Veterancy> {
+        let veterans = self.0.get_mut(&player)?;
+        let index = veterans.iter().position(|veteran| veteran.caste == caste)?;
+        Some(Veterancy::from_xp(veterans.remove(index).xp))
+    }
+}
+
+fn restore_veterans(
+    mut commands: Commands,
+    mut returning: ResMut<ReturningVeterans>,
+    mut new_ants: Query<(Entity, &Faction, &Caste, &mut Health), Added<Ant>>,
+) {
+    if returning.0.is_empty() {
+        return;
+    }
+    for (entity, faction, &caste, mut health) in new_ants.iter_mut() {
+        let Some(veterancy) = returning.take(faction.0, caste) else {
+            continue;
+        };
+        health.max *= veterancy.health_multiplier();
+        health.current *= veterancy.health_multiplier();
+        commands.entity(entity).insert(veterancy);
+    }
+    returning.0.retain(|_, veterans| !veterans.is_empty());
+}
+
+fn gain_experience(
+    mut commands: Commands,
+    mut experience: EventReader<Experience>,
+    mut unit_q: Query<(Option<&mut Veterancy>, &mut Health)>,
+) {
+    // Summed per ant first, so a new veteran with several events this frame
+    // gets a single insert
+    let mut gained: HashMap<Entity, u32> = HashMap::new();
+    for event in experience.read() {
+        let (unit, xp) = match *event {
+            Experience::Delivery(unit) => (unit, XP_PER_DELIVERY),
+            Experience::Kill(unit) => (unit, XP_PER_KILL),
+        };
+        *gained.entry(unit).or_default() += xp;
+    }
+
+    for (unit, xp) in gained {
+        let Ok((veterancy, mut health)) = unit_q.get_mut(unit) else {
+            continue;
+        };
+        let before = veterancy.as_deref().copied().unwrap_or_default();
+        let after = Veterancy::from_xp(before.xp + xp);
+        match veterancy {
+            Some(mut veterancy) => *veterancy = after,
+            None => {
+                commands.entity(unit).insert(after);
+            }
+        }
+        if after.rank != before.rank {
+            // Same as the toughness upgrade: the ant keeps its share of health
+            let ratio = after.health_multiplier() / before.health_multiplier();
+            health.max *= ratio;
+            health.current *= ratio;
+        }
+    }
+}
+
+fn draw_rank_markers(
+    mut gizmos: Gizmos,
+    camera_q: Query<&OrthographicProjection, With<MainCamera>>,
+    unit_q: Query<(&Veterancy, &GlobalTransform, Option<&Sprite>, &ViewVisibility)>,
+) {
+    let Ok(projection) = camera_q.get_single() else {
+        return;
+    };
+    let scale = projection.scale;
+    let gold = Color::from(Srgba::hex("FFD54F").unwrap());
+
+    for (veterancy, transform, sprite, visibility) in unit_q.iter() {
+        if veterancy.rank == 0 || !visibility.get() {
+            continue;
+        }
+        let pos = transform.translation().truncate();
+        let half_height = sprite.and_then(|sprite| sprite.custom_size).map_or(5.0, |size| size.y / 2.0);
+        let top = pos.y - half_height - BAR_GAP * scale;
+        // Stacked downwards, one per rank
+        for i in 0..veterancy.rank {
+            let y = top - i as f32 * CHEVRON_SPACING * scale;
+            let tip = Vec2::new(pos.x, y - CHEVRON_HEIGHT * scale);
+            let half = CHEVRON_WIDTH / 2.0 * scale;
+            gizmos.line_2d(Vec2::new(pos.x - half, y), tip, gold);
+            gizmos.line_2d(tip, Vec2::new(pos.x + half, y), gold);
+        }
+    }
+}
+
+pub struct VeterancyPlugin;
+
+impl Plugin for VeterancyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Experience>()
+            .init_resource::<ReturningVeterans>()
+            .add_systems(Update, (restore_veterans, gain_experience, draw_rank_markers));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_start_at_each_threshold() {
+        assert_eq!(Veterancy::rank_for(0), 0);
+        assert_eq!(Veterancy::rank_for(4), 0);
+        assert_eq!(Veterancy::rank_for(5), 1);
+        assert_eq!(Veterancy::rank_for(14), 1);
+        assert_eq!(Veterancy::rank_for(15), 2);
+        assert_eq!(Veterancy::rank_for(29), 2);
+        assert_eq!(Veterancy::rank_for(30), 3);
+    }
+
+    #[test]
+    fn rank_stops_at_the_last_threshold() {
+        assert_eq!(Veterancy::rank_for(10_000), RANK_XP.len() as u32);
+    }
+
+    #[test]
+    fn each_rank_raises_every_multiplier() {
+        let green = Veterancy::default();
+        let veteran = Veterancy::from_xp(RANK_XP[1]);
+        assert_eq!(veteran.rank, 2);
+        assert!(veteran.speed_multiplier() > green.speed_multiplier());
+        assert!(veteran.health_multiplier() > green.health_multiplier());
+        assert!(veteran.damage_multiplier() > green.damage_multiplier());
+        assert_eq!(green.health_multiplier(), 1.0);
+    }
+
+    #[test]
+    fn returning_veterans_go_to_ants_of_their_caste() {
+        let saved = BTreeMap::from([(1, vec![SavedVeteran { caste: Caste::Soldier, xp: 20 }])]);
+        let mut returning = ReturningVeterans::from_saved(saved);
+        assert_eq!(returning.take(PlayerId(1), Caste::Worker), None);
+        assert_eq!(returning.take(PlayerId(2), Caste::Soldier), None);
+        assert_eq!(returning.take(PlayerId(1), Caste::Soldier), Some(Veterancy { xp: 20, rank: 2 }));
+        assert_eq!(returning.take(PlayerId(1), Caste::Soldier), None);
+    }
+
+    #[test]
+    fn saved_veterans_include_those_not_yet_returned() {
+        let returning = ReturningVeterans::from_saved(BTreeMap::from([(1, vec![SavedVeteran { caste: Caste::Worker, xp: 3 }])]));
+        let living = [
+            (Faction(PlayerId(1)), Caste::Soldier, Veterancy::from_xp(40)),
+            (Faction(PlayerId(1)), Caste::Worker, Veterancy::default()),
+        ];
+        let saved = returning.to_saved(living.iter().map(|(faction, caste, veterancy)| (faction, caste, veterancy)));
+        assert_eq!(
+            saved[&1],
+            vec![SavedVeteran { caste: Caste::Soldier, xp: 40 }, SavedVeteran { caste: Caste::Worker, xp: 3 }]
+        );
+    }
+}
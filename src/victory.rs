@@ -0,0 +1,287 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use std::collections::BTreeSet;
+
+use crate::game_speed::GameSpeed;
+use crate::loading::AppState;
+use crate::locale::{Locale, Localized};
+use crate::net::{LocalPlayer, PlayerId};
+use crate::scoring::{MatchEnded, Scores};
+use crate::succession::Succession;
+use crate::{Faction, Queen, Relation, Teams};
+
+// Winning and losing. `--victory` picks the conditions, a comma-separated
+// list of `queens` (every rival queen is dead), `food:<amount>` (deliver that
+// much food) and `survive:<minutes>` (keep our queen alive that long); the
+// default is `queens`. Meeting any of them wins the match; losing our queen,
+// or a rival delivering the food target first, loses it. A colony whose queen
+// died isn't beaten while it can still crown a princess (see `succession`).
+// Rivals are every colony that isn't ours or allied, pacts included, since a
+// pact can be ended. The outcome ends the match (`MatchEnded`, which opens the
+// score summary) and pauses the game behind a banner, from which the player
+// either keeps playing or leaves the game. Conditions are checked every frame
+// until the first outcome.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Condition {
+    Queens,
+    Food(u32),
+    Survive(f32),
+}
+
+impl Condition {
+    fn parse(text: &str) -> Option<Self> {
+        match text.split_once(':') {
+            None if text == "queens" => Some(Self::Queens),
+            Some(("food", amount)) => amount.parse().ok().map(Self::Food),
+            Some(("survive", minutes)) => {
+                minutes.parse().ok().filter(|&minutes: &f32| minutes > 0.0).map(Self::Survive)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct VictoryConditions(Vec<Condition>);
+
+impl VictoryConditions {
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let Some(list) = args.windows(2).find(|pair| pair[0] == "--victory").map(|pair| &pair[1]) else {
+            return Self(vec![Condition::Queens]);
+        };
+        let mut conditions = Vec::new();
+        for text in list.split(',') {
+            match Condition::parse(text.trim()) {
+                Some(condition) => conditions.push(condition),
+                None => warn!("--victory {list}: {text:?} is not a victory condition"),
+            }
+        }
+        Self(conditions)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Outcome {
+    Victory,
+    Defeat,
+}
+
+#[derive(Resource, Default)]
+pub struct MatchState {
+    elapsed_secs: f32,
+    // Colonies whose queen has been seen at least once, so a colony that
+    // hasn't spawned yet doesn't count as beaten
+    seen_queens: BTreeSet<PlayerId>,
+    // The outcome and the locale key of the reason for it
+    outcome: Option<(Outcome, &'static str)>,
+}
+
+pub fn evaluate_conditions(
+    time: Res<Time>,
+    conditions: Res<VictoryConditions>,
+    local_player: Res<LocalPlayer>,
+    teams: Res<Teams>,
+    scores: Res<Scores>,
+    succession: Res<Succession>,
+    queen_q: Query<&Faction, With<Queen>>,
+    mut state: ResMut<MatchState>,
+    mut ended: EventWriter<MatchEnded>,
+    mut game_speed: ResMut<GameSpeed>,
+) {
+    if state.outcome.is_some() {
+        return;
+    }
+    state.elapsed_secs += time.delta_seconds();
+    // A colony waiting to crown a princess is still in the match
+    let alive: BTreeSet<PlayerId> = queen_q
+        .iter()
+        .map(|faction| faction.0)
+        .chain(state.seen_queens.iter().copied().filter(|&player| succession.pending(player)))
+        .collect();
+    state.seen_queens.extend(alive.iter().copied());
+
+    let us = local_player.0;
+    let is_rival = |player: PlayerId| matches!(teams.relation(us, player), Relation::Neutral | Relation::Enemy);
+    let outcome = if state.seen_queens.contains(&us) && !alive.contains(&us) {
+        Some((Outcome::Defeat, "victory.reason-queen-lost"))
+    } else {
+        conditions.0.iter().find_map(|condition| match *condition {
+            Condition::Queens => {
+                let mut rivals = state.seen_queens.iter().filter(|&&player| is_rival(player)).peekable();
+                let beaten = rivals.peek().is_some() && rivals.all(|player| !alive.contains(player));
+                beaten.then_some((Outcome::Victory, "victory.reason-queens"))
+            }
+            Condition::Food(target) => {
+                if scores.get(us).food_gathered >= target {
+                    Some((Outcome::Victory, "victory.reason-food"))
+                } else if state
+                    .seen_queens
+                    .iter()
+                    .any(|&player| is_rival(player) && scores.get(player).food_gathered >= target)
+                {
+                    Some((Outcome::Defeat, "victory.reason-rival-food"))
+                } else {
+                    None
+                }
+            }
+            Condition::Survive(minutes) => {
+                (state.elapsed_secs >= minutes * 60.0).then_some((Outcome::Victory, "victory.reason-survived"))
+            }
+        })
+    };
+    let Some((outcome, reason)) = outcome else {
+        return;
+    };
+    info!("Match over: {outcome:?} ({reason})");
+    state.outcome = Some((outcome, reason));
+    ended.send(MatchEnded);
+    game_speed.paused = true;
+}
+
+#[derive(Component)]
+struct OutcomeBanner;
+
+#[derive(Component)]
+struct OutcomeTitle;
+
+#[derive(Component)]
+struct OutcomeReason;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum OutcomeButton {
+    KeepPlaying,
+    Leave,
+}
+
+fn setup_outcome_banner(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(8.0),
+                    left: Val::Percent(35.0),
+                    width: Val::Percent(30.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(6.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    display: Display::None,
+                    ..default()
+                },
+                background_color: Color::srgba(0.1, 0.1, 0.1, 0.9).into(),
+                ..default()
+            },
+            Interaction::default(),
+            OutcomeBanner,
+        ))
+        .with_children(|banner| {
+            banner.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 28.0, color: Color::WHITE, ..default() }),
+                OutcomeTitle,
+            ));
+            banner.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 14.0, color: Color::WHITE, ..default() }),
+                OutcomeReason,
+            ));
+            banner
+                .spawn(NodeBundle { style: Style { column_gap: Val::Px(8.0), ..default() }, ..default() })
+                .with_children(|row| {
+                    for (button, key) in
+                        [(OutcomeButton::KeepPlaying, "victory.keep-playing"), (OutcomeButton::Leave, "victory.leave")]
+                    {
+                        row.spawn((
+                            ButtonBundle {
+                                style: Style { padding: UiRect::axes(Val::Px(6.0), Val::Px(2.0)), ..default() },
+                                background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                                ..default()
+                            },
+                            button,
+                        ))
+                        .with_children(|button| {
+                            button.spawn((
+                                TextBundle::from_section(
+                                    "",
+                                    TextStyle { font_size: 14.0, color: Color::WHITE, ..default() },
+                                ),
+                                Localized(key),
+                            ));
+                        });
+                    }
+                });
+        });
+}
+
+fn show_outcome(
+    state: Res<MatchState>,
+    locale: Res<Locale>,
+    mut banner_q: Query<&mut Style, With<OutcomeBanner>>,
+    mut title_q: Query<&mut Text, (With<OutcomeTitle>, Without<OutcomeReason>)>,
+    mut reason_q: Query<&mut Text, With<OutcomeReason>>,
+) {
+    if !state.is_changed() && !locale.is_changed() {
+        return;
+    }
+    let Some((outcome, reason)) = state.outcome else {
+        return;
+    };
+    // Nothing touches the state after the outcome, so this opens the banner
+    // once; a locale change just relabels it
+    if state.is_changed() {
+        for mut style in banner_q.iter_mut() {
+            style.display = Display::Flex;
+        }
+    }
+    let (title, color) = match outcome {
+        Outcome::Victory => ("victory.victory", Srgba::hex("FFD54F").unwrap()),
+        Outcome::Defeat => ("victory.defeat", Srgba::hex("E57373").unwrap()),
+    };
+    for mut text in title_q.iter_mut() {
+        text.sections[0].value = locale.get(title).to_string();
+        text.sections[0].style.color = color.into();
+    }
+    for mut text in reason_q.iter_mut() {
+        text.sections[0].value = locale.get(reason).to_string();
+    }
+}
+
+fn outcome_buttons(
+    button_q: Query<(&Interaction, &OutcomeButton), Changed<Interaction>>,
+    mut banner_q: Query<&mut Style, With<OutcomeBanner>>,
+    mut game_speed: ResMut<GameSpeed>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button) in button_q.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            // The match stays decided; it just goes on without further checks
+            OutcomeButton::KeepPlaying => {
+                game_speed.paused = false;
+                for mut style in banner_q.iter_mut() {
+                    style.display = Display::None;
+                }
+            }
+            OutcomeButton::Leave => {
+                exit.send(AppExit::Success);
+            }
+        }
+    }
+}
+
+pub struct VictoryPlugin;
+
+impl Plugin for VictoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(VictoryConditions::from_args())
+            .init_resource::<MatchState>()
+            .add_systems(Startup, setup_outcome_banner)
+            .add_systems(
+                Update,
+                (evaluate_conditions.run_if(in_state(AppState::InGame)), show_outcome, outcome_buttons).chain(),
+            );
+    }
+}
@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::HashSet;
+
+use crate::burrow::InTunnel;
+use crate::dig_in::DugIn;
+use crate::coords::HexLayoutExt;
+use crate::gpu_fields::{FieldReadbacks, GpuFields};
+use crate::lobby::MatchSettings;
+use crate::lod::DetailLevel;
+use crate::net::LocalPlayer;
+use crate::territory::Territory;
+use crate::{Ant, Faction, MapLayout, Relation, Teams};
+
+// Fog of war over enemy units. Every ant of ours or an ally's sees the hexes
+// within `SIGHT_RADIUS` of its own, and all of our team's territory is in
+// view, so allied colonies share what they see; enemy ants outside all of it
+// are hidden and can't be hovered or picked. Terrain, food and structures are
+// never fogged. Worked out a few times a second rather than every frame: an
+// enemy stepping into view a moment late goes unnoticed. Enemies dug into
+// the ground (see `dig_in`) stay hidden in plain view, and are only noticed
+// by an ant of ours within `DETECT_RADIUS` of them. A match played with the
+// no fog mutator (see `mutators`) shows every enemy that isn't dug in. Where
+// the GPU can take it, the hexes in sight are worked out there from where
+// our ants stand (see `gpu_fields`), as of the last readback.
+
+pub const SIGHT_RADIUS: u32 = 6;
+const REFRESH_SECS: f32 = 0.2;
+// Dug-in enemies are noticed this many hexes away or closer
+const DETECT_RADIUS: u32 = 1;
+
+// Hexes the local player's team can see
+#[derive(Resource, Default)]
+pub struct Vision(HashSet<Hex>);
+
+impl Vision {
+    pub fn reveals(&self, hex: Hex) -> bool {
+        self.0.contains(&hex)
+    }
+
+    pub fn hexes(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+// An enemy unit nobody on our team can see
+#[derive(Component)]
+pub struct InFog;
+
+#[derive(Resource)]
+struct VisionRefresh(Timer);
+
+fn update_vision(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut refresh: ResMut<VisionRefresh>,
+    layout: Res<MapLayout>,
+    teams: Res<Teams>,
+    local_player: Res<LocalPlayer>,
+    territory: Res<Territory>,
+    level: Res<DetailLevel>,
+    settings: Res<MatchSettings>,
+    mut fields: ResMut<GpuFields>,
+    readbacks: Res<FieldReadbacks>,
+    mut images: ResMut<Assets<Image>>,
+    mut vision: ResMut<Vision>,
+    mut ant_q: Query<
+        (Entity, &Transform, &Faction, &mut Visibility, Has<InFog>, Has<DugIn>),
+        (With<Ant>, Without<InTunnel>),
+    >,
+) {
+    if !refresh.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    // Colonies we're merely at peace with keep their secrets
+    let is_enemy = |player| matches!(teams.relation(local_player.0, player), Relation::Neutral | Relation::Enemy);
+
+    // Ants standing together see the same hexes, so expand each hex only once
+    let watched: HashSet<Hex> = ant_q
+        .iter()
+        .filter(|(_, _, faction, ..)| !is_enemy(faction.0))
+        .map(|(_, transform, ..)| layout.0.world_to_hex(transform.translation.truncate()))
+        .collect();
+    vision.0.clear();
+    if fields.reveal(&mut images, watched.iter().copied()) {
+        vision.0.extend(readbacks.visible());
+    } else {
+        for &hex in &watched {
+            vision.0.extend(hex.range(SIGHT_RADIUS));
+        }
+    }
+    vision.0.extend(territory.hexes().filter(|&(_, owner)| !is_enemy(owner)).map(|(hex, _)| hex));
+
+    let detected = |hex: Hex| hex.range(DETECT_RADIUS).any(|near| watched.contains(&near));
+
+    for (entity, transform, faction, mut visibility, fogged, dug_in) in ant_q.iter_mut() {
+        let hex = layout.0.world_to_hex(transform.translation.truncate());
+        let out_of_sight = settings.mutators.fog() && !vision.reveals(hex);
+        let hidden = is_enemy(faction.0) && (out_of_sight || (dug_in && !detected(hex)));
+        if hidden && !fogged {
+            commands.entity(entity).insert(InFog);
+            *visibility = Visibility::Hidden;
+        } else if !hidden && fogged {
+            commands.entity(entity).remove::<InFog>();
+            // Back under the level of detail's rule; see `lod`
+            *visibility = if *level == DetailLevel::Full { Visibility::Inherited } else { Visibility::Hidden };
+        }
+    }
+}
+
+pub struct VisionPlugin;
+
+impl Plugin for VisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Vision>()
+            .insert_resource(VisionRefresh(Timer::from_seconds(REFRESH_SECS, TimerMode::Repeating)))
+            .add_systems(Update, update_vision);
+    }
+}
@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::HashSet;
+
+use crate::burrow::BurrowNetwork;
+use crate::coords::HexLayoutExt;
+use crate::culling::CameraView;
+use crate::pathfinding::{PathPriority, PathQueue};
+use crate::{Ant, MapLayout, MapSize, Path, Queen, TargetPosition};
+
+// Weather: every so often it rains, and while it rains low ground floods.
+// Until the map has elevation, "low ground" is the tunnel network. Flooded
+// hexes are impassable: routes through them are recomputed, ants standing in
+// them wade to the nearest dry hex, and the water drains a while after the
+// rain stops.
+
+const CLEAR_SECS: f32 = 90.0;
+const RAIN_SECS: f32 = 20.0;
+const DRAIN_SECS: f32 = 15.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WeatherState {
+    Clear,
+    Raining,
+    Draining,
+}
+
+#[derive(Resource)]
+pub struct Weather {
+    state: WeatherState,
+    timer: Timer,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            state: WeatherState::Clear,
+            timer: Timer::from_seconds(CLEAR_SECS, TimerMode::Once),
+        }
+    }
+}
+
+#[cfg(feature = "sandbox")]
+impl Weather {
+    // Let the current clear spell end on the next update
+    pub fn start_rain(&mut self) {
+        if self.state == WeatherState::Clear {
+            self.timer = Timer::from_seconds(0.0, TimerMode::Once);
+        }
+    }
+}
+
+#[cfg(feature = "sandbox")]
+impl Weather {
+    // Flood these hexes right away; they drain with the rest of the water,
+    // or after the usual wait if it isn't raining
+    pub fn flood(&mut self, flooded: &mut Flooded, hexes: impl IntoIterator<Item = Hex>) {
+        flooded.hexes.extend(hexes);
+        if self.state == WeatherState::Clear {
+            self.state = WeatherState::Draining;
+            self.timer = Timer::from_seconds(DRAIN_SECS, TimerMode::Once);
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Flooded {
+    hexes: HashSet<Hex>,
+}
+
+impl Flooded {
+    pub fn contains(&self, hex: Hex) -> bool {
+        self.hexes.contains(&hex)
+    }
+
+    pub fn hexes(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.hexes.iter().copied()
+    }
+}
+
+fn advance_weather(
+    time: Res<Time>,
+    mut weather: ResMut<Weather>,
+    mut flooded: ResMut<Flooded>,
+    network: Res<BurrowNetwork>,
+    map_size: Res<MapSize>,
+) {
+    if !weather.timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    let (next, secs) = match weather.state {
+        WeatherState::Clear => {
+            flooded.hexes = map_size.hexes().filter(|&hex| network.is_dug(hex)).collect();
+            (WeatherState::Raining, RAIN_SECS)
+        }
+        WeatherState::Raining => (WeatherState::Draining, DRAIN_SECS),
+        WeatherState::Draining => {
+            flooded.hexes.clear();
+            (WeatherState::Clear, CLEAR_SECS)
+        }
+    };
+    weather.state = next;
+    weather.timer = Timer::from_seconds(secs, TimerMode::Once);
+}
+
+// When the water rises: reroute anyone whose path crosses it and move anyone
+// standing in it to dry ground
+fn react_to_flooding(
+    flooded: Res<Flooded>,
+    layout: Res<MapLayout>,
+    map_size: Res<MapSize>,
+    mut path_queue: ResMut<PathQueue>,
+    mut ant_q: Query<(Entity, &Transform, &mut TargetPosition, &mut Path), (With<Ant>, Without<Queen>)>,
+) {
+    if !flooded.is_changed() || flooded.hexes.is_empty() {
+        return;
+    }
+    let to_hex = |pos: Vec2| layout.0.world_to_hex(pos);
+
+    for (entity, transform, mut target, mut path) in ant_q.iter_mut() {
+        let current = to_hex(transform.translation.truncate());
+        if flooded.contains(current) {
+            let dry = current
+                .spiral_range(1..map_size.radius * 2)
+                .find(|&hex| map_size.contains(hex) && !flooded.contains(hex));
+            if let Some(dry) = dry {
+                target.0 = layout.0.hex_to_world(dry);
+                path.waypoints.clear();
+            }
+            continue;
+        }
+
+        let crosses_water = std::iter::once(target.0)
+            .chain(path.waypoints.iter().copied())
+            .any(|pos| flooded.contains(to_hex(pos)));
+        if crosses_water {
+            let destination = path.waypoints.back().copied().unwrap_or(target.0);
+            path_queue.request(entity, current, to_hex(destination), PathPriority::Ai);
+        }
+    }
+}
+
+fn draw_flooding(mut gizmos: Gizmos, flooded: Res<Flooded>, layout: Res<MapLayout>, view: Res<CameraView>) {
+    let water_color = Color::from(Srgba::hex("2F6FD6").unwrap());
+    let radius = layout.0.scale.min_element() * 0.6;
+    for &hex in flooded.hexes.iter() {
+        let center = layout.0.hex_to_world(hex);
+        if view.overlaps_circle(center, radius) {
+            gizmos.circle_2d(center, radius, water_color);
+        }
+    }
+}
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .init_resource::<Flooded>()
+            .add_systems(
+                Update,
+                (
+                    (advance_weather, react_to_flooding)
+                        .chain()
+                        .before(crate::pathfinding::process_path_requests),
+                    draw_flooding,
+                ),
+            );
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use js_sys::{Array, Function, Object, Reflect};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use wasm_bindgen::prelude::*;
+
+use crate::interactions::FoodStores;
+use crate::net::{LocalPlayer, PlayerToken};
+use crate::scoring::Scores;
+use crate::{Ant, Faction, Selected};
+
+// JavaScript embedding API for the web build. The hosting page loads the wasm
+// module and talks to the game through these exports:
+//
+//   start(token?)        run the game in `#bevy-canvas`, optionally signed in
+//   stop()               shut the game down
+//   setPlayerToken(t)    hand over (or refresh) the player's session token
+//   onGameEvent(fn)      fn(name, payload) for "selection" and "score"
+//
+// By default the game starts as soon as the module loads, like the native
+// build. A page that wants to pass a token first puts `data-manual-start` on
+// the canvas and calls `start` itself. The browser's event loop can only be
+// taken over once, so a stopped game needs a page reload to start again.
+//
+// Calls from the page land in a queue that a system drains every frame; game
+// events go out the other way through the registered callbacks.
+
+const CANVAS_ID: &str = "bevy-canvas";
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+static COMMANDS: Mutex<Vec<HostCommand>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // JS functions can't leave the main thread, and neither does the game on the web
+    static CALLBACKS: RefCell<Vec<Function>> = const { RefCell::new(Vec::new()) };
+}
+
+enum HostCommand {
+    Stop,
+    SetToken(String),
+}
+
+fn queue(command: HostCommand) {
+    COMMANDS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(command);
+}
+
+// Whether `main` should start the game, or leave it to the page
+pub fn autostart() -> bool {
+    let document = web_sys::window().and_then(|window| window.document());
+    let canvas = document.and_then(|document| document.get_element_by_id(CANVAS_ID));
+    !canvas.is_some_and(|canvas| canvas.has_attribute("data-manual-start"))
+}
+
+#[wasm_bindgen]
+pub fn start(token: Option<String>) -> Result<(), JsError> {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return Err(JsError::new("the game has already been started on this page"));
+    }
+    if let Some(token) = token {
+        queue(HostCommand::SetToken(token));
+    }
+    crate::run_game();
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn stop() {
+    queue(HostCommand::Stop);
+}
+
+#[wasm_bindgen(js_name = setPlayerToken)]
+pub fn set_player_token(token: String) {
+    queue(HostCommand::SetToken(token));
+}
+
+#[wasm_bindgen(js_name = onGameEvent)]
+pub fn on_game_event(callback: Function) {
+    CALLBACKS.with_borrow_mut(|callbacks| callbacks.push(callback));
+}
+
+fn emit(name: &str, payload: &JsValue) {
+    CALLBACKS.with_borrow(|callbacks| {
+        for callback in callbacks {
+            if let Err(err) = callback.call2(&JsValue::NULL, &JsValue::from_str(name), payload) {
+                warn!("onGameEvent callback failed: {err:?}");
+            }
+        }
+    });
+}
+
+fn object(fields: &[(&str, JsValue)]) -> JsValue {
+    let object = Object::new();
+    for (key, value) in fields {
+        // Setting a property on a fresh plain object can't fail
+        let _ = Reflect::set(&object, &JsValue::from_str(key), value);
+    }
+    object.into()
+}
+
+fn apply_host_commands(mut token: ResMut<PlayerToken>, mut exit: EventWriter<AppExit>) {
+    let commands = std::mem::take(&mut *COMMANDS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    for command in commands {
+        match command {
+            HostCommand::Stop => {
+                exit.send(AppExit::Success);
+            }
+            HostCommand::SetToken(value) => token.0 = Some(value),
+        }
+    }
+}
+
+// `selection`: { count, units } with entity ids as numbers, whenever it changes
+fn emit_selection(
+    added_q: Query<(), Added<Selected>>,
+    mut removed: RemovedComponents<Selected>,
+    selected_q: Query<Entity, With<Selected>>,
+) {
+    // Always drain the removals, or they'd be reported again next frame
+    let removed_any = removed.read().count() > 0;
+    if added_q.is_empty() && !removed_any {
+        return;
+    }
+    let units: Array = selected_q.iter().map(|entity| JsValue::from_f64(entity.to_bits() as f64)).collect();
+    emit("selection", &object(&[("count", JsValue::from(units.length())), ("units", units.into())]));
+}
+
+// `score`: { score, food, units } for the local player, whenever any changes
+fn emit_score(
+    scores: Res<Scores>,
+    stores: Res<FoodStores>,
+    local_player: Res<LocalPlayer>,
+    ant_q: Query<&Faction, With<Ant>>,
+    mut last: Local<Option<(u32, u32, u32)>>,
+) {
+    let score = scores.get(local_player.0).total();
+    let food = stores.0.get(&local_player.0).copied().unwrap_or(0);
+    let units = ant_q.iter().filter(|faction| faction.0 == local_player.0).count() as u32;
+    if *last == Some((score, food, units)) {
+        return;
+    }
+    *last = Some((score, food, units));
+    let fields = [("score", JsValue::from(score)), ("food", JsValue::from(food)), ("units", JsValue::from(units))];
+    emit("score", &object(&fields));
+}
+
+pub struct WebPlugin;
+
+impl Plugin for WebPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, apply_host_commands)
+            .add_systems(Last, (emit_selection, emit_score).run_if(in_state(crate::loading::AppState::InGame)));
+    }
+}
@@ -0,0 +1,207 @@
+use bevy::prelude::*;
+use hexx::Hex;
+use std::collections::HashMap;
+
+use crate::coords::HexLayoutExt;
+use crate::culling::CameraView;
+use crate::health::Health;
+use crate::net::{LocalPlayer, PlayerId};
+use crate::spatial::SpatialIndex;
+use crate::{Ant, Faction, MapLayout, Relation, Teams};
+
+// Area-effect zones: an entity with a position, a hex radius, who it affects,
+// what it does and optionally how long it lasts. Buildings, abilities and map
+// events spawn these instead of each writing their own radius queries: the
+// nest heals its colony's ants and slows intruders, and a finished guard post
+// heals the ants around it (see `structures`). A zone can be a child of what
+// it belongs to, and goes when that does.
+//
+// Continuous effects (speed) are folded into per-unit modifiers every frame;
+// anything periodic (healing) listens for `ZoneTick`, sent once per unit per
+// tick.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZoneFilter {
+    Everyone,
+    // The zone owner's units and allies
+    Friendly,
+    // Everyone the zone owner is hostile to
+    Hostile,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ZoneEffect {
+    SpeedMultiplier(f32),
+    // Hit points restored each tick
+    Heal(f32),
+}
+
+#[derive(Component)]
+pub struct Zone {
+    // Hexes within this distance of the zone's hex are covered
+    pub radius: u32,
+    // `None` for neutral zones (hazards, map events)
+    pub owner: Option<PlayerId>,
+    pub filter: ZoneFilter,
+    pub effect: ZoneEffect,
+    pub tick: Timer,
+    // `None` lasts forever
+    pub lifetime: Option<Timer>,
+}
+
+impl Zone {
+    pub fn new(radius: u32, owner: Option<PlayerId>, filter: ZoneFilter, effect: ZoneEffect) -> Self {
+        Self {
+            radius,
+            owner,
+            filter,
+            effect,
+            tick: Timer::from_seconds(1.0, TimerMode::Repeating),
+            lifetime: None,
+        }
+    }
+
+    fn affects(&self, faction: Option<&Faction>, teams: &Teams) -> bool {
+        let relation = match (self.owner, faction) {
+            (Some(owner), Some(faction)) => teams.relation(owner, faction.0),
+            // Neutral zones and unowned units only match `Everyone`
+            _ => return self.filter == ZoneFilter::Everyone,
+        };
+        match self.filter {
+            ZoneFilter::Everyone => true,
+            ZoneFilter::Friendly => matches!(relation, Relation::Own | Relation::Ally),
+            ZoneFilter::Hostile => relation == Relation::Enemy,
+        }
+    }
+}
+
+// Periodic effect hook: `target` was inside `zone` when its tick timer fired
+#[derive(Event)]
+pub struct ZoneTick {
+    pub zone: Entity,
+    pub target: Entity,
+}
+
+// Product of every speed effect currently covering a unit
+#[derive(Component)]
+pub struct SpeedModifier(pub f32);
+
+fn spawn_zone(commands: &mut Commands, layout: &MapLayout, hex: Hex, zone: Zone) {
+    let pos = layout.0.hex_to_world(hex);
+    commands.spawn((SpatialBundle::from_transform(Transform::from_translation(pos.extend(0.0))), zone));
+}
+
+// A zone centered on `parent` that lasts as long as it does
+pub fn attach_zone(commands: &mut Commands, parent: Entity, zone: Zone) {
+    let child = commands.spawn((SpatialBundle::default(), zone)).id();
+    commands.entity(parent).add_child(child);
+}
+
+// Map features that are zones from the start: the nest's home turf (ants
+// move faster along their own trails and heal there, intruders are slowed)
+// and a mud patch.
+fn spawn_starting_zones(mut commands: Commands, layout: Res<MapLayout>, local_player: Res<LocalPlayer>) {
+    let owner = Some(local_player.0);
+    spawn_zone(&mut commands, &layout, Hex::ZERO, Zone::new(2, owner, ZoneFilter::Friendly, ZoneEffect::SpeedMultiplier(1.25)));
+    spawn_zone(&mut commands, &layout, Hex::ZERO, Zone::new(2, owner, ZoneFilter::Friendly, ZoneEffect::Heal(1.0)));
+    spawn_zone(&mut commands, &layout, Hex::ZERO, Zone::new(3, owner, ZoneFilter::Hostile, ZoneEffect::SpeedMultiplier(0.6)));
+    spawn_zone(&mut commands, &layout, Hex::new(-4, 6), Zone::new(1, None, ZoneFilter::Everyone, ZoneEffect::SpeedMultiplier(0.5)));
+}
+
+fn expire_zones(mut commands: Commands, time: Res<Time>, mut zone_q: Query<(Entity, &mut Zone)>) {
+    for (entity, mut zone) in zone_q.iter_mut() {
+        let expired = zone.lifetime.as_mut().is_some_and(|lifetime| lifetime.tick(time.delta()).finished());
+        if expired {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn apply_zones(
+    mut commands: Commands,
+    time: Res<Time>,
+    layout: Res<MapLayout>,
+    teams: Res<Teams>,
+    spatial_index: Res<SpatialIndex>,
+    mut zone_q: Query<(Entity, &mut Zone, &GlobalTransform), Without<Ant>>,
+    mut unit_q: Query<(Entity, Option<&Faction>, Option<&mut SpeedModifier>), With<Ant>>,
+    mut ticks: EventWriter<ZoneTick>,
+) {
+    let mut speeds: HashMap<Entity, f32> = HashMap::new();
+    for (zone_entity, mut zone, transform) in zone_q.iter_mut() {
+        let fired = zone.tick.tick(time.delta()).just_finished();
+        let zone_hex = layout.0.world_to_hex(transform.translation().truncate());
+
+        for unit in spatial_index.within(zone_hex, zone.radius) {
+            let Ok((_, faction, _)) = unit_q.get(unit) else {
+                continue;
+            };
+            if !zone.affects(faction, &teams) {
+                continue;
+            }
+            if let ZoneEffect::SpeedMultiplier(factor) = zone.effect {
+                *speeds.entry(unit).or_insert(1.0) *= factor;
+            }
+            if fired {
+                ticks.send(ZoneTick { zone: zone_entity, target: unit });
+            }
+        }
+    }
+
+    for (unit, _, modifier) in unit_q.iter_mut() {
+        let speed = speeds.get(&unit).copied().unwrap_or(1.0);
+        match modifier {
+            Some(mut modifier) => modifier.0 = speed,
+            None => {
+                commands.entity(unit).insert(SpeedModifier(speed));
+            }
+        }
+    }
+}
+
+// Tick effects: healing tops up the units covered when the zone ticks
+fn apply_zone_ticks(mut ticks: EventReader<ZoneTick>, zone_q: Query<&Zone>, mut health_q: Query<&mut Health>) {
+    for tick in ticks.read() {
+        let (Ok(zone), Ok(mut health)) = (zone_q.get(tick.zone), health_q.get_mut(tick.target)) else {
+            continue;
+        };
+        if let ZoneEffect::Heal(amount) = zone.effect {
+            health.heal(amount);
+        }
+    }
+}
+
+fn draw_zones(mut gizmos: Gizmos, layout: Res<MapLayout>, view: Res<CameraView>, zone_q: Query<(&Zone, &GlobalTransform)>) {
+    for (zone, transform) in zone_q.iter() {
+        let color = match zone.effect {
+            ZoneEffect::SpeedMultiplier(factor) if factor < 1.0 => Color::from(Srgba::hex("6B4F2A").unwrap()),
+            ZoneEffect::SpeedMultiplier(_) => Color::from(Srgba::hex("2E6B3A").unwrap()),
+            ZoneEffect::Heal(_) => Color::from(Srgba::hex("E57373").unwrap()),
+        };
+        // Outline the covered area by its outer ring of hexes
+        let center = layout.0.world_to_hex(transform.translation().truncate());
+        for hex in center.ring(zone.radius) {
+            let pos = layout.0.hex_to_world(hex);
+            if view.overlaps_circle(pos, 3.0) {
+                gizmos.circle_2d(pos, 3.0, color);
+            }
+        }
+    }
+}
+
+pub struct ZonesPlugin;
+
+impl Plugin for ZonesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ZoneTick>()
+            .add_systems(Startup, spawn_starting_zones.after(crate::setup_hex_grid))
+            .add_systems(
+                Update,
+                (
+                    expire_zones,
+                    (apply_zones.after(crate::spatial::update_spatial_index).before(crate::move_ants), apply_zone_ticks).chain(),
+                    draw_zones,
+                ),
+            );
+    }
+}